@@ -1,24 +1,32 @@
-use crate::codegen::{Codegen, Executable, ExecutionContext};
+use crate::codegen::{
+    BlockKey, CacheStats, Codegen, CodegenCache, Executable, ExecutionContext, TranslatedBlock,
+};
 use crate::compiler::Compiler;
 use crate::cpu::Cpu;
 use crate::debug::{DebugEvent, Event, ExecutionMode};
+use crate::device::{CpuLines, GenericTimer, Poll};
 use crate::error::{CompileError, DebugError, Error};
-use crate::ir::{BlockDestination, IrBlock};
-use crate::softmmu::{Mmu, MmuData};
+use crate::exception::{fault_status, pending_interrupt, take_exception, Exception, FSC_EXTERNAL};
+use crate::ir::opt::PassManager;
+use crate::ir::{BlockDestination, Ir, IrBlock, Operand, Type};
+use crate::psci::Shutdown;
+use crate::softmmu::{Access, Mmu, MmuData, MmuEvent};
+use crate::user::Kernel;
 
 use gdbstub::arch::Arch;
 use gdbstub::target::Target;
 use machineinstr::{MachineInstParser, MachineInstrParserRule};
 use utility::ByteReader;
 
+use smallvec::SmallVec;
 use thread_local::ThreadLocal;
 
+use std::any::Any;
 use std::borrow::BorrowMut;
 use std::collections::HashSet;
-use std::convert::Infallible;
 use std::sync::{Arc, Mutex};
 
-pub struct Board<C, R, G, A> {
+pub struct Board<C, R, G: Codegen, A> {
     ir_comp: C, // IR compiler, which compiles machine instructions into IR blocks.
     ir_cgen: G, // IR codegen, which generates executable code from IR blocks.
     mci_parser: R,
@@ -30,9 +38,40 @@ pub struct Board<C, R, G, A> {
 
     debug_arch: A,
     breakpoints: HashSet<u64>,
+
+    cache: Mutex<CodegenCache<G::ExecBlock>>,
+    passes: PassManager,
+
+    interrupts: CpuLines,
+    devices: Vec<Arc<Mutex<dyn Poll>>>,
+    timer: Option<GenericTimer>,
+    trace: bool,
+    kernel: Option<Mutex<Box<dyn Kernel>>>,
+    undefined: UndefinedPolicy,
+}
+
+// What the board does with instructions which can't be compiled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UndefinedPolicy {
+    // Stop running with the error of the instruction.
+    #[default]
+    Stop,
+    // Raise an Undefined Instruction exception to the guest, as the cpu would for
+    // instructions it doesn't implement.
+    Raise,
 }
 
-impl<C, R, G, A> Board<C, R, G, A> {
+// Mode bit of blocks translated for single stepping, which hold only one instruction.
+// It's not used by `Cpu::translation_mode`.
+const SINGLE_STEP_MODE: u64 = 1;
+
+// Number of blocks executed between polls of devices.
+const POLL_INTERVAL: u64 = 1024;
+
+// Number of blocks executed in a chain before the board ticks the timer.
+const CHAIN_BUDGET: u64 = 64;
+
+impl<C, R, G: Codegen, A> Board<C, R, G, A> {
     pub fn new(
         ir_comp: C,
         ir_cgen: G,
@@ -51,6 +90,14 @@ impl<C, R, G, A> Board<C, R, G, A> {
             cpu_core: ThreadLocal::new(),
             exec_mode: ExecutionMode::Step,
             breakpoints: HashSet::new(),
+            cache: Mutex::new(CodegenCache::new()),
+            passes: PassManager::default(),
+            interrupts: CpuLines::default(),
+            devices: Vec::new(),
+            timer: None,
+            trace: false,
+            kernel: None,
+            undefined: UndefinedPolicy::default(),
         }
     }
 
@@ -77,6 +124,77 @@ impl<C, R, G, A> Board<C, R, G, A> {
             Err(DebugError::BreakpointNotExist(addr))
         }
     }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats()
+    }
+
+    pub fn flush_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    // Connect interrupt lines of the cpu, driven by the interrupt controller.
+    pub fn set_interrupt_lines(&mut self, lines: CpuLines) {
+        self.interrupts = lines;
+    }
+
+    // Poll `device` periodically while running.
+    pub fn add_device(&mut self, device: Arc<Mutex<dyn Poll>>) {
+        self.devices.push(device);
+    }
+
+    // Advance the generic timer of the cpu while running.
+    pub fn set_timer(&mut self, timer: GenericTimer) {
+        self.timer = Some(timer);
+    }
+
+    // Print the address of every block executed while running.
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn set_undefined_policy(&mut self, policy: UndefinedPolicy) {
+        self.undefined = policy;
+    }
+
+    // Emulate `kernel` in place of the guest's, for user-mode emulation.
+    pub fn set_kernel(&mut self, kernel: Box<dyn Kernel>) {
+        self.kernel = Some(Mutex::new(kernel));
+    }
+
+    fn tick_timer(&self, ctx: &mut ExecutionContext, instructions: u64) {
+        if let Some(timer) = &self.timer {
+            timer.tick(ctx.cpu_mut(), instructions);
+        }
+    }
+
+    fn poll_devices(&self) {
+        for device in &self.devices {
+            device.lock().unwrap().poll();
+        }
+    }
+
+    // Interrupt signalled to the cpu, unless it's masked.
+    fn pending_interrupt(&self, ctx: &ExecutionContext) -> Option<Exception> {
+        let (irq, fiq) = (self.interrupts.irq(), self.interrupts.fiq());
+        if !irq && !fiq {
+            return None;
+        }
+
+        pending_interrupt(ctx.cpu(), irq, fiq)
+    }
+
+    fn take_interrupt(&self, ctx: &mut ExecutionContext) {
+        if let Some(exc) = self.pending_interrupt(ctx) {
+            take_exception(ctx.cpu_mut(), exc);
+        }
+    }
+
+    // Replace IR optimization passes run before codegen.
+    pub fn set_passes(&mut self, passes: PassManager) {
+        self.passes = passes;
+        self.flush_cache();
+    }
 }
 
 impl<C, R, G, A> Board<C, R, G, A>
@@ -85,89 +203,259 @@ where
     R: MachineInstrParserRule<MachineInstr = C::Item>,
     G: Codegen,
 {
-    pub unsafe fn run(&self) -> Result<Infallible, Error> {
+    // Run the guest until it requests a power state through PSCI.
+    //
+    // A panic of the emulator dumps the cpu, and returns `Error::Panic`.
+    pub unsafe fn run(&self) -> Result<Shutdown, Error> {
         use std::panic;
-        use std::process::exit;
 
-        let mut mmu = self.mmu.clone();
+        let mmu = self.mmu.clone();
         let mut cpu = self
             .cpu_core
             .get_or(|| Mutex::new(self.cpu_init.clone()))
             .lock()
             .unwrap();
 
-        let mut ctx = ExecutionContext {
-            cpu: cpu.borrow_mut(),
-            mmu: &mut mmu,
-        };
-
-        let this = panic::AssertUnwindSafe(|| self.run_inner(&mut ctx));
-        match panic::catch_unwind(this) {
-            Err(_) => {}
-            Ok(Err(err)) => return Err(err),
-            Ok(Ok(_)) => unreachable!(),
+        let mut kernel = self.kernel.as_ref().map(|kernel| kernel.lock().unwrap());
+        let mut ctx = ExecutionContext::new(cpu.borrow_mut(), &mmu);
+        if let Some(kernel) = kernel.as_deref_mut() {
+            ctx.set_kernel(kernel.as_mut());
         }
-        cpu.dump();
 
-        exit(-1);
+        let this = panic::AssertUnwindSafe(|| self.run_inner(&mut ctx));
+        panic::catch_unwind(this).unwrap_or_else(|payload| {
+            cpu.dump();
+            Err(Error::Panic(panic_message(payload.as_ref())))
+        })
     }
 
-    pub unsafe fn run_inner(&self, ctx: &mut ExecutionContext) -> Result<Infallible, Error> {
+    pub unsafe fn run_inner(&self, ctx: &mut ExecutionContext) -> Result<Shutdown, Error> {
+        let mut block = self.translate(ctx, false)?;
+
+        // Every block is traced, so blocks aren't chained while tracing.
+        let budget = if self.trace { 1 } else { CHAIN_BUDGET };
+
+        let mut blocks = 0u64;
         loop {
-            let block = self.compile_until_branch_or_eof(ctx.mmu.clone(), ctx.cpu().pc())?;
+            if self.trace {
+                let cpu = ctx.cpu();
+                eprintln!("trace: {}", cpu.symbols().describe(cpu.pc()));
+            }
+            let (last, chain) =
+                block.execute_chain(ctx, budget, |ctx| self.pending_interrupt(ctx).is_some());
+            block = last;
+            if let Some(shutdown) = ctx.take_shutdown() {
+                return Ok(shutdown);
+            }
+
+            self.invalidate_modified_code();
+            self.tick_timer(ctx, chain.size / 4);
+
+            if blocks / POLL_INTERVAL != (blocks + chain.blocks) / POLL_INTERVAL {
+                self.poll_devices();
+            }
+            blocks += chain.blocks;
+            self.take_interrupt(ctx);
+
+            // Use the linked successor if it is still current, otherwise look it up and link
+            // it.
+            let key = BlockKey::new(ctx.cpu().pc(), ctx.cpu().translation_mode());
+            block = match block.linked(&key) {
+                Some(next) if self.is_current(ctx, &next) => next,
+                _ => {
+                    let next = self.translate(ctx, false)?;
+                    block.link(key, &next);
+                    next
+                }
+            };
+        }
+    }
 
-            let compiled = codegen_ir_blocks(block, &self.ir_cgen);
+    // Remove translations whose guest code has been modified or explicitly invalidated.
+    fn invalidate_modified_code(&self) {
+        if !self.mmu.has_code_events() {
+            return;
+        }
 
-            debug_assert!(!compiled.is_empty());
-            for code in compiled {
-                code.execute(ctx);
+        let mut cache = self.cache.lock().unwrap();
+        for event in self.mmu.take_code_events() {
+            if let MmuEvent::Write(range) = event {
+                cache.invalidate_range(range);
             }
         }
     }
 
+    // Whether `block` is still the code at its pc. Address translations may change
+    // without the code being written, so the pc is translated again after TLBs are
+    // flushed, and the block is used only if it maps to the same physical address.
+    fn is_current(&self, ctx: &ExecutionContext, block: &TranslatedBlock<G::ExecBlock>) -> bool {
+        let generation = ctx.mmu.generation();
+        if block.is_checked(generation) {
+            return true;
+        }
+
+        match ctx.translate(block.start(), Access::Execute) {
+            Ok(fetch) if fetch.pa == block.phys_start() => {
+                block.set_checked(generation);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // Find translated block of current pc from cache, or translate it.
+    // A block for single stepping holds only the instruction at pc.
+    unsafe fn translate(
+        &self,
+        ctx: &ExecutionContext,
+        single_step: bool,
+    ) -> Result<Arc<TranslatedBlock<G::ExecBlock>>, Error> {
+        let pc = ctx.cpu().pc();
+        let mut mode = ctx.cpu().translation_mode();
+        if single_step {
+            mode |= SINGLE_STEP_MODE;
+        }
+        let key = BlockKey::new(pc, mode);
+
+        let cached = self.cache.lock().unwrap().get(&key);
+        match cached {
+            Some(block) if self.is_current(ctx, &block) => return Ok(block),
+            _ => {}
+        }
+
+        // Instructions are fetched until the end of the page, as the next page may be
+        // translated into another physical address.
+        let generation = ctx.mmu.generation();
+        let fetch = ctx.translate(pc, Access::Execute);
+        let mut blocks = match fetch {
+            Ok(fetch) => self.compile_until_branch_or_eof(
+                ctx.mmu.clone(),
+                pc,
+                fetch.pa,
+                fetch.remaining(),
+                ctx.cpu().translation_mode(),
+            )?,
+            Err(_) => Vec::new(),
+        };
+        if blocks.is_empty() {
+            // Instruction can't be fetched. Not cached, as the page may be mapped later.
+            let status = match fetch {
+                Ok(fetch) => match ctx.mmu.read(fetch.pa, &mut [0u8; 4]) {
+                    Err(err) => fault_status(&err),
+                    Ok(()) => FSC_EXTERNAL,
+                },
+                Err(err) => fault_status(&err),
+            };
+            let block = instruction_abort_block(pc, status);
+            let compiled = codegen_ir_blocks(vec![block], &self.ir_cgen);
+            return Ok(Arc::new(TranslatedBlock::new(pc, 0, compiled, &[])));
+        }
+        let pa = fetch.unwrap().pa;
+        if single_step {
+            blocks.truncate(1);
+        }
+
+        let size = blocks.iter().map(|b| b.original_size() as u64).sum();
+        let successors = match blocks.last() {
+            Some(last) => {
+                let last_pc = pc + size - last.original_size() as u64;
+                last.branch_targets(last_pc).unwrap_or_default()
+            }
+            None => SmallVec::new(),
+        };
+
+        // Instructions are translated as one unit, so passes may optimize across them.
+        let mut blocks = vec![IrBlock::superblock(pc, blocks)];
+        self.passes.run(&mut blocks);
+        let compiled = codegen_ir_blocks(blocks, &self.ir_cgen);
+
+        debug_assert!(!compiled.is_empty());
+        let block = TranslatedBlock::new(pc, size, compiled, &successors).at_phys(pa);
+        block.set_checked(generation);
+        ctx.mmu.mark_translated(pa..pa + size);
+
+        Ok(self.cache.lock().unwrap().insert(key, block))
+    }
+
+    // Compile instructions from `pc` until a branch or `len` bytes. Instructions which
+    // can't be compiled are handled as the undefined policy says.
     unsafe fn compile_until_branch_or_eof(
         &self,
         mmu: Mmu,
-        pc: u64,
+        mut pc: u64,
+        pa: u64,
+        len: u64,
+        mode: u64,
     ) -> Result<Vec<IrBlock>, CompileError> {
-        compile_until_branch_or_eof(mmu, pc, &self.mci_parser, &self.ir_comp)
+        let mut results = Vec::new();
+
+        // This should not be implemented globally.
+        // reading MemoryFrame is unsafe.
+
+        let bytes = mmu.iter(pa).take(len.try_into().unwrap_or(usize::MAX));
+        let parser = MachineInstParser::new(ByteReader::new(bytes), self.mci_parser.clone());
+        for instr in parser {
+            let size = instr.size as usize;
+            let block = match self.ir_comp.compile(instr, pc, mode) {
+                Ok(block) => block,
+                Err(_) if self.undefined == UndefinedPolicy::Raise => undefined_block(size),
+                Err(err) if results.is_empty() => return Err(err),
+                // Instructions before it are run first, and the error is returned once the
+                // cpu reaches it.
+                Err(_) => break,
+            };
+            pc += size as u64;
+            let ends_block = block.items().iter().any(|item| {
+                item.dest().is_branch() || matches!(item.dest(), BlockDestination::Exit)
+            });
+            results.push(block);
+
+            if ends_block {
+                break;
+            }
+        }
+
+        Ok(results)
     }
 }
 
-unsafe fn codegen_ir_blocks<C>(blocks: Vec<IrBlock>, codegen: &C) -> Vec<C::ExecBlock>
-where
-    C: Codegen,
-{
-    blocks.iter().map(|b| codegen.compile_ir_block(b)).collect()
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(msg) => msg.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_default(),
+    }
 }
 
-unsafe fn compile_until_branch_or_eof<R, C>(
-    mmu: Mmu,
-    pc: u64,
-    rule: &R,
-    compiler: &C,
-) -> Result<Vec<IrBlock>, CompileError>
-where
-    C: Compiler,
-    R: MachineInstrParserRule<MachineInstr = C::Item>,
-{
-    let mut results = Vec::new();
+fn undefined_block(size: usize) -> IrBlock {
+    let exc = Exception::Undefined;
 
-    // This should not be implemented globally.
-    // reading MemoryFrame is unsafe.
+    let mut block = IrBlock::new(size);
+    block.append(
+        Ir::Value(Operand::imm(Type::U64, exc.syndrome(false))),
+        BlockDestination::Exception,
+    );
+    block
+}
 
-    let parser = MachineInstParser::new(ByteReader::new(mmu.iter(pc)), rule.clone());
-    for instr in parser {
-        let block = compiler.compile(instr.op);
-        let last_dest = block.items().last().unwrap().dest().clone();
-        results.push(block);
+fn instruction_abort_block(pc: u64, status: u8) -> IrBlock {
+    let exc = Exception::InstructionAbort { addr: pc, status };
 
-        if let BlockDestination::Exit | BlockDestination::Pc = last_dest {
-            break;
-        }
-    }
+    let mut block = IrBlock::new(0);
+    block.append(
+        Ir::Value(Operand::imm(Type::U64, exc.syndrome(false))),
+        BlockDestination::Exception,
+    );
+    block
+}
 
-    Ok(results)
+unsafe fn codegen_ir_blocks<C>(blocks: Vec<IrBlock>, codegen: &C) -> Vec<C::ExecBlock>
+where
+    C: Codegen,
+{
+    blocks.iter().map(|b| codegen.compile_ir_block(b)).collect()
 }
 
 impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> Board<C, R, G, A>
@@ -180,17 +468,14 @@ where
         &self,
         mut poll_incoming_data: impl FnMut() -> bool,
     ) -> Result<DebugEvent, Error> {
-        let mut mmu = self.mmu().clone();
+        let mmu = self.mmu().clone();
         let mut cpu = self
             .cpu_core
             .get_or(|| Mutex::new(self.cpu_init.clone()))
             .lock()
             .unwrap();
 
-        let mut ctx = ExecutionContext {
-            cpu: cpu.borrow_mut(),
-            mmu: &mut mmu,
-        };
+        let mut ctx = ExecutionContext::new(cpu.borrow_mut(), &mmu);
 
         match self.exec_mode {
             ExecutionMode::Continue => {
@@ -204,36 +489,40 @@ where
                     }
                     cycles += 1;
 
-                    if let Some(event) = self.step(&mut ctx) {
+                    if let Some(event) = self.step(&mut ctx)? {
                         break Ok(DebugEvent::Event(event));
                     };
                 }
             }
             ExecutionMode::Step => Ok(DebugEvent::Event(
-                self.step(&mut ctx).unwrap_or(Event::DoneStep),
+                self.step(&mut ctx)?.unwrap_or(Event::DoneStep),
             )),
         }
     }
 
-    pub unsafe fn step(&self, ctx: &mut ExecutionContext) -> Option<Event> {
-        let mut rule = self.mci_parser.clone();
-        let instr = rule
-            .parse(&mut ByteReader::new(ctx.mmu.iter(ctx.cpu().pc())))
-            .unwrap();
-        let block = self.ir_comp.compile(instr.op);
-        let compiled = self.ir_cgen.compile_ir_block(&block);
+    pub unsafe fn step(&self, ctx: &mut ExecutionContext) -> Result<Option<Event>, Error> {
+        let block = self.translate(ctx, true)?;
         self.mmu().clear_events();
 
-        compiled.execute(ctx);
+        for code in block.code() {
+            code.execute(ctx);
+        }
+        if ctx.take_shutdown().is_some() {
+            return Ok(Some(Event::Exit));
+        }
+        self.invalidate_modified_code();
+        self.tick_timer(ctx, 1);
+        self.poll_devices();
+        self.take_interrupt(ctx);
 
         if let Some(wp) = self.mmu().check_watchpoint_hit() {
-            return Some(Event::Watch(wp.0, wp.1));
+            return Ok(Some(Event::Watch(wp.0, wp.1)));
         }
 
         if self.breakpoints.contains(&ctx.cpu().pc()) {
-            return Some(Event::SwBreak);
+            return Ok(Some(Event::SwBreak));
         }
 
-        None
+        Ok(None)
     }
 }
@@ -1,7 +1,454 @@
-pub struct CodegenCache {}
+use crate::codegen::{Executable, ExecutionContext};
 
-impl CodegenCache {
+use smallvec::SmallVec;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+// Key of a translated block.
+//
+// Same guest address can be translated differently depending on the cpu mode
+// (e.g. exception level, selected stack pointer), so the mode bits are part of the key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockKey {
+    pub pc: u64,
+    pub mode: u64,
+}
+
+impl BlockKey {
+    pub fn new(pc: u64, mode: u64) -> Self {
+        Self { pc, mode }
+    }
+}
+
+// Translations of successors, by the key they were looked up with.
+type Links<E> = Mutex<SmallVec<[(BlockKey, Weak<TranslatedBlock<E>>); 2]>>;
+
+// Sequence of executable blocks generated from a guest code starting at `start`,
+// which are executed in order.
+//
+// A block whose branch targets are known at translation time is linked to the
+// translations of its successors, and execution continues into them directly
+// (see `execute_chain`). Links are removed when either block is invalidated.
+pub struct TranslatedBlock<E> {
+    start: u64,
+    phys_start: u64, // physical address of guest code, which is used for invalidation
+    size: u64,
+    code: Vec<E>,
+
+    successors: SmallVec<[u64; 2]>,
+    links: Links<E>,
+    valid: AtomicBool,
+    checked: AtomicU64, // MMU generation at which `start` last translated into `phys_start`
+}
+
+impl<E> TranslatedBlock<E> {
+    pub fn new(start: u64, size: u64, code: Vec<E>, successors: &[u64]) -> Self {
+        Self {
+            start,
+            phys_start: start,
+            size,
+            code,
+            successors: SmallVec::from_slice(successors),
+            links: Mutex::new(SmallVec::new()),
+            valid: AtomicBool::new(true),
+            checked: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    // Set the physical address of guest code, when it differs from the virtual address.
+    pub fn at_phys(mut self, phys_start: u64) -> Self {
+        self.phys_start = phys_start;
+        self
+    }
+
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn phys_start(&self) -> u64 {
+        self.phys_start
+    }
+
+    // Whether the translation of `start` was checked at MMU `generation`.
+    pub fn is_checked(&self, generation: u64) -> bool {
+        self.checked.load(Ordering::Acquire) == generation
+    }
+
+    pub fn set_checked(&self, generation: u64) {
+        self.checked.store(generation, Ordering::Release);
+    }
+
+    // Size of guest code this block is translated from.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn code(&self) -> &[E] {
+        &self.code
+    }
+
+    // Whether guest code of this block overlaps physical address `range`.
+    pub fn overlaps(&self, range: &Range<u64>) -> bool {
+        self.phys_start < range.end && range.start < self.phys_start + self.size
+    }
+
+    // Statically known branch targets of this block.
+    pub fn successors(&self) -> &[u64] {
+        &self.successors
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.valid.load(Ordering::Acquire)
+    }
+
+    // Link `next` as the successor of `key`. Only statically known successors can be
+    // linked.
+    pub fn link(&self, key: BlockKey, next: &Arc<TranslatedBlock<E>>) -> bool {
+        if !self.is_valid() || !self.successors.contains(&key.pc) {
+            return false;
+        }
+
+        let mut links = self.links.lock().unwrap();
+        links.retain(|(k, _)| *k != key);
+        links.push((key, Arc::downgrade(next)));
+
+        true
+    }
+
+    // Find the linked successor of `key`. Links to invalidated blocks are removed.
+    pub fn linked(&self, key: &BlockKey) -> Option<Arc<TranslatedBlock<E>>> {
+        let mut links = self.links.lock().unwrap();
+        let idx = links.iter().position(|(k, _)| k == key)?;
+
+        match links[idx].1.upgrade() {
+            Some(next) if next.is_valid() => Some(next),
+            _ => {
+                links.swap_remove(idx);
+                None
+            }
+        }
+    }
+
+    // Unlink this block from its successors. Predecessors find it invalid and drop
+    // their links when they are followed.
+    fn invalidate(&self) {
+        self.valid.store(false, Ordering::Release);
+        self.links.lock().unwrap().clear();
+    }
+}
+
+// Blocks executed by one call of `execute_chain`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Chain {
+    pub blocks: u64,
+    pub size: u64, // Size of guest code executed.
+}
+
+impl<E: Executable> TranslatedBlock<E> {
+    // Execute this block and continue into linked successors without returning.
+    //
+    // The chain leaves on an exit to a successor which isn't linked, after `budget`
+    // blocks, or when `stop` says so (e.g. for a pending interrupt). It also leaves when
+    // the guest requests a shutdown, writes translated code or flushes TLBs, as the
+    // caller has to handle those before the next block. Returns the last executed block.
+    pub(crate) unsafe fn execute_chain(
+        self: &Arc<Self>,
+        ctx: &mut ExecutionContext,
+        budget: u64,
+        mut stop: impl FnMut(&ExecutionContext) -> bool,
+    ) -> (Arc<Self>, Chain) {
+        let mut block = self.clone();
+        let mut chain = Chain::default();
+
+        loop {
+            for code in block.code() {
+                code.execute(ctx);
+            }
+            chain.blocks += 1;
+            chain.size += block.size();
+
+            if chain.blocks >= budget
+                || ctx.has_shutdown()
+                || ctx.mmu.has_code_events()
+                || stop(ctx)
+            {
+                break;
+            }
+
+            let key = BlockKey::new(ctx.cpu().pc(), ctx.cpu().translation_mode());
+            match block.linked(&key) {
+                Some(next) if next.is_checked(ctx.mmu.generation()) => block = next,
+                _ => break,
+            }
+        }
+
+        (block, chain)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+}
+
+impl CacheStats {
+    pub fn lookups(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    pub fn hit_ratio(&self) -> f64 {
+        if self.lookups() == 0 {
+            0.0
+        } else {
+            self.hits as f64 / self.lookups() as f64
+        }
+    }
+}
+
+pub struct CodegenCache<E> {
+    blocks: HashMap<BlockKey, Arc<TranslatedBlock<E>>>,
+    stats: CacheStats,
+}
+
+impl<E> CodegenCache<E> {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            blocks: HashMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, key: &BlockKey) -> Option<Arc<TranslatedBlock<E>>> {
+        let result = self.blocks.get(key).cloned();
+
+        if result.is_some() {
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+
+        result
+    }
+
+    pub fn insert(&mut self, key: BlockKey, block: TranslatedBlock<E>) -> Arc<TranslatedBlock<E>> {
+        let block = Arc::new(block);
+        self.blocks.insert(key, block.clone());
+
+        block
+    }
+
+    pub fn contains(&self, key: &BlockKey) -> bool {
+        self.blocks.contains_key(key)
+    }
+
+    // Remove every block translated from guest code in `range`.
+    // Returns the number of removed blocks.
+    pub fn invalidate_range(&mut self, range: Range<u64>) -> usize {
+        let before = self.blocks.len();
+        self.blocks.retain(|_, block| {
+            if block.overlaps(&range) {
+                block.invalidate();
+                false
+            } else {
+                true
+            }
+        });
+
+        let removed = before - self.blocks.len();
+        self.stats.invalidations += removed as u64;
+
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.stats.invalidations += self.blocks.len() as u64;
+        for block in self.blocks.values() {
+            block.invalidate();
+        }
+        self.blocks.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+}
+
+impl<E> Default for CodegenCache<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cache_hit_miss_test() {
+        let mut cache = CodegenCache::new();
+        let key = BlockKey::new(0x1000, 0);
+
+        assert!(cache.get(&key).is_none());
+
+        cache.insert(key, TranslatedBlock::new(0x1000, 8, vec![1, 2], &[]));
+
+        let block = cache.get(&key).unwrap();
+        assert_eq!(block.code(), &[1, 2]);
+        assert_eq!(block.size(), 8);
+
+        // Same address in other mode is not the same block.
+        assert!(cache.get(&BlockKey::new(0x1000, 1)).is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 2);
+    }
+
+    #[test]
+    fn cache_invalidate_range_test() {
+        let mut cache = CodegenCache::new();
+
+        cache.insert(
+            BlockKey::new(0x1000, 0),
+            TranslatedBlock::new(0x1000, 0x10, vec![0], &[]),
+        );
+        cache.insert(
+            BlockKey::new(0x1ff8, 0),
+            TranslatedBlock::new(0x1ff8, 0x10, vec![0], &[]),
+        );
+        cache.insert(
+            BlockKey::new(0x3000, 0),
+            TranslatedBlock::new(0x3000, 0x10, vec![0], &[]),
+        );
+
+        // Block crossing the page boundary is also removed.
+        assert_eq!(cache.invalidate_range(0x2000..0x3000), 1);
+        assert!(!cache.contains(&BlockKey::new(0x1ff8, 0)));
+        assert!(cache.contains(&BlockKey::new(0x1000, 0)));
+        assert!(cache.contains(&BlockKey::new(0x3000, 0)));
+
+        assert_eq!(cache.invalidate_range(0..u64::MAX), 2);
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats().invalidations, 3);
+    }
+
+    #[test]
+    fn block_link_test() {
+        let mut cache = CodegenCache::new();
+
+        let key_a = BlockKey::new(0x1000, 0);
+        let key_b = BlockKey::new(0x2000, 0);
+        let a = cache.insert(key_a, TranslatedBlock::new(0x1000, 4, vec![0], &[0x2000]));
+        let b = cache.insert(key_b, TranslatedBlock::new(0x2000, 4, vec![1], &[0x1000]));
+
+        // Only static successors can be linked.
+        assert!(!a.link(key_a, &a));
+        assert!(a.link(key_b, &b));
+        assert!(b.link(key_a, &a));
+
+        assert_eq!(a.linked(&key_b).unwrap().code(), &[1]);
+        assert!(a.linked(&BlockKey::new(0x2000, 1)).is_none());
+
+        // Invalidating a block unlinks it from its predecessors.
+        cache.invalidate_range(0x2000..0x2004);
+        assert!(!b.is_valid());
+        assert!(a.linked(&key_b).is_none());
+        assert!(b.linked(&key_a).is_none());
+    }
+
+    #[test]
+    fn block_chain_test() {
+        use crate::codegen::FnExec;
+        use crate::cpu::Architecture;
+        use crate::softmmu::Mmu;
+        use crate::Cpu;
+
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let x0 = cpu.reg_by_name("x0").unwrap();
+        cpu.set_pc(0x1000);
+
+        // Loop of two blocks, which counts iterations in x0 and leaves after 10 of them.
+        let mut cache = CodegenCache::new();
+        let mode = cpu.translation_mode();
+        let key_a = BlockKey::new(0x1000, mode);
+        let key_b = BlockKey::new(0x1004, mode);
+        let a = TranslatedBlock::new(
+            0x1000,
+            4,
+            vec![FnExec::new(|ctx| ctx.cpu_mut().set_pc(0x1004))],
+            &[0x1004],
+        );
+        let b = TranslatedBlock::new(
+            0x1004,
+            4,
+            vec![FnExec::new(move |ctx| {
+                let count = ctx.cpu().gpr(x0).u64() + 1;
+                *ctx.cpu_mut().gpr_mut(x0).u64_mut() = count;
+                ctx.cpu_mut()
+                    .set_pc(if count < 10 { 0x1000 } else { 0x2000 });
+            })],
+            &[0x1000, 0x2000],
+        );
+        let a = cache.insert(key_a, a);
+        let b = cache.insert(key_b, b);
+        a.set_checked(mmu.generation());
+        b.set_checked(mmu.generation());
+
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+
+        // Without links, only the first block runs.
+        let (last, chain) = unsafe { a.execute_chain(&mut ctx, u64::MAX, |_| false) };
+        assert!(Arc::ptr_eq(&last, &a));
+        assert_eq!(chain.blocks, 1);
+
+        assert!(a.link(key_b, &b));
+        assert!(b.link(key_a, &a));
+
+        // Linked blocks run until the budget runs out.
+        let (last, chain) = unsafe { b.execute_chain(&mut ctx, 5, |_| false) };
+        assert!(Arc::ptr_eq(&last, &b));
+        assert_eq!(
+            chain,
+            Chain {
+                blocks: 5,
+                size: 20
+            }
+        );
+        assert_eq!(ctx.cpu().gpr(x0).u64(), 3);
+
+        // Then until the exit to 0x2000, which isn't linked.
+        let (last, chain) = unsafe { a.execute_chain(&mut ctx, u64::MAX, |_| false) };
+        assert!(Arc::ptr_eq(&last, &b));
+        assert_eq!(chain.blocks, 14);
+        assert_eq!(ctx.cpu().gpr(x0).u64(), 10);
+        assert_eq!(ctx.cpu().pc(), 0x2000);
+
+        // Or until it's stopped.
+        *ctx.cpu_mut().gpr_mut(x0).u64_mut() = 0;
+        ctx.cpu_mut().set_pc(0x1000);
+        let (_, chain) =
+            unsafe { a.execute_chain(&mut ctx, u64::MAX, |ctx| ctx.cpu().gpr(x0).u64() == 2) };
+        assert_eq!(chain.blocks, 4);
+
+        // An invalidated successor isn't entered.
+        cache.invalidate_range(0x1004..0x1008);
+        ctx.cpu_mut().set_pc(0x1000);
+        let (last, chain) = unsafe { a.execute_chain(&mut ctx, u64::MAX, |_| false) };
+        assert!(Arc::ptr_eq(&last, &a));
+        assert_eq!(chain.blocks, 1);
     }
 }
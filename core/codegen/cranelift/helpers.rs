@@ -0,0 +1,172 @@
+use crate::codegen::flag_policy::FlagPolicy;
+use crate::codegen::ExecutionContext;
+use crate::exception::{self, Exception};
+use crate::ir::Type;
+use crate::register::RegId;
+use crate::value::Value;
+
+pub(super) const STATUS_OK: u8 = 0;
+pub(super) const STATUS_EXCEPTION: u8 = 1;
+pub(super) const STATUS_EXIT: u8 = 2;
+
+// State of a single native code call.
+//
+// Unwinding through JIT frames is not supported, so helpers never panic. Exceptions
+// are recorded here instead, the generated code returns early when `status` is set and
+// the caller takes them on the Rust side.
+#[repr(C)]
+pub(super) struct Frame<'a> {
+    pub status: u8,
+    pub exception: Option<Exception>,
+    pub flag_policy: &'a dyn FlagPolicy,
+}
+
+impl<'a> Frame<'a> {
+    pub fn new(flag_policy: &'a dyn FlagPolicy) -> Self {
+        Self {
+            status: STATUS_OK,
+            exception: None,
+            flag_policy,
+        }
+    }
+}
+
+type Ctx<'a> = ExecutionContext<'a>;
+
+fn write_sized(reg: &mut Value, size: u8, val: u64) {
+    match size {
+        1 => *reg.u8_mut() = val as u8,
+        2 => *reg.u16_mut() = val as u16,
+        4 => *reg.u32_mut() = val as u32,
+        _ => *reg.u64_mut() = val,
+    }
+}
+
+pub(super) extern "C" fn gpr_read(ctx: *mut Ctx<'_>, id: u8) -> u64 {
+    unsafe { (*ctx).cpu().gpr(RegId(id)).u64() }
+}
+
+pub(super) extern "C" fn gpr_write(ctx: *mut Ctx<'_>, id: u8, val: u64, size: u8) {
+    unsafe { write_sized((*ctx).cpu_mut().gpr_mut(RegId(id)), size, val) }
+}
+
+pub(super) extern "C" fn fpr_read(ctx: *mut Ctx<'_>, id: u8) -> u64 {
+    unsafe { (*ctx).cpu().fpr(RegId(id)).u64() }
+}
+
+pub(super) extern "C" fn fpr_write(ctx: *mut Ctx<'_>, id: u8, val: u64, size: u8) {
+    unsafe { write_sized((*ctx).cpu_mut().fpr_mut(RegId(id)), size, val) }
+}
+
+pub(super) extern "C" fn sys_read(ctx: *mut Ctx<'_>, id: u8) -> u64 {
+    unsafe { (*ctx).cpu().sys(RegId(id)).u64() }
+}
+
+pub(super) extern "C" fn sys_write(ctx: *mut Ctx<'_>, id: u8, val: u64, size: u8) {
+    unsafe { write_sized((*ctx).cpu_mut().sys_mut(RegId(id)), size, val) }
+}
+
+pub(super) extern "C" fn pc_read(ctx: *mut Ctx<'_>) -> u64 {
+    unsafe { (*ctx).cpu().pc() }
+}
+
+pub(super) extern "C" fn pc_write(ctx: *mut Ctx<'_>, val: u64) {
+    unsafe { (*ctx).cpu_mut().set_pc(val) }
+}
+
+pub(super) extern "C" fn flag_read(ctx: *mut Ctx<'_>) -> u64 {
+    unsafe { (*ctx).cpu().flag() }
+}
+
+pub(super) extern "C" fn flag_write(ctx: *mut Ctx<'_>, val: u64) {
+    unsafe { (*ctx).cpu().set_flag(val) }
+}
+
+pub(super) extern "C" fn add_carry(
+    ctx: *mut Ctx<'_>,
+    frame: *mut Frame<'_>,
+    size: u8,
+    a: u64,
+    b: u64,
+) {
+    unsafe {
+        let ty = Type::uscalar_from_size(size as usize);
+        (*frame).flag_policy.add_carry(ty, a, b, (*ctx).cpu());
+    }
+}
+
+pub(super) extern "C" fn sub_carry(
+    ctx: *mut Ctx<'_>,
+    frame: *mut Frame<'_>,
+    size: u8,
+    a: u64,
+    b: u64,
+) {
+    unsafe {
+        let ty = Type::uscalar_from_size(size as usize);
+        (*frame).flag_policy.sub_carry(ty, a, b, (*ctx).cpu());
+    }
+}
+
+pub(super) extern "C" fn mem_read(
+    ctx: *mut Ctx<'_>,
+    frame: *mut Frame<'_>,
+    addr: u64,
+    size: u8,
+) -> u64 {
+    unsafe {
+        let mut buf = [0u8; 8];
+        match (*ctx).mem_read(addr, &mut buf[..size as usize]) {
+            Ok(()) => u64::from_le_bytes(buf),
+            Err(e) => {
+                (*frame).status = STATUS_EXCEPTION;
+                (*frame).exception = Some(Exception::data_abort(addr, false, &e));
+                0
+            }
+        }
+    }
+}
+
+pub(super) extern "C" fn mem_write(
+    ctx: *mut Ctx<'_>,
+    frame: *mut Frame<'_>,
+    addr: u64,
+    val: u64,
+    size: u8,
+) {
+    unsafe {
+        let buf = val.to_le_bytes();
+        if let Err(e) = (*ctx).mem_write(addr, &buf[..size as usize]) {
+            (*frame).status = STATUS_EXCEPTION;
+            (*frame).exception = Some(Exception::data_abort(addr, true, &e));
+        }
+    }
+}
+
+pub(super) extern "C" fn invalidate_code(ctx: *mut Ctx<'_>, addr: u64) {
+    unsafe { (*ctx).mmu.invalidate_code(addr..addr + 1) }
+}
+
+pub(super) extern "C" fn invalidate_code_all(ctx: *mut Ctx<'_>) {
+    unsafe { (*ctx).mmu.invalidate_all_code() }
+}
+
+pub(super) extern "C" fn invalidate_tlb(ctx: *mut Ctx<'_>) {
+    unsafe { (*ctx).mmu.flush_tlb() }
+}
+
+pub(super) extern "C" fn raise_exception(ctx: *mut Ctx<'_>, frame: *mut Frame<'_>, esr: u64) {
+    unsafe {
+        let pc = (*ctx).cpu().pc();
+        (*frame).status = STATUS_EXCEPTION;
+        (*frame).exception = Some(Exception::from_syndrome(esr, pc));
+    }
+}
+
+pub(super) extern "C" fn exception_return(ctx: *mut Ctx<'_>) {
+    unsafe { exception::exception_return((*ctx).cpu_mut()) }
+}
+
+pub(super) extern "C" fn exit(frame: *mut Frame<'_>) {
+    unsafe { (*frame).status = STATUS_EXIT }
+}
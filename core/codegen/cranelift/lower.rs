@@ -0,0 +1,633 @@
+use ::cranelift::codegen::ir::FuncRef;
+use ::cranelift::prelude::{
+    types, AbiParam, Block, FunctionBuilder, InstBuilder, IntCC, MemFlags, Signature,
+    Type as ClType, Value as ClValue, Variable,
+};
+use cranelift_jit::JITModule;
+use cranelift_module::{FuncId, Module};
+
+use crate::error::CodegenError;
+use crate::ir::{BlockDestination, Ir, IrBlock, Operand, TempId, Type};
+
+use super::helpers;
+
+// Runtime functions called from the generated code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum Helper {
+    GprRead,
+    GprWrite,
+    FprRead,
+    FprWrite,
+    SysRead,
+    SysWrite,
+    PcRead,
+    PcWrite,
+    FlagRead,
+    FlagWrite,
+    AddCarry,
+    SubCarry,
+    MemRead,
+    MemWrite,
+    InvalidateCode,
+    InvalidateCodeAll,
+    InvalidateTlb,
+    RaiseException,
+    ExceptionReturn,
+    Exit,
+}
+
+impl Helper {
+    pub const ALL: [Helper; 20] = [
+        Helper::GprRead,
+        Helper::GprWrite,
+        Helper::FprRead,
+        Helper::FprWrite,
+        Helper::SysRead,
+        Helper::SysWrite,
+        Helper::PcRead,
+        Helper::PcWrite,
+        Helper::FlagRead,
+        Helper::FlagWrite,
+        Helper::AddCarry,
+        Helper::SubCarry,
+        Helper::MemRead,
+        Helper::MemWrite,
+        Helper::InvalidateCode,
+        Helper::InvalidateCodeAll,
+        Helper::InvalidateTlb,
+        Helper::RaiseException,
+        Helper::ExceptionReturn,
+        Helper::Exit,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Helper::GprRead => "gpr_read",
+            Helper::GprWrite => "gpr_write",
+            Helper::FprRead => "fpr_read",
+            Helper::FprWrite => "fpr_write",
+            Helper::SysRead => "sys_read",
+            Helper::SysWrite => "sys_write",
+            Helper::PcRead => "pc_read",
+            Helper::PcWrite => "pc_write",
+            Helper::FlagRead => "flag_read",
+            Helper::FlagWrite => "flag_write",
+            Helper::AddCarry => "add_carry",
+            Helper::SubCarry => "sub_carry",
+            Helper::MemRead => "mem_read",
+            Helper::MemWrite => "mem_write",
+            Helper::InvalidateCode => "invalidate_code",
+            Helper::InvalidateCodeAll => "invalidate_code_all",
+            Helper::InvalidateTlb => "invalidate_tlb",
+            Helper::RaiseException => "raise_exception",
+            Helper::ExceptionReturn => "exception_return",
+            Helper::Exit => "exit",
+        }
+    }
+
+    pub fn address(self) -> *const u8 {
+        match self {
+            Helper::GprRead => helpers::gpr_read as *const u8,
+            Helper::GprWrite => helpers::gpr_write as *const u8,
+            Helper::FprRead => helpers::fpr_read as *const u8,
+            Helper::FprWrite => helpers::fpr_write as *const u8,
+            Helper::SysRead => helpers::sys_read as *const u8,
+            Helper::SysWrite => helpers::sys_write as *const u8,
+            Helper::PcRead => helpers::pc_read as *const u8,
+            Helper::PcWrite => helpers::pc_write as *const u8,
+            Helper::FlagRead => helpers::flag_read as *const u8,
+            Helper::FlagWrite => helpers::flag_write as *const u8,
+            Helper::AddCarry => helpers::add_carry as *const u8,
+            Helper::SubCarry => helpers::sub_carry as *const u8,
+            Helper::MemRead => helpers::mem_read as *const u8,
+            Helper::MemWrite => helpers::mem_write as *const u8,
+            Helper::InvalidateCode => helpers::invalidate_code as *const u8,
+            Helper::InvalidateCodeAll => helpers::invalidate_code_all as *const u8,
+            Helper::InvalidateTlb => helpers::invalidate_tlb as *const u8,
+            Helper::RaiseException => helpers::raise_exception as *const u8,
+            Helper::ExceptionReturn => helpers::exception_return as *const u8,
+            Helper::Exit => helpers::exit as *const u8,
+        }
+    }
+
+    pub fn signature(self, mut sig: Signature, ptr: ClType) -> Signature {
+        use types::{I64, I8};
+
+        let (params, returns): (&[ClType], &[ClType]) = match self {
+            Helper::GprRead | Helper::FprRead | Helper::SysRead => (&[ptr, I8], &[I64]),
+            Helper::GprWrite | Helper::FprWrite | Helper::SysWrite => (&[ptr, I8, I64, I8], &[]),
+            Helper::PcRead | Helper::FlagRead => (&[ptr], &[I64]),
+            Helper::PcWrite | Helper::FlagWrite | Helper::InvalidateCode => (&[ptr, I64], &[]),
+            Helper::AddCarry | Helper::SubCarry => (&[ptr, ptr, I8, I64, I64], &[]),
+            Helper::MemRead => (&[ptr, ptr, I64, I8], &[I64]),
+            Helper::MemWrite => (&[ptr, ptr, I64, I64, I8], &[]),
+            Helper::RaiseException => (&[ptr, ptr, I64], &[]),
+            Helper::InvalidateCodeAll
+            | Helper::InvalidateTlb
+            | Helper::ExceptionReturn
+            | Helper::Exit => (&[ptr], &[]),
+        };
+
+        sig.params
+            .extend(params.iter().map(|ty| AbiParam::new(*ty)));
+        sig.returns
+            .extend(returns.iter().map(|ty| AbiParam::new(*ty)));
+        sig
+    }
+}
+
+fn cl_type(ty: Type) -> Result<ClType, CodegenError> {
+    Ok(match ty {
+        Type::Bool | Type::U8 | Type::I8 | Type::Void => types::I8,
+        Type::U16 | Type::I16 => types::I16,
+        Type::U32 | Type::I32 => types::I32,
+        Type::U64 | Type::I64 => types::I64,
+        _ => return Err(CodegenError::Unsupported("float and vector types")),
+    })
+}
+
+fn is_signed(ty: Type) -> bool {
+    matches!(ty, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+}
+
+// Lowers IR into a cranelift function `fn(ctx, frame) -> u64`.
+//
+// Guest state is only accessed through helper calls. Any fallible helper is followed
+// by a check of `Frame::status`, which leaves the function early on error.
+// Temporaries of the block live in cranelift variables.
+pub(super) struct Translator<'a, 'b> {
+    builder: FunctionBuilder<'b>,
+    module: &'a mut JITModule,
+    helpers: &'a [FuncId],
+    refs: [Option<FuncRef>; Helper::ALL.len()],
+    temps: Vec<Option<Type>>,
+
+    ctx: ClValue,
+    frame: ClValue,
+    bail: Block,
+}
+
+impl<'a, 'b> Translator<'a, 'b> {
+    pub fn new(
+        mut builder: FunctionBuilder<'b>,
+        module: &'a mut JITModule,
+        helpers: &'a [FuncId],
+    ) -> Self {
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+
+        let ctx = builder.block_params(entry)[0];
+        let frame = builder.block_params(entry)[1];
+
+        let bail = builder.create_block();
+
+        Self {
+            builder,
+            module,
+            helpers,
+            refs: [None; Helper::ALL.len()],
+            temps: Vec::new(),
+            ctx,
+            frame,
+            bail,
+        }
+    }
+
+    // Body of `Codegen::compile_ir`. Returns the value of `ir`.
+    pub fn translate_ir(mut self, ir: &Ir) -> Result<(), CodegenError> {
+        let (val, ty) = self.lower_ir(ir)?;
+        let val = self.convert(val, ty, Type::U64, false)?;
+        self.finish(val);
+
+        Ok(())
+    }
+
+    // Body of `Codegen::compile_ir_block`.
+    pub fn translate_block(mut self, block: &IrBlock) -> Result<(), CodegenError> {
+        let mut pc_written = false;
+        for item in block.items() {
+            let (val, ty) = self.lower_ir(item.root())?;
+            if item.dest().is_branch() {
+                pc_written = true;
+            }
+            self.lower_dest(item.dest(), val, ty)?;
+        }
+
+        if !pc_written {
+            let pc = self.call(Helper::PcRead, &[self.ctx])[0];
+            let next_pc = self
+                .builder
+                .ins()
+                .iadd_imm(pc, block.original_size() as i64);
+            self.call(Helper::PcWrite, &[self.ctx, next_pc]);
+        }
+
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.finish(zero);
+
+        Ok(())
+    }
+
+    fn finish(mut self, ret: ClValue) {
+        self.builder.ins().return_(&[ret]);
+
+        self.builder.switch_to_block(self.bail);
+        let zero = self.builder.ins().iconst(types::I64, 0);
+        self.builder.ins().return_(&[zero]);
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+    }
+
+    fn call(&mut self, helper: Helper, args: &[ClValue]) -> Vec<ClValue> {
+        let idx = helper as usize;
+        let func_ref = match self.refs[idx] {
+            Some(func_ref) => func_ref,
+            None => {
+                let func_ref = self
+                    .module
+                    .declare_func_in_func(self.helpers[idx], self.builder.func);
+                self.refs[idx] = Some(func_ref);
+                func_ref
+            }
+        };
+
+        let inst = self.builder.ins().call(func_ref, args);
+        self.builder.inst_results(inst).to_vec()
+    }
+
+    // Leave the function if a helper reported an error.
+    fn check_status(&mut self) {
+        let status = self
+            .builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), self.frame, 0);
+        let next = self.builder.create_block();
+
+        self.builder.ins().brnz(status, self.bail, &[]);
+        self.builder.ins().jump(next, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    fn imm(&mut self, ty: ClType, val: u64) -> ClValue {
+        self.builder.ins().iconst(ty, val as i64)
+    }
+
+    fn convert(
+        &mut self,
+        val: ClValue,
+        from: Type,
+        to: Type,
+        signed: bool,
+    ) -> Result<ClValue, CodegenError> {
+        let (from_cl, to_cl) = (cl_type(from)?, cl_type(to)?);
+
+        let val = if from_cl.bits() > to_cl.bits() {
+            self.builder.ins().ireduce(to_cl, val)
+        } else if from_cl.bits() < to_cl.bits() {
+            if signed {
+                self.builder.ins().sextend(to_cl, val)
+            } else {
+                self.builder.ins().uextend(to_cl, val)
+            }
+        } else {
+            val
+        };
+
+        if to == Type::Bool && from != Type::Bool {
+            Ok(self.builder.ins().band_imm(val, 1))
+        } else {
+            Ok(val)
+        }
+    }
+
+    // Lower `op` and convert it to `ty`.
+    fn lower_op_as(&mut self, op: &Operand, ty: Type) -> Result<ClValue, CodegenError> {
+        let (val, from) = self.lower_op(op)?;
+        self.convert(val, from, ty, false)
+    }
+
+    fn lower_op(&mut self, op: &Operand) -> Result<(ClValue, Type), CodegenError> {
+        Ok(match op {
+            Operand::Ir(ir) => self.lower_ir(ir)?,
+            Operand::VoidIr(ir) => {
+                self.lower_ir(ir)?;
+                (self.imm(types::I8, 0), Type::Void)
+            }
+            Operand::Gpr(ty, reg) => self.read_reg(Helper::GprRead, *ty, reg.0)?,
+            Operand::Fpr(ty, reg) => self.read_reg(Helper::FprRead, *ty, reg.0)?,
+            Operand::Sys(ty, reg) => self.read_reg(Helper::SysRead, *ty, reg.0)?,
+            Operand::Immediate(ty, imm) => (self.imm(cl_type(*ty)?, imm & ty.gen_mask()), *ty),
+            Operand::ImmediateValue(ty, imm) if ty.is_scalar() && !ty.is_float() => {
+                (self.imm(cl_type(*ty)?, imm.u64() & ty.gen_mask()), *ty)
+            }
+            Operand::Temp(ty, id) => (self.read_temp(*ty, *id)?, *ty),
+            Operand::Ip => (self.call(Helper::PcRead, &[self.ctx])[0], Type::U64),
+            Operand::Flag => (self.call(Helper::FlagRead, &[self.ctx])[0], Type::U64),
+            Operand::ImmediateValue(..) => {
+                return Err(CodegenError::Unsupported("float and vector types"))
+            }
+            Operand::Dbg(..) => return Err(CodegenError::Unsupported("debug operands")),
+        })
+    }
+
+    fn read_reg(
+        &mut self,
+        helper: Helper,
+        ty: Type,
+        id: u8,
+    ) -> Result<(ClValue, Type), CodegenError> {
+        let id = self.imm(types::I8, id as u64);
+        let val = self.call(helper, &[self.ctx, id])[0];
+
+        Ok((self.convert(val, Type::U64, ty, false)?, ty))
+    }
+
+    fn read_temp(&mut self, ty: Type, id: TempId) -> Result<ClValue, CodegenError> {
+        let declared = self.temps.get(id.0 as usize).copied().flatten();
+        match declared {
+            Some(declared) => {
+                let val = self.builder.use_var(Variable::from_u32(id.0 as u32));
+                self.convert(val, declared, ty, false)
+            }
+            // Read before any write, e.g. in a single IR.
+            None => Err(CodegenError::InvalidType),
+        }
+    }
+
+    fn write_temp(&mut self, ty: Type, id: TempId, val: ClValue) -> Result<(), CodegenError> {
+        let idx = id.0 as usize;
+        if idx >= self.temps.len() {
+            self.temps.resize(idx + 1, None);
+        }
+
+        let var = Variable::from_u32(id.0 as u32);
+        let declared = match self.temps[idx] {
+            Some(declared) => declared,
+            None => {
+                self.builder.declare_var(var, cl_type(ty)?);
+                self.temps[idx] = Some(ty);
+                ty
+            }
+        };
+
+        let val = self.convert(val, ty, declared, false)?;
+        self.builder.def_var(var, val);
+
+        Ok(())
+    }
+
+    fn lower_ir(&mut self, ir: &Ir) -> Result<(ClValue, Type), CodegenError> {
+        let val = match ir {
+            Ir::Add(t, op1, op2)
+            | Ir::Sub(t, op1, op2)
+            | Ir::Mul(t, op1, op2)
+            | Ir::And(t, op1, op2)
+            | Ir::Or(t, op1, op2)
+            | Ir::Xor(t, op1, op2) => {
+                let lhs = self.lower_op_as(op1, *t)?;
+                let rhs = self.lower_op_as(op2, *t)?;
+                let ins = self.builder.ins();
+
+                match ir {
+                    Ir::Add(..) => ins.iadd(lhs, rhs),
+                    Ir::Sub(..) => ins.isub(lhs, rhs),
+                    Ir::Mul(..) => ins.imul(lhs, rhs),
+                    Ir::And(..) => ins.band(lhs, rhs),
+                    Ir::Or(..) => ins.bor(lhs, rhs),
+                    _ => ins.bxor(lhs, rhs),
+                }
+            }
+            Ir::Div(t, op1, op2) | Ir::Mod(t, op1, op2) => {
+                let lhs = self.lower_op_as(op1, *t)?;
+                let rhs = self.lower_op_as(op2, *t)?;
+                self.lower_div(*t, lhs, rhs, matches!(ir, Ir::Mod(..)))?
+            }
+            Ir::Addc(t, op1, op2) | Ir::Subc(t, op1, op2) => {
+                let lhs = self.lower_op_as(op1, *t)?;
+                let rhs = self.lower_op_as(op2, *t)?;
+                let a = self.convert(lhs, *t, Type::U64, false)?;
+                let b = self.convert(rhs, *t, Type::U64, false)?;
+                let size = self.imm(types::I8, t.size() as u64);
+
+                if let Ir::Addc(..) = ir {
+                    self.call(Helper::AddCarry, &[self.ctx, self.frame, size, a, b]);
+                    self.builder.ins().iadd(lhs, rhs)
+                } else {
+                    self.call(Helper::SubCarry, &[self.ctx, self.frame, size, a, b]);
+                    self.builder.ins().isub(lhs, rhs)
+                }
+            }
+            Ir::Not(t, op) => {
+                let val = self.lower_op_as(op, *t)?;
+                if *t == Type::Bool {
+                    self.builder.ins().bxor_imm(val, 1)
+                } else {
+                    self.builder.ins().bnot(val)
+                }
+            }
+            Ir::LShl(t, op1, op2)
+            | Ir::LShr(t, op1, op2)
+            | Ir::AShr(t, op1, op2)
+            | Ir::Rotr(t, op1, op2) => {
+                let lhs = self.lower_op_as(op1, *t)?;
+                let (amount, _) = self.lower_op(op2)?;
+                let ins = self.builder.ins();
+
+                match ir {
+                    Ir::LShl(..) => ins.ishl(lhs, amount),
+                    Ir::LShr(..) => ins.ushr(lhs, amount),
+                    Ir::AShr(..) if is_signed(*t) => ins.sshr(lhs, amount),
+                    Ir::AShr(..) => ins.ushr(lhs, amount),
+                    _ => ins.rotr(lhs, amount),
+                }
+            }
+            Ir::Load(t, op) => {
+                let addr = self.lower_op_as(op, Type::U64)?;
+                let size = self.imm(types::I8, t.size() as u64);
+                cl_type(*t)?;
+
+                let val = self.call(Helper::MemRead, &[self.ctx, self.frame, addr, size])[0];
+                self.check_status();
+                self.convert(val, Type::U64, *t, false)?
+            }
+            Ir::ZextCast(t, op) | Ir::BitCast(t, op) => self.lower_op_as(op, *t)?,
+            Ir::SextCast(t, op) => {
+                let (val, from) = self.lower_op(op)?;
+                self.convert(val, from, *t, is_signed(from))?
+            }
+            Ir::If(t, cond, if_true, if_false) => {
+                let (cond, _) = self.lower_op(cond)?;
+
+                let then_block = self.builder.create_block();
+                let else_block = self.builder.create_block();
+                let merge_block = self.builder.create_block();
+                let result = self.builder.append_block_param(merge_block, cl_type(*t)?);
+
+                self.builder.ins().brnz(cond, then_block, &[]);
+                self.builder.ins().jump(else_block, &[]);
+
+                self.builder.switch_to_block(then_block);
+                let val = self.lower_op_as(if_true, *t)?;
+                self.builder.ins().jump(merge_block, &[val]);
+
+                self.builder.switch_to_block(else_block);
+                let val = self.lower_op_as(if_false, *t)?;
+                self.builder.ins().jump(merge_block, &[val]);
+
+                self.builder.switch_to_block(merge_block);
+                result
+            }
+            Ir::CmpEq(op1, op2)
+            | Ir::CmpNe(op1, op2)
+            | Ir::CmpGt(op1, op2)
+            | Ir::CmpLt(op1, op2) => {
+                let (lhs, t) = self.lower_op(op1)?;
+                let rhs = self.lower_op_as(op2, t)?;
+                let cc = match ir {
+                    Ir::CmpEq(..) => IntCC::Equal,
+                    Ir::CmpNe(..) => IntCC::NotEqual,
+                    Ir::CmpGt(..) => IntCC::UnsignedGreaterThan,
+                    _ => IntCC::UnsignedLessThan,
+                };
+
+                self.builder.ins().icmp(cc, lhs, rhs)
+            }
+            Ir::Value(op) => return self.lower_op(op),
+            Ir::Nop => self.imm(types::I8, 0),
+            Ir::Shuffle(..) => return Err(CodegenError::Unsupported("vector shuffles")),
+        };
+
+        Ok((val, ir.get_type()))
+    }
+
+    // Division never traps: x / 0 is 0 and signed overflow wraps, as on AArch64.
+    fn lower_div(
+        &mut self,
+        t: Type,
+        lhs: ClValue,
+        rhs: ClValue,
+        rem: bool,
+    ) -> Result<ClValue, CodegenError> {
+        let ty = cl_type(t)?;
+        let zero = self.imm(ty, 0);
+        let one = self.imm(ty, 1);
+
+        let mut invalid = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        if is_signed(t) {
+            let min = self.imm(ty, 1 << (ty.bits() - 1));
+            let lhs_min = self.builder.ins().icmp(IntCC::Equal, lhs, min);
+            let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+            let overflow = self.builder.ins().band(lhs_min, minus_one);
+            invalid = self.builder.ins().bor(invalid, overflow);
+        }
+
+        let divisor = self.builder.ins().select(invalid, one, rhs);
+        let ins = self.builder.ins();
+        let result = match (is_signed(t), rem) {
+            (true, false) => ins.sdiv(lhs, divisor),
+            (true, true) => ins.srem(lhs, divisor),
+            (false, false) => ins.udiv(lhs, divisor),
+            (false, true) => ins.urem(lhs, divisor),
+        };
+
+        let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, rhs, 0);
+        Ok(self.builder.ins().select(is_zero, zero, result))
+    }
+
+    fn lower_dest(
+        &mut self,
+        dest: &BlockDestination,
+        val: ClValue,
+        ty: Type,
+    ) -> Result<(), CodegenError> {
+        match dest {
+            BlockDestination::Flags => {
+                let val = self.convert(val, ty, Type::U64, false)?;
+                self.call(Helper::FlagWrite, &[self.ctx, val]);
+            }
+            BlockDestination::Pc => {
+                let val = self.convert(val, ty, Type::U64, false)?;
+                self.call(Helper::PcWrite, &[self.ctx, val]);
+            }
+            BlockDestination::Gpr(t, reg) => self.write_reg(Helper::GprWrite, *t, reg.0, val)?,
+            BlockDestination::Fpr(t, reg) => self.write_reg(Helper::FprWrite, *t, reg.0, val)?,
+            BlockDestination::Sys(t, reg) => self.write_reg(Helper::SysWrite, *t, reg.0, val)?,
+            BlockDestination::Memory(t, addr) => {
+                let addr = self.imm(types::I64, *addr);
+                self.write_mem(*t, addr, val)?;
+            }
+            BlockDestination::MemoryRelI64(t, reg, offs) => {
+                let (base, _) = self.read_reg(Helper::GprRead, Type::U64, reg.0)?;
+                let addr = self.builder.ins().iadd_imm(base, *offs);
+                self.write_mem(*t, addr, val)?;
+            }
+            BlockDestination::MemoryRelU64(t, reg, offs) => {
+                let (base, _) = self.read_reg(Helper::GprRead, Type::U64, reg.0)?;
+                let addr = self.builder.ins().iadd_imm(base, *offs as i64);
+                self.write_mem(*t, addr, val)?;
+            }
+            BlockDestination::MemoryIr(ir) => {
+                let (addr, addr_ty) = self.lower_ir(ir)?;
+                let addr = self.convert(addr, addr_ty, Type::U64, false)?;
+                self.write_mem(ty, addr, val)?;
+            }
+            BlockDestination::InvalidateCode => {
+                self.call(Helper::InvalidateCode, &[self.ctx, val]);
+            }
+            BlockDestination::InvalidateCodeAll => {
+                self.call(Helper::InvalidateCodeAll, &[self.ctx]);
+            }
+            BlockDestination::InvalidateTlb => {
+                self.call(Helper::InvalidateTlb, &[self.ctx]);
+            }
+            BlockDestination::Temp(t, id) => self.write_temp(*t, *id, val)?,
+            BlockDestination::Exception => {
+                let esr = self.convert(val, ty, Type::U64, false)?;
+                self.call(Helper::RaiseException, &[self.ctx, self.frame, esr]);
+                self.check_status();
+            }
+            BlockDestination::ExceptionReturn => {
+                self.call(Helper::ExceptionReturn, &[self.ctx]);
+            }
+            BlockDestination::None => {}
+            BlockDestination::Exit => {
+                self.call(Helper::Exit, &[self.frame]);
+                self.builder.ins().jump(self.bail, &[]);
+
+                let unreachable = self.builder.create_block();
+                self.builder.switch_to_block(unreachable);
+            }
+            BlockDestination::FprSlot(..) => {
+                return Err(CodegenError::Unsupported("vector elements"))
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_reg(
+        &mut self,
+        helper: Helper,
+        ty: Type,
+        id: u8,
+        val: ClValue,
+    ) -> Result<(), CodegenError> {
+        let val = self.convert(val, ty, Type::U64, false)?;
+        let id = self.imm(types::I8, id as u64);
+        let size = self.imm(types::I8, ty.size() as u64);
+        self.call(helper, &[self.ctx, id, val, size]);
+
+        Ok(())
+    }
+
+    fn write_mem(&mut self, ty: Type, addr: ClValue, val: ClValue) -> Result<(), CodegenError> {
+        let val = self.convert(val, ty, Type::U64, false)?;
+        let size = self.imm(types::I8, ty.size() as u64);
+        self.call(Helper::MemWrite, &[self.ctx, self.frame, addr, val, size]);
+        self.check_status();
+
+        Ok(())
+    }
+}
@@ -1 +1,537 @@
+mod helpers;
+mod lower;
 
+use ::cranelift::codegen::Context;
+use ::cranelift::prelude::{
+    settings, types, AbiParam, Configurable, FunctionBuilder, FunctionBuilderContext,
+};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{FuncId, Linkage, Module};
+
+use crate::codegen::flag_policy::FlagPolicy;
+use crate::codegen::rustjit::InterpretCodegen;
+use crate::codegen::*;
+use crate::error::CodegenError;
+use crate::ir::{Ir, IrBlock, Type};
+use crate::value::Value;
+
+use helpers::{Frame, STATUS_EXIT, STATUS_OK};
+use lower::{Helper, Translator};
+
+use std::cell::{Cell, RefCell};
+use std::mem::ManuallyDrop;
+use std::rc::Rc;
+use std::sync::Arc;
+
+type NativeFn = unsafe extern "C" fn(*mut ExecutionContext<'_>, *mut Frame<'_>) -> u64;
+
+// Number of functions compiled into a jit module before another one is started.
+// Memory of a module is freed once none of its code is used, e.g. after its blocks
+// are invalidated, so code of invalidated blocks isn't kept for the whole run.
+const MODULE_FUNCTIONS: usize = 4096;
+
+// Compiles IR into host machine code with cranelift.
+//
+// IR which the backend doesn't support (vector and float operations) falls back to the
+// interpreter. Other errors are bugs of lowering, which panic.
+pub struct CraneliftCodegen {
+    jit: RefCell<Rc<RefCell<Jit>>>, // Module which functions are compiled into.
+    flag_policy: Arc<dyn FlagPolicy>,
+    fallback: InterpretCodegen,
+    fallbacks: Cell<u64>,
+}
+
+impl CraneliftCodegen {
+    pub fn new<F>(flag_policy: F) -> Self
+    where
+        F: FlagPolicy + 'static,
+    {
+        let flag_policy: Arc<dyn FlagPolicy> = Arc::new(flag_policy);
+
+        Self {
+            jit: RefCell::new(Rc::new(RefCell::new(Jit::new()))),
+            fallback: InterpretCodegen::new(flag_policy.clone()),
+            flag_policy,
+            fallbacks: Cell::new(0),
+        }
+    }
+
+    // Number of IRs and blocks which are interpreted, as the backend doesn't support them.
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks.get()
+    }
+
+    fn compile_native<F>(&self, translate: F) -> Result<NativeCode, CodegenError>
+    where
+        F: FnOnce(Translator<'_, '_>) -> Result<(), CodegenError>,
+    {
+        let mut jit = self.jit.borrow_mut();
+        if jit.borrow().functions >= MODULE_FUNCTIONS {
+            *jit = Rc::new(RefCell::new(Jit::new()));
+        }
+        let func = jit.borrow_mut().compile(translate)?;
+
+        Ok(NativeCode {
+            func,
+            flag_policy: self.flag_policy.clone(),
+            _jit: jit.clone(),
+        })
+    }
+
+    fn interpret<T>(
+        &self,
+        err: CodegenError,
+        what: &dyn std::fmt::Debug,
+        f: impl FnOnce() -> T,
+    ) -> T {
+        match err {
+            CodegenError::Unsupported(_) => {
+                self.fallbacks.set(self.fallbacks.get() + 1);
+                f()
+            }
+            err => panic!("Can't compile {:?}: {}", what, err),
+        }
+    }
+}
+
+impl Codegen for CraneliftCodegen {
+    type Exec = CraneliftExec;
+    type ExecBlock = CraneliftExecBlock;
+
+    fn compile_ir(&self, ir: &Ir) -> Self::Exec {
+        match self.compile_native(|t| t.translate_ir(ir)) {
+            Ok(code) => CraneliftExec::Native(code, ir.get_type()),
+            Err(err) => self.interpret(err, ir, || {
+                CraneliftExec::Interpret(self.fallback.compile_ir(ir))
+            }),
+        }
+    }
+
+    fn compile_ir_block(&self, ir_block: &IrBlock) -> Self::ExecBlock {
+        match self.compile_native(|t| t.translate_block(ir_block)) {
+            Ok(code) => CraneliftExecBlock::Native(code),
+            Err(err) => self.interpret(err, ir_block, || {
+                CraneliftExecBlock::Interpret(self.fallback.compile_ir_block(ir_block))
+            }),
+        }
+    }
+}
+
+struct Jit {
+    module: ManuallyDrop<JITModule>, // Its memory is freed when the jit is dropped.
+    ctx: Context,
+    helpers: Vec<FuncId>,
+    functions: usize,
+}
+
+impl Jit {
+    fn new() -> Self {
+        let mut flags = settings::builder();
+        flags.set("use_colocated_libcalls", "false").unwrap();
+        flags.set("is_pic", "false").unwrap();
+        flags.set("opt_level", "speed").unwrap();
+
+        let isa = cranelift_native::builder()
+            .expect("host machine is not supported by cranelift")
+            .finish(settings::Flags::new(flags))
+            .unwrap();
+
+        let mut builder = JITBuilder::with_isa(isa, cranelift_module::default_libcall_names());
+        for helper in Helper::ALL {
+            builder.symbol(helper.name(), helper.address());
+        }
+
+        let mut module = JITModule::new(builder);
+        let ptr = module.target_config().pointer_type();
+        let helpers = Helper::ALL
+            .iter()
+            .map(|helper| {
+                let sig = helper.signature(module.make_signature(), ptr);
+                module
+                    .declare_function(helper.name(), Linkage::Import, &sig)
+                    .unwrap()
+            })
+            .collect();
+
+        Self {
+            ctx: module.make_context(),
+            module: ManuallyDrop::new(module),
+            helpers,
+            functions: 0,
+        }
+    }
+
+    fn compile<F>(&mut self, translate: F) -> Result<NativeFn, CodegenError>
+    where
+        F: FnOnce(Translator<'_, '_>) -> Result<(), CodegenError>,
+    {
+        let result = self.define(translate);
+        self.module.clear_context(&mut self.ctx);
+
+        let id = result?;
+        self.functions += 1;
+        self.module
+            .finalize_definitions()
+            .map_err(|e| CodegenError::Backend(e.to_string()))?;
+
+        let code = self.module.get_finalized_function(id);
+        Ok(unsafe { std::mem::transmute::<*const u8, NativeFn>(code) })
+    }
+
+    fn define<F>(&mut self, translate: F) -> Result<FuncId, CodegenError>
+    where
+        F: FnOnce(Translator<'_, '_>) -> Result<(), CodegenError>,
+    {
+        let ptr = self.module.target_config().pointer_type();
+        let sig = &mut self.ctx.func.signature;
+        sig.params.push(AbiParam::new(ptr));
+        sig.params.push(AbiParam::new(ptr));
+        sig.returns.push(AbiParam::new(types::I64));
+
+        let mut builder_ctx = FunctionBuilderContext::new();
+        let builder = FunctionBuilder::new(&mut self.ctx.func, &mut builder_ctx);
+        translate(Translator::new(builder, &mut self.module, &self.helpers))?;
+
+        let id = self
+            .module
+            .declare_anonymous_function(&self.ctx.func.signature)
+            .map_err(|e| CodegenError::Backend(e.to_string()))?;
+        self.module
+            .define_function(id, &mut self.ctx)
+            .map_err(|e| CodegenError::Backend(e.to_string()))?;
+
+        Ok(id)
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        // Every function of the module is gone, as each keeps the jit alive.
+        unsafe { ManuallyDrop::take(&mut self.module).free_memory() };
+    }
+}
+
+// Entry point of a compiled function. Keeps the jit module (and so the code) alive.
+pub struct NativeCode {
+    func: NativeFn,
+    flag_policy: Arc<dyn FlagPolicy>,
+    _jit: Rc<RefCell<Jit>>,
+}
+
+impl NativeCode {
+    unsafe fn call(&self, ctx: &mut ExecutionContext<'_>) -> u64 {
+        let mut frame = Frame::new(self.flag_policy.as_ref());
+        let ret = (self.func)(ctx, &mut frame);
+
+        match frame.status {
+            STATUS_OK => ret,
+            STATUS_EXIT => panic!("Exit"),
+            _ => {
+                ctx.take_exception(frame.exception.unwrap());
+                0
+            }
+        }
+    }
+}
+
+pub enum CraneliftExec {
+    Native(NativeCode, Type),
+    Interpret(FnExec<Value>),
+}
+
+impl CraneliftExec {
+    pub fn is_native(&self) -> bool {
+        matches!(self, CraneliftExec::Native(..))
+    }
+}
+
+impl Executable for CraneliftExec {
+    type Output = Value;
+
+    unsafe fn execute<'a>(&self, ctx: &mut ExecutionContext<'a>) -> Value {
+        match self {
+            CraneliftExec::Native(code, ty) => Value::from_u64(code.call(ctx)).truncate_to(*ty),
+            CraneliftExec::Interpret(exec) => exec.execute(ctx),
+        }
+    }
+}
+
+pub enum CraneliftExecBlock {
+    Native(NativeCode),
+    Interpret(FnExec<()>),
+}
+
+impl CraneliftExecBlock {
+    pub fn is_native(&self) -> bool {
+        matches!(self, CraneliftExecBlock::Native(..))
+    }
+}
+
+impl Executable for CraneliftExecBlock {
+    type Output = ();
+
+    unsafe fn execute<'a>(&self, ctx: &mut ExecutionContext<'a>) {
+        match self {
+            CraneliftExecBlock::Native(code) => {
+                code.call(ctx);
+            }
+            CraneliftExecBlock::Interpret(exec) => exec.execute(ctx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::flag_policy::AArch64FlagPolicy;
+    use crate::cpu::Architecture;
+    use crate::ir::opt::PassManager;
+    use crate::ir::{BlockDestination, Operand, TempId, VecType};
+    use crate::softmmu::{BasicPage, Mmu};
+    use crate::Cpu;
+
+    fn eval_both(ir: Ir, cpu: &mut Cpu, mmu: &Mmu) -> (Value, Value) {
+        let native = CraneliftCodegen::new(AArch64FlagPolicy).compile_ir(&ir);
+        let interp = InterpretCodegen::new(AArch64FlagPolicy).compile_ir(&ir);
+        assert!(native.is_native());
+
+        let mut ctx = ExecutionContext::new(cpu, mmu);
+        unsafe { (native.execute(&mut ctx), interp.execute(&mut ctx)) }
+    }
+
+    #[test]
+    fn cranelift_ir_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let x0 = cpu.reg_by_name("x0").unwrap();
+        *cpu.gpr_mut(x0).u64_mut() = 0xffff_ffff_0000_0010;
+
+        let irs = [
+            Ir::Add(
+                Type::U32,
+                Operand::gpr(Type::U32, x0),
+                Operand::imm(Type::U32, 0xfffffff8),
+            ),
+            Ir::Sub(Type::U64, Operand::Ip, Operand::imm(Type::U64, 4)),
+            Ir::LShr(
+                Type::U64,
+                Operand::gpr(Type::U64, x0),
+                Operand::imm(Type::U64, 36),
+            ),
+            Ir::Rotr(
+                Type::U64,
+                Operand::gpr(Type::U64, x0),
+                Operand::imm(Type::U64, 8),
+            ),
+            Ir::Not(Type::U16, Operand::gpr(Type::U16, x0)),
+            Ir::ZextCast(Type::U64, Operand::gpr(Type::U8, x0)),
+            Ir::SextCast(
+                Type::U64,
+                Operand::ir(Ir::BitCast(Type::I32, Operand::gpr(Type::U32, x0))),
+            ),
+            Ir::If(
+                Type::U64,
+                Operand::ir(Ir::CmpGt(
+                    Operand::gpr(Type::U64, x0),
+                    Operand::imm(Type::U64, 1),
+                )),
+                Operand::imm(Type::U64, 0x1234),
+                Operand::imm(Type::U64, 0x5678),
+            ),
+            Ir::Addc(
+                Type::U64,
+                Operand::gpr(Type::U64, x0),
+                Operand::imm(Type::U64, u64::MAX),
+            ),
+        ];
+
+        for ir in irs {
+            let (native, interp) = eval_both(ir.clone(), &mut cpu, &mmu);
+            assert_eq!(native.u64(), interp.u64(), "{:?}", ir);
+        }
+    }
+
+    #[test]
+    fn cranelift_div_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let cgen = CraneliftCodegen::new(AArch64FlagPolicy);
+
+        let mut div = |t, a, b| {
+            let exec = cgen.compile_ir(&Ir::Div(t, Operand::imm(t, a), Operand::imm(t, b)));
+            let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+            unsafe { exec.execute(&mut ctx).u64() }
+        };
+
+        assert_eq!(div(Type::U64, 7, 0), 0);
+        assert_eq!(div(Type::I32, -9i32 as u64, 2), -4i32 as u32 as u64);
+        assert_eq!(
+            div(Type::I64, i64::MIN as u64, -1i64 as u64),
+            i64::MIN as u64
+        );
+    }
+
+    #[test]
+    fn cranelift_block_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        mmu.mmap(0x1000, 0x3000, Box::new(BasicPage::new(true, true, true)))
+            .unwrap();
+
+        let x0 = cpu.reg_by_name("x0").unwrap();
+        let x1 = cpu.reg_by_name("x1").unwrap();
+        *cpu.gpr_mut(x0).u64_mut() = 0x1010;
+        cpu.set_pc(0x1000);
+
+        // str w1, [x0, #8]; ldr x1, [x0, #8]
+        let mut block = IrBlock::new(4);
+        block.append(
+            Ir::Value(Operand::imm(Type::U32, 0xdead_beef)),
+            BlockDestination::MemoryRelU64(Type::U32, x0, 8),
+        );
+        block.append(
+            Ir::Load(
+                Type::U64,
+                Operand::ir(Ir::Add(
+                    Type::U64,
+                    Operand::gpr(Type::U64, x0),
+                    Operand::imm(Type::U64, 8),
+                )),
+            ),
+            BlockDestination::Gpr(Type::U64, x1),
+        );
+
+        let exec = CraneliftCodegen::new(AArch64FlagPolicy).compile_ir_block(&block);
+        assert!(exec.is_native());
+
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+        unsafe { exec.execute(&mut ctx) };
+
+        assert_eq!(cpu.gpr(x1).u64(), 0xdead_beef);
+        assert_eq!(cpu.pc(), 0x1004);
+    }
+
+    #[test]
+    fn cranelift_superblock_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let (x0, x1) = (
+            cpu.reg_by_name("x0").unwrap(),
+            cpu.reg_by_name("x1").unwrap(),
+        );
+        let inc = |id| {
+            let mut block = IrBlock::new(4);
+            block.append(
+                Ir::Add(
+                    Type::U64,
+                    Operand::gpr(Type::U64, id),
+                    Operand::imm(Type::U64, 1),
+                ),
+                BlockDestination::Gpr(Type::U64, id),
+            );
+            block
+        };
+
+        // add x0, x0, #1; add x0, x0, #1; str x0, [x1]; add x0, x0, #1
+        let mut store = IrBlock::new(4);
+        store.append(
+            Ir::Value(Operand::gpr(Type::U64, x0)),
+            BlockDestination::MemoryRelU64(Type::U64, x1, 0),
+        );
+        let blocks = vec![inc(x0), inc(x0), store, inc(x0)];
+
+        let mut blocks = vec![IrBlock::superblock(0x1000, blocks)];
+        PassManager::default().run(&mut blocks);
+
+        let run = |exec: &dyn Executable<Output = ()>| {
+            let mut cpu = cpu.clone();
+            let mmu = Mmu::new();
+            mmu.mmap(0x2000, 0x3000, Box::new(BasicPage::new(true, true, true)))
+                .unwrap();
+            *cpu.gpr_mut(x1).u64_mut() = 0x2000;
+
+            let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+            unsafe { exec.execute(&mut ctx) };
+
+            let mut stored = [0u8; 8];
+            unsafe { mmu.read(0x2000, &mut stored).unwrap() };
+            (cpu.gpr(x0).u64(), u64::from_le_bytes(stored), cpu.pc())
+        };
+
+        let native = CraneliftCodegen::new(AArch64FlagPolicy).compile_ir_block(&blocks[0]);
+        let interp = InterpretCodegen::new(AArch64FlagPolicy).compile_ir_block(&blocks[0]);
+        assert!(native.is_native());
+
+        assert_eq!(run(&native), (3, 2, 0x1010));
+        assert_eq!(run(&interp), (3, 2, 0x1010));
+    }
+
+    #[test]
+    fn cranelift_fault_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let x0 = cpu.reg_by_name("x0").unwrap();
+        let vbar = cpu.reg_by_name("vbar_el1").unwrap();
+        *cpu.sys_mut(vbar).u64_mut() = 0x4000;
+        cpu.set_pc(0x1000);
+
+        // Faulting store leaves x0 untouched.
+        let mut block = IrBlock::new(4);
+        block.append(
+            Ir::Value(Operand::imm(Type::U64, 0)),
+            BlockDestination::Memory(Type::U64, 0x8000),
+        );
+        block.append(
+            Ir::Value(Operand::imm(Type::U64, 1)),
+            BlockDestination::Gpr(Type::U64, x0),
+        );
+
+        let exec = CraneliftCodegen::new(AArch64FlagPolicy).compile_ir_block(&block);
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+        unsafe { exec.execute(&mut ctx) };
+
+        let sys = |name| cpu.sys(cpu.reg_by_name(name).unwrap()).u64();
+        assert_eq!(cpu.pc(), 0x4200);
+        assert_eq!(sys("elr_el1"), 0x1000);
+        assert_eq!(sys("far_el1"), 0x8000);
+        assert_eq!(cpu.gpr(x0).u64(), 0);
+    }
+
+    #[test]
+    fn cranelift_fallback_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let v0 = cpu.reg_by_name("v0").unwrap();
+        let x0 = cpu.reg_by_name("x0").unwrap();
+        let v1 = cpu.reg_by_name("v1").unwrap();
+        *cpu.fpr_mut(v0).u64x2_mut() = [1, 2];
+
+        // Vector operations are interpreted.
+        let mut block = IrBlock::new(4);
+        block.append(
+            Ir::Value(Operand::fpr(Type::Vec(VecType::U64, 2), v0)),
+            BlockDestination::Fpr(Type::Vec(VecType::U64, 2), v1),
+        );
+        block.append(
+            Ir::Value(Operand::imm(Type::U64, 1)),
+            BlockDestination::Gpr(Type::U64, x0),
+        );
+
+        let cgen = CraneliftCodegen::new(AArch64FlagPolicy);
+        let exec = cgen.compile_ir_block(&block);
+        assert!(!exec.is_native());
+        assert_eq!(cgen.fallbacks(), 1);
+
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+        unsafe { exec.execute(&mut ctx) };
+        assert_eq!(cpu.gpr(x0).u64(), 1);
+        assert_eq!(cpu.fpr(v1).u64x2(), [1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't compile")]
+    fn cranelift_error_test() {
+        // A temporary read before it's written is a bug of the IR, which isn't interpreted.
+        let ir = Ir::Value(Operand::Temp(Type::U64, TempId(0)));
+        CraneliftCodegen::new(AArch64FlagPolicy).compile_ir(&ir);
+    }
+}
@@ -1,14 +1,146 @@
 use crate::error::MmuError;
+use crate::exception::{take_exception, Exception};
+use crate::ir::TempId;
+use crate::psci::{self, Shutdown};
+use crate::value::Value;
 use crate::Cpu;
 
-use crate::softmmu::Mmu;
+use crate::softmmu::{Access, Mmu, Stage1, Tlb, Translation};
+use crate::user::Kernel;
 
 pub struct ExecutionContext<'a> {
     pub cpu: &'a mut Cpu,
     pub mmu: &'a Mmu,
+    temps: Vec<Value>,                  // Temporaries of the block being executed.
+    exception: Option<Exception>,       // Raised by the item being executed.
+    shutdown: Option<Shutdown>,         // Requested by the guest, which stops it.
+    kernel: Option<&'a mut dyn Kernel>, // Handles exceptions from EL0 in user-mode emulation.
+    stage1: Option<Stage1>,             // Translation of guest virtual addresses.
+    tlb: Tlb,
 }
 
 impl<'a> ExecutionContext<'a> {
+    pub fn new(cpu: &'a mut Cpu, mmu: &'a Mmu) -> Self {
+        let stage1 = Stage1::new(cpu);
+
+        Self {
+            cpu,
+            mmu,
+            temps: Vec::new(),
+            exception: None,
+            shutdown: None,
+            kernel: None,
+            stage1,
+            tlb: Tlb::new(),
+        }
+    }
+
+    // Translate guest virtual address `addr` into physical address.
+    pub fn translate(&self, addr: u64, access: Access) -> Result<Translation, MmuError> {
+        match self.stage1 {
+            Some(stage1) if stage1.is_enabled(self.cpu) => {
+                stage1.translate(self.cpu, self.mmu, addr, access)
+            }
+            _ => Ok(Translation::flat(addr)),
+        }
+    }
+
+    // Host address of `len` bytes at `addr` if its page is cached in the TLB.
+    #[inline]
+    fn lookup_tlb(&mut self, addr: u64, len: usize, write: bool) -> Option<*mut u8> {
+        let context = self.cpu.access_mode();
+        self.tlb
+            .lookup(self.mmu.generation(), context, addr, len, write)
+    }
+
+    // Access `len` bytes at `addr` page by page, as each page is translated separately.
+    // Pages of RAM are cached in the TLB.
+    unsafe fn for_each_page(
+        &mut self,
+        addr: u64,
+        len: usize,
+        access: Access,
+        mut f: impl FnMut(u64, std::ops::Range<usize>) -> Result<(), MmuError>,
+    ) -> Result<(), MmuError> {
+        let write = access == Access::Write;
+
+        let mut cursor = 0;
+        while cursor < len {
+            let va = addr.wrapping_add(cursor as u64);
+            let translation = self.translate(va, access)?;
+            let chunk = translation.remaining().min((len - cursor) as u64) as usize;
+
+            f(translation.pa, cursor..cursor + chunk)?;
+            cursor += chunk;
+
+            if let Some(host) = self.mmu.host_page(translation.pa, write) {
+                let context = self.cpu.access_mode();
+                self.tlb.fill(context, va, host, write);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Record an exception, which is taken after the current item.
+    pub fn raise(&mut self, exc: Exception) {
+        self.exception = Some(exc);
+    }
+
+    pub fn take_raised(&mut self) -> Option<Exception> {
+        self.exception.take()
+    }
+
+    // Handle exceptions taken from EL0 with `kernel` instead of the guest.
+    pub fn set_kernel(&mut self, kernel: &'a mut dyn Kernel) {
+        self.kernel = Some(kernel);
+    }
+
+    // Take an exception raised by the guest.
+    //
+    // There is no firmware running at EL2 or EL3, so `HVC` and `SMC` are PSCI calls
+    // handled by the emulator, which return to the next instruction.
+    pub fn take_exception(&mut self, exc: Exception) {
+        if let Some(kernel) = self.kernel.as_deref_mut() {
+            if self.cpu.el() == 0 {
+                self.shutdown = kernel
+                    .handle_exception(self.cpu, self.mmu, exc)
+                    .or(self.shutdown);
+                return;
+            }
+        }
+
+        match exc {
+            Exception::Hvc(_) | Exception::Smc(_) if self.cpu.el() > 0 => {
+                let pc = self.cpu.pc();
+                self.shutdown = psci::call(self.cpu).or(self.shutdown);
+                self.cpu.set_pc(pc + 4);
+            }
+            exc => take_exception(self.cpu, exc),
+        }
+    }
+
+    pub fn has_shutdown(&self) -> bool {
+        self.shutdown.is_some()
+    }
+
+    // Power state requested by the guest, which stops running it.
+    pub fn take_shutdown(&mut self) -> Option<Shutdown> {
+        self.shutdown.take()
+    }
+
+    pub fn temp(&self, id: TempId) -> &Value {
+        &self.temps[id.0 as usize]
+    }
+
+    pub fn set_temp(&mut self, id: TempId, val: Value) {
+        let idx = id.0 as usize;
+        if idx >= self.temps.len() {
+            self.temps.resize(idx + 1, Value::new(0));
+        }
+        self.temps[idx] = val;
+    }
+
     pub fn cpu(&self) -> &Cpu {
         &self.cpu
     }
@@ -18,7 +150,15 @@ impl<'a> ExecutionContext<'a> {
     }
 
     pub unsafe fn mem_read(&mut self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError> {
-        self.mmu.read(addr, buf)
+        if let Some(host) = self.lookup_tlb(addr, buf.len(), false) {
+            std::ptr::copy_nonoverlapping(host, buf.as_mut_ptr(), buf.len());
+            return Ok(());
+        }
+
+        let mmu = self.mmu;
+        self.for_each_page(addr, buf.len(), Access::Read, |pa, range| {
+            mmu.read(pa, &mut buf[range])
+        })
     }
 
     pub unsafe fn mem_read_u8(&mut self, addr: u64) -> Result<u8, MmuError> {
@@ -53,7 +193,15 @@ impl<'a> ExecutionContext<'a> {
     }
 
     pub unsafe fn mem_write(&mut self, addr: u64, buf: &[u8]) -> Result<(), MmuError> {
-        self.mmu.write(addr, buf)
+        if let Some(host) = self.lookup_tlb(addr, buf.len(), true) {
+            std::ptr::copy_nonoverlapping(buf.as_ptr(), host, buf.len());
+            return Ok(());
+        }
+
+        let mmu = self.mmu;
+        self.for_each_page(addr, buf.len(), Access::Write, |pa, range| {
+            mmu.write(pa, &buf[range])
+        })
     }
 
     pub unsafe fn mem_write_u8(&mut self, addr: u64, val: u8) -> Result<(), MmuError> {
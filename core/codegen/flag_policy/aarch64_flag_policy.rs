@@ -1,4 +1,4 @@
-use crate::codegen::flag_policy::FlagPolicy;
+use crate::codegen::flag_policy::{FlagPolicy, LazyFlag};
 use crate::compiler::aarch64_prelude::Pstate;
 use crate::ir::Type;
 use crate::Cpu;
@@ -8,160 +8,241 @@ pub struct AArch64FlagPolicy;
 
 impl FlagPolicy for AArch64FlagPolicy {
     fn add_carry(&self, ty: Type, a: u64, b: u64, vm: &Cpu) {
-        let (n, z, c, v) = match ty {
-            Type::U8 | Type::I8 => {
-                let ua = a as u8;
-                let ub = b as u8;
-                let sa = a as i8;
-                let sb = b as i8;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::U16 | Type::I16 => {
-                let ua = a as u16;
-                let ub = b as u16;
-                let sa = a as i16;
-                let sb = b as i16;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::U32 | Type::I32 => {
-                let ua = a as u32;
-                let ub = b as u32;
-                let sa = a as i32;
-                let sb = b as i32;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::U64 | Type::I64 => {
-                let ua = a;
-                let ub = b;
-                let sa = a as i64;
-                let sb = b as i64;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::F32 | Type::F64 => unimplemented!("Float type is not supported!"),
-            Type::Void => panic!("Void type is not supported!"),
-            Type::Bool => panic!("Bool type is not supported!"),
-            _ => panic!("Unknown type!"),
-        };
-
-        let (n, z, c, v): (u64, u64, u64, u64) = (n.into(), z.into(), c.into(), v.into());
         vm.del_flag(Pstate::NZCV.mask());
-        vm.add_flag(
-            n << Pstate::N.idx()
-                | z << Pstate::Z.idx()
-                | c << Pstate::C.idx()
-                | v << Pstate::V.idx(),
-        )
+        vm.add_flag(add_nzcv(ty, a, b));
     }
 
     fn sub_carry(&self, ty: Type, a: u64, b: u64, vm: &Cpu) {
-        let b = -(b as i64) as u64;
-        let (n, z, mut c, v) = match ty {
-            Type::U8 | Type::I8 => {
-                let ua = a as u8;
-                let ub = b as u8;
-                let sa = a as i8;
-                let sb = b as i8;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::U16 | Type::I16 => {
-                let ua = a as u16;
-                let ub = b as u16;
-                let sa = a as i16;
-                let sb = b as i16;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::U32 | Type::I32 => {
-                let ua = a as u32;
-                let ub = b as u32;
-                let sa = a as i32;
-                let sb = b as i32;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::U64 | Type::I64 => {
-                let ua = a;
-                let ub = b;
-                let sa = a as i64;
-                let sb = b as i64;
-
-                let (uresult, c) = ua.overflowing_add(ub);
-                let (sresult, v) = sa.overflowing_add(sb);
-
-                let n = sresult < 0;
-                let z = uresult == 0;
-
-                (n, z, c, v)
-            }
-            Type::F32 | Type::F64 => unimplemented!("Float type is not supported!"),
-            Type::Void => panic!("Void type is not supported!"),
-            Type::Bool => panic!("Bool type is not supported!"),
-            _ => panic!("Unknown type!"),
-        };
-
-        if a == 0 && b == 0 {
-            c = true;
-        }
+        vm.del_flag(Pstate::NZCV.mask());
+        vm.add_flag(sub_nzcv(ty, a, b));
+    }
 
-        let (n, z, c, v): (u64, u64, u64, u64) = (n.into(), z.into(), c.into(), v.into());
+    fn carry(&self, vm: &Cpu) -> bool {
+        ((vm.flag() >> 61) & 1) == 1
+    }
+}
 
-        vm.del_flag(Pstate::NZCV.mask());
-        vm.add_flag(
-            n << Pstate::N.idx()
-                | z << Pstate::Z.idx()
-                | c << Pstate::C.idx()
-                | v << Pstate::V.idx(),
-        )
+// Records flag-setting operations in the cpu instead of computing NZCV. Flags are
+// computed when they are read (see `Cpu::flag`), so flags overwritten before being
+// read are never computed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AArch64LazyFlagPolicy;
+
+impl FlagPolicy for AArch64LazyFlagPolicy {
+    fn add_carry(&self, ty: Type, a: u64, b: u64, vm: &Cpu) {
+        vm.set_lazy_flag(LazyFlag::new(ty, a, b, Pstate::NZCV.mask(), eval_add_nzcv));
+    }
+
+    fn sub_carry(&self, ty: Type, a: u64, b: u64, vm: &Cpu) {
+        vm.set_lazy_flag(LazyFlag::new(ty, a, b, Pstate::NZCV.mask(), eval_sub_nzcv));
     }
 
     fn carry(&self, vm: &Cpu) -> bool {
         ((vm.flag() >> 61) & 1) == 1
     }
 }
+
+fn eval_add_nzcv(op: &LazyFlag) -> u64 {
+    add_nzcv(op.ty, op.a, op.b)
+}
+
+fn eval_sub_nzcv(op: &LazyFlag) -> u64 {
+    sub_nzcv(op.ty, op.a, op.b)
+}
+
+// NZCV bits of `a + b`.
+pub fn add_nzcv(ty: Type, a: u64, b: u64) -> u64 {
+    let (n, z, c, v) = match ty {
+        Type::U8 | Type::I8 => {
+            let ua = a as u8;
+            let ub = b as u8;
+            let sa = a as i8;
+            let sb = b as i8;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::U16 | Type::I16 => {
+            let ua = a as u16;
+            let ub = b as u16;
+            let sa = a as i16;
+            let sb = b as i16;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::U32 | Type::I32 => {
+            let ua = a as u32;
+            let ub = b as u32;
+            let sa = a as i32;
+            let sb = b as i32;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::U64 | Type::I64 => {
+            let ua = a;
+            let ub = b;
+            let sa = a as i64;
+            let sb = b as i64;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::F32 | Type::F64 => unimplemented!("Float type is not supported!"),
+        Type::Void => panic!("Void type is not supported!"),
+        Type::Bool => panic!("Bool type is not supported!"),
+        _ => panic!("Unknown type!"),
+    };
+
+    let (n, z, c, v): (u64, u64, u64, u64) = (n.into(), z.into(), c.into(), v.into());
+    n << Pstate::N.idx() | z << Pstate::Z.idx() | c << Pstate::C.idx() | v << Pstate::V.idx()
+}
+
+// NZCV bits of `a - b`.
+pub fn sub_nzcv(ty: Type, a: u64, b: u64) -> u64 {
+    let b = -(b as i64) as u64;
+    let (n, z, mut c, v) = match ty {
+        Type::U8 | Type::I8 => {
+            let ua = a as u8;
+            let ub = b as u8;
+            let sa = a as i8;
+            let sb = b as i8;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::U16 | Type::I16 => {
+            let ua = a as u16;
+            let ub = b as u16;
+            let sa = a as i16;
+            let sb = b as i16;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::U32 | Type::I32 => {
+            let ua = a as u32;
+            let ub = b as u32;
+            let sa = a as i32;
+            let sb = b as i32;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::U64 | Type::I64 => {
+            let ua = a;
+            let ub = b;
+            let sa = a as i64;
+            let sb = b as i64;
+
+            let (uresult, c) = ua.overflowing_add(ub);
+            let (sresult, v) = sa.overflowing_add(sb);
+
+            let n = sresult < 0;
+            let z = uresult == 0;
+
+            (n, z, c, v)
+        }
+        Type::F32 | Type::F64 => unimplemented!("Float type is not supported!"),
+        Type::Void => panic!("Void type is not supported!"),
+        Type::Bool => panic!("Bool type is not supported!"),
+        _ => panic!("Unknown type!"),
+    };
+
+    if a == 0 && b == 0 {
+        c = true;
+    }
+
+    let (n, z, c, v): (u64, u64, u64, u64) = (n.into(), z.into(), c.into(), v.into());
+
+    n << Pstate::N.idx() | z << Pstate::Z.idx() | c << Pstate::C.idx() | v << Pstate::V.idx()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::Architecture;
+
+    #[test]
+    fn lazy_flag_test() {
+        let eager = Cpu::new(Architecture::AArch64Bin);
+        let lazy = Cpu::new(Architecture::AArch64Bin);
+
+        let ops = [
+            (Type::U64, 1, u64::MAX),
+            (Type::U32, 0x7fff_ffff, 1),
+            (Type::U8, 0x80, 0x80),
+            (Type::U16, 0, 0),
+        ];
+
+        for (ty, a, b) in ops {
+            AArch64FlagPolicy.add_carry(ty, a, b, &eager);
+            AArch64LazyFlagPolicy.add_carry(ty, a, b, &lazy);
+            assert_eq!(lazy.flag(), eager.flag());
+
+            AArch64FlagPolicy.sub_carry(ty, a, b, &eager);
+            AArch64LazyFlagPolicy.sub_carry(ty, a, b, &lazy);
+            assert_eq!(lazy.flag(), eager.flag());
+        }
+    }
+
+    #[test]
+    fn lazy_flag_materialize_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        cpu.set_flag(0b01 << Pstate::EL.idx() | Pstate::Z.mask());
+
+        AArch64LazyFlagPolicy.sub_carry(Type::U64, 3, 5, &cpu);
+        assert!(cpu.has_lazy_flag());
+
+        // Mode bits don't depend on the pending operation.
+        assert_eq!(cpu.translation_mode(), 0b01 << Pstate::EL.idx());
+        assert!(cpu.has_lazy_flag());
+
+        // Only NZCV is replaced: 3 - 5 is negative and borrows.
+        let flag = cpu.flag();
+        assert!(!cpu.has_lazy_flag());
+        assert_eq!(flag & Pstate::NZCV.mask(), Pstate::N.mask());
+        assert_eq!(flag & Pstate::EL.mask(), 0b01 << Pstate::EL.idx());
+
+        // Overwriting flags drops the pending operation.
+        AArch64LazyFlagPolicy.add_carry(Type::U64, 0, 0, &cpu);
+        cpu.set_flag(0);
+        assert_eq!(cpu.flag(), 0);
+    }
+}
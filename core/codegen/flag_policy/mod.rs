@@ -9,6 +9,34 @@ use crate::Cpu;
 
 use std::sync::Arc;
 
+// Last flag-setting operation on `a` and `b`, whose flags are computed by `eval` on
+// demand. `mask` is the set of flag bits written by the operation.
+#[derive(Clone, Copy, Debug)]
+pub struct LazyFlag {
+    pub ty: Type,
+    pub a: u64,
+    pub b: u64,
+    pub mask: u64,
+    pub eval: fn(&LazyFlag) -> u64,
+}
+
+impl LazyFlag {
+    pub fn new(ty: Type, a: u64, b: u64, mask: u64, eval: fn(&LazyFlag) -> u64) -> Self {
+        Self {
+            ty,
+            a,
+            b,
+            mask,
+            eval,
+        }
+    }
+
+    // Flag bits in `mask` after the operation.
+    pub fn flags(&self) -> u64 {
+        (self.eval)(self) & self.mask
+    }
+}
+
 pub trait FlagPolicy {
     fn carry(&self, vm: &Cpu) -> bool;
 
@@ -2,7 +2,8 @@ use smallvec::SmallVec;
 
 use crate::codegen::flag_policy::{DummyFlagPolicy, FlagPolicy};
 use crate::codegen::*;
-use crate::error::CodegenError;
+use crate::error::{CodegenError, MmuError};
+use crate::exception::{exception_return, Exception};
 use crate::ir::{BlockDestination, Ir, Operand, Type, VecType};
 use crate::value::Value;
 
@@ -51,7 +52,16 @@ unsafe fn execute(
     let mut ip_modified = false;
     for (exec, dest) in code {
         let val = unsafe { exec.execute(ctx) };
+        if let Some(exc) = ctx.take_raised() {
+            ctx.take_exception(exc);
+            return;
+        }
+
         handle_block_dest(dest.clone(), val, ctx, &mut ip_modified);
+        if let Some(exc) = ctx.take_raised() {
+            ctx.take_exception(exc);
+            return;
+        }
     }
 
     if !ip_modified {
@@ -75,6 +85,7 @@ unsafe fn handle_block_dest<'a>(
             *ip_modified = true;
         }
         BlockDestination::None => { /* do nothing */ }
+        BlockDestination::Temp(_, id) => ctx.set_temp(id, val),
         BlockDestination::Gpr(ty, reg_id) => {
             let gpr = ctx.cpu_mut().gpr_mut(reg_id);
 
@@ -125,6 +136,25 @@ unsafe fn handle_block_dest<'a>(
         BlockDestination::Exit => {
             panic!("Exit");
         }
+        BlockDestination::Exception => {
+            let pc = ctx.cpu().pc();
+            ctx.raise(Exception::from_syndrome(val.u64(), pc));
+            *ip_modified = true;
+        }
+        BlockDestination::ExceptionReturn => {
+            exception_return(ctx.cpu_mut());
+            *ip_modified = true;
+        }
+        BlockDestination::InvalidateCode => {
+            let addr = val.u64();
+            ctx.mmu.invalidate_code(addr..addr + 1);
+        }
+        BlockDestination::InvalidateCodeAll => {
+            ctx.mmu.invalidate_all_code();
+        }
+        BlockDestination::InvalidateTlb => {
+            ctx.mmu.flush_tlb();
+        }
         BlockDestination::Memory(ty, addr) => {
             match ty {
                 Type::U8 | Type::I8 => ctx.mem_write_u8(addr, val.u8()),
@@ -136,7 +166,7 @@ unsafe fn handle_block_dest<'a>(
                 }
                 _ => unreachable!(),
             }
-            .unwrap();
+            .unwrap_or_else(|err| ctx.raise(Exception::data_abort(addr, true, &err)));
         }
         BlockDestination::MemoryRelI64(ty, reg_id, offs) => {
             let (addr, of) = ctx.cpu().gpr(reg_id).u64().overflowing_add_signed(offs);
@@ -152,7 +182,7 @@ unsafe fn handle_block_dest<'a>(
                 }
                 _ => unreachable!(),
             }
-            .unwrap();
+            .unwrap_or_else(|err| ctx.raise(Exception::data_abort(addr, true, &err)));
         }
         BlockDestination::MemoryRelU64(ty, reg_id, offs) => {
             let (addr, of) = ctx.cpu().gpr(reg_id).u64().overflowing_add(offs);
@@ -168,7 +198,7 @@ unsafe fn handle_block_dest<'a>(
                 }
                 _ => unreachable!(),
             }
-            .unwrap();
+            .unwrap_or_else(|err| ctx.raise(Exception::data_abort(addr, true, &err)));
         }
         BlockDestination::MemoryIr(ir) => {
             let ty = ir.get_type();
@@ -184,7 +214,7 @@ unsafe fn handle_block_dest<'a>(
                 }
                 _ => unreachable!(),
             }
-            .unwrap();
+            .unwrap_or_else(|err| ctx.raise(Exception::data_abort(addr, true, &err)));
         }
     }
 }
@@ -379,6 +409,10 @@ where
                 Value::new(0)
             })
         }
+        Operand::Temp(_, id) => {
+            let id = *id;
+            FnExec::new(move |ctx| ctx.temp(id).clone())
+        }
         Operand::Ip => FnExec::new(move |ctx| Value::from_u64(ctx.cpu().pc())),
         Operand::Flag => FnExec::new(move |ctx| Value::from_u64(ctx.cpu().flag())),
         Operand::Dbg(s, op) => {
@@ -963,6 +997,14 @@ where
     })
 }
 
+// Result of a memory read, or zero after raising a data abort.
+fn or_abort<T: Default>(ctx: &mut ExecutionContext, addr: u64, result: Result<T, MmuError>) -> T {
+    result.unwrap_or_else(|err| {
+        ctx.raise(Exception::data_abort(addr, false, &err));
+        T::default()
+    })
+}
+
 unsafe fn gen_load<T>(t: &Type, op: &Operand, flag_policy: T) -> Result<FnExec<Value>, CodegenError>
 where
     T: FlagPolicy + Clone + 'static,
@@ -971,29 +1013,41 @@ where
     Ok(match t {
         Type::Bool => FnExec::new(move |ctx| {
             let mut var = op.execute(ctx);
-            (ctx.mem_read_u8(var.u64()).unwrap() & 0b1).into()
+            let addr = var.u64();
+            let val = ctx.mem_read_u8(addr);
+            (or_abort(ctx, addr, val) & 0b1).into()
         }),
         Type::U8 | Type::I8 => FnExec::new(move |ctx| {
             let mut var = op.execute(ctx);
-            ctx.mem_read_u8(var.u64()).unwrap().into()
+            let addr = var.u64();
+            let val = ctx.mem_read_u8(addr);
+            or_abort(ctx, addr, val).into()
         }),
         Type::U16 | Type::I16 => FnExec::new(move |ctx| {
             let mut var = op.execute(ctx);
-            ctx.mem_read_u16(var.u64()).unwrap().into()
+            let addr = var.u64();
+            let val = ctx.mem_read_u16(addr);
+            or_abort(ctx, addr, val).into()
         }),
         Type::U32 | Type::I32 | Type::F32 => FnExec::new(move |ctx| {
             let mut var = op.execute(ctx);
-            ctx.mem_read_u32(var.u64()).unwrap().into()
+            let addr = var.u64();
+            let val = ctx.mem_read_u32(addr);
+            or_abort(ctx, addr, val).into()
         }),
         Type::U64 | Type::I64 | Type::F64 => FnExec::new(move |ctx| {
             let mut var = op.execute(ctx);
-            ctx.mem_read_u64(var.u64()).unwrap().into()
+            let addr = var.u64();
+            let val = ctx.mem_read_u64(addr);
+            or_abort(ctx, addr, val).into()
         }),
         Type::Vec(VecType::U64, 2) => FnExec::new(move |ctx| {
             let mut var = op.execute(ctx);
 
+            let addr = var.u64();
             let mut value = Value::new(16);
-            ctx.mem_read(var.u64(), value.u8_slice_mut()).unwrap();
+            let result = ctx.mem_read(addr, value.u8_slice_mut());
+            or_abort(ctx, addr, result);
 
             value
         }),
@@ -1,23 +1,31 @@
 use std::collections::HashMap;
+use std::ops::Deref;
 
 use crate::compiler::aarch64_prelude::*;
 use crate::compiler::Compiler;
+use crate::error::CompileError;
+use crate::exception::Exception;
 use crate::ir::*;
 use crate::register::RegId;
 use crate::value::Value;
 
 use machineinstr::aarch64::*;
+use machineinstr::instr::NativeInstr;
 
 use utility::Pattern;
 
 pub struct AArch64Compiler {
     register_info: HashMap<String, RegId>,
+    stack_regs: [RegId; 4], // `SP_EL0` to `SP_EL3`
 }
 
 impl AArch64Compiler {
     pub fn new(reg_info: HashMap<String, RegId>) -> Self {
+        let stack_regs = [0, 1, 2, 3].map(|el| reg_info[&format!("sp_el{}", el)]);
+
         Self {
             register_info: reg_info,
+            stack_regs,
         }
     }
     pub fn gpr(&self, index: u8) -> RegId {
@@ -28,178 +36,243 @@ impl AArch64Compiler {
         *self.register_info.get(&format!("v{}", index)).unwrap()
     }
 
+    pub fn reg_by_name(&self, name: impl AsRef<str>) -> RegId {
+        *self.register_info.get(name.as_ref()).unwrap()
+    }
+}
+
+// Compiler of one instruction, in the translation mode it's compiled in.
+pub struct InstrContext<'a> {
+    compiler: &'a AArch64Compiler,
+    mode: u64,
+    pc: u64,
+    raw: u32,
+}
+
+impl InstrContext<'_> {
+    // Stack pointer selected by the exception level and `SPSel` of the translation mode.
     pub fn stack_reg(&self) -> RegId {
-        *self.register_info.get("sp").unwrap()
+        self.compiler.stack_regs[stack_index(self.mode)]
     }
 
-    pub fn reg_by_name(&self, name: impl AsRef<str>) -> RegId {
-        *self.register_info.get(name.as_ref()).unwrap()
+    pub fn el(&self) -> u8 {
+        current_el(self.mode)
+    }
+
+    pub fn unimplemented(&self) -> CompileError {
+        CompileError::Unimplemented(self.pc, self.raw)
+    }
+
+    pub fn unallocated(&self) -> CompileError {
+        CompileError::Unallocated(self.pc, self.raw)
+    }
+}
+
+impl Deref for InstrContext<'_> {
+    type Target = AArch64Compiler;
+
+    fn deref(&self) -> &AArch64Compiler {
+        self.compiler
     }
 }
 
 impl Compiler for AArch64Compiler {
     type Item = AArch64Instr;
 
-    fn compile(&self, item: Self::Item) -> IrBlock {
-        // println!("{item:?}");
-
-        match item {
+    fn compile(
+        &self,
+        instr: NativeInstr<Self::Item>,
+        pc: u64,
+        mode: u64,
+    ) -> Result<IrBlock, CompileError> {
+        // println!("{:?}", instr.op);
+        let compiler = InstrContext {
+            compiler: self,
+            mode,
+            pc,
+            raw: instr.raw,
+        };
+        let unimplemented = || compiler.unimplemented();
+
+        let block = match instr.op {
             AArch64Instr::MovzVar32(operand) | AArch64Instr::MovzVar64(operand) => {
-                gen_movz(self, operand)
+                gen_movz(&compiler, operand)
             }
-            AArch64Instr::MovnVar32(operand) => gen_movn(self, operand, Type::U32),
-            AArch64Instr::MovnVar64(operand) => gen_movn(self, operand, Type::U64),
-            AArch64Instr::MovkVar32(operand) => gen_movk(self, operand, Type::U32),
-            AArch64Instr::MovkVar64(operand) => gen_movk(self, operand, Type::U64),
-            AArch64Instr::MoviVectorVar64(operand) => gen_movi(self, operand),
-            AArch64Instr::Adr(operand) => gen_adr(self, operand),
-            AArch64Instr::Adrp(operand) => gen_adrp(self, operand),
+            AArch64Instr::MovnVar32(operand) => gen_movn(&compiler, operand, Type::U32),
+            AArch64Instr::MovnVar64(operand) => gen_movn(&compiler, operand, Type::U64),
+            AArch64Instr::MovkVar32(operand) => gen_movk(&compiler, operand, Type::U32),
+            AArch64Instr::MovkVar64(operand) => gen_movk(&compiler, operand, Type::U64),
+            AArch64Instr::MoviVectorVar64(operand) => gen_movi(&compiler, operand),
+            AArch64Instr::Adr(operand) => gen_adr(&compiler, operand),
+            AArch64Instr::Adrp(operand) => gen_adrp(&compiler, operand),
 
-            AArch64Instr::RevVar32(operand) => gen_rev_var(self, operand, Type::U32),
-            AArch64Instr::RevVar64(operand) => gen_rev_var(self, operand, Type::U64),
+            AArch64Instr::RevVar32(operand) => gen_rev_var(&compiler, operand, Type::U32),
+            AArch64Instr::RevVar64(operand) => gen_rev_var(&compiler, operand, Type::U64),
 
             // Load and Stores
-            AArch64Instr::LdrImm32(operand) => gen_ldr_imm(self, operand, Type::U32),
-            AArch64Instr::LdrImm64(operand) => gen_ldr_imm(self, operand, Type::U64),
-            AArch64Instr::LdrImmSimdFP64(operand) => gen_ldr_imm_simd_fp(self, operand, Type::U64),
+            AArch64Instr::LdrImm32(operand) => gen_ldr_imm(&compiler, operand, Type::U32),
+            AArch64Instr::LdrImm64(operand) => gen_ldr_imm(&compiler, operand, Type::U64),
+            AArch64Instr::LdrImmSimdFP64(operand) => {
+                gen_ldr_imm_simd_fp(&compiler, operand, Type::U64)
+            }
             AArch64Instr::LdrImmSimdFP128(operand) => {
-                gen_ldr_imm_simd_fp(self, operand, Type::Vec(VecType::U64, 2))
+                gen_ldr_imm_simd_fp(&compiler, operand, Type::Vec(VecType::U64, 2))
             }
-            AArch64Instr::LdrLitVar64(operand) => gen_ldr_lit_var64(self, operand),
-            AArch64Instr::LdrhImm(operand) => gen_ldrh_imm(self, operand),
-            AArch64Instr::LdrbImm(operand) => gen_ldrb_imm(self, operand),
-            AArch64Instr::LdrReg32(operand) => gen_ldr_reg(self, operand, Type::U32),
-            AArch64Instr::LdrReg64(operand) => gen_ldr_reg(self, operand, Type::U64),
-            AArch64Instr::LdrbRegShiftedReg(operand) => gen_ldrb_reg_shifted_reg(self, operand),
-            AArch64Instr::LdpVar64(operand) => gen_ldp(self, operand, Type::U64),
-            AArch64Instr::LdpVar32(operand) => gen_ldp(self, operand, Type::U32),
-            AArch64Instr::LdrshReg64(operand) => gen_ldrsh_reg(self, operand, Type::U64),
-            AArch64Instr::LdrshReg32(operand) => gen_ldrsh_reg(self, operand, Type::U32),
-            AArch64Instr::LdaxrVar32(operand) => gen_ldaxr(self, operand, Type::U32),
-            AArch64Instr::LdarVar64(operand) => gen_ldar(self, operand, Type::U64),
-            AArch64Instr::Ldur64(operand) => gen_ldur(self, operand, Type::U64),
+            AArch64Instr::LdrLitVar64(operand) => gen_ldr_lit_var64(&compiler, operand),
+            AArch64Instr::LdrhImm(operand) => gen_ldrh_imm(&compiler, operand),
+            AArch64Instr::LdrbImm(operand) => gen_ldrb_imm(&compiler, operand),
+            AArch64Instr::LdrReg32(operand) => gen_ldr_reg(&compiler, operand, Type::U32),
+            AArch64Instr::LdrReg64(operand) => gen_ldr_reg(&compiler, operand, Type::U64),
+            AArch64Instr::LdrbRegShiftedReg(operand) => {
+                gen_ldrb_reg_shifted_reg(&compiler, operand)
+            }
+            AArch64Instr::LdpVar64(operand) => gen_ldp(&compiler, operand, Type::U64),
+            AArch64Instr::LdpVar32(operand) => gen_ldp(&compiler, operand, Type::U32),
+            AArch64Instr::LdrshReg64(operand) => gen_ldrsh_reg(&compiler, operand, Type::U64),
+            AArch64Instr::LdrshReg32(operand) => gen_ldrsh_reg(&compiler, operand, Type::U32),
+            AArch64Instr::LdaxrVar32(operand) => gen_ldaxr(&compiler, operand, Type::U32),
+            AArch64Instr::LdarVar64(operand) => gen_ldar(&compiler, operand, Type::U64),
+            AArch64Instr::Ldur64(operand) => gen_ldur(&compiler, operand, Type::U64),
             AArch64Instr::LdpSimdFpVar128(operand) => {
-                gen_ldp_simd_fp(self, operand, Type::Vec(VecType::U64, 2))
+                gen_ldp_simd_fp(&compiler, operand, Type::Vec(VecType::U64, 2))
             }
-            AArch64Instr::LdrRegSimdFP(operand) => gen_ldr_reg_simd_fp(self, operand),
-            AArch64Instr::LdxrVar64(operand) => gen_ldxr(self, operand, Type::U64),
-
-            AArch64Instr::StrImm32(operand) => gen_str_imm(self, operand, Type::U32),
-            AArch64Instr::StrImm64(operand) => gen_str_imm(self, operand, Type::U64),
-            AArch64Instr::StpVar64(operand) => gen_stp_var(self, operand, Type::U64),
-            AArch64Instr::StpVar32(operand) => gen_stp_var(self, operand, Type::U32),
-            AArch64Instr::StrbImm(operand) => gen_strb_imm(self, operand),
-            AArch64Instr::Sturb(operand) => gen_sturb_imm(self, operand),
-            AArch64Instr::StrReg32(operand) => gen_str_reg(self, operand, Type::U32),
-            AArch64Instr::StrReg64(operand) => gen_str_reg(self, operand, Type::U64),
-            AArch64Instr::Stur32(operand) => gen_stur(self, operand, Type::U32),
-            AArch64Instr::Stur64(operand) => gen_stur(self, operand, Type::U64),
-            AArch64Instr::SturSimdFP64(operand) => gen_stur_simd_fp(self, operand, Type::U64),
+            AArch64Instr::LdrRegSimdFP(operand) => gen_ldr_reg_simd_fp(&compiler, operand),
+            AArch64Instr::LdxrVar64(operand) => gen_ldxr(&compiler, operand, Type::U64),
+
+            AArch64Instr::StrImm32(operand) => gen_str_imm(&compiler, operand, Type::U32),
+            AArch64Instr::StrImm64(operand) => gen_str_imm(&compiler, operand, Type::U64),
+            AArch64Instr::StpVar64(operand) => gen_stp_var(&compiler, operand, Type::U64),
+            AArch64Instr::StpVar32(operand) => gen_stp_var(&compiler, operand, Type::U32),
+            AArch64Instr::StrbImm(operand) => gen_strb_imm(&compiler, operand),
+            AArch64Instr::Sturb(operand) => gen_sturb_imm(&compiler, operand),
+            AArch64Instr::StrReg32(operand) => gen_str_reg(&compiler, operand, Type::U32),
+            AArch64Instr::StrReg64(operand) => gen_str_reg(&compiler, operand, Type::U64),
+            AArch64Instr::Stur32(operand) => gen_stur(&compiler, operand, Type::U32),
+            AArch64Instr::Stur64(operand) => gen_stur(&compiler, operand, Type::U64),
+            AArch64Instr::SturSimdFP64(operand) => gen_stur_simd_fp(&compiler, operand, Type::U64),
             AArch64Instr::SturSimdFP128(operand) => {
-                gen_stur_simd_fp(self, operand, Type::Vec(VecType::U64, 2))
+                gen_stur_simd_fp(&compiler, operand, Type::Vec(VecType::U64, 2))
             }
             AArch64Instr::StpSimdFpVar128(operand) => {
-                gen_stp_simd_fp(self, operand, Type::Vec(VecType::U64, 2))
+                gen_stp_simd_fp(&compiler, operand, Type::Vec(VecType::U64, 2))
+            }
+            AArch64Instr::StrImmSimdFP64(operand) => {
+                gen_str_imm_simd_fp(&compiler, operand, Type::U64)
             }
-            AArch64Instr::StrImmSimdFP64(operand) => gen_str_imm_simd_fp(self, operand, Type::U64),
             AArch64Instr::StrImmSimdFP128(operand) => {
-                gen_str_imm_simd_fp(self, operand, Type::Vec(VecType::U64, 2))
+                gen_str_imm_simd_fp(&compiler, operand, Type::Vec(VecType::U64, 2))
             }
-            AArch64Instr::StrRegSimdFP(operand) => gen_str_reg_simd_fp(self, operand),
-            AArch64Instr::StlxrVar32(operand) => gen_stlxr(self, operand, Type::U32),
-            AArch64Instr::StxrVar64(operand) => gen_stxr(self, operand, Type::U64),
-            AArch64Instr::StxrVar32(operand) => gen_stxr(self, operand, Type::U32),
-            AArch64Instr::StrbRegShiftedReg(operand) => gen_strb_reg(self, operand),
+            AArch64Instr::StrRegSimdFP(operand) => gen_str_reg_simd_fp(&compiler, operand),
+            AArch64Instr::StlxrVar32(operand) => gen_stlxr(&compiler, operand, Type::U32),
+            AArch64Instr::StxrVar64(operand) => gen_stxr(&compiler, operand, Type::U64),
+            AArch64Instr::StxrVar32(operand) => gen_stxr(&compiler, operand, Type::U32),
+            AArch64Instr::StrbRegShiftedReg(operand) => gen_strb_reg(&compiler, operand),
 
             // Advanced SIMD and FP
-            AArch64Instr::DupGeneral(operand) => gen_dup_general(self, operand),
+            AArch64Instr::DupGeneral(operand) => gen_dup_general(&compiler, operand),
 
             // Arithmetic instructions
-            AArch64Instr::AddImm64(operand) => gen_add_imm(self, operand, Type::U64),
-            AArch64Instr::AddImm32(operand) => gen_add_imm(self, operand, Type::U32),
-            AArch64Instr::AddsImm64(operand) => gen_adds_imm(self, operand, Type::U64),
-            AArch64Instr::AddsImm32(operand) => gen_adds_imm(self, operand, Type::U32),
-            AArch64Instr::AddShiftedReg64(operand) => gen_add_shifted_reg64(self, operand),
+            AArch64Instr::AddImm64(operand) => gen_add_imm(&compiler, operand, Type::U64),
+            AArch64Instr::AddImm32(operand) => gen_add_imm(&compiler, operand, Type::U32),
+            AArch64Instr::AddsImm64(operand) => gen_adds_imm(&compiler, operand, Type::U64),
+            AArch64Instr::AddsImm32(operand) => gen_adds_imm(&compiler, operand, Type::U32),
+            AArch64Instr::AddShiftedReg64(operand) => gen_add_shifted_reg64(&compiler, operand),
             AArch64Instr::AddsShiftedReg64(operand) => {
-                gen_adds_shifted_reg(self, operand, Type::U64)
+                gen_adds_shifted_reg(&compiler, operand, Type::U64)
             }
-            AArch64Instr::AddExtReg64(operand) => gen_add_ext_reg64(self, operand),
-            AArch64Instr::SubImm64(operand) => gen_sub_imm(self, operand, Type::U64),
-            AArch64Instr::SubImm32(operand) => gen_sub_imm(self, operand, Type::U32),
-            AArch64Instr::SubShiftedReg64(operand) => gen_sub_shifted_reg_64(self, operand),
+            AArch64Instr::AddExtReg64(operand) => gen_add_ext_reg64(&compiler, operand),
+            AArch64Instr::SubImm64(operand) => gen_sub_imm(&compiler, operand, Type::U64),
+            AArch64Instr::SubImm32(operand) => gen_sub_imm(&compiler, operand, Type::U32),
+            AArch64Instr::SubShiftedReg64(operand) => gen_sub_shifted_reg_64(&compiler, operand),
             AArch64Instr::SubsShiftedReg32(operand) => {
-                gen_subs_shifted_reg(self, operand, Type::U32)
+                gen_subs_shifted_reg(&compiler, operand, Type::U32)
             }
             AArch64Instr::SubsShiftedReg64(operand) => {
-                gen_subs_shifted_reg(self, operand, Type::U64)
+                gen_subs_shifted_reg(&compiler, operand, Type::U64)
             }
-            AArch64Instr::SubsExtReg64(operand) => gen_subs_ext_reg(self, operand, Type::U64),
-            AArch64Instr::SubsImm64(operand) => gen_subs_imm(self, operand, Type::U64),
-            AArch64Instr::SubsImm32(operand) => gen_subs_imm(self, operand, Type::U32),
-            AArch64Instr::Madd32(operand) => gen_madd(self, operand, Type::U32),
-            AArch64Instr::Madd64(operand) => gen_madd(self, operand, Type::U64),
-            AArch64Instr::Msub32(operand) => gen_msub(self, operand, Type::U32),
-            AArch64Instr::SdivVar32(operand) => gen_div(self, operand, Type::I32),
-            AArch64Instr::SdivVar64(operand) => gen_div(self, operand, Type::I64),
-            AArch64Instr::UdivVar32(operand) => gen_div(self, operand, Type::U32),
-            AArch64Instr::UdivVar64(operand) => gen_div(self, operand, Type::U64),
+            AArch64Instr::SubsExtReg64(operand) => gen_subs_ext_reg(&compiler, operand, Type::U64),
+            AArch64Instr::SubsImm64(operand) => gen_subs_imm(&compiler, operand, Type::U64),
+            AArch64Instr::SubsImm32(operand) => gen_subs_imm(&compiler, operand, Type::U32),
+            AArch64Instr::Madd32(operand) => gen_madd(&compiler, operand, Type::U32),
+            AArch64Instr::Madd64(operand) => gen_madd(&compiler, operand, Type::U64),
+            AArch64Instr::Msub32(operand) => gen_msub(&compiler, operand, Type::U32),
+            AArch64Instr::SdivVar32(operand) => gen_div(&compiler, operand, Type::I32),
+            AArch64Instr::SdivVar64(operand) => gen_div(&compiler, operand, Type::I64),
+            AArch64Instr::UdivVar32(operand) => gen_div(&compiler, operand, Type::U32),
+            AArch64Instr::UdivVar64(operand) => gen_div(&compiler, operand, Type::U64),
 
             // bitwise isntructions
-            AArch64Instr::Ubfm32(operand) => gen_ubfm(self, operand, Type::U32),
-            AArch64Instr::Ubfm64(operand) => gen_ubfm(self, operand, Type::U64),
-            AArch64Instr::Sbfm64(operand) => gen_sbfm(self, operand, Type::U64),
-            AArch64Instr::AndImm64(operand) => gen_and_imm(self, operand, Type::U64),
-            AArch64Instr::AndImm32(operand) => gen_and_imm(self, operand, Type::U32),
-            AArch64Instr::AndsImm64(operand) => gen_ands_imm(self, operand, Type::U64),
-            AArch64Instr::AndsImm32(operand) => gen_ands_imm(self, operand, Type::U32),
+            AArch64Instr::Ubfm32(operand) => gen_ubfm(&compiler, operand, Type::U32),
+            AArch64Instr::Ubfm64(operand) => gen_ubfm(&compiler, operand, Type::U64),
+            AArch64Instr::Sbfm64(operand) => gen_sbfm(&compiler, operand, Type::U64),
+            AArch64Instr::AndImm64(operand) => gen_and_imm(&compiler, operand, Type::U64),
+            AArch64Instr::AndImm32(operand) => gen_and_imm(&compiler, operand, Type::U32),
+            AArch64Instr::AndsImm64(operand) => gen_ands_imm(&compiler, operand, Type::U64),
+            AArch64Instr::AndsImm32(operand) => gen_ands_imm(&compiler, operand, Type::U32),
             AArch64Instr::AndsShiftedReg32(operand) => {
-                gen_ands_shifted_reg(self, operand, Type::U32)
+                gen_ands_shifted_reg(&compiler, operand, Type::U32)
             }
             AArch64Instr::AndsShiftedReg64(operand) => {
-                gen_ands_shifted_reg(self, operand, Type::U64)
+                gen_ands_shifted_reg(&compiler, operand, Type::U64)
+            }
+            AArch64Instr::AndShiftedReg64(operand) => {
+                gen_and_shifted_reg(&compiler, operand, Type::U64)
+            }
+            AArch64Instr::OrrImm64(operand) => gen_orr_imm(&compiler, operand, Type::U64),
+            AArch64Instr::OrrImm32(operand) => gen_orr_imm(&compiler, operand, Type::U32),
+            AArch64Instr::OrrShiftedReg64(operand) => {
+                gen_orr_shifted_reg(&compiler, operand, Type::U64)
+            }
+            AArch64Instr::OrrShiftedReg32(operand) => {
+                gen_orr_shifted_reg(&compiler, operand, Type::U32)
+            }
+            AArch64Instr::OrnShiftedReg64(operand) => {
+                gen_orn_shifted_reg(&compiler, operand, Type::U64)
+            }
+            AArch64Instr::OrnShiftedReg32(operand) => {
+                gen_orn_shifted_reg(&compiler, operand, Type::U32)
             }
-            AArch64Instr::AndShiftedReg64(operand) => gen_and_shifted_reg(self, operand, Type::U64),
-            AArch64Instr::OrrImm64(operand) => gen_orr_imm(self, operand, Type::U64),
-            AArch64Instr::OrrImm32(operand) => gen_orr_imm(self, operand, Type::U32),
-            AArch64Instr::OrrShiftedReg64(operand) => gen_orr_shifted_reg(self, operand, Type::U64),
-            AArch64Instr::OrrShiftedReg32(operand) => gen_orr_shifted_reg(self, operand, Type::U32),
-            AArch64Instr::OrnShiftedReg64(operand) => gen_orn_shifted_reg(self, operand, Type::U64),
-            AArch64Instr::OrnShiftedReg32(operand) => gen_orn_shifted_reg(self, operand, Type::U32),
 
-            AArch64Instr::LslvVar64(operand) => gen_lslv(self, operand, Type::U64),
-            AArch64Instr::LslvVar32(operand) => gen_lslv(self, operand, Type::U32),
+            AArch64Instr::LslvVar64(operand) => gen_lslv(&compiler, operand, Type::U64),
+            AArch64Instr::LslvVar32(operand) => gen_lslv(&compiler, operand, Type::U32),
 
             // Branch instructions
-            AArch64Instr::BlImm(operand) => gen_bl_imm(self, operand),
-            AArch64Instr::BImm(operand) => gen_b_imm(self, operand),
-            AArch64Instr::Br(operand) => gen_br(self, operand),
-            AArch64Instr::Blr(operand) => gen_blr(self, operand),
-            AArch64Instr::BCond(operand) => gen_b_cond(self, operand),
-            AArch64Instr::Cbz64(operand) => gen_cbz(self, operand, Type::U32),
-            AArch64Instr::Cbz32(operand) => gen_cbz(self, operand, Type::U64),
-            AArch64Instr::Cbnz32(operand) => gen_cbnz(self, operand, Type::U32),
-            AArch64Instr::Cbnz64(operand) => gen_cbnz(self, operand, Type::U64),
-            AArch64Instr::Ret(operand) => gen_ret(self, operand),
-            AArch64Instr::Tbz(operand) => gen_tbz(self, operand),
-            AArch64Instr::Tbnz(operand) => gen_tbnz(self, operand),
+            AArch64Instr::BlImm(operand) => gen_bl_imm(&compiler, operand),
+            AArch64Instr::BImm(operand) => gen_b_imm(&compiler, operand),
+            AArch64Instr::Br(operand) => gen_br(&compiler, operand),
+            AArch64Instr::Blr(operand) => gen_blr(&compiler, operand),
+            AArch64Instr::BCond(operand) => gen_b_cond(&compiler, operand),
+            AArch64Instr::Cbz64(operand) => gen_cbz(&compiler, operand, Type::U32),
+            AArch64Instr::Cbz32(operand) => gen_cbz(&compiler, operand, Type::U64),
+            AArch64Instr::Cbnz32(operand) => gen_cbnz(&compiler, operand, Type::U32),
+            AArch64Instr::Cbnz64(operand) => gen_cbnz(&compiler, operand, Type::U64),
+            AArch64Instr::Ret(operand) => gen_ret(&compiler, operand),
+            AArch64Instr::Tbz(operand) => gen_tbz(&compiler, operand),
+            AArch64Instr::Tbnz(operand) => gen_tbnz(&compiler, operand),
 
             // Conditional Instructions
-            AArch64Instr::CcmpImmVar32(operand) => gen_ccmp_imm(self, operand, Type::U32),
-            AArch64Instr::CcmpImmVar64(operand) => gen_ccmp_imm(self, operand, Type::U64),
-            AArch64Instr::CcmpRegVar64(operand) => gen_ccmp_reg(self, operand, Type::U64),
-            AArch64Instr::CcmnImmVar64(operand) => gen_ccmn_imm(self, operand, Type::U64),
-            AArch64Instr::Csel32(operand) => gen_csel(self, operand, Type::U32),
-            AArch64Instr::Csel64(operand) => gen_csel(self, operand, Type::U64),
-            AArch64Instr::Csinv64(operand) => gen_csinv(self, operand, Type::U64),
+            AArch64Instr::CcmpImmVar32(operand) => gen_ccmp_imm(&compiler, operand, Type::U32),
+            AArch64Instr::CcmpImmVar64(operand) => gen_ccmp_imm(&compiler, operand, Type::U64),
+            AArch64Instr::CcmpRegVar64(operand) => gen_ccmp_reg(&compiler, operand, Type::U64),
+            AArch64Instr::CcmnImmVar64(operand) => gen_ccmn_imm(&compiler, operand, Type::U64),
+            AArch64Instr::Csel32(operand) => gen_csel(&compiler, operand, Type::U32),
+            AArch64Instr::Csel64(operand) => gen_csel(&compiler, operand, Type::U64),
+            AArch64Instr::Csinv64(operand) => gen_csinv(&compiler, operand, Type::U64),
 
             // Interrupt Instructions
-            AArch64Instr::Svc(operand) => gen_svc(self, operand),
-            AArch64Instr::Brk(operand) => gen_brk(self, operand),
+            AArch64Instr::Svc(operand) => gen_exception(Exception::Svc(operand.imm16)),
+            AArch64Instr::Hvc(operand) => gen_exception(Exception::Hvc(operand.imm16)),
+            AArch64Instr::Smc(operand) => gen_exception(Exception::Smc(operand.imm16)),
+            AArch64Instr::Brk(operand) => gen_brk(&compiler, operand),
+            AArch64Instr::ERet(_) => gen_eret(&compiler),
 
             // Speical instructions
-            AArch64Instr::Mrs(operand) => gen_mrs(self, operand),
-            AArch64Instr::MsrReg(operand) => gen_msr_reg(self, operand),
-            AArch64Instr::MsrImm(operand) => gen_msr_imm(self, operand),
+            AArch64Instr::Mrs(operand) => gen_mrs(&compiler, operand).ok_or_else(unimplemented)?,
+            AArch64Instr::MsrReg(operand) => {
+                gen_msr_reg(&compiler, operand).ok_or_else(unimplemented)?
+            }
+            AArch64Instr::MsrImm(operand) => gen_msr_imm(&compiler, operand)?,
+            AArch64Instr::Sys(operand) => gen_sys(&compiler, operand).ok_or_else(unimplemented)?,
             AArch64Instr::Nop | AArch64Instr::Wfi | AArch64Instr::Dmb(_) | AArch64Instr::Isb(_) => {
                 let mut block = IrBlock::new(4);
 
@@ -210,12 +283,15 @@ impl Compiler for AArch64Compiler {
                 block
             }
 
-            _ => unimplemented!("unimplemented instruction: {:?}", item),
-        }
+            AArch64Instr::Udf(_) | AArch64Instr::Unallocated => return Err(compiler.unallocated()),
+            _ => return Err(unimplemented()),
+        };
+
+        Ok(block)
     }
 }
 
-fn gen_movz(compiler: &AArch64Compiler, operand: HwImm16Rd) -> IrBlock {
+fn gen_movz(compiler: &InstrContext, operand: HwImm16Rd) -> IrBlock {
     let mut block = IrBlock::new(4);
     let pos = operand.hw << 4;
 
@@ -226,7 +302,7 @@ fn gen_movz(compiler: &AArch64Compiler, operand: HwImm16Rd) -> IrBlock {
     block
 }
 
-fn gen_adr(compiler: &AArch64Compiler, operand: PcRelAddressing) -> IrBlock {
+fn gen_adr(compiler: &InstrContext, operand: PcRelAddressing) -> IrBlock {
     let mut block = IrBlock::new(4);
     let imm = sign_extend((operand.immhi as i64) << 2 | (operand.immlo as i64), 21);
 
@@ -237,7 +313,7 @@ fn gen_adr(compiler: &AArch64Compiler, operand: PcRelAddressing) -> IrBlock {
     block
 }
 
-fn gen_adrp(compiler: &AArch64Compiler, operand: PcRelAddressing) -> IrBlock {
+fn gen_adrp(compiler: &InstrContext, operand: PcRelAddressing) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let imm = sign_extend(
@@ -260,7 +336,7 @@ fn gen_adrp(compiler: &AArch64Compiler, operand: PcRelAddressing) -> IrBlock {
     block
 }
 
-fn gen_orr_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
+fn gen_orr_shifted_reg(compiler: &InstrContext, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     let rd = compiler.gpr(operand.rd);
     let rm = if operand.rm == 31 {
@@ -290,7 +366,7 @@ fn gen_orr_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty:
     block
 }
 
-fn gen_ldr_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
+fn gen_ldr_imm(compiler: &InstrContext, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (mut wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, false);
@@ -335,7 +411,7 @@ fn gen_ldr_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty: Type)
     block
 }
 
-fn gen_str_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
+fn gen_str_imm(compiler: &InstrContext, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, false);
@@ -371,7 +447,7 @@ fn gen_str_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty: Type)
     block
 }
 
-fn gen_ldr_lit_var64(compiler: &AArch64Compiler, operand: Imm19Rt) -> IrBlock {
+fn gen_ldr_lit_var64(compiler: &InstrContext, operand: Imm19Rt) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let offset = sign_extend((operand.imm19 << 2) as i64, 21);
@@ -384,7 +460,7 @@ fn gen_ldr_lit_var64(compiler: &AArch64Compiler, operand: Imm19Rt) -> IrBlock {
     block
 }
 
-fn gen_stp_var(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
+fn gen_stp_var(compiler: &InstrContext, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let scale = 2 + (operand.opc >> 1);
@@ -433,7 +509,7 @@ fn gen_stp_var(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Type)
     block
 }
 
-fn gen_add_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> IrBlock {
+fn gen_add_imm(compiler: &InstrContext, operand: ShImm12RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rd = if operand.rd == 31 {
@@ -463,7 +539,7 @@ fn gen_add_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> Ir
     block
 }
 
-fn gen_add_shifted_reg64(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd) -> IrBlock {
+fn gen_add_shifted_reg64(compiler: &InstrContext, operand: ShiftRmImm6RnRd) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = compiler.gpr(operand.rn);
@@ -490,7 +566,7 @@ fn gen_add_shifted_reg64(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd) -
     block
 }
 
-fn gen_sub_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> IrBlock {
+fn gen_sub_imm(compiler: &InstrContext, operand: ShImm12RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = if operand.rn == 31 {
@@ -519,7 +595,7 @@ fn gen_sub_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> Ir
     block
 }
 
-fn gen_sub_shifted_reg_64(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd) -> IrBlock {
+fn gen_sub_shifted_reg_64(compiler: &InstrContext, operand: ShiftRmImm6RnRd) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rm = compiler.gpr(operand.rm);
@@ -547,7 +623,7 @@ fn gen_sub_shifted_reg_64(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd)
     block
 }
 
-fn gen_subs_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
+fn gen_subs_shifted_reg(compiler: &InstrContext, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = if operand.rn == 31 {
@@ -579,7 +655,7 @@ fn gen_subs_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty
     block
 }
 
-fn gen_subs_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> IrBlock {
+fn gen_subs_imm(compiler: &InstrContext, operand: ShImm12RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let imm = match operand.sh {
@@ -608,7 +684,7 @@ fn gen_subs_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> I
     block
 }
 
-fn gen_ands_imm(compiler: &AArch64Compiler, operand: LogicalImm, ty: Type) -> IrBlock {
+fn gen_ands_imm(compiler: &InstrContext, operand: LogicalImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (imm, _) = decode_bit_masks(operand.n, operand.imms, operand.immr, true, 64);
@@ -630,7 +706,7 @@ fn gen_ands_imm(compiler: &AArch64Compiler, operand: LogicalImm, ty: Type) -> Ir
     block
 }
 
-fn gen_bl_imm(compiler: &AArch64Compiler, operand: Imm26) -> IrBlock {
+fn gen_bl_imm(compiler: &InstrContext, operand: Imm26) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ir = Ir::Add(Type::U64, Operand::Ip, Operand::imm(Type::U64, 4));
@@ -648,7 +724,7 @@ fn gen_bl_imm(compiler: &AArch64Compiler, operand: Imm26) -> IrBlock {
     block
 }
 
-fn gen_b_imm(_compiler: &AArch64Compiler, operand: Imm26) -> IrBlock {
+fn gen_b_imm(_compiler: &InstrContext, operand: Imm26) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let imm = sign_extend((operand.imm26 << 2) as i64, 28);
@@ -661,7 +737,7 @@ fn gen_b_imm(_compiler: &AArch64Compiler, operand: Imm26) -> IrBlock {
     block
 }
 
-fn gen_br(compiler: &AArch64Compiler, operand: UncondBranchReg) -> IrBlock {
+fn gen_br(compiler: &InstrContext, operand: UncondBranchReg) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ir = Ir::Value(Operand::gpr(Type::U64, compiler.gpr(operand.rn)));
@@ -672,7 +748,7 @@ fn gen_br(compiler: &AArch64Compiler, operand: UncondBranchReg) -> IrBlock {
     block
 }
 
-fn gen_b_cond(_compiler: &AArch64Compiler, operand: Imm19Cond) -> IrBlock {
+fn gen_b_cond(_compiler: &InstrContext, operand: Imm19Cond) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let offset = sign_extend((operand.imm19 << 2) as i64, 21);
@@ -689,7 +765,7 @@ fn gen_b_cond(_compiler: &AArch64Compiler, operand: Imm19Cond) -> IrBlock {
     block
 }
 
-fn gen_cbz(compiler: &AArch64Compiler, operand: Imm19Rt, ty: Type) -> IrBlock {
+fn gen_cbz(compiler: &InstrContext, operand: Imm19Rt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let offset = sign_extend((operand.imm19 << 2) as i64, 21);
@@ -713,7 +789,7 @@ fn gen_cbz(compiler: &AArch64Compiler, operand: Imm19Rt, ty: Type) -> IrBlock {
     block
 }
 
-fn gen_cbnz(compiler: &AArch64Compiler, operand: Imm19Rt, ty: Type) -> IrBlock {
+fn gen_cbnz(compiler: &InstrContext, operand: Imm19Rt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let offset = sign_extend((operand.imm19 << 2) as i64, 21);
@@ -737,7 +813,7 @@ fn gen_cbnz(compiler: &AArch64Compiler, operand: Imm19Rt, ty: Type) -> IrBlock {
     block
 }
 
-fn gen_ccmp_imm(compiler: &AArch64Compiler, operand: CondCmpImm, ty: Type) -> IrBlock {
+fn gen_ccmp_imm(compiler: &InstrContext, operand: CondCmpImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = compiler.gpr(operand.rn);
@@ -769,7 +845,7 @@ fn gen_ccmp_imm(compiler: &AArch64Compiler, operand: CondCmpImm, ty: Type) -> Ir
     block
 }
 
-fn gen_csel(compiler: &AArch64Compiler, operand: RmCondRnRd, ty: Type) -> IrBlock {
+fn gen_csel(compiler: &InstrContext, operand: RmCondRnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = if operand.rn == 31 {
@@ -794,11 +870,33 @@ fn gen_csel(compiler: &AArch64Compiler, operand: RmCondRnRd, ty: Type) -> IrBloc
     block
 }
 
-fn gen_svc(_compiler: &AArch64Compiler, _operand: ExceptionGen) -> IrBlock {
-    todo!()
+fn gen_exception(exc: Exception) -> IrBlock {
+    let mut block = IrBlock::new(4);
+
+    let ir = Ir::Value(Operand::imm(Type::U64, exc.syndrome(false)));
+    let ds = BlockDestination::Exception;
+
+    block.append(ir, ds);
+
+    block
 }
 
-fn gen_brk(_compiler: &AArch64Compiler, operand: ExceptionGen) -> IrBlock {
+fn gen_eret(compiler: &InstrContext) -> IrBlock {
+    if compiler.el() == 0 {
+        return gen_exception(Exception::Undefined);
+    }
+
+    let mut block = IrBlock::new(4);
+
+    let ir = Ir::Nop;
+    let ds = BlockDestination::ExceptionReturn;
+
+    block.append(ir, ds);
+
+    block
+}
+
+fn gen_brk(_compiler: &InstrContext, operand: ExceptionGen) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ir = Ir::Value(Operand::imm(Type::U16, operand.imm16 as u64));
@@ -809,7 +907,7 @@ fn gen_brk(_compiler: &AArch64Compiler, operand: ExceptionGen) -> IrBlock {
     block
 }
 
-fn gen_ubfm(compiler: &AArch64Compiler, operand: Bitfield, ty: Type) -> IrBlock {
+fn gen_ubfm(compiler: &InstrContext, operand: Bitfield, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let src = Operand::Gpr(ty, compiler.gpr(operand.rn));
@@ -837,7 +935,7 @@ fn gen_ubfm(compiler: &AArch64Compiler, operand: Bitfield, ty: Type) -> IrBlock
     block
 }
 
-fn gen_sbfm(compiler: &AArch64Compiler, operand: Bitfield, ty: Type) -> IrBlock {
+fn gen_sbfm(compiler: &InstrContext, operand: Bitfield, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let src = Operand::Gpr(ty, compiler.gpr(operand.rn));
@@ -866,7 +964,7 @@ fn gen_sbfm(compiler: &AArch64Compiler, operand: Bitfield, ty: Type) -> IrBlock
     block
 }
 
-fn gen_ldrb_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt) -> IrBlock {
+fn gen_ldrb_imm(compiler: &InstrContext, operand: OpcSizeImm12RnRt) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, false);
@@ -911,7 +1009,7 @@ fn gen_ldrb_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt) -> IrBloc
     block
 }
 
-fn gen_ret(compiler: &AArch64Compiler, operand: UncondBranchReg) -> IrBlock {
+fn gen_ret(compiler: &InstrContext, operand: UncondBranchReg) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ir = Ir::Value(Operand::gpr(Type::U64, compiler.gpr(operand.rn)));
@@ -921,7 +1019,7 @@ fn gen_ret(compiler: &AArch64Compiler, operand: UncondBranchReg) -> IrBlock {
     block
 }
 
-fn gen_add_ext_reg64(compiler: &AArch64Compiler, operand: AddSubtractExtReg) -> IrBlock {
+fn gen_add_ext_reg64(compiler: &InstrContext, operand: AddSubtractExtReg) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -948,7 +1046,7 @@ fn gen_add_ext_reg64(compiler: &AArch64Compiler, operand: AddSubtractExtReg) ->
     block
 }
 
-fn gen_ldrh_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt) -> IrBlock {
+fn gen_ldrh_imm(compiler: &InstrContext, operand: OpcSizeImm12RnRt) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (mut wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, false);
@@ -993,8 +1091,26 @@ fn gen_ldrh_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt) -> IrBloc
     block
 }
 
-fn gen_mrs(compiler: &AArch64Compiler, operand: SysRegMov) -> IrBlock {
+// `None` if the system register is not emulated.
+fn gen_mrs(compiler: &InstrContext, operand: SysRegMov) -> Option<IrBlock> {
     let mut block = IrBlock::new(4);
+    let ds = BlockDestination::Gpr(Type::U64, compiler.gpr(operand.rt));
+
+    // TVAL is the 32-bit signed distance from the counter to the compare value.
+    if let Some((cnt, cval)) = timer_tval(&operand) {
+        let ir = Ir::And(
+            Type::U64,
+            Operand::ir(Ir::Sub(
+                Type::U64,
+                Operand::Sys(Type::U64, compiler.reg_by_name(cval)),
+                Operand::Sys(Type::U64, compiler.reg_by_name(cnt)),
+            )),
+            Operand::imm(Type::U64, 0xffff_ffff),
+        );
+        block.append(ir, ds);
+
+        return Some(block);
+    }
 
     // TODO: emulate system registers
     let op = match (
@@ -1016,13 +1132,21 @@ fn gen_mrs(compiler: &AArch64Compiler, operand: SysRegMov) -> IrBlock {
             Operand::imm(Type::U64, ret)
         }
         (0b11, 0b000, 0b0100, 0b0010, 0b010) => {
-            // Get current exception level.
-            // We are using exception level one.
-            let current_exception_level = 1;
-            Operand::imm(Type::U64, current_exception_level << 2)
+            // CurrentEL holds the current exception level at bits [3:2].
+            Operand::ir(Ir::LShl(
+                Type::U64,
+                Operand::ir(flag(Pstate::EL.range())),
+                Operand::imm(Type::U64, 2),
+            ))
         }
-        (0b11, 0b000, 0b1100, 0b0000, 0b000) => {
-            Operand::Sys(Type::U64, compiler.reg_by_name("vbar_el1"))
+        (0b11, 0b000, 0b0100, 0b0010, 0b000) => Operand::ir(flag(Pstate::SP.range())),
+        (0b11, 0b011, 0b0100, 0b0010, 0b001) => {
+            // DAIF holds the flags at bits [9:6].
+            Operand::ir(Ir::LShl(
+                Type::U64,
+                Operand::ir(flag(Pstate::F.idx()..Pstate::D.idx() + 1)),
+                Operand::imm(Type::U64, 6),
+            ))
         }
         (0b11, 0b011, 0b1101, 0b0000, 0b010) => {
             Operand::Sys(Type::U64, compiler.reg_by_name("tpidr_el0"))
@@ -1030,43 +1154,235 @@ fn gen_mrs(compiler: &AArch64Compiler, operand: SysRegMov) -> IrBlock {
         (0b11, 0b000, 0b0000, 0b0000, 0b101) => {
             Operand::Sys(Type::U64, compiler.reg_by_name("mpidr_el1"))
         }
-        _ => unimplemented!("MRS: {:?}", operand),
+        _ => match (timer_reg(&operand), banked_reg(compiler, &operand)) {
+            (Some(name), _) => Operand::Sys(Type::U64, compiler.reg_by_name(name)),
+            (_, Some(BlockDestination::Sys(ty, id))) => Operand::Sys(ty, id),
+            (_, Some(BlockDestination::Gpr(ty, id))) => Operand::Gpr(ty, id),
+            _ => return None,
+        },
     };
 
     let ir = Ir::Value(op);
-    let ds = BlockDestination::Gpr(Type::U64, compiler.gpr(operand.rt));
 
     block.append(ir, ds);
 
-    block
+    Some(block)
 }
 
-fn gen_msr_reg(compiler: &AArch64Compiler, operand: SysRegMov) -> IrBlock {
+// `None` if the system register is not emulated.
+fn gen_msr_reg(compiler: &InstrContext, operand: SysRegMov) -> Option<IrBlock> {
     let mut block = IrBlock::new(4);
 
+    let src = Operand::Gpr(Type::U64, compiler.gpr(operand.rt));
+
+    if let Some((cnt, cval)) = timer_tval(&operand) {
+        // Sign extend the lower 32 bits of the source.
+        let sign = Operand::imm(Type::U64, 0x8000_0000);
+        let tval = Ir::Sub(
+            Type::U64,
+            Operand::ir(Ir::Xor(
+                Type::U64,
+                Operand::ir(Ir::And(
+                    Type::U64,
+                    src,
+                    Operand::imm(Type::U64, 0xffff_ffff),
+                )),
+                sign.clone(),
+            )),
+            sign,
+        );
+        let ir = Ir::Add(
+            Type::U64,
+            Operand::Sys(Type::U64, compiler.reg_by_name(cnt)),
+            Operand::ir(tval),
+        );
+        block.append(
+            ir,
+            BlockDestination::Sys(Type::U64, compiler.reg_by_name(cval)),
+        );
+
+        return Some(block);
+    }
+
+    if let Some(name) = timer_reg(&operand) {
+        let id = compiler.reg_by_name(name);
+        let ir = match name {
+            // Counters are read-only.
+            "cntpct_el0" | "cntvct_el0" => return Some(block),
+            // ISTATUS is read-only, and maintained by the timer.
+            "cntp_ctl_el0" | "cntv_ctl_el0" => Ir::Or(
+                Type::U64,
+                Operand::ir(Ir::And(
+                    Type::U64,
+                    Operand::Sys(Type::U64, id),
+                    Operand::imm(Type::U64, 0b100),
+                )),
+                Operand::ir(Ir::And(Type::U64, src, Operand::imm(Type::U64, 0b11))),
+            ),
+            _ => Ir::Value(src),
+        };
+        block.append(ir, BlockDestination::Sys(Type::U64, id));
+
+        return Some(block);
+    }
+
     // TODO: emulate system registers
-    let dest = match (
+    let ds = match (
         operand.o0 + 2,
         operand.op1,
         operand.crn,
         operand.crm,
         operand.op2,
     ) {
-        (0b11, 0b011, 0b1101, 0b0000, 0b010) => compiler.reg_by_name("tpidr_el0"), // tpidr_el0, get current thread.
-        (0b11, 0b000, 0b1100, 0b0000, 0b000) => compiler.reg_by_name("vbar_el1"),
-        (0b11, 0b000, 0b0001, 0b0000, 0b010) => compiler.reg_by_name("cpacr_el1"),
-        _ => unimplemented!("MSR: {:x?}", operand),
+        (0b11, 0b000, 0b0100, 0b0010, 0b000) => {
+            let sp = Operand::ir(Ir::And(Type::U64, src, Operand::imm(Type::U64, 1)));
+            let ir = Ir::Or(
+                Type::U64,
+                Operand::ir(Ir::And(
+                    Type::U64,
+                    Operand::Flag,
+                    Operand::imm(Type::U64, !Pstate::SP.mask()),
+                )),
+                Operand::ir(Ir::LShl(
+                    Type::U64,
+                    sp,
+                    Operand::imm(Type::U64, Pstate::SP.idx()),
+                )),
+            );
+            block.append(ir, BlockDestination::Flags);
+            end_block(&mut block);
+
+            return Some(block);
+        }
+        (0b11, 0b011, 0b0100, 0b0010, 0b001) => {
+            let daif = Operand::ir(Ir::And(Type::U64, src, Operand::imm(Type::U64, 0xf << 6)));
+            let mask = Pstate::D.mask() | Pstate::A.mask() | Pstate::I.mask() | Pstate::F.mask();
+            let ir = Ir::Or(
+                Type::U64,
+                Operand::ir(Ir::And(
+                    Type::U64,
+                    Operand::Flag,
+                    Operand::imm(Type::U64, !mask),
+                )),
+                Operand::ir(Ir::LShl(
+                    Type::U64,
+                    daif,
+                    Operand::imm(Type::U64, Pstate::F.idx() - 6),
+                )),
+            );
+            block.append(ir, BlockDestination::Flags);
+            end_block(&mut block);
+
+            return Some(block);
+        }
+        (0b11, 0b011, 0b1101, 0b0000, 0b010) => {
+            BlockDestination::Sys(Type::U64, compiler.reg_by_name("tpidr_el0"))
+        } // tpidr_el0, get current thread.
+        (0b11, 0b000, 0b0001, 0b0000, 0b010) => {
+            BlockDestination::Sys(Type::U64, compiler.reg_by_name("cpacr_el1"))
+        }
+        _ => banked_reg(compiler, &operand)?,
     };
 
-    let ir = Ir::Value(Operand::Gpr(Type::U64, compiler.gpr(operand.rt)));
-    let ds = BlockDestination::Sys(Type::U64, dest);
+    let ir = Ir::Value(src);
+    let changes_translation = match ds {
+        BlockDestination::Sys(_, id) => ["sctlr_el1", "tcr_el1", "ttbr0_el1", "ttbr1_el1"]
+            .iter()
+            .any(|name| compiler.reg_by_name(name) == id),
+        _ => false,
+    };
 
     block.append(ir, ds);
 
-    block
+    // Translations cached in TLBs depend on the registers.
+    if changes_translation {
+        block.append(Ir::Nop, BlockDestination::InvalidateTlb);
+        end_block(&mut block);
+    }
+
+    Some(block)
+}
+
+// Generic timer registers, which are backed by system registers of the same name.
+fn timer_reg(operand: &SysRegMov) -> Option<&'static str> {
+    let name = match (
+        operand.o0 + 2,
+        operand.op1,
+        operand.crn,
+        operand.crm,
+        operand.op2,
+    ) {
+        (0b11, 0b011, 0b1110, 0b0000, 0b000) => "cntfrq_el0",
+        // CNTPCTSS_EL0 and CNTVCTSS_EL0 are self-synchronized views of the counters.
+        (0b11, 0b011, 0b1110, 0b0000, 0b001 | 0b101) => "cntpct_el0",
+        (0b11, 0b011, 0b1110, 0b0000, 0b010 | 0b110) => "cntvct_el0",
+        (0b11, 0b000, 0b1110, 0b0001, 0b000) => "cntkctl_el1",
+        (0b11, 0b100, 0b1110, 0b0001, 0b000) => "cnthctl_el2",
+        (0b11, 0b100, 0b1110, 0b0000, 0b011) => "cntvoff_el2",
+        (0b11, 0b011, 0b1110, 0b0010, 0b001) => "cntp_ctl_el0",
+        (0b11, 0b011, 0b1110, 0b0010, 0b010) => "cntp_cval_el0",
+        (0b11, 0b011, 0b1110, 0b0011, 0b001) => "cntv_ctl_el0",
+        (0b11, 0b011, 0b1110, 0b0011, 0b010) => "cntv_cval_el0",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+// Counter and compare value of a timer, if the operand is its TVAL register.
+fn timer_tval(operand: &SysRegMov) -> Option<(&'static str, &'static str)> {
+    match (
+        operand.o0 + 2,
+        operand.op1,
+        operand.crn,
+        operand.crm,
+        operand.op2,
+    ) {
+        (0b11, 0b011, 0b1110, 0b0010, 0b000) => Some(("cntpct_el0", "cntp_cval_el0")),
+        (0b11, 0b011, 0b1110, 0b0011, 0b000) => Some(("cntvct_el0", "cntv_cval_el0")),
+        _ => None,
+    }
+}
+
+// System registers and stack pointers banked by exception level.
+fn banked_reg(compiler: &InstrContext, operand: &SysRegMov) -> Option<BlockDestination> {
+    let el = match operand.op1 {
+        0b000 => 1,
+        0b100 => 2,
+        0b110 => 3,
+        _ => return None,
+    };
+
+    let name = match (operand.o0 + 2, operand.crn, operand.crm, operand.op2) {
+        (0b11, 0b1100, 0b0000, 0b000) => "vbar",
+        (0b11, 0b0100, 0b0000, 0b000) => "spsr",
+        (0b11, 0b0100, 0b0000, 0b001) => "elr",
+        (0b11, 0b0101, 0b0010, 0b000) => "esr",
+        (0b11, 0b0110, 0b0000, 0b000) => "far",
+        (0b11, 0b0001, 0b0000, 0b000) => "sctlr",
+        (0b11, 0b0010, 0b0000, 0b000) => "ttbr0",
+        (0b11, 0b0010, 0b0000, 0b001) if el == 1 => "ttbr1",
+        (0b11, 0b0010, 0b0000, 0b010) => "tcr",
+        (0b11, 0b1010, 0b0010, 0b000) => "mair",
+        (0b11, 0b0100, 0b0001, 0b000) => {
+            // SP_ELx is accessed with the encoding of the next exception level.
+            let id = compiler.reg_by_name(format!("sp_el{}", el - 1));
+            return Some(BlockDestination::Gpr(Type::U64, id));
+        }
+        _ => return None,
+    };
+
+    let id = compiler.reg_by_name(format!("{}_el{}", name, el));
+    Some(BlockDestination::Sys(Type::U64, id))
 }
 
-fn gen_ldr_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset, ty: Type) -> IrBlock {
+// Continue at the next instruction in another block, as the translation mode is changed.
+fn end_block(block: &mut IrBlock) {
+    let ir = Ir::Add(Type::U64, Operand::Ip, Operand::imm(Type::U64, 4));
+    block.append(ir, BlockDestination::Pc);
+}
+
+fn gen_ldr_reg(compiler: &InstrContext, operand: LoadStoreRegRegOffset, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -1097,7 +1413,7 @@ fn gen_ldr_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset, ty: T
     block
 }
 
-fn gen_blr(compiler: &AArch64Compiler, operand: UncondBranchReg) -> IrBlock {
+fn gen_blr(compiler: &InstrContext, operand: UncondBranchReg) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ir = Ir::Add(Type::U64, Operand::Ip, Operand::Immediate(Type::U64, 4));
@@ -1113,7 +1429,7 @@ fn gen_blr(compiler: &AArch64Compiler, operand: UncondBranchReg) -> IrBlock {
     block
 }
 
-fn gen_ldp(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
+fn gen_ldp(compiler: &InstrContext, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (mut wback, post_index) = decode_o_for_ld_st_pair_offset(operand.o);
@@ -1181,7 +1497,7 @@ fn gen_ldp(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Type) -> I
     block
 }
 
-fn gen_ands_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
+fn gen_ands_shifted_reg(compiler: &InstrContext, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let shift_type = decode_shift(operand.shift);
@@ -1211,7 +1527,7 @@ fn gen_ands_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty
     block
 }
 
-fn gen_and_imm(compiler: &AArch64Compiler, operand: LogicalImm, ty: Type) -> IrBlock {
+fn gen_and_imm(compiler: &InstrContext, operand: LogicalImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (imm, _) = decode_bit_masks(
@@ -1240,7 +1556,7 @@ fn gen_and_imm(compiler: &AArch64Compiler, operand: LogicalImm, ty: Type) -> IrB
     block
 }
 
-fn gen_tbz(compiler: &AArch64Compiler, operand: B5B40Imm14Rt) -> IrBlock {
+fn gen_tbz(compiler: &InstrContext, operand: B5B40Imm14Rt) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ty = if operand.b5 == 1 {
@@ -1272,7 +1588,7 @@ fn gen_tbz(compiler: &AArch64Compiler, operand: B5B40Imm14Rt) -> IrBlock {
     block
 }
 
-fn gen_tbnz(compiler: &AArch64Compiler, operand: B5B40Imm14Rt) -> IrBlock {
+fn gen_tbnz(compiler: &InstrContext, operand: B5B40Imm14Rt) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ty = if operand.b5 == 1 {
@@ -1304,7 +1620,7 @@ fn gen_tbnz(compiler: &AArch64Compiler, operand: B5B40Imm14Rt) -> IrBlock {
     block
 }
 
-fn gen_movn(compiler: &AArch64Compiler, operand: HwImm16Rd, ty: Type) -> IrBlock {
+fn gen_movn(compiler: &InstrContext, operand: HwImm16Rd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let pos = operand.hw << 4;
@@ -1319,7 +1635,7 @@ fn gen_movn(compiler: &AArch64Compiler, operand: HwImm16Rd, ty: Type) -> IrBlock
     block
 }
 
-fn gen_strb_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt) -> IrBlock {
+fn gen_strb_imm(compiler: &InstrContext, operand: OpcSizeImm12RnRt) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, false);
@@ -1356,7 +1672,7 @@ fn gen_strb_imm(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt) -> IrBloc
     block
 }
 
-fn gen_sturb_imm(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm) -> IrBlock {
+fn gen_sturb_imm(compiler: &InstrContext, operand: LdStRegUnscaledImm) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let offset = sign_extend(operand.imm9 as i64, 9);
@@ -1374,7 +1690,7 @@ fn gen_sturb_imm(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm) -> IrB
     block
 }
 
-fn gen_orr_imm(compiler: &AArch64Compiler, operand: LogicalImm, ty: Type) -> IrBlock {
+fn gen_orr_imm(compiler: &InstrContext, operand: LogicalImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (imm, _) = decode_bit_masks(
@@ -1406,7 +1722,7 @@ fn gen_orr_imm(compiler: &AArch64Compiler, operand: LogicalImm, ty: Type) -> IrB
     block
 }
 
-fn gen_madd(compiler: &AArch64Compiler, operand: DataProc3Src, ty: Type) -> IrBlock {
+fn gen_madd(compiler: &InstrContext, operand: DataProc3Src, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let op1 = Operand::gpr(ty, compiler.gpr(operand.rn));
@@ -1425,7 +1741,7 @@ fn gen_madd(compiler: &AArch64Compiler, operand: DataProc3Src, ty: Type) -> IrBl
     block
 }
 
-fn gen_str_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset, ty: Type) -> IrBlock {
+fn gen_str_reg(compiler: &InstrContext, operand: LoadStoreRegRegOffset, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let shift = if operand.s == 1 { operand.size } else { 0 };
@@ -1450,7 +1766,7 @@ fn gen_str_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset, ty: T
     block
 }
 
-fn gen_stur(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm, ty: Type) -> IrBlock {
+fn gen_stur(compiler: &InstrContext, operand: LdStRegUnscaledImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = if operand.rn == 31 {
@@ -1470,7 +1786,7 @@ fn gen_stur(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm, ty: Type) -
     block
 }
 
-fn gen_and_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
+fn gen_and_shifted_reg(compiler: &InstrContext, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let shift_type = decode_shift(operand.shift);
@@ -1493,7 +1809,7 @@ fn gen_and_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty:
     block
 }
 
-fn gen_dup_general(compiler: &AArch64Compiler, operand: AdvancedSimdCopy) -> IrBlock {
+fn gen_dup_general(compiler: &InstrContext, operand: AdvancedSimdCopy) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let size = operand.imm5.trailing_zeros();
@@ -1514,7 +1830,7 @@ fn gen_dup_general(compiler: &AArch64Compiler, operand: AdvancedSimdCopy) -> IrB
     block
 }
 
-fn gen_stur_simd_fp(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm, ty: Type) -> IrBlock {
+fn gen_stur_simd_fp(compiler: &InstrContext, operand: LdStRegUnscaledImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     let offset = sign_extend(operand.imm9 as i64, 9);
 
@@ -1532,7 +1848,7 @@ fn gen_stur_simd_fp(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm, ty:
     block
 }
 
-fn gen_stp_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
+fn gen_stp_simd_fp(compiler: &InstrContext, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index) = decode_o_for_ld_st_pair_offset(operand.o);
@@ -1571,7 +1887,7 @@ fn gen_stp_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Ty
     block
 }
 
-fn gen_movk(compiler: &AArch64Compiler, operand: HwImm16Rd, ty: Type) -> IrBlock {
+fn gen_movk(compiler: &InstrContext, operand: HwImm16Rd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rd = compiler.gpr(operand.rd);
@@ -1593,7 +1909,7 @@ fn gen_movk(compiler: &AArch64Compiler, operand: HwImm16Rd, ty: Type) -> IrBlock
 
 //==================================================================================
 
-fn gen_div(compiler: &AArch64Compiler, operand: DataProc2Src, ty: Type) -> IrBlock {
+fn gen_div(compiler: &InstrContext, operand: DataProc2Src, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let op1 = Operand::gpr(ty, compiler.gpr(operand.rn));
@@ -1614,7 +1930,7 @@ fn gen_div(compiler: &AArch64Compiler, operand: DataProc2Src, ty: Type) -> IrBlo
     block
 }
 
-fn gen_msub(compiler: &AArch64Compiler, operand: DataProc3Src, ty: Type) -> IrBlock {
+fn gen_msub(compiler: &InstrContext, operand: DataProc3Src, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let op1 = Operand::gpr(ty, compiler.gpr(operand.rn));
@@ -1630,7 +1946,7 @@ fn gen_msub(compiler: &AArch64Compiler, operand: DataProc3Src, ty: Type) -> IrBl
     block
 }
 
-fn gen_adds_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> IrBlock {
+fn gen_adds_imm(compiler: &InstrContext, operand: ShImm12RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let imm = if operand.sh == 0 {
@@ -1658,7 +1974,7 @@ fn gen_adds_imm(compiler: &AArch64Compiler, operand: ShImm12RnRd, ty: Type) -> I
     block
 }
 
-fn gen_ldrb_reg_shifted_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset) -> IrBlock {
+fn gen_ldrb_reg_shifted_reg(compiler: &InstrContext, operand: LoadStoreRegRegOffset) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = if operand.rn == 31 {
@@ -1684,7 +2000,7 @@ fn gen_ldrb_reg_shifted_reg(compiler: &AArch64Compiler, operand: LoadStoreRegReg
     block
 }
 
-fn gen_movi(compiler: &AArch64Compiler, operand: AdvSimdModifiedImm) -> IrBlock {
+fn gen_movi(compiler: &InstrContext, operand: AdvSimdModifiedImm) -> IrBlock {
     use utility::Pattern;
 
     let mut block = IrBlock::new(4);
@@ -1755,7 +2071,7 @@ fn gen_movi(compiler: &AArch64Compiler, operand: AdvSimdModifiedImm) -> IrBlock
     block
 }
 
-fn gen_str_imm_simd_fp(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
+fn gen_str_imm_simd_fp(compiler: &InstrContext, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, true);
@@ -1787,7 +2103,7 @@ fn gen_str_imm_simd_fp(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty
     block
 }
 
-fn gen_adds_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
+fn gen_adds_shifted_reg(compiler: &InstrContext, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = compiler.gpr(operand.rn);
@@ -1811,7 +2127,7 @@ fn gen_adds_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty
     block
 }
 
-fn gen_ldrsh_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset, ty: Type) -> IrBlock {
+fn gen_ldrsh_reg(compiler: &InstrContext, operand: LoadStoreRegRegOffset, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -1836,7 +2152,7 @@ fn gen_ldrsh_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset, ty:
     block
 }
 
-fn gen_orn_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
+fn gen_orn_shifted_reg(compiler: &InstrContext, operand: ShiftRmImm6RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let shift_type = decode_shift(operand.shift);
@@ -1866,7 +2182,7 @@ fn gen_orn_shifted_reg(compiler: &AArch64Compiler, operand: ShiftRmImm6RnRd, ty:
     block
 }
 
-fn gen_lslv(compiler: &AArch64Compiler, operand: DataProc2Src, ty: Type) -> IrBlock {
+fn gen_lslv(compiler: &InstrContext, operand: DataProc2Src, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let shift_type = decode_shift(0);
@@ -1890,7 +2206,7 @@ fn gen_lslv(compiler: &AArch64Compiler, operand: DataProc2Src, ty: Type) -> IrBl
     block
 }
 
-fn gen_ccmn_imm(compiler: &AArch64Compiler, operand: CondCmpImm, ty: Type) -> IrBlock {
+fn gen_ccmn_imm(compiler: &InstrContext, operand: CondCmpImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = compiler.gpr(operand.rn);
@@ -1922,7 +2238,7 @@ fn gen_ccmn_imm(compiler: &AArch64Compiler, operand: CondCmpImm, ty: Type) -> Ir
     block
 }
 
-fn gen_ldr_imm_simd_fp(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
+fn gen_ldr_imm_simd_fp(compiler: &InstrContext, operand: OpcSizeImm12RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index, offset) = decode_operand_for_ld_st_reg_imm(operand, true);
@@ -1959,7 +2275,7 @@ fn gen_ldr_imm_simd_fp(compiler: &AArch64Compiler, operand: OpcSizeImm12RnRt, ty
     block
 }
 
-fn gen_ldaxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock {
+fn gen_ldaxr(compiler: &InstrContext, operand: RsRt2RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     block.set_atomic(); // this is atomic operation.
 
@@ -1978,7 +2294,7 @@ fn gen_ldaxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBloc
     block
 }
 
-fn gen_stlxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock {
+fn gen_stlxr(compiler: &InstrContext, operand: RsRt2RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     block.set_atomic(); // this is atomic operation.
 
@@ -2007,7 +2323,7 @@ fn gen_stlxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBloc
     block
 }
 
-fn gen_ldar(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock {
+fn gen_ldar(compiler: &InstrContext, operand: RsRt2RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     block.set_atomic(); // this is atomic operation.
 
@@ -2028,7 +2344,7 @@ fn gen_ldar(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock
     block
 }
 
-fn gen_ldp_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
+fn gen_ldp_simd_fp(compiler: &InstrContext, operand: LoadStoreRegPair, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (wback, post_index) = decode_o_for_ld_st_pair_offset(operand.o);
@@ -2079,7 +2395,7 @@ fn gen_ldp_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegPair, ty: Ty
     block
 }
 
-fn gen_subs_ext_reg(compiler: &AArch64Compiler, operand: AddSubtractExtReg, ty: Type) -> IrBlock {
+fn gen_subs_ext_reg(compiler: &InstrContext, operand: AddSubtractExtReg, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -2110,7 +2426,7 @@ fn gen_subs_ext_reg(compiler: &AArch64Compiler, operand: AddSubtractExtReg, ty:
     block
 }
 
-fn gen_ldr_reg_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset) -> IrBlock {
+fn gen_ldr_reg_simd_fp(compiler: &InstrContext, operand: LoadStoreRegRegOffset) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -2139,7 +2455,7 @@ fn gen_ldr_reg_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffse
     block
 }
 
-fn gen_str_reg_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset) -> IrBlock {
+fn gen_str_reg_simd_fp(compiler: &InstrContext, operand: LoadStoreRegRegOffset) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -2168,7 +2484,7 @@ fn gen_str_reg_simd_fp(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffse
     block
 }
 
-fn gen_ccmp_reg(compiler: &AArch64Compiler, operand: CondCmpReg, ty: Type) -> IrBlock {
+fn gen_ccmp_reg(compiler: &InstrContext, operand: CondCmpReg, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let rn = compiler.gpr(operand.rn);
@@ -2197,7 +2513,7 @@ fn gen_ccmp_reg(compiler: &AArch64Compiler, operand: CondCmpReg, ty: Type) -> Ir
     block
 }
 
-fn gen_ldur(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm, ty: Type) -> IrBlock {
+fn gen_ldur(compiler: &InstrContext, operand: LdStRegUnscaledImm, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let addr = if operand.rn == 31 {
@@ -2223,7 +2539,7 @@ fn gen_ldur(compiler: &AArch64Compiler, operand: LdStRegUnscaledImm, ty: Type) -
     block
 }
 
-fn gen_ldxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock {
+fn gen_ldxr(compiler: &InstrContext, operand: RsRt2RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     block.set_atomic();
 
@@ -2242,7 +2558,7 @@ fn gen_ldxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock
     block
 }
 
-fn gen_stxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock {
+fn gen_stxr(compiler: &InstrContext, operand: RsRt2RnRt, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     block.set_atomic();
 
@@ -2269,7 +2585,7 @@ fn gen_stxr(compiler: &AArch64Compiler, operand: RsRt2RnRt, ty: Type) -> IrBlock
     block
 }
 
-fn gen_csinv(compiler: &AArch64Compiler, operand: RmCondRnRd, ty: Type) -> IrBlock {
+fn gen_csinv(compiler: &InstrContext, operand: RmCondRnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
     let cond = condition_holds(operand.cond);
 
@@ -2293,7 +2609,7 @@ fn gen_csinv(compiler: &AArch64Compiler, operand: RmCondRnRd, ty: Type) -> IrBlo
     block
 }
 
-fn gen_msr_imm(compiler: &AArch64Compiler, operand: PstateOp) -> IrBlock {
+fn gen_msr_imm(compiler: &InstrContext, operand: PstateOp) -> Result<IrBlock, CompileError> {
     let mut block = IrBlock::new(4);
 
     let min_el = match operand.op1 {
@@ -2306,6 +2622,9 @@ fn gen_msr_imm(compiler: &AArch64Compiler, operand: PstateOp) -> IrBlock {
         0b111 => el(1),
         _ => unreachable!(),
     };
+    if compiler.el() < min_el {
+        return Err(compiler.unallocated());
+    }
 
     let field = match operand.op1 << 3 | operand.op2 {
         0b000_011 => PSTATEField::UAO,
@@ -2317,13 +2636,13 @@ fn gen_msr_imm(compiler: &AArch64Compiler, operand: PstateOp) -> IrBlock {
             _ if Pattern::from("001x").test_u8(operand.crm) => PSTATEField::SVCRSM,
             _ if Pattern::from("010x").test_u8(operand.crm) => PSTATEField::SVCRZA,
             _ if Pattern::from("011x").test_u8(operand.crm) => PSTATEField::SVCRSMZA,
-            _ => unreachable!(),
+            _ => return Err(compiler.unimplemented()),
         },
         0b011_100 => PSTATEField::TCO,
         0b011_110 => PSTATEField::DAIFSet,
         0b011_111 => PSTATEField::DAIFClr,
         0b011_001 => PSTATEField::SSBS,
-        _ => unreachable!(),
+        _ => return Err(compiler.unimplemented()),
     };
 
     let crm0 = bit8(operand.crm, 0) as u64;
@@ -2333,7 +2652,12 @@ fn gen_msr_imm(compiler: &AArch64Compiler, operand: PstateOp) -> IrBlock {
 
     let ir = match field {
         PSTATEField::SSBS => set_flag(Pstate::SSBS.range(), crm0),
-        PSTATEField::SP => set_flag(Pstate::SP.range(), crm0),
+        PSTATEField::SP => {
+            block.append(set_flag(Pstate::SP.range(), crm0), BlockDestination::Flags);
+            end_block(&mut block);
+
+            return Ok(block);
+        }
         PSTATEField::DAIFSet => {
             let imm = crm3 << Pstate::D.idx()
                 | crm2 << Pstate::A.idx()
@@ -2349,26 +2673,31 @@ fn gen_msr_imm(compiler: &AArch64Compiler, operand: PstateOp) -> IrBlock {
                 | crm0 << Pstate::F.idx();
             let imm = !imm;
 
-            Ir::And(Type::U64, Operand::Flag, Operand::imm(Type::U64, imm))
+            // Pending interrupts are taken after the block, once they are unmasked.
+            let ir = Ir::And(Type::U64, Operand::Flag, Operand::imm(Type::U64, imm));
+            block.append(ir, BlockDestination::Flags);
+            end_block(&mut block);
+
+            return Ok(block);
         }
         PSTATEField::PAN => set_flag(Pstate::PAN.range(), crm0),
         PSTATEField::UAO => set_flag(Pstate::UAO.range(), crm0),
         PSTATEField::DIT => set_flag(Pstate::DIT.range(), crm0),
         PSTATEField::TCO => set_flag(Pstate::TCO.range(), crm0),
         PSTATEField::ALLINT => set_flag(Pstate::ALLINT.range(), crm0),
-        PSTATEField::SVCRSM => todo!(),
-        PSTATEField::SVCRZA => todo!(),
-        PSTATEField::SVCRSMZA => todo!(),
+        PSTATEField::SVCRSM | PSTATEField::SVCRZA | PSTATEField::SVCRSMZA => {
+            return Err(compiler.unimplemented())
+        }
     };
 
     let ds = BlockDestination::Flags;
 
     block.append(ir, ds);
 
-    block
+    Ok(block)
 }
 
-fn gen_strb_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset) -> IrBlock {
+fn gen_strb_reg(compiler: &InstrContext, operand: LoadStoreRegRegOffset) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let ext_type = decode_reg_extend(operand.option);
@@ -2394,7 +2723,7 @@ fn gen_strb_reg(compiler: &AArch64Compiler, operand: LoadStoreRegRegOffset) -> I
     block
 }
 
-fn gen_rev_var(compiler: &AArch64Compiler, operand: RnRd, ty: Type) -> IrBlock {
+fn gen_rev_var(compiler: &InstrContext, operand: RnRd, ty: Type) -> IrBlock {
     let mut block = IrBlock::new(4);
 
     let (vec_ty, mask) = match ty {
@@ -2420,3 +2749,133 @@ fn gen_rev_var(compiler: &AArch64Compiler, operand: RnRd, ty: Type) -> IrBlock {
     block.append(ir, ds);
     block
 }
+
+// `None` if the system instruction is not emulated.
+fn gen_sys(compiler: &InstrContext, operand: SystemInstructions) -> Option<IrBlock> {
+    let mut block = IrBlock::new(4);
+
+    let (ir, ds) = match (operand.op1, operand.crn, operand.crm, operand.op2) {
+        // IC IALLUIS, IC IALLU
+        (0b000, 0b0111, 0b0001, 0b000) | (0b000, 0b0111, 0b0101, 0b000) => {
+            (Ir::Nop, BlockDestination::InvalidateCodeAll)
+        }
+        // IC IVAU
+        (0b011, 0b0111, 0b0101, 0b001) => (
+            Ir::Value(Operand::gpr(Type::U64, compiler.gpr(operand.rt))),
+            BlockDestination::InvalidateCode,
+        ),
+        // TLBI
+        (0b000 | 0b100 | 0b110, 0b1000, _, _) => (Ir::Nop, BlockDestination::InvalidateTlb),
+        // DC IVAC, DC ISW, DC CSW, DC CISW, DC CVAC, DC CVAU, DC CIVAC
+        // Data cache is not emulated, so these are no-op.
+        (0b000, 0b0111, 0b0110, 0b001 | 0b010)
+        | (0b000, 0b0111, 0b1010 | 0b1110, 0b010)
+        | (0b011, 0b0111, 0b1010 | 0b1011 | 0b1110, 0b001) => (Ir::Nop, BlockDestination::None),
+        _ => return None,
+    };
+
+    block.append(ir, ds);
+
+    Some(block)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cpu::{Architecture, Cpu};
+
+    use machineinstr::aarch64::AArch64InstrParserRule;
+    use machineinstr::MachineInstParser;
+    use utility::ByteReader;
+
+    use super::*;
+
+    fn compile(compiler: &AArch64Compiler, raw: u32) -> Result<IrBlock, CompileError> {
+        let bytes = raw.to_le_bytes().into_iter();
+        let mut parser = MachineInstParser::new(ByteReader::new(bytes), AArch64InstrParserRule);
+        compiler.compile(parser.next().unwrap(), 0x1000, 0)
+    }
+
+    #[test]
+    fn compile_error_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let compiler = AArch64Compiler::new(cpu.get_register_info());
+
+        // nop
+        assert!(compile(&compiler, 0xd503201f).is_ok());
+        assert!(matches!(
+            compile(&compiler, 0x0001_0000),
+            Err(CompileError::Unallocated(0x1000, 0x0001_0000))
+        ));
+        assert!(matches!(
+            compile(&compiler, 0x0200_0000),
+            Err(CompileError::Unallocated(0x1000, 0x0200_0000))
+        ));
+        // udf #0
+        assert!(matches!(
+            compile(&compiler, 0x0000_0000),
+            Err(CompileError::Unallocated(0x1000, 0x0000_0000))
+        ));
+        // ccmp w3, w1, #0, ls
+        assert!(matches!(
+            compile(&compiler, 0x7a41_9060),
+            Err(CompileError::Unimplemented(0x1000, 0x7a41_9060))
+        ));
+        // SVE, which isn't decoded
+        assert!(matches!(
+            compile(&compiler, 0x0420_0000),
+            Err(CompileError::Unimplemented(0x1000, 0x0420_0000))
+        ));
+        // smstart sm
+        assert!(matches!(
+            compile(&compiler, 0xd503_437f),
+            Err(CompileError::Unimplemented(0x1000, 0xd503_437f))
+        ));
+    }
+
+    #[test]
+    fn msr_imm_el_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let compiler = AArch64Compiler::new(cpu.get_register_info());
+        let compile = |raw: u32, mode| {
+            let bytes = raw.to_le_bytes().into_iter();
+            let mut parser = MachineInstParser::new(ByteReader::new(bytes), AArch64InstrParserRule);
+            compiler.compile(parser.next().unwrap(), 0x1000, mode)
+        };
+        let el1 = 1 << Pstate::EL.idx();
+
+        // msr spsel, #1
+        assert!(matches!(
+            compile(0xd500_41bf, 0),
+            Err(CompileError::Unallocated(0x1000, 0xd500_41bf))
+        ));
+        assert!(compile(0xd500_41bf, el1).is_ok());
+        // msr daifset, #2
+        assert!(compile(0xd503_42df, 0).is_ok());
+    }
+
+    #[test]
+    fn stack_reg_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let compiler = AArch64Compiler::new(cpu.get_register_info());
+        let stack_reg = |mode| {
+            InstrContext {
+                compiler: &compiler,
+                mode,
+                pc: 0,
+                raw: 0,
+            }
+            .stack_reg()
+        };
+
+        let el1 = 1 << Pstate::EL.idx();
+        assert_eq!(stack_reg(el1), compiler.reg_by_name("sp_el0"));
+        assert_eq!(
+            stack_reg(el1 | Pstate::SP.mask()),
+            compiler.reg_by_name("sp_el1")
+        );
+
+        // The mode is passed with each instruction, so cpus of every thread share the compiler.
+        fn is_sync<T: Sync>() {}
+        is_sync::<AArch64Compiler>();
+    }
+}
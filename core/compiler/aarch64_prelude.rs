@@ -78,6 +78,21 @@ impl Pstate {
     }
 }
 
+// Exception level of `flags`.
+pub const fn current_el(flags: u64) -> u8 {
+    ((flags & Pstate::EL.mask()) >> Pstate::EL.idx()) as u8
+}
+
+// Index of the stack pointer selected by `flags`, which is `SP_EL0` if `SPSel` is clear
+// and `SP_ELx` of the current exception level otherwise.
+pub const fn stack_index(flags: u64) -> usize {
+    if flags & Pstate::SP.mask() == 0 {
+        0
+    } else {
+        current_el(flags) as usize
+    }
+}
+
 pub const fn sign_extend(value: i64, size: u8) -> i64 {
     let mask = 1 << (size - 1);
     let sign = value & mask;
@@ -520,6 +535,13 @@ mod test {
         assert_eq!(u64::MAX & pstate.mask(), 0b1111 << pstate.idx())
     }
 
+    #[test]
+    fn test_stack_index() {
+        assert_eq!(stack_index(2 << Pstate::EL.idx()), 0);
+        assert_eq!(stack_index(2 << Pstate::EL.idx() | Pstate::SP.mask()), 2);
+        assert_eq!(current_el(3 << Pstate::EL.idx() | Pstate::SP.mask()), 3);
+    }
+
     #[test]
     fn test_sign_extend() {
         for i in i16::MIN..i16::MAX {
@@ -532,11 +554,8 @@ mod test {
     #[test]
     fn test_gen_ip_rel() {
         let mut cpu = Cpu::new_for_test();
-        let mut mmu = Mmu::new();
-        let mut ctx = ExecutionContext {
-            cpu: &mut cpu,
-            mmu: &mut mmu,
-        };
+        let mmu = Mmu::new();
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
         let cg = InterpretCodegen::new(AArch64FlagPolicy);
 
         let diff = 100;
@@ -561,11 +580,8 @@ mod test {
     #[test]
     fn test_condition_holds() {
         let mut cpu = Cpu::new_for_test();
-        let mut mmu = Mmu::new();
-        let mut ctx = ExecutionContext {
-            cpu: &mut cpu,
-            mmu: &mut mmu,
-        };
+        let mmu = Mmu::new();
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
         let cg = InterpretCodegen::new(AArch64FlagPolicy);
 
         ctx.cpu.set_flag(0b1 << Pstate::Z.idx());
@@ -605,11 +621,8 @@ mod test {
     #[test]
     fn test_flag() {
         let mut cpu = Cpu::new_for_test();
-        let mut mmu = Mmu::new();
-        let mut ctx = ExecutionContext {
-            cpu: &mut cpu,
-            mmu: &mut mmu,
-        };
+        let mmu = Mmu::new();
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
         let cg = InterpretCodegen::new(AArch64FlagPolicy);
 
         ctx.cpu.set_flag(0b1010 << Pstate::NZCV.idx());
@@ -639,11 +652,8 @@ mod test {
     #[test]
     fn test_replicate_reg64() {
         let mut cpu = Cpu::new_for_test();
-        let mut mmu = Mmu::new();
-        let mut ctx = ExecutionContext {
-            cpu: &mut cpu,
-            mmu: &mut mmu,
-        };
+        let mmu = Mmu::new();
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
         let cg = InterpretCodegen::new(AArch64FlagPolicy);
 
         let reg = ctx.cpu.reg_by_name("x0").unwrap();
@@ -664,11 +674,8 @@ mod test {
     #[test]
     fn test_replace_bits() {
         let mut cpu = Cpu::new_for_test();
-        let mut mmu = Mmu::new();
-        let mut ctx = ExecutionContext {
-            cpu: &mut cpu,
-            mmu: &mut mmu,
-        };
+        let mmu = Mmu::new();
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
         let cg = InterpretCodegen::new(AArch64FlagPolicy);
         let reg = ctx.cpu.reg_by_name("x0").unwrap();
 
@@ -697,11 +704,8 @@ mod test {
     #[test]
     fn test_set_flag() {
         let mut cpu = Cpu::new_for_test();
-        let mut mmu = Mmu::new();
-        let mut ctx = ExecutionContext {
-            cpu: &mut cpu,
-            mmu: &mut mmu,
-        };
+        let mmu = Mmu::new();
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
         let cg = InterpretCodegen::new(AArch64FlagPolicy);
 
         ctx.cpu.set_flag(0b1111 << Pstate::NZCV.idx());
@@ -1,10 +1,20 @@
 pub mod aarch64;
 pub mod aarch64_prelude;
 
+use crate::error::CompileError;
 use crate::ir::IrBlock;
 
+use machineinstr::instr::NativeInstr;
+
 pub trait Compiler {
     type Item;
 
-    fn compile(&self, item: Self::Item) -> IrBlock;
+    // Compile `instr` at `pc` executed in `mode`, which is the translation mode of the
+    // cpu. Instructions which can't be compiled are reported with their encoding.
+    fn compile(
+        &self,
+        instr: NativeInstr<Self::Item>,
+        pc: u64,
+        mode: u64,
+    ) -> Result<IrBlock, CompileError>;
 }
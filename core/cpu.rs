@@ -1,5 +1,10 @@
+use crate::codegen::flag_policy::LazyFlag;
+use crate::compiler::aarch64_prelude::{current_el, stack_index, Pstate};
+use crate::device::timer::TIMER_FREQUENCY;
 use crate::register::*;
+use crate::symbol::SymbolTable;
 
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -15,8 +20,10 @@ pub struct Cpu {
     reg_name_map: HashMap<String, RegId>,
 
     flags: AtomicU64,
+    lazy_flag: Cell<Option<LazyFlag>>, // flag-setting operation not reflected in `flags` yet
     pc: u64,
     arch: Architecture,
+    symbols: Arc<SymbolTable>, // Symbols of the guest, to describe addresses in dumps.
 }
 
 impl PartialEq for Cpu {
@@ -62,8 +69,10 @@ impl Clone for Cpu {
             reg_name_map: self.reg_name_map.clone(),
 
             flags: AtomicU64::new(self.flags.load(Ordering::Relaxed)),
+            lazy_flag: self.lazy_flag.clone(),
             pc: self.pc,
             arch: self.arch.clone(),
+            symbols: self.symbols.clone(),
         }
     }
 }
@@ -90,13 +99,30 @@ impl Cpu {
             reg_name_map,
 
             flags: AtomicU64::new(0),
+            lazy_flag: Cell::new(None),
             pc: 0,
             arch: Architecture::Test,
+            symbols: Arc::default(),
         }
     }
 
+    // `sp` names the stack pointer selected by the current exception level and `SPSel`.
     pub fn reg_by_name(&self, name: impl AsRef<str>) -> Option<RegId> {
-        self.reg_name_map.get(name.as_ref()).copied()
+        match name.as_ref() {
+            "sp" => self.stack_reg(),
+            name => self.reg_name_map.get(name).copied(),
+        }
+    }
+
+    pub fn stack_reg(&self) -> Option<RegId> {
+        let flags = self.flags.load(Ordering::SeqCst);
+        let name = format!("sp_el{}", stack_index(flags));
+        self.reg_name_map.get(&name).copied()
+    }
+
+    // Current exception level. It's never computed lazily, so flags are not materialized.
+    pub fn el(&self) -> u8 {
+        current_el(self.flags.load(Ordering::SeqCst))
     }
 
     pub fn get_register_info(&self) -> HashMap<String, RegId> {
@@ -142,25 +168,76 @@ impl Cpu {
     }
 
     pub fn flag(&self) -> u64 {
+        self.materialize_flag();
         self.flags.load(Ordering::SeqCst)
     }
 
     pub fn set_flag(&self, flag: u64) {
+        self.lazy_flag.set(None);
         self.flags.store(flag, Ordering::SeqCst);
     }
 
     pub fn add_flag(&self, flag: u64) {
+        self.materialize_flag();
         self.flags.fetch_or(flag, Ordering::SeqCst);
     }
 
     pub fn del_flag(&self, flag: u64) {
+        self.materialize_flag();
         self.flags.fetch_and(!flag, Ordering::SeqCst);
     }
 
+    // Record a flag-setting operation. Its flags are computed when flags are read next time.
+    pub fn set_lazy_flag(&self, lazy: LazyFlag) {
+        self.lazy_flag.set(Some(lazy));
+    }
+
+    pub fn has_lazy_flag(&self) -> bool {
+        self.lazy_flag.get().is_some()
+    }
+
+    fn materialize_flag(&self) {
+        if let Some(lazy) = self.lazy_flag.take() {
+            let flags = self.flags.load(Ordering::SeqCst);
+            self.flags
+                .store((flags & !lazy.mask) | lazy.flags(), Ordering::SeqCst);
+        }
+    }
+
+    // Mode bits which affect how guest code is translated.
+    pub fn translation_mode(&self) -> u64 {
+        // Lazily computed flags never include the mode bits, so flags are not materialized.
+        let flags = self.flags.load(Ordering::SeqCst);
+
+        match self.arch {
+            Architecture::AArch64Bin => flags & (Pstate::EL.mask() | Pstate::SP.mask()),
+            Architecture::Test => 0,
+        }
+    }
+
+    // Mode bits which affect how guest memory is accessed.
+    pub fn access_mode(&self) -> u64 {
+        // Lazily computed flags never include the mode bits, so flags are not materialized.
+        let flags = self.flags.load(Ordering::SeqCst);
+
+        match self.arch {
+            Architecture::AArch64Bin => flags & (Pstate::EL.mask() | Pstate::PAN.mask()),
+            Architecture::Test => 0,
+        }
+    }
+
     pub fn arch(&self) -> &Architecture {
         &self.arch
     }
 
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn set_symbols(&mut self, symbols: Arc<SymbolTable>) {
+        self.symbols = symbols;
+    }
+
     pub fn dump(&self) {
         self.dump_gpr();
         self.dump_fpr();
@@ -187,11 +264,11 @@ impl Cpu {
     }
 
     pub fn dump_flags(&self) {
-        println!("{:14} 0x{:b}", "flags", self.flags.load(Ordering::SeqCst));
+        println!("{:14} 0x{:b}", "flags", self.flag());
     }
 
     pub fn dump_pc(&self) {
-        println!("{:14} 0x{:x}", "pc", self.pc);
+        println!("{:14} {}", "pc", self.symbols.describe(self.pc));
     }
 }
 
@@ -222,8 +299,10 @@ fn init_base_aarch64_cpu() -> Cpu {
         sys_registers: Slab::new(),
         reg_name_map: HashMap::new(),
         flags: AtomicU64::new(0),
+        lazy_flag: Cell::new(None),
         pc: 0,
         arch: Architecture::AArch64Bin,
+        symbols: Arc::default(),
     };
 
     for i in 0..31 {
@@ -240,17 +319,17 @@ fn init_base_aarch64_cpu() -> Cpu {
         cpu.reg_name_map.insert(format!("v{}", i), RegId(id as u8));
     }
 
-    let id = cpu.gpr_registers.insert(GprRegister::new("sp", 8));
-    cpu.reg_name_map.insert("sp".to_string(), RegId(id as u8));
+    // Stack pointers banked by exception level.
+    for el in 0..4 {
+        let name = format!("sp_el{}", el);
+        let id = cpu.gpr_registers.insert(GprRegister::new(&name, 8));
+        cpu.reg_name_map.insert(name, RegId(id as u8));
+    }
 
     let id = cpu.sys_registers.insert(SysRegister::new("tpidr_el0", 8));
     cpu.reg_name_map
         .insert("tpidr_el0".to_string(), RegId(id as u8));
 
-    let id = cpu.sys_registers.insert(SysRegister::new("vbar_el1", 8));
-    cpu.reg_name_map
-        .insert("vbar_el1".to_string(), RegId(id as u8));
-
     let id = cpu.sys_registers.insert(SysRegister::new("cpacr_el1", 8));
     cpu.reg_name_map
         .insert("cpacr_el1".to_string(), RegId(id as u8));
@@ -259,5 +338,50 @@ fn init_base_aarch64_cpu() -> Cpu {
     cpu.reg_name_map
         .insert("mpidr_el1".to_string(), RegId(id as u8));
 
+    // Registers used to take exceptions to and return from EL1 to EL3, and to control
+    // address translation.
+    for el in 1..4 {
+        for name in [
+            "vbar", "esr", "elr", "spsr", "far", "sctlr", "tcr", "ttbr0", "mair",
+        ] {
+            let name = format!("{}_el{}", name, el);
+            let id = cpu.sys_registers.insert(SysRegister::new(&name, 8));
+            cpu.reg_name_map.insert(name, RegId(id as u8));
+        }
+    }
+
+    let id = cpu.sys_registers.insert(SysRegister::new("ttbr1_el1", 8));
+    cpu.reg_name_map
+        .insert("ttbr1_el1".to_string(), RegId(id as u8));
+
+    // Generic timer, whose counters are updated by `device::GenericTimer`.
+    for name in [
+        "cntfrq_el0",
+        "cntpct_el0",
+        "cntvct_el0",
+        "cntkctl_el1",
+        "cntp_ctl_el0",
+        "cntp_cval_el0",
+        "cntv_ctl_el0",
+        "cntv_cval_el0",
+        "cnthctl_el2",
+        "cntvoff_el2",
+    ] {
+        let id = cpu.sys_registers.insert(SysRegister::new(name, 8));
+        cpu.reg_name_map.insert(name.to_string(), RegId(id as u8));
+    }
+    let cntfrq = cpu.reg_by_name("cntfrq_el0").unwrap();
+    *cpu.sys_mut(cntfrq).u64_mut() = TIMER_FREQUENCY;
+
+    // Reset state: EL1 using SP_EL1, with all exceptions masked.
+    cpu.set_flag(
+        1 << Pstate::EL.idx()
+            | Pstate::SP.mask()
+            | Pstate::D.mask()
+            | Pstate::A.mask()
+            | Pstate::I.mask()
+            | Pstate::F.mask(),
+    );
+
     cpu
 }
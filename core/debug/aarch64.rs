@@ -56,6 +56,13 @@ pub fn serialize_aarch64(cpu: &Cpu) -> Result<Vec<u8>, DebugError> {
 }
 
 pub fn deserialize_aarch64(bytes: &[u8], cpu: &mut Cpu) {
+    let len = bytes.len();
+
+    // Flags select the stack pointer named `sp`, so they are restored first.
+    let val: [u8; 8] = bytes[len - 8..].try_into().unwrap();
+    let val = u64::from_ne_bytes(val);
+    cpu.set_flag(val);
+
     let idxs = (0..bytes.len()).step_by(8);
     let regs = cpu.arch().gprs();
 
@@ -71,13 +78,7 @@ pub fn deserialize_aarch64(bytes: &[u8], cpu: &mut Cpu) {
         *reg.u64_mut() = val;
     }
 
-    let len = bytes.len();
-
     let val: [u8; 8] = bytes[len - 16..len - 8].try_into().unwrap();
     let val = u64::from_ne_bytes(val);
     cpu.set_pc(val);
-
-    let val: [u8; 8] = bytes[len - 8..].try_into().unwrap();
-    let val = u64::from_ne_bytes(val);
-    cpu.set_flag(val);
 }
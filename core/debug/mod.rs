@@ -95,8 +95,7 @@ pub struct GdbEventLoop<C, R, G, A> {
     a: PhantomData<A>,
 }
 
-impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> BlockingEventLoop
-    for GdbEventLoop<C, R, G, A>
+impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> BlockingEventLoop for GdbEventLoop<C, R, G, A>
 where
     C: Compiler,
     R: MachineInstrParserRule<MachineInstr = C::Item>,
@@ -118,7 +117,9 @@ where
     > {
         let poll_incoming_data = || conn.peek().map(|b| b.is_some()).unwrap_or(true);
 
-        let debug_event = unsafe { target.debug(poll_incoming_data).unwrap() };
+        let debug_event = unsafe { target.debug(poll_incoming_data) }.map_err(|err| {
+            gdbstub::stub::run_blocking::WaitForStopReasonError::Target(err.into())
+        })?;
 
         let stop_reason = match debug_event {
             DebugEvent::IncomingData => todo!(),
@@ -148,13 +149,18 @@ where
     }
 }
 
-impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> SingleThreadBase for Board<C, R, G, A> {
+impl<C, R, G: Codegen, A: Arch<Usize = u64, Registers = Cpu>> SingleThreadBase
+    for Board<C, R, G, A>
+{
     fn read_registers(
         &mut self,
         regs: &mut <Self::Arch as Arch>::Registers,
     ) -> gdbstub::target::TargetResult<(), Self> {
         let cpu = self.cpu().get().unwrap().lock().unwrap();
 
+        // Flags select the stack pointer named `sp`, so they are copied first.
+        regs.set_flag(cpu.flag());
+
         for name in cpu.arch().gprs() {
             let src = cpu.reg_by_name(&name).unwrap();
             let src = cpu.gpr(src);
@@ -165,7 +171,6 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> SingleThreadBase for Board<
         }
 
         regs.set_pc(cpu.pc());
-        regs.set_flag(cpu.flag());
 
         Ok(())
     }
@@ -177,6 +182,8 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> SingleThreadBase for Board<
         let mut cpu = self.cpu().get().unwrap().lock().unwrap();
         let cpu = cpu.borrow_mut();
 
+        cpu.set_flag(regs.flag());
+
         for name in Architecture::AArch64Bin.gprs() {
             let dst = cpu
                 .reg_by_name(&name)
@@ -191,7 +198,6 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> SingleThreadBase for Board<
         }
 
         cpu.set_pc(regs.pc());
-        cpu.set_flag(regs.flag());
 
         Ok(())
     }
@@ -227,7 +233,7 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> SingleThreadBase for Board<
     }
 }
 
-impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> Target for Board<C, R, G, A> {
+impl<C, R, G: Codegen, A: Arch<Usize = u64, Registers = Cpu>> Target for Board<C, R, G, A> {
     type Arch = A;
     type Error = DebugError;
 
@@ -236,7 +242,7 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> Target for Board<C, R, G, A
     }
 }
 
-impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> Breakpoints for Board<C, R, G, A> {
+impl<C, R, G: Codegen, A: Arch<Usize = u64, Registers = Cpu>> Breakpoints for Board<C, R, G, A> {
     fn support_sw_breakpoint(
         &mut self,
     ) -> Option<gdbstub::target::ext::breakpoints::SwBreakpointOps<'_, Self>> {
@@ -256,7 +262,7 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> Breakpoints for Board<C, R,
     }
 }
 
-impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> HwBreakpoint for Board<C, R, G, A> {
+impl<C, R, G: Codegen, A: Arch<Usize = u64, Registers = Cpu>> HwBreakpoint for Board<C, R, G, A> {
     fn add_hw_breakpoint(
         &mut self,
         addr: <Self::Arch as Arch>::Usize,
@@ -274,7 +280,7 @@ impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> HwBreakpoint for Board<C, R
     }
 }
 
-impl<C, R, G, A: Arch<Usize = u64, Registers = Cpu>> HwWatchpoint for Board<C, R, G, A> {
+impl<C, R, G: Codegen, A: Arch<Usize = u64, Registers = Cpu>> HwWatchpoint for Board<C, R, G, A> {
     fn add_hw_watchpoint(
         &mut self,
         addr: <Self::Arch as Arch>::Usize,
@@ -334,4 +340,4 @@ impl Registers for Cpu {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,458 @@
+use super::{CpuLines, InterruptController};
+use crate::softmmu::MmioDevice;
+
+// Offset of the cpu interface from the distributor.
+pub const GIC_CPU_OFFSET: u64 = 0x1_0000;
+
+// Size of the distributor and cpu interface frames.
+pub const GIC_SIZE: u64 = 0x2_0000;
+
+const NUM_IRQS: usize = 288;
+const NUM_SGIS: u32 = 16;
+const SPURIOUS: u32 = 1023;
+
+// Priority of the cpu when no interrupt is active.
+const IDLE_PRIORITY: u8 = 0xff;
+
+// Distributor registers
+const GICD_CTLR: u64 = 0x000;
+const GICD_TYPER: u64 = 0x004;
+const GICD_IIDR: u64 = 0x008;
+const GICD_IGROUPR: u64 = 0x080;
+const GICD_ISENABLER: u64 = 0x100;
+const GICD_ICENABLER: u64 = 0x180;
+const GICD_ISPENDR: u64 = 0x200;
+const GICD_ICPENDR: u64 = 0x280;
+const GICD_ISACTIVER: u64 = 0x300;
+const GICD_ICACTIVER: u64 = 0x380;
+const GICD_IPRIORITYR: u64 = 0x400;
+const GICD_ITARGETSR: u64 = 0x800;
+const GICD_ICFGR: u64 = 0xc00;
+const GICD_SGIR: u64 = 0xf00;
+const GICD_ICPIDR2: u64 = 0xfe8;
+
+// Cpu interface registers
+const GICC_CTLR: u64 = 0x00;
+const GICC_PMR: u64 = 0x04;
+const GICC_BPR: u64 = 0x08;
+const GICC_IAR: u64 = 0x0c;
+const GICC_EOIR: u64 = 0x10;
+const GICC_RPR: u64 = 0x14;
+const GICC_HPPIR: u64 = 0x18;
+const GICC_IIDR: u64 = 0xfc;
+const GICC_DIR: u64 = 0x1000;
+
+// Group enables of GICD_CTLR and GICC_CTLR.
+const ENABLE_GRP0: u64 = 1 << 0;
+const ENABLE_GRP1: u64 = 1 << 1;
+const GICC_CTLR_FIQEN: u64 = 1 << 3;
+const GICC_CTLR_EOIMODE: u64 = 1 << 9;
+
+#[derive(Clone, Copy, Default)]
+struct Irq {
+    enabled: bool,
+    pending: bool, // latched by an edge or GICD_ISPENDR
+    level: bool,   // level of the input line
+    active: bool,
+    edge: bool,
+    group1: bool,
+    priority: u8,
+}
+
+impl Irq {
+    fn is_pending(&self) -> bool {
+        self.pending || (!self.edge && self.level)
+    }
+}
+
+// GICv2 distributor and cpu interface of a single cpu.
+//
+// The cpu interface is mapped at `GIC_CPU_OFFSET` from the distributor, and
+// interrupts are signalled to the cpu through `CpuLines`.
+pub struct Gic {
+    irqs: Vec<Irq>,
+    dist_ctlr: u64,
+    cpu_ctlr: u64,
+    pmr: u8,
+    bpr: u64,
+    // Active interrupts acknowledged by the cpu, whose priority is not dropped yet.
+    running: Vec<u32>,
+    lines: CpuLines,
+}
+
+impl Gic {
+    pub fn new(lines: CpuLines) -> Self {
+        let mut irqs = vec![Irq::default(); NUM_IRQS];
+        for irq in &mut irqs[..NUM_SGIS as usize] {
+            // SGIs are always enabled and edge triggered.
+            irq.enabled = true;
+            irq.edge = true;
+        }
+
+        Self {
+            irqs,
+            dist_ctlr: 0,
+            cpu_ctlr: 0,
+            pmr: 0,
+            bpr: 0,
+            running: Vec::new(),
+            lines,
+        }
+    }
+
+    fn running_priority(&self) -> u8 {
+        self.running
+            .iter()
+            .map(|&id| self.irqs[id as usize].priority)
+            .min()
+            .unwrap_or(IDLE_PRIORITY)
+    }
+
+    fn group_enabled(&self, irq: &Irq) -> bool {
+        let enable = if irq.group1 { ENABLE_GRP1 } else { ENABLE_GRP0 };
+        self.dist_ctlr & enable != 0 && self.cpu_ctlr & enable != 0
+    }
+
+    // Highest priority pending interrupt which can be forwarded to the cpu.
+    fn highest_pending(&self) -> Option<u32> {
+        self.irqs
+            .iter()
+            .enumerate()
+            .filter(|(_, irq)| {
+                irq.enabled && irq.is_pending() && !irq.active && self.group_enabled(irq)
+            })
+            .min_by_key(|(_, irq)| irq.priority)
+            .map(|(id, _)| id as u32)
+    }
+
+    // Update interrupt lines of the cpu.
+    fn update(&mut self) {
+        let signal = self.highest_pending().map(|id| self.irqs[id as usize]);
+        let signal = signal
+            .filter(|irq| irq.priority < self.pmr)
+            .filter(|irq| irq.priority < self.running_priority());
+
+        let fiq = signal.is_some_and(|irq| !irq.group1 && self.cpu_ctlr & GICC_CTLR_FIQEN != 0);
+        let irq = signal.is_some() && !fiq;
+        self.lines.set(irq, fiq);
+    }
+
+    fn acknowledge(&mut self) -> u64 {
+        let id = match self.highest_pending() {
+            Some(id)
+                if self.irqs[id as usize].priority < self.pmr
+                    && self.irqs[id as usize].priority < self.running_priority() =>
+            {
+                id
+            }
+            _ => return SPURIOUS as u64,
+        };
+
+        let irq = &mut self.irqs[id as usize];
+        irq.pending = false;
+        irq.active = true;
+        self.running.push(id);
+
+        id as u64
+    }
+
+    fn end_of_interrupt(&mut self, id: u32) {
+        self.running.retain(|&running| running != id);
+        if self.cpu_ctlr & GICC_CTLR_EOIMODE == 0 {
+            self.deactivate(id);
+        }
+    }
+
+    fn deactivate(&mut self, id: u32) {
+        if let Some(irq) = self.irqs.get_mut(id as usize) {
+            irq.active = false;
+        }
+    }
+
+    fn software_interrupt(&mut self, value: u64) {
+        let filter = (value >> 24) & 0b11;
+        let targets = (value >> 16) & 0xff;
+
+        // Only cpu 0 exists, which is never targeted by "all but self".
+        let targeted = match filter {
+            0 => targets & 1 != 0,
+            2 => true,
+            _ => false,
+        };
+        if targeted {
+            self.irqs[(value & 0xf) as usize].pending = true;
+        }
+    }
+
+    // Read a bit of each interrupt from `base`, 32 interrupts per register.
+    fn read_bits(&self, offset: u64, base: u64, f: impl Fn(&Irq) -> bool) -> u64 {
+        let first = (offset - base) as usize * 8;
+        (0..32)
+            .filter(|i| self.irqs.get(first + i).is_some_and(&f))
+            .fold(0, |acc, i| acc | 1 << i)
+    }
+
+    // Apply `f` to interrupts whose bit is set in `value`.
+    fn write_bits(&mut self, offset: u64, base: u64, value: u64, f: impl Fn(&mut Irq)) {
+        let first = (offset - base) as usize * 8;
+        for i in (0..32).filter(|i| value & 1 << i != 0) {
+            if let Some(irq) = self.irqs.get_mut(first + i) {
+                f(irq);
+            }
+        }
+    }
+
+    fn read_distributor(&self, offset: u64, size: usize) -> u64 {
+        let irqs = NUM_IRQS as u64;
+        match offset {
+            GICD_CTLR => self.dist_ctlr,
+            GICD_TYPER => irqs / 32 - 1,
+            GICD_IIDR => 0x0200_143b,
+            GICD_IGROUPR..GICD_ISENABLER => self.read_bits(offset, GICD_IGROUPR, |irq| irq.group1),
+            GICD_ISENABLER..GICD_ICENABLER => {
+                self.read_bits(offset, GICD_ISENABLER, |irq| irq.enabled)
+            }
+            GICD_ICENABLER..GICD_ISPENDR => {
+                self.read_bits(offset, GICD_ICENABLER, |irq| irq.enabled)
+            }
+            GICD_ISPENDR..GICD_ICPENDR => {
+                self.read_bits(offset, GICD_ISPENDR, |irq| irq.is_pending())
+            }
+            GICD_ICPENDR..GICD_ISACTIVER => {
+                self.read_bits(offset, GICD_ICPENDR, |irq| irq.is_pending())
+            }
+            GICD_ISACTIVER..GICD_ICACTIVER => {
+                self.read_bits(offset, GICD_ISACTIVER, |irq| irq.active)
+            }
+            GICD_ICACTIVER..GICD_IPRIORITYR => {
+                self.read_bits(offset, GICD_ICACTIVER, |irq| irq.active)
+            }
+            GICD_IPRIORITYR..GICD_ITARGETSR => {
+                let first = (offset - GICD_IPRIORITYR) as usize;
+                self.read_bytes(first, size, |irq| irq.priority)
+            }
+            GICD_ITARGETSR..GICD_ICFGR => {
+                // Every interrupt targets cpu 0.
+                let first = (offset - GICD_ITARGETSR) as usize;
+                self.read_bytes(first, size, |_| 1)
+            }
+            GICD_ICFGR..GICD_SGIR => {
+                let first = (offset - GICD_ICFGR) as usize * 4;
+                (0..16)
+                    .filter(|i| self.irqs.get(first + i).is_some_and(|irq| irq.edge))
+                    .fold(0, |acc, i| acc | 0b10 << (i * 2))
+            }
+            GICD_ICPIDR2 => 0x2b,
+            _ => 0,
+        }
+    }
+
+    fn read_bytes(&self, first: usize, size: usize, f: impl Fn(&Irq) -> u8) -> u64 {
+        (0..size)
+            .filter_map(|i| self.irqs.get(first + i).map(|irq| (i, f(irq))))
+            .fold(0, |acc, (i, byte)| acc | (byte as u64) << (i * 8))
+    }
+
+    fn write_distributor(&mut self, offset: u64, size: usize, value: u64) {
+        match offset {
+            GICD_CTLR => self.dist_ctlr = value & (ENABLE_GRP0 | ENABLE_GRP1),
+            GICD_IGROUPR..GICD_ISENABLER => {
+                let first = (offset - GICD_IGROUPR) as usize * 8;
+                for (i, irq) in self.irqs.iter_mut().skip(first).take(32).enumerate() {
+                    irq.group1 = value & 1 << i != 0;
+                }
+            }
+            GICD_ISENABLER..GICD_ICENABLER => {
+                self.write_bits(offset, GICD_ISENABLER, value, |irq| irq.enabled = true)
+            }
+            GICD_ICENABLER..GICD_ISPENDR => {
+                // SGIs can't be disabled.
+                let value = if offset == GICD_ICENABLER {
+                    value & !0xffff
+                } else {
+                    value
+                };
+                self.write_bits(offset, GICD_ICENABLER, value, |irq| irq.enabled = false)
+            }
+            GICD_ISPENDR..GICD_ICPENDR => {
+                self.write_bits(offset, GICD_ISPENDR, value, |irq| irq.pending = true)
+            }
+            GICD_ICPENDR..GICD_ISACTIVER => {
+                self.write_bits(offset, GICD_ICPENDR, value, |irq| irq.pending = false)
+            }
+            GICD_ISACTIVER..GICD_ICACTIVER => {
+                self.write_bits(offset, GICD_ISACTIVER, value, |irq| irq.active = true)
+            }
+            GICD_ICACTIVER..GICD_IPRIORITYR => {
+                self.write_bits(offset, GICD_ICACTIVER, value, |irq| irq.active = false)
+            }
+            GICD_IPRIORITYR..GICD_ITARGETSR => {
+                let first = (offset - GICD_IPRIORITYR) as usize;
+                for i in 0..size {
+                    if let Some(irq) = self.irqs.get_mut(first + i) {
+                        irq.priority = (value >> (i * 8)) as u8;
+                    }
+                }
+            }
+            GICD_ICFGR..GICD_SGIR => {
+                // Configuration of SGIs is read-only.
+                let first = (offset - GICD_ICFGR) as usize * 4;
+                for i in 0..16 {
+                    if let Some(irq) = self.irqs.get_mut(first + i) {
+                        if first + i >= NUM_SGIS as usize {
+                            irq.edge = value & 0b10 << (i * 2) != 0;
+                        }
+                    }
+                }
+            }
+            GICD_SGIR => self.software_interrupt(value),
+            _ => {}
+        }
+    }
+
+    fn read_cpu(&mut self, offset: u64) -> u64 {
+        match offset {
+            GICC_CTLR => self.cpu_ctlr,
+            GICC_PMR => self.pmr as u64,
+            GICC_BPR => self.bpr,
+            GICC_IAR => self.acknowledge(),
+            GICC_RPR => self.running_priority() as u64,
+            GICC_HPPIR => self.highest_pending().unwrap_or(SPURIOUS) as u64,
+            GICC_IIDR => 0x0202_143b,
+            _ => 0,
+        }
+    }
+
+    fn write_cpu(&mut self, offset: u64, value: u64) {
+        match offset {
+            GICC_CTLR => {
+                self.cpu_ctlr =
+                    value & (ENABLE_GRP0 | ENABLE_GRP1 | GICC_CTLR_FIQEN | GICC_CTLR_EOIMODE)
+            }
+            GICC_PMR => self.pmr = value as u8,
+            GICC_BPR => self.bpr = value & 0x7,
+            GICC_EOIR => self.end_of_interrupt(value as u32 & 0x3ff),
+            GICC_DIR => self.deactivate(value as u32 & 0x3ff),
+            _ => {}
+        }
+    }
+}
+
+impl MmioDevice for Gic {
+    fn read(&mut self, offset: u64, size: usize) -> u64 {
+        let value = if offset < GIC_CPU_OFFSET {
+            self.read_distributor(offset, size)
+        } else {
+            self.read_cpu(offset - GIC_CPU_OFFSET)
+        };
+        self.update();
+
+        value
+    }
+
+    fn write(&mut self, offset: u64, size: usize, value: u64) {
+        if offset < GIC_CPU_OFFSET {
+            self.write_distributor(offset, size, value);
+        } else {
+            self.write_cpu(offset - GIC_CPU_OFFSET, value);
+        }
+        self.update();
+    }
+}
+
+impl InterruptController for Gic {
+    fn set_level(&mut self, id: u32, level: bool) {
+        let Some(irq) = self.irqs.get_mut(id as usize) else {
+            return;
+        };
+
+        if irq.edge && level && !irq.level {
+            irq.pending = true;
+        }
+        irq.level = level;
+        self.update();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SPI: u32 = 33;
+
+    // Gic with `SPI` enabled in group 0, and the cpu interface accepting every priority.
+    fn gic() -> (Gic, CpuLines) {
+        let lines = CpuLines::default();
+        let mut gic = Gic::new(lines.clone());
+        gic.write(GICD_CTLR, 4, ENABLE_GRP0);
+        gic.write(GICD_ISENABLER + 4, 4, 1 << (SPI - 32));
+        gic.write(GIC_CPU_OFFSET + GICC_CTLR, 4, ENABLE_GRP0);
+        gic.write(GIC_CPU_OFFSET + GICC_PMR, 4, 0xff);
+        (gic, lines)
+    }
+
+    #[test]
+    fn gic_level_test() {
+        let (mut gic, lines) = gic();
+        assert!(!lines.irq());
+
+        gic.set_level(SPI, true);
+        assert!(lines.irq());
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_HPPIR, 4), SPI as u64);
+
+        // Acknowledged interrupt is active, and doesn't signal until the end of it.
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_IAR, 4), SPI as u64);
+        assert!(!lines.irq());
+        assert_eq!(gic.read(GICD_ISACTIVER + 4, 4), 1 << (SPI - 32));
+
+        // Level sensitive interrupt is pending again while the line is high.
+        gic.write(GIC_CPU_OFFSET + GICC_EOIR, 4, SPI as u64);
+        assert!(lines.irq());
+
+        gic.set_level(SPI, false);
+        assert!(!lines.irq());
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_IAR, 4), SPURIOUS as u64);
+    }
+
+    #[test]
+    fn gic_priority_test() {
+        let (mut gic, lines) = gic();
+        gic.write(GICD_ISENABLER + 4, 4, 0b11 << (SPI - 32));
+        gic.write(GICD_IPRIORITYR + SPI as u64, 2, 0x4080);
+
+        gic.set_level(SPI, true);
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_IAR, 4), SPI as u64);
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_RPR, 4), 0x80);
+
+        // Higher priority interrupt preempts the active one.
+        gic.set_level(SPI + 1, true);
+        assert!(lines.irq());
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_IAR, 4), SPI as u64 + 1);
+
+        // Priority mask hides interrupts with lower priority.
+        gic.write(GIC_CPU_OFFSET + GICC_EOIR, 4, SPI as u64 + 1);
+        gic.write(GIC_CPU_OFFSET + GICC_EOIR, 4, SPI as u64);
+        gic.write(GIC_CPU_OFFSET + GICC_PMR, 4, 0x80);
+        assert!(lines.irq());
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_IAR, 4), SPI as u64 + 1);
+        assert!(!lines.irq());
+    }
+
+    #[test]
+    fn gic_sgi_test() {
+        let (mut gic, lines) = gic();
+
+        // Edge triggered interrupts are pending until acknowledged.
+        gic.write(GICD_SGIR, 4, 2 << 24 | 5);
+        assert!(lines.irq());
+        assert_eq!(gic.read(GIC_CPU_OFFSET + GICC_IAR, 4), 5);
+        gic.write(GIC_CPU_OFFSET + GICC_EOIR, 4, 5);
+        assert!(!lines.irq());
+
+        // Group 0 interrupts are signalled as FIQ if enabled.
+        gic.write(GIC_CPU_OFFSET + GICC_CTLR, 4, ENABLE_GRP0 | GICC_CTLR_FIQEN);
+        gic.write(GICD_SGIR, 4, 1 << 16 | 3);
+        assert!(lines.fiq());
+        assert!(!lines.irq());
+    }
+}
@@ -0,0 +1,65 @@
+// Emulated peripherals, mapped into the MMU through `softmmu::MmioBus`.
+pub mod gic;
+pub mod pl011;
+pub mod timer;
+
+pub use gic::Gic;
+pub use pl011::Pl011;
+pub use timer::{Clock, GenericTimer};
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+
+const LINE_IRQ: u8 = 1 << 0;
+const LINE_FIQ: u8 = 1 << 1;
+
+// Interrupt request lines of the cpu, driven by the interrupt controller.
+#[derive(Clone, Default)]
+pub struct CpuLines {
+    state: Arc<AtomicU8>,
+}
+
+impl CpuLines {
+    pub fn set(&self, irq: bool, fiq: bool) {
+        let state = if irq { LINE_IRQ } else { 0 } | if fiq { LINE_FIQ } else { 0 };
+        self.state.store(state, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn irq(&self) -> bool {
+        self.state.load(Ordering::Acquire) & LINE_IRQ != 0
+    }
+
+    #[inline]
+    pub fn fiq(&self) -> bool {
+        self.state.load(Ordering::Acquire) & LINE_FIQ != 0
+    }
+}
+
+pub trait InterruptController: Send {
+    // Drive input line `irq` of the controller.
+    fn set_level(&mut self, irq: u32, level: bool);
+}
+
+// Output interrupt line of a device, connected to an input of the controller.
+#[derive(Clone)]
+pub struct IrqLine {
+    controller: Arc<Mutex<dyn InterruptController>>,
+    irq: u32,
+}
+
+impl IrqLine {
+    pub fn new(controller: Arc<Mutex<dyn InterruptController>>, irq: u32) -> Self {
+        Self { controller, irq }
+    }
+
+    pub fn set_level(&self, level: bool) {
+        self.controller.lock().unwrap().set_level(self.irq, level);
+    }
+}
+
+// Device which has work to do without being accessed by the guest, such as taking
+// input from the host. It's polled periodically between translated blocks.
+pub trait Poll: Send {
+    fn poll(&mut self);
+}
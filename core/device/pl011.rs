@@ -0,0 +1,332 @@
+use super::{IrqLine, Poll};
+use crate::softmmu::MmioDevice;
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::mpsc::{channel, Receiver};
+
+// Registers
+const UARTDR: u64 = 0x000;
+const UARTRSR: u64 = 0x004;
+const UARTFR: u64 = 0x018;
+const UARTILPR: u64 = 0x020;
+const UARTIBRD: u64 = 0x024;
+const UARTFBRD: u64 = 0x028;
+const UARTLCR_H: u64 = 0x02c;
+const UARTCR: u64 = 0x030;
+const UARTIFLS: u64 = 0x034;
+const UARTIMSC: u64 = 0x038;
+const UARTRIS: u64 = 0x03c;
+const UARTMIS: u64 = 0x040;
+const UARTICR: u64 = 0x044;
+const UARTDMACR: u64 = 0x048;
+
+// Flag register
+const FR_RXFE: u64 = 1 << 4;
+const FR_RXFF: u64 = 1 << 6;
+const FR_TXFE: u64 = 1 << 7;
+
+// Interrupts
+const INT_RX: u64 = 1 << 4;
+const INT_TX: u64 = 1 << 5;
+const INT_ALL: u64 = 0x7ff;
+
+const LCR_H_FEN: u64 = 1 << 4;
+
+const FIFO_DEPTH: usize = 16;
+
+// UARTPeriphID0-3 and UARTPCellID0-3
+const ID: [u8; 8] = [0x11, 0x10, 0x14, 0x00, 0x0d, 0xf0, 0x05, 0xb1];
+
+// PL011 UART.
+//
+// Transmitted bytes are written into the host immediately, so the transmit FIFO
+// is always empty. Received bytes are taken from `rx` when the guest accesses
+// the UART, or when it's polled.
+pub struct Pl011 {
+    tx: Box<dyn Write + Send>,
+    rx: Option<Receiver<u8>>,
+    fifo: VecDeque<u8>,
+
+    ilpr: u64,
+    ibrd: u64,
+    fbrd: u64,
+    lcr_h: u64,
+    cr: u64,
+    ifls: u64,
+    imsc: u64,
+    dmacr: u64,
+    int_level: u64,
+    irq: Option<IrqLine>,
+}
+
+impl Pl011 {
+    pub fn new(tx: Box<dyn Write + Send>, rx: Option<Receiver<u8>>) -> Self {
+        Self {
+            tx,
+            rx,
+            fifo: VecDeque::new(),
+            ilpr: 0,
+            ibrd: 0,
+            fbrd: 0,
+            lcr_h: 0,
+            cr: 0x300,
+            ifls: 0x12,
+            imsc: 0,
+            dmacr: 0,
+            int_level: INT_TX,
+            irq: None,
+        }
+    }
+
+    // UART connected to stdout and stdin of the host.
+    pub fn stdio() -> Self {
+        Self::new(Box::new(std::io::stdout()), Some(stdin_receiver()))
+    }
+
+    pub fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+        self.update_irq();
+    }
+
+    // Whether the interrupt line is asserted.
+    pub fn irq_pending(&self) -> bool {
+        self.int_level & self.imsc != 0
+    }
+
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set_level(self.irq_pending());
+        }
+    }
+
+    // Move received bytes into the receive FIFO.
+    fn receive_from_host(&mut self) {
+        let Some(rx) = &self.rx else {
+            return;
+        };
+
+        while self.fifo.len() < self.fifo_depth() {
+            match rx.try_recv() {
+                Ok(byte) => {
+                    self.fifo.push_back(byte);
+                    self.int_level |= INT_RX;
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    fn fifo_depth(&self) -> usize {
+        if self.lcr_h & LCR_H_FEN != 0 {
+            FIFO_DEPTH
+        } else {
+            1
+        }
+    }
+
+    fn flags(&self) -> u64 {
+        // Transmit FIFO is always empty.
+        let mut flags = FR_TXFE;
+        if self.fifo.is_empty() {
+            flags |= FR_RXFE;
+        }
+        if self.fifo.len() >= self.fifo_depth() {
+            flags |= FR_RXFF;
+        }
+
+        flags
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        // Output is best effort, as a real UART drops bytes nobody listens to.
+        let _ = self.tx.write_all(&[byte]).and_then(|_| self.tx.flush());
+        self.int_level |= INT_TX;
+    }
+
+    fn receive(&mut self) -> u64 {
+        let byte = self.fifo.pop_front().unwrap_or(0);
+        if self.fifo.is_empty() {
+            self.int_level &= !INT_RX;
+        }
+
+        byte as u64
+    }
+}
+
+impl MmioDevice for Pl011 {
+    fn read(&mut self, offset: u64, _size: usize) -> u64 {
+        self.receive_from_host();
+
+        let value = match offset {
+            UARTDR => self.receive(),
+            UARTRSR => 0,
+            UARTFR => self.flags(),
+            UARTILPR => self.ilpr,
+            UARTIBRD => self.ibrd,
+            UARTFBRD => self.fbrd,
+            UARTLCR_H => self.lcr_h,
+            UARTCR => self.cr,
+            UARTIFLS => self.ifls,
+            UARTIMSC => self.imsc,
+            UARTRIS => self.int_level,
+            UARTMIS => self.int_level & self.imsc,
+            UARTDMACR => self.dmacr,
+            0xfe0..=0xffc => ID[(offset as usize - 0xfe0) / 4] as u64,
+            _ => 0,
+        };
+        self.update_irq();
+
+        value
+    }
+
+    fn write(&mut self, offset: u64, _size: usize, value: u64) {
+        match offset {
+            UARTDR => self.transmit(value as u8),
+            UARTILPR => self.ilpr = value & 0xff,
+            UARTIBRD => self.ibrd = value & 0xffff,
+            UARTFBRD => self.fbrd = value & 0x3f,
+            UARTLCR_H => {
+                // Disabling the FIFO leaves only its first entry.
+                self.lcr_h = value & 0xff;
+                self.fifo.truncate(self.fifo_depth());
+            }
+            UARTCR => self.cr = value & 0xffff,
+            UARTIFLS => self.ifls = value & 0x3f,
+            UARTIMSC => self.imsc = value & INT_ALL,
+            UARTICR => self.int_level &= !value,
+            UARTDMACR => self.dmacr = value & 0x7,
+            _ => {}
+        }
+        self.update_irq();
+    }
+}
+
+impl Poll for Pl011 {
+    fn poll(&mut self) {
+        self.receive_from_host();
+        self.update_irq();
+    }
+}
+
+// Channel fed with bytes read from stdin of the host by a background thread.
+pub fn stdin_receiver() -> Receiver<u8> {
+    let (sender, receiver) = channel();
+    std::thread::spawn(move || {
+        for byte in std::io::stdin().lock().bytes() {
+            let Ok(byte) = byte else {
+                break;
+            };
+            if sender.send(byte).is_err() {
+                break;
+            }
+        }
+    });
+
+    receiver
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::device::InterruptController;
+
+    use std::sync::mpsc::Sender;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn uart() -> (Pl011, Output, Sender<u8>) {
+        let output = Output::default();
+        let (sender, receiver) = channel();
+        let uart = Pl011::new(Box::new(output.clone()), Some(receiver));
+        (uart, output, sender)
+    }
+
+    #[test]
+    fn pl011_transmit_test() {
+        let (mut uart, output, _) = uart();
+
+        for byte in "Hi\n".bytes() {
+            uart.write(UARTDR, 4, byte as u64);
+        }
+        assert_eq!(*output.0.lock().unwrap(), b"Hi\n");
+
+        assert_eq!(uart.read(UARTFR, 4), FR_TXFE | FR_RXFE);
+        assert_eq!(uart.read(0xfe0, 4), 0x11);
+        assert_eq!(uart.read(0xffc, 4), 0xb1);
+    }
+
+    #[test]
+    fn pl011_receive_test() {
+        let (mut uart, _, sender) = uart();
+        uart.write(UARTLCR_H, 4, LCR_H_FEN);
+        uart.write(UARTIMSC, 4, INT_RX);
+        assert!(!uart.irq_pending());
+
+        sender.send(b'a').unwrap();
+        sender.send(b'b').unwrap();
+        uart.poll();
+        assert!(uart.irq_pending());
+        assert_eq!(uart.read(UARTMIS, 4), INT_RX);
+        assert_eq!(uart.read(UARTFR, 4) & FR_RXFE, 0);
+
+        assert_eq!(uart.read(UARTDR, 4), b'a' as u64);
+        assert_eq!(uart.read(UARTDR, 4), b'b' as u64);
+        assert_eq!(uart.read(UARTFR, 4) & FR_RXFE, FR_RXFE);
+        assert!(!uart.irq_pending());
+    }
+
+    #[test]
+    fn pl011_interrupt_test() {
+        let (mut uart, _, _) = uart();
+
+        uart.write(UARTIMSC, 4, INT_TX);
+        assert!(uart.irq_pending());
+
+        uart.write(UARTICR, 4, INT_TX);
+        assert!(!uart.irq_pending());
+        assert_eq!(uart.read(UARTRIS, 4), 0);
+
+        uart.write(UARTDR, 4, b'x' as u64);
+        assert_eq!(uart.read(UARTMIS, 4), INT_TX);
+    }
+
+    #[test]
+    fn pl011_irq_line_test() {
+        #[derive(Default)]
+        struct Levels(Vec<(u32, bool)>);
+
+        impl InterruptController for Levels {
+            fn set_level(&mut self, irq: u32, level: bool) {
+                self.0.push((irq, level));
+            }
+        }
+
+        let (mut uart, _, sender) = uart();
+        let levels = Arc::new(Mutex::new(Levels::default()));
+        uart.connect_irq(IrqLine::new(levels.clone(), 33));
+        uart.write(UARTIMSC, 4, INT_RX);
+
+        // Received bytes raise the line when the uart is polled.
+        sender.send(b'a').unwrap();
+        uart.poll();
+        uart.read(UARTDR, 4);
+
+        let levels = &levels.lock().unwrap().0;
+        assert_eq!(levels.last(), Some(&(33, false)));
+        assert!(levels.contains(&(33, true)));
+    }
+}
@@ -0,0 +1,190 @@
+use super::IrqLine;
+use crate::register::RegId;
+use crate::Cpu;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+
+// Frequency of the system counter, reset value of `CNTFRQ_EL0`.
+pub const TIMER_FREQUENCY: u64 = 62_500_000;
+
+// Bits of `CNTx_CTL_EL0`.
+const CTL_ENABLE: u64 = 1 << 0;
+const CTL_IMASK: u64 = 1 << 1;
+const CTL_ISTATUS: u64 = 1 << 2;
+
+// Source of the system counter.
+pub enum Clock {
+    // Host monotonic time, scaled to `TIMER_FREQUENCY`.
+    Host(Instant),
+    // Number of executed instructions, which makes the guest deterministic.
+    Instructions(AtomicU64),
+}
+
+impl Clock {
+    pub fn host() -> Self {
+        Clock::Host(Instant::now())
+    }
+
+    pub fn instructions() -> Self {
+        Clock::Instructions(AtomicU64::new(0))
+    }
+
+    fn advance(&self, instructions: u64) -> u64 {
+        match self {
+            Clock::Host(start) => {
+                let nanos = start.elapsed().as_nanos();
+                (nanos * TIMER_FREQUENCY as u128 / 1_000_000_000) as u64
+            }
+            Clock::Instructions(count) => {
+                count.fetch_add(instructions, Ordering::Relaxed) + instructions
+            }
+        }
+    }
+}
+
+// Physical or virtual timer of the cpu, with its interrupt line.
+struct Timer {
+    ctl: RegId,
+    cval: RegId,
+    irq: Option<IrqLine>,
+    level: AtomicBool,
+}
+
+impl Timer {
+    fn new(cpu: &Cpu, name: &str) -> Self {
+        let reg = |suffix| cpu.reg_by_name(format!("{}_{}_el0", name, suffix)).unwrap();
+        Self {
+            ctl: reg("ctl"),
+            cval: reg("cval"),
+            irq: None,
+            level: AtomicBool::new(false),
+        }
+    }
+
+    fn update(&self, cpu: &mut Cpu, count: u64) {
+        let cval = cpu.sys(self.cval).u64();
+        let ctl = cpu.sys_mut(self.ctl).u64_mut();
+
+        let fired = *ctl & CTL_ENABLE != 0 && count >= cval;
+        if fired {
+            *ctl |= CTL_ISTATUS;
+        } else {
+            *ctl &= !CTL_ISTATUS;
+        }
+
+        let level = fired && *ctl & CTL_IMASK == 0;
+        if self.level.swap(level, Ordering::Relaxed) != level {
+            if let Some(irq) = &self.irq {
+                irq.set_level(level);
+            }
+        }
+    }
+}
+
+// ARM generic timer of a cpu.
+//
+// Counters are system registers of the cpu, which are updated between translated
+// blocks, as well as the status of the physical and virtual timers.
+pub struct GenericTimer {
+    clock: Clock,
+    cntpct: RegId,
+    cntvct: RegId,
+    cntvoff: RegId,
+    phys: Timer,
+    virt: Timer,
+}
+
+impl GenericTimer {
+    pub fn new(cpu: &Cpu, clock: Clock) -> Self {
+        let reg = |name| cpu.reg_by_name(name).unwrap();
+        Self {
+            clock,
+            cntpct: reg("cntpct_el0"),
+            cntvct: reg("cntvct_el0"),
+            cntvoff: reg("cntvoff_el2"),
+            phys: Timer::new(cpu, "cntp"),
+            virt: Timer::new(cpu, "cntv"),
+        }
+    }
+
+    pub fn connect_irq(&mut self, phys: IrqLine, virt: IrqLine) {
+        self.phys.irq = Some(phys);
+        self.virt.irq = Some(virt);
+    }
+
+    // Advance the counter after `instructions` are executed, and raise interrupts of
+    // timers which are fired.
+    pub fn tick(&self, cpu: &mut Cpu, instructions: u64) {
+        let count = self.clock.advance(instructions);
+        let virtual_count = count.wrapping_sub(cpu.sys(self.cntvoff).u64());
+
+        *cpu.sys_mut(self.cntpct).u64_mut() = count;
+        *cpu.sys_mut(self.cntvct).u64_mut() = virtual_count;
+
+        self.phys.update(cpu, count);
+        self.virt.update(cpu, virtual_count);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::Architecture;
+    use crate::device::InterruptController;
+
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Levels(Vec<(u32, bool)>);
+
+    impl InterruptController for Levels {
+        fn set_level(&mut self, irq: u32, level: bool) {
+            self.0.push((irq, level));
+        }
+    }
+
+    #[test]
+    fn timer_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let reg = |cpu: &Cpu, name| cpu.reg_by_name(name).unwrap();
+        let set = |cpu: &mut Cpu, name, val| *cpu.sys_mut(reg(cpu, name)).u64_mut() = val;
+        let get = |cpu: &Cpu, name| cpu.sys(reg(cpu, name)).u64();
+
+        let levels = Arc::new(Mutex::new(Levels::default()));
+        let mut timer = GenericTimer::new(&cpu, Clock::instructions());
+        timer.connect_irq(
+            IrqLine::new(levels.clone(), 30),
+            IrqLine::new(levels.clone(), 27),
+        );
+
+        set(&mut cpu, "cntvoff_el2", 10);
+        set(&mut cpu, "cntp_cval_el0", 100);
+        set(&mut cpu, "cntp_ctl_el0", CTL_ENABLE);
+        set(&mut cpu, "cntv_cval_el0", 105);
+        set(&mut cpu, "cntv_ctl_el0", CTL_ENABLE | CTL_IMASK);
+
+        timer.tick(&mut cpu, 50);
+        assert_eq!(get(&cpu, "cntpct_el0"), 50);
+        assert_eq!(get(&cpu, "cntvct_el0"), 40);
+        assert_eq!(get(&cpu, "cntp_ctl_el0"), CTL_ENABLE);
+        assert!(levels.lock().unwrap().0.is_empty());
+
+        // Masked timer reports its status without raising the interrupt.
+        timer.tick(&mut cpu, 60);
+        assert_eq!(get(&cpu, "cntp_ctl_el0"), CTL_ENABLE | CTL_ISTATUS);
+        assert_eq!(get(&cpu, "cntv_ctl_el0"), CTL_ENABLE | CTL_IMASK);
+        timer.tick(&mut cpu, 5);
+        assert_eq!(
+            get(&cpu, "cntv_ctl_el0"),
+            CTL_ENABLE | CTL_IMASK | CTL_ISTATUS
+        );
+        assert_eq!(levels.lock().unwrap().0, vec![(30, true)]);
+
+        // Moving the compare value lowers the interrupt.
+        set(&mut cpu, "cntp_cval_el0", 200);
+        timer.tick(&mut cpu, 0);
+        assert_eq!(get(&cpu, "cntp_ctl_el0"), CTL_ENABLE);
+        assert_eq!(levels.lock().unwrap().0, vec![(30, true), (30, false)]);
+    }
+}
@@ -13,12 +13,30 @@ pub enum MmuError {
     #[error("Page already mapped: {0:016x}")]
     PageAlreadyMapped(u64),
 
+    #[error("Region is not page aligned: {0:016x}")]
+    UnalignedRegion(u64),
+
+    #[error("Device already mapped: {0:016x}")]
+    DeviceAlreadyMapped(u64),
+
     #[error("Access violation: {0:016x}")]
     AccessViolation(u64),
 
     #[error("Page fault: {0:016x}")]
     PageFault(u64),
 
+    #[error("Translation fault at level {1}: {0:016x}")]
+    TranslationFault(u64, u8),
+
+    #[error("Access flag fault at level {1}: {0:016x}")]
+    AccessFlagFault(u64, u8),
+
+    #[error("Permission fault at level {1}: {0:016x}")]
+    PermissionFault(u64, u8),
+
+    #[error("Address size fault at level {1}: {0:016x}")]
+    AddressSizeFault(u64, u8),
+
     #[error("Fail to write size: {0:016x}")]
     WriteFail(usize),
 
@@ -27,12 +45,49 @@ pub enum MmuError {
 }
 
 #[derive(Debug, Error, Clone)]
-pub enum CompileError {}
+pub enum CompileError {
+    #[error("Unimplemented instruction {1:08x} at: {0:016x}")]
+    Unimplemented(u64, u32),
+
+    #[error("Unallocated instruction {1:08x} at: {0:016x}")]
+    Unallocated(u64, u32),
+}
+
+#[derive(Debug, Error, Clone)]
+pub enum ImageError {
+    #[error("Invalid ELF: {0}")]
+    Elf(String),
+
+    #[error("Unsupported machine: {0}")]
+    UnsupportedMachine(u16),
+
+    #[error("Invalid segment at: {0:016x}")]
+    InvalidSegment(u64),
+
+    #[error("Segment is outside of memory: {0:016x}")]
+    OutsideMemory(u64),
+
+    #[error("MMU error: {0}")]
+    Mmu(#[from] MmuError),
+
+    #[error("Invalid Linux image header")]
+    InvalidLinuxHeader,
+
+    #[error("Big-endian kernels are not supported")]
+    BigEndianKernel,
+}
 
 #[derive(Debug, Error, Clone)]
 pub enum CodegenError {
     #[error("Invalid type")]
     InvalidType,
+
+    // IR which the backend doesn't lower, and which is interpreted instead.
+    #[error("Unsupported by the backend: {0}")]
+    Unsupported(&'static str),
+
+    #[error("Backend error: {0}")]
+    Backend(String),
 }
 
 #[derive(Debug, Error, Clone)]
@@ -54,6 +109,9 @@ pub enum DebugError {
 
     #[error("Breakpoint not exist: {0}")]
     BreakpointNotExist(u64),
+
+    #[error("{0}")]
+    Execution(#[from] Error),
 }
 
 #[derive(Debug, Error, Clone)]
@@ -66,4 +124,8 @@ pub enum Error {
 
     #[error("Codegen error: {0}")]
     Codegen(#[from] CodegenError),
+
+    // The emulator itself failed while running the guest.
+    #[error("Emulator panicked: {0}")]
+    Panic(String),
 }
@@ -0,0 +1,398 @@
+use crate::compiler::aarch64_prelude::{current_el, Pstate};
+use crate::error::MmuError;
+use crate::Cpu;
+
+// Exception classes of `ESR_ELx.EC`.
+const EC_UNKNOWN: u64 = 0x00;
+const EC_SVC64: u64 = 0x15;
+const EC_HVC64: u64 = 0x16;
+const EC_SMC64: u64 = 0x17;
+const EC_IABT_LOWER: u64 = 0x20;
+const EC_IABT_CUR: u64 = 0x21;
+const EC_DABT_LOWER: u64 = 0x24;
+const EC_DABT_CUR: u64 = 0x25;
+
+const ESR_IL: u64 = 1 << 25; // 32-bit instruction
+const ISS_WNR: u64 = 1 << 6; // Data abort caused by a write
+
+// Fault status codes of aborts (`DFSC`, `IFSC`).
+pub const FSC_ADDRESS_SIZE: u8 = 0b000000;
+pub const FSC_TRANSLATION: u8 = 0b000100;
+pub const FSC_ACCESS_FLAG: u8 = 0b001000;
+pub const FSC_PERMISSION: u8 = 0b001100;
+pub const FSC_EXTERNAL: u8 = 0b010000;
+
+// Vector offsets from `VBAR_ELx`.
+const VECTOR_CUR_SP0: u64 = 0x000;
+const VECTOR_CUR_SPX: u64 = 0x200;
+const VECTOR_LOWER_A64: u64 = 0x400;
+
+// Offsets of exception types in a group of vectors.
+const VECTOR_IRQ: u64 = 0x080;
+const VECTOR_FIQ: u64 = 0x100;
+
+// Exception delivered to the guest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Exception {
+    Undefined,
+    Svc(u16),
+    Hvc(u16),
+    Smc(u16),
+    InstructionAbort { addr: u64, status: u8 },
+    DataAbort { addr: u64, write: bool, status: u8 },
+    Irq,
+    Fiq,
+}
+
+impl Exception {
+    pub fn data_abort(addr: u64, write: bool, err: &MmuError) -> Self {
+        Exception::DataAbort {
+            addr,
+            write,
+            status: fault_status(err),
+        }
+    }
+
+    // Exception raised by an instruction, from the syndrome generated by the compiler.
+    // Aborts use `pc` as the faulting address.
+    pub fn from_syndrome(esr: u64, pc: u64) -> Self {
+        let imm16 = esr as u16;
+        let status = (esr & 0x3f) as u8;
+
+        match esr >> 26 {
+            EC_SVC64 => Exception::Svc(imm16),
+            EC_HVC64 => Exception::Hvc(imm16),
+            EC_SMC64 => Exception::Smc(imm16),
+            EC_IABT_LOWER | EC_IABT_CUR => Exception::InstructionAbort { addr: pc, status },
+            _ => Exception::Undefined,
+        }
+    }
+
+    // Value of `ESR_ELx` when the exception is taken from a lower exception level or not.
+    pub fn syndrome(&self, from_lower: bool) -> u64 {
+        let (ec, iss) = match *self {
+            Exception::Undefined | Exception::Irq | Exception::Fiq => (EC_UNKNOWN, 0),
+            Exception::Svc(imm) => (EC_SVC64, imm as u64),
+            Exception::Hvc(imm) => (EC_HVC64, imm as u64),
+            Exception::Smc(imm) => (EC_SMC64, imm as u64),
+            Exception::InstructionAbort { status, .. } => {
+                let ec = if from_lower {
+                    EC_IABT_LOWER
+                } else {
+                    EC_IABT_CUR
+                };
+                (ec, status as u64)
+            }
+            Exception::DataAbort { write, status, .. } => {
+                let ec = if from_lower {
+                    EC_DABT_LOWER
+                } else {
+                    EC_DABT_CUR
+                };
+                let wnr = if write { ISS_WNR } else { 0 };
+                (ec, wnr | status as u64)
+            }
+        };
+
+        ec << 26 | ESR_IL | iss
+    }
+
+    // Address saved in `ELR_ELx`, where `pc` is the address of the instruction.
+    fn preferred_return(&self, pc: u64) -> u64 {
+        match self {
+            Exception::Svc(_) | Exception::Hvc(_) | Exception::Smc(_) => pc + 4,
+            _ => pc,
+        }
+    }
+
+    fn is_interrupt(&self) -> bool {
+        matches!(self, Exception::Irq | Exception::Fiq)
+    }
+
+    fn fault_address(&self) -> Option<u64> {
+        match *self {
+            Exception::InstructionAbort { addr, .. } | Exception::DataAbort { addr, .. } => {
+                Some(addr)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Fault status code reported for a failed memory access.
+pub fn fault_status(err: &MmuError) -> u8 {
+    match err {
+        MmuError::TranslationFault(_, level) => FSC_TRANSLATION | level,
+        MmuError::AccessFlagFault(_, level) => FSC_ACCESS_FLAG | level,
+        MmuError::PermissionFault(_, level) => FSC_PERMISSION | level,
+        MmuError::AddressSizeFault(_, level) => FSC_ADDRESS_SIZE | level,
+        MmuError::AccessViolation(_) => FSC_PERMISSION | 3,
+        MmuError::PageNotMapped(_) | MmuError::PageNotExist(_) | MmuError::PageFault(_) => {
+            FSC_TRANSLATION | 3
+        }
+        _ => FSC_EXTERNAL,
+    }
+}
+
+// Target exception level of `exc` taken from `from_el`.
+// Exceptions are never taken to a lower exception level.
+fn target_el(exc: &Exception, from_el: u8) -> u8 {
+    let el = match exc {
+        Exception::Hvc(_) => 2,
+        Exception::Smc(_) => 3,
+        _ => 1,
+    };
+    el.max(from_el)
+}
+
+// Take `exc` to the exception level which handles it.
+//
+// HVC and SMC are undefined at EL0.
+pub fn take_exception(cpu: &mut Cpu, exc: Exception) {
+    let flags = cpu.flag();
+    let from_el = current_el(flags);
+
+    let exc = match exc {
+        Exception::Hvc(_) | Exception::Smc(_) if from_el == 0 => Exception::Undefined,
+        exc => exc,
+    };
+    let target_el = target_el(&exc, from_el);
+
+    let offset = if from_el < target_el {
+        VECTOR_LOWER_A64
+    } else if flags & Pstate::SP.mask() != 0 {
+        VECTOR_CUR_SPX
+    } else {
+        VECTOR_CUR_SP0
+    };
+    let offset = match exc {
+        Exception::Irq => offset + VECTOR_IRQ,
+        Exception::Fiq => offset + VECTOR_FIQ,
+        _ => offset,
+    };
+
+    let pc = cpu.pc();
+    set_sys(cpu, "spsr", target_el, pstate_to_spsr(flags));
+    set_sys(cpu, "elr", target_el, exc.preferred_return(pc));
+    if !exc.is_interrupt() {
+        set_sys(cpu, "esr", target_el, exc.syndrome(from_el < target_el));
+    }
+    if let Some(addr) = exc.fault_address() {
+        set_sys(cpu, "far", target_el, addr);
+    }
+
+    let keep = Pstate::NZCV.mask() | Pstate::PAN.mask() | Pstate::UAO.mask() | Pstate::DIT.mask();
+    let masked = Pstate::D.mask() | Pstate::A.mask() | Pstate::I.mask() | Pstate::F.mask();
+    let flags =
+        (flags & keep) | masked | (target_el as u64) << Pstate::EL.idx() | Pstate::SP.mask();
+    cpu.set_flag(flags);
+
+    let vbar = sys(cpu, "vbar", target_el);
+    cpu.set_pc(vbar + offset);
+}
+
+// Interrupt to take when `irq` and `fiq` lines of the cpu are asserted.
+//
+// Interrupts are routed to EL1, so they are not taken at higher exception levels,
+// and are masked by `PSTATE.I` and `PSTATE.F` at EL0 and EL1.
+pub fn pending_interrupt(cpu: &Cpu, irq: bool, fiq: bool) -> Option<Exception> {
+    let flags = cpu.flag();
+    if current_el(flags) > 1 {
+        return None;
+    }
+
+    if fiq && flags & Pstate::F.mask() == 0 {
+        Some(Exception::Fiq)
+    } else if irq && flags & Pstate::I.mask() == 0 {
+        Some(Exception::Irq)
+    } else {
+        None
+    }
+}
+
+// Return from an exception taken to the current exception level (`ERET`).
+pub fn exception_return(cpu: &mut Cpu) {
+    let el = cpu.el();
+    let spsr = sys(cpu, "spsr", el);
+    let elr = sys(cpu, "elr", el);
+
+    cpu.set_flag(spsr_to_pstate(spsr));
+    cpu.set_pc(elr);
+}
+
+fn sys(cpu: &Cpu, name: &str, el: u8) -> u64 {
+    let id = cpu.reg_by_name(format!("{}_el{}", name, el)).unwrap();
+    cpu.sys(id).u64()
+}
+
+fn set_sys(cpu: &mut Cpu, name: &str, el: u8, val: u64) {
+    let id = cpu.reg_by_name(format!("{}_el{}", name, el)).unwrap();
+    *cpu.sys_mut(id).u64_mut() = val;
+}
+
+// Bit positions of `SPSR_ELx` fields, paired with the corresponding flags.
+const SPSR_FIELDS: [(Pstate, u64); 16] = [
+    (Pstate::N, 31),
+    (Pstate::Z, 30),
+    (Pstate::C, 29),
+    (Pstate::V, 28),
+    (Pstate::TCO, 25),
+    (Pstate::DIT, 24),
+    (Pstate::UAO, 23),
+    (Pstate::PAN, 22),
+    (Pstate::SS, 21),
+    (Pstate::IL, 20),
+    (Pstate::ALLINT, 13),
+    (Pstate::SSBS, 12),
+    (Pstate::D, 9),
+    (Pstate::A, 8),
+    (Pstate::I, 7),
+    (Pstate::F, 6),
+];
+
+// `M[3:2]` is the exception level and `M[0]` the selected stack pointer.
+const SPSR_EL: u64 = 2;
+const SPSR_SP: u64 = 0;
+
+pub fn pstate_to_spsr(flags: u64) -> u64 {
+    let mut spsr = 0;
+    for (field, pos) in SPSR_FIELDS {
+        spsr |= ((flags >> field.idx()) & 1) << pos;
+    }
+
+    spsr |= (flags & Pstate::BTYPE.mask()) >> Pstate::BTYPE.idx() << 10;
+    spsr |= (flags & Pstate::EL.mask()) >> Pstate::EL.idx() << SPSR_EL;
+    spsr |= (flags & Pstate::SP.mask()) >> Pstate::SP.idx() << SPSR_SP;
+    spsr
+}
+
+pub fn spsr_to_pstate(spsr: u64) -> u64 {
+    let mut flags = 0;
+    for (field, pos) in SPSR_FIELDS {
+        flags |= ((spsr >> pos) & 1) << field.idx();
+    }
+
+    flags |= (spsr >> 10 & 0b11) << Pstate::BTYPE.idx();
+    flags |= (spsr >> SPSR_EL & 0b11) << Pstate::EL.idx();
+    flags |= (spsr >> SPSR_SP & 1) << Pstate::SP.idx();
+    flags
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::Architecture;
+
+    #[test]
+    fn spsr_test() {
+        let flags = Pstate::N.mask()
+            | Pstate::C.mask()
+            | Pstate::I.mask()
+            | Pstate::F.mask()
+            | 1 << Pstate::EL.idx()
+            | Pstate::SP.mask();
+
+        let spsr = pstate_to_spsr(flags);
+        assert_eq!(spsr, 0xa000_00c5);
+        assert_eq!(spsr_to_pstate(spsr), flags);
+    }
+
+    #[test]
+    fn take_exception_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let vbar = cpu.reg_by_name("vbar_el1").unwrap();
+        *cpu.sys_mut(vbar).u64_mut() = 0x8000;
+
+        let flags = Pstate::Z.mask() | 1 << Pstate::EL.idx() | Pstate::SP.mask();
+        cpu.set_flag(flags);
+        cpu.set_pc(0x1000);
+
+        take_exception(&mut cpu, Exception::Svc(0x42));
+        let sys = |cpu: &Cpu, name| cpu.sys(cpu.reg_by_name(name).unwrap()).u64();
+        assert_eq!(cpu.pc(), 0x8200);
+        assert_eq!(sys(&cpu, "elr_el1"), 0x1004);
+        assert_eq!(sys(&cpu, "esr_el1"), 0x5600_0042);
+        assert_eq!(sys(&cpu, "spsr_el1"), pstate_to_spsr(flags));
+        assert_ne!(cpu.flag() & Pstate::I.mask(), 0);
+
+        exception_return(&mut cpu);
+        assert_eq!(cpu.pc(), 0x1004);
+        assert_eq!(cpu.flag(), flags);
+
+        // Aborts from EL0 use the lower exception level vectors.
+        cpu.set_flag(0);
+        take_exception(
+            &mut cpu,
+            Exception::data_abort(0xdead, true, &MmuError::PageNotMapped(0xdead)),
+        );
+        assert_eq!(cpu.pc(), 0x8400);
+        assert_eq!(sys(&cpu, "elr_el1"), 0x1004);
+        assert_eq!(sys(&cpu, "far_el1"), 0xdead);
+        assert_eq!(sys(&cpu, "esr_el1"), 0x9200_0047);
+    }
+
+    #[test]
+    fn interrupt_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let vbar = cpu.reg_by_name("vbar_el1").unwrap();
+        *cpu.sys_mut(vbar).u64_mut() = 0x8000;
+        let sys = |cpu: &Cpu, name| cpu.sys(cpu.reg_by_name(name).unwrap()).u64();
+
+        // Interrupts are masked by PSTATE.
+        let flags = 1 << Pstate::EL.idx() | Pstate::SP.mask() | Pstate::I.mask();
+        cpu.set_flag(flags);
+        cpu.set_pc(0x1000);
+        assert_eq!(pending_interrupt(&cpu, true, false), None);
+        assert_eq!(pending_interrupt(&cpu, true, true), Some(Exception::Fiq));
+
+        cpu.set_flag(flags & !Pstate::I.mask());
+        let exc = pending_interrupt(&cpu, true, false).unwrap();
+        take_exception(&mut cpu, exc);
+        assert_eq!(cpu.pc(), 0x8280);
+        assert_eq!(sys(&cpu, "elr_el1"), 0x1000);
+        assert_eq!(sys(&cpu, "esr_el1"), 0);
+        assert_eq!(pending_interrupt(&cpu, true, true), None);
+
+        // FIQ from EL0 uses the lower exception level vectors.
+        cpu.set_flag(0);
+        take_exception(&mut cpu, Exception::Fiq);
+        assert_eq!(cpu.pc(), 0x8500);
+
+        // Interrupts are routed to EL1, so never taken at EL2.
+        cpu.set_flag(2 << Pstate::EL.idx());
+        assert_eq!(pending_interrupt(&cpu, true, true), None);
+    }
+
+    #[test]
+    fn exception_level_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let vbar = cpu.reg_by_name("vbar_el2").unwrap();
+        *cpu.sys_mut(vbar).u64_mut() = 0x2000;
+
+        // HVC from EL1 is taken to EL2, which switches the stack pointer.
+        let flags = 1 << Pstate::EL.idx() | Pstate::SP.mask();
+        cpu.set_flag(flags);
+        cpu.set_pc(0x1000);
+        assert_eq!(cpu.reg_by_name("sp"), cpu.reg_by_name("sp_el1"));
+
+        take_exception(&mut cpu, Exception::Hvc(0));
+        assert_eq!(cpu.el(), 2);
+        assert_eq!(cpu.pc(), 0x2400);
+        assert_eq!(cpu.reg_by_name("sp"), cpu.reg_by_name("sp_el2"));
+
+        exception_return(&mut cpu);
+        assert_eq!(cpu.el(), 1);
+        assert_eq!(cpu.pc(), 0x1004);
+
+        // SVC at EL2 is taken to EL2, and HVC at EL0 is undefined.
+        cpu.set_flag(2 << Pstate::EL.idx());
+        take_exception(&mut cpu, Exception::Svc(0));
+        assert_eq!(cpu.el(), 2);
+        assert_eq!(cpu.pc(), 0x2000);
+
+        cpu.set_flag(0);
+        take_exception(&mut cpu, Exception::Hvc(0));
+        assert_eq!(cpu.el(), 1);
+        assert_eq!(cpu.reg_by_name("sp"), cpu.reg_by_name("sp_el1"));
+    }
+}
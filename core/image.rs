@@ -1,11 +1,31 @@
+use crate::error::{ImageError, MmuError};
+use crate::softmmu::{Mmu, RamRegion, PAGE_SIZE};
+use crate::symbol::SymbolTable;
+
 use std::collections::HashMap;
 
+use elf::abi::{EM_AARCH64, PF_W, PF_X, PT_LOAD};
+use elf::endian::AnyEndian;
+use elf::ElfBytes;
+
+// Header of arm64 Linux kernel `Image`s.
+const LINUX_HEADER_SIZE: usize = 64;
+const LINUX_MAGIC: &[u8] = b"ARM\x64";
+const LINUX_FLAG_BE: u64 = 1;
+// Text offset of kernels older than 3.17, whose header has no image size.
+const LINUX_LEGACY_TEXT_OFFSET: u64 = 0x8_0000;
+
+// Alignment of the base address Linux kernels are placed above.
+pub const LINUX_BASE_ALIGN: u64 = 0x20_0000;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct Section {
     sec_addr: u64,
 
     beg: usize,
     end: usize,
+    // Size in memory, where bytes past the data are zero.
+    mem_size: u64,
 
     writable: bool,
     executable: bool,
@@ -16,6 +36,7 @@ pub struct Image {
     image_code_entry: u64,
 
     sections: HashMap<String, Section>,
+    symbols: SymbolTable,
 }
 
 impl Image {
@@ -25,7 +46,60 @@ impl Image {
             image_code_entry: 0,
 
             sections: HashMap::new(),
+            symbols: SymbolTable::default(),
+        }
+    }
+
+    // Raw binary loaded at `addr`, which is also the entry point.
+    pub fn from_raw(image: Vec<u8>, addr: u64) -> Self {
+        let len = image.len();
+        let mut raw = Self::from_image(image);
+        raw.add_section("raw", addr, true, true, 0, len)
+            .set_entrypoint(addr);
+        raw
+    }
+
+    // Executable ELF for AArch64, whose PT_LOAD segments become sections named
+    // `segment<index>` after their program headers.
+    pub fn from_elf(image: Vec<u8>) -> Result<Self, ImageError> {
+        let file = ElfBytes::<AnyEndian>::minimal_parse(&image)
+            .map_err(|e| ImageError::Elf(e.to_string()))?;
+        if file.ehdr.e_machine != EM_AARCH64 {
+            return Err(ImageError::UnsupportedMachine(file.ehdr.e_machine));
+        }
+
+        let entry = file.ehdr.e_entry;
+        let mut sections = HashMap::new();
+        for (index, phdr) in file.segments().into_iter().flatten().enumerate() {
+            if phdr.p_type != PT_LOAD {
+                continue;
+            }
+
+            let beg = phdr.p_offset as usize;
+            let end = beg.checked_add(phdr.p_filesz as usize);
+            match end {
+                Some(end) if end <= image.len() && phdr.p_filesz <= phdr.p_memsz => {
+                    let section = Section {
+                        sec_addr: phdr.p_vaddr,
+                        beg,
+                        end,
+                        mem_size: phdr.p_memsz,
+                        writable: phdr.p_flags & PF_W != 0,
+                        executable: phdr.p_flags & PF_X != 0,
+                    };
+                    sections.insert(format!("segment{}", index), section);
+                }
+                _ => return Err(ImageError::InvalidSegment(phdr.p_vaddr)),
+            }
         }
+
+        let symbols = SymbolTable::from_elf(&image)?;
+        Ok(Self {
+            image,
+            image_code_entry: entry,
+            sections,
+            symbols,
+        })
     }
 
     pub fn set_entrypoint(&mut self, ep: u64) -> &mut Self {
@@ -37,6 +111,57 @@ impl Image {
         self.image_code_entry
     }
 
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    // Replace symbols, such as with those of `System.map` for raw images.
+    pub fn set_symbols(&mut self, symbols: SymbolTable) -> &mut Self {
+        self.symbols = symbols;
+        self
+    }
+
+    // End of the memory spanned by sections.
+    pub fn end(&self) -> u64 {
+        self.sections
+            .values()
+            .map(|sec| sec.sec_addr + sec.mem_size)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_linux(image: &[u8]) -> bool {
+        image.len() >= LINUX_HEADER_SIZE && &image[56..60] == LINUX_MAGIC
+    }
+
+    // arm64 Linux kernel `Image`, placed above the 2 MiB aligned `base` and entered at
+    // its start. The kernel expects memory up to its image size to be zero.
+    pub fn from_linux(image: Vec<u8>, base: u64) -> Result<Self, ImageError> {
+        if !Self::is_linux(&image) || !base.is_multiple_of(LINUX_BASE_ALIGN) {
+            return Err(ImageError::InvalidLinuxHeader);
+        }
+
+        let header =
+            |offset: usize| u64::from_le_bytes(image[offset..offset + 8].try_into().unwrap());
+        let (text_offset, image_size, flags) = match header(16) {
+            0 => (LINUX_LEGACY_TEXT_OFFSET, 0, 0),
+            image_size => (header(8), image_size, header(24)),
+        };
+        if flags & LINUX_FLAG_BE != 0 {
+            return Err(ImageError::BigEndianKernel);
+        }
+
+        let addr = base + text_offset;
+        let len = image.len();
+        let mut kernel = Self::from_image(image);
+        kernel
+            .add_section("kernel", addr, true, true, 0, len)
+            .set_entrypoint(addr);
+        kernel.sections.get_mut("kernel").unwrap().mem_size = image_size.max(len as u64);
+
+        Ok(kernel)
+    }
+
     // Add secment into image
     pub fn add_section(
         &mut self,
@@ -55,12 +180,64 @@ impl Image {
                 executable,
                 beg,
                 end,
+                mem_size: (end - beg) as u64,
             },
         );
 
         self
     }
 
+    // Map sections into `mmu` with their permissions, on pages which are not mapped yet.
+    // Sections sharing a page are mapped as one region, with permissions of all of them.
+    pub fn map(&self, mmu: &Mmu) -> Result<(), MmuError> {
+        let pages = |sec: &Section| {
+            let start = sec.sec_addr & !(PAGE_SIZE as u64 - 1);
+            let end = (sec.sec_addr + sec.mem_size).next_multiple_of(PAGE_SIZE as u64);
+            (start, end)
+        };
+
+        let mut sections: Vec<&Section> = self.sections.values().collect();
+        sections.sort_by_key(|sec| sec.sec_addr);
+        let mut sections = sections.into_iter().peekable();
+        while let Some(first) = sections.next() {
+            let (start, mut end) = pages(first);
+            let mut group = vec![first];
+            while let Some(sec) = sections.next_if(|sec| pages(sec).0 < end) {
+                end = end.max(pages(sec).1);
+                group.push(sec);
+            }
+
+            let writable = group.iter().any(|sec| sec.writable);
+            let executable = group.iter().any(|sec| sec.executable);
+            let region = RamRegion::new(end - start, true, writable, executable);
+            for sec in group {
+                region.fill(sec.sec_addr - start, &self.image[sec.beg..sec.end]);
+            }
+
+            mmu.mmap_region(start, region)?;
+        }
+
+        Ok(())
+    }
+
+    // Write sections into memory already mapped in `mmu`, and zero the rest of them.
+    // Sections outside of memory, including those over devices, aren't loaded.
+    pub fn load(&self, mmu: &Mmu) -> Result<(), ImageError> {
+        for sec in self.sections.values() {
+            if !mmu.is_memory(sec.sec_addr..sec.sec_addr.saturating_add(sec.mem_size)) {
+                return Err(ImageError::OutsideMemory(sec.sec_addr));
+            }
+            let data_size = (sec.end - sec.beg) as u64;
+            let zeros = vec![0u8; (sec.mem_size - data_size) as usize];
+            unsafe {
+                mmu.write(sec.sec_addr, &self.image[sec.beg..sec.end])?;
+                mmu.write(sec.sec_addr + data_size, &zeros)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn sections(&self) -> impl Iterator<Item = &str> {
         self.sections.keys().map(|v| v.as_str())
     }
@@ -80,3 +257,132 @@ impl Image {
         (sec.writable, sec.executable)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // ELF with a read-only code segment and a writable data segment, whose last 8
+    // bytes are bss.
+    fn elf() -> Vec<u8> {
+        let mut elf = vec![0u8; 0x200];
+        elf[0..4].copy_from_slice(b"\x7fELF");
+        elf[4] = 2; // ELFCLASS64
+        elf[5] = 1; // ELFDATA2LSB
+        elf[6] = 1; // EV_CURRENT
+        elf[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+        elf[18..20].copy_from_slice(&EM_AARCH64.to_le_bytes());
+        elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        elf[24..32].copy_from_slice(&0x1_0010u64.to_le_bytes()); // e_entry
+        elf[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+        elf[52..54].copy_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf[56..58].copy_from_slice(&2u16.to_le_bytes()); // e_phnum
+
+        let segments = [
+            (0x100u64, 0x1_0000u64, 0x20u64, 0x20u64, PF_X | 4),
+            (0x120, 0x2_0ff8, 0x10, 0x18, PF_W | 4),
+        ];
+        for (i, (offset, vaddr, filesz, memsz, flags)) in segments.into_iter().enumerate() {
+            let phdr = &mut elf[64 + i * 56..64 + (i + 1) * 56];
+            phdr[0..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+            phdr[4..8].copy_from_slice(&flags.to_le_bytes());
+            phdr[8..16].copy_from_slice(&offset.to_le_bytes());
+            phdr[16..24].copy_from_slice(&vaddr.to_le_bytes());
+            phdr[24..32].copy_from_slice(&vaddr.to_le_bytes());
+            phdr[32..40].copy_from_slice(&filesz.to_le_bytes());
+            phdr[40..48].copy_from_slice(&memsz.to_le_bytes());
+        }
+
+        elf[0x100..0x130].fill(0xaa);
+        elf
+    }
+
+    #[test]
+    fn linux_test() {
+        let mut kernel = vec![0u8; 0x1000];
+        kernel[16..24].copy_from_slice(&0x2000u64.to_le_bytes()); // image_size
+        kernel[56..60].copy_from_slice(LINUX_MAGIC);
+        kernel[0x800] = 0xaa;
+
+        let image = Image::from_linux(kernel.clone(), 0x4000_0000).unwrap();
+        assert_eq!(image.entrypoint(), 0x4000_0000);
+        assert_eq!(image.end(), 0x4000_2000);
+
+        // Legacy kernels have a fixed text offset.
+        kernel[16..24].fill(0);
+        let image = Image::from_linux(kernel.clone(), 0x4000_0000).unwrap();
+        assert_eq!(image.entrypoint(), 0x4008_0000);
+
+        kernel[16..24].copy_from_slice(&0x2000u64.to_le_bytes());
+        kernel[24] = LINUX_FLAG_BE as u8;
+        assert!(matches!(
+            Image::from_linux(kernel.clone(), 0x4000_0000),
+            Err(ImageError::BigEndianKernel)
+        ));
+        kernel[56] = 0;
+        assert!(matches!(
+            Image::from_linux(kernel, 0x4000_0000),
+            Err(ImageError::InvalidLinuxHeader)
+        ));
+    }
+
+    #[test]
+    fn elf_test() {
+        let image = Image::from_elf(elf()).unwrap();
+        assert_eq!(image.entrypoint(), 0x1_0010);
+        assert_eq!(image.section_addr("segment0"), 0x1_0000);
+        assert_eq!(image.section_access_info("segment0"), (false, true));
+        assert_eq!(image.section_access_info("segment1"), (true, false));
+        assert_eq!(image.section_data("segment1"), [0xaa; 0x10]);
+
+        // The data segment spans two pages, including its bss.
+        let mmu = Mmu::new();
+        image.map(&mmu).unwrap();
+        assert!(mmu.is_executable(0x1_0000..0x1_1000));
+        assert!(!mmu.is_writable(0x1_0000..0x1_1000));
+        assert!(mmu.is_writable(0x2_0000..0x2_2000));
+
+        let mut buf = [0xffu8; 0x18];
+        unsafe { mmu.read(0x2_0ff8, &mut buf).unwrap() };
+        assert_eq!(buf[..0x10], [0xaa; 0x10]);
+        assert_eq!(buf[0x10..], [0; 8]);
+
+        // Segments sharing a page are mapped together.
+        let mut shared = elf();
+        let phdr = 64 + 56;
+        shared[phdr + 16..phdr + 32].copy_from_slice(&[0x1_0800u64.to_le_bytes(); 2].concat());
+        let image = Image::from_elf(shared).unwrap();
+        let mmu = Mmu::new();
+        image.map(&mmu).unwrap();
+        assert!(mmu.is_executable(0x1_0000..0x1_1000));
+        assert!(mmu.is_writable(0x1_0000..0x1_1000));
+
+        let mut buf = [0u8; 0x20];
+        unsafe { mmu.read(0x1_0000, &mut buf).unwrap() };
+        assert_eq!(buf, [0xaa; 0x20]);
+        unsafe { mmu.read(0x1_0800, &mut buf[..0x18]).unwrap() };
+        assert_eq!(buf[..0x10], [0xaa; 0x10]);
+        assert_eq!(buf[0x10..0x18], [0; 8]);
+
+        // Loading needs memory at every segment.
+        let image = Image::from_elf(elf()).unwrap();
+        let mmu = Mmu::new();
+        mmu.mmap_region(0x1_0000, RamRegion::new(0x1_0000, true, true, true))
+            .unwrap();
+        assert!(matches!(
+            image.load(&mmu),
+            Err(ImageError::OutsideMemory(0x2_0ff8))
+        ));
+        mmu.mmap_region(0x2_0000, RamRegion::new(0x2000, true, true, true))
+            .unwrap();
+        image.load(&mmu).unwrap();
+
+        let mut elf = elf();
+        elf[18] = 0x3e; // EM_X86_64
+        assert!(matches!(
+            Image::from_elf(elf),
+            Err(ImageError::UnsupportedMachine(0x3e))
+        ));
+    }
+}
@@ -1,4 +1,4 @@
-use smallvec::SmallVec;
+use smallvec::{smallvec, SmallVec};
 
 use crate::ir::*;
 use crate::register::RegId;
@@ -15,14 +15,31 @@ pub enum BlockDestination {
     MemoryRelI64(Type, RegId, i64),
     MemoryRelU64(Type, RegId, u64),
     MemoryIr(Ir),
+    Temp(Type, TempId),
+    InvalidateCode, // Invalidate translations of the code at the address
+    InvalidateCodeAll,
+    InvalidateTlb, // Invalidate cached address translations
+    Exception,     // Take the exception with the syndrome, see `Exception::from_syndrome`
+    ExceptionReturn,
     None,
     Exit,
 }
 
+impl BlockDestination {
+    // Whether the destination sets pc, which ends the translation unit.
+    pub fn is_branch(&self) -> bool {
+        matches!(
+            self,
+            BlockDestination::Pc | BlockDestination::Exception | BlockDestination::ExceptionReturn
+        )
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct IrBlock {
     items: SmallVec<[IrBlockItem; 2]>,
     original_size: usize,
+    temp_count: u16,
 
     is_restore_flag: bool,
     is_atomic: bool,
@@ -33,6 +50,7 @@ impl IrBlock {
         Self {
             items: SmallVec::new(),
             original_size,
+            temp_count: 0,
             is_restore_flag: false,
             is_atomic: false,
         }
@@ -52,6 +70,12 @@ impl IrBlock {
             BlockDestination::MemoryRelI64(ty, _, _) => Some(ty),
             BlockDestination::MemoryRelU64(ty, _, _) => Some(ty),
             BlockDestination::MemoryIr(_) => None,
+            BlockDestination::Temp(ty, _) => Some(ty),
+            BlockDestination::InvalidateCode => Some(&Type::U64),
+            BlockDestination::InvalidateCodeAll => None,
+            BlockDestination::InvalidateTlb => None,
+            BlockDestination::Exception => Some(&Type::U64),
+            BlockDestination::ExceptionReturn => None,
             BlockDestination::None => None,
             BlockDestination::Exit => None,
             _ => unreachable!(),
@@ -71,6 +95,32 @@ impl IrBlock {
         &self.items
     }
 
+    pub fn items_mut(&mut self) -> &mut [IrBlockItem] {
+        &mut self.items
+    }
+
+    pub fn retain_items<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut IrBlockItem) -> bool,
+    {
+        self.items.retain(f);
+    }
+
+    // Remove all items, e.g. to rebuild the block with `append`.
+    pub fn take_items(&mut self) -> SmallVec<[IrBlockItem; 2]> {
+        std::mem::take(&mut self.items)
+    }
+
+    pub fn new_temp(&mut self) -> TempId {
+        let id = TempId(self.temp_count);
+        self.temp_count += 1;
+        id
+    }
+
+    pub fn temp_count(&self) -> u16 {
+        self.temp_count
+    }
+
     pub fn restore_flag(&self) -> bool {
         self.is_restore_flag
     }
@@ -86,6 +136,95 @@ impl IrBlock {
     pub fn original_size(&self) -> usize {
         self.original_size
     }
+
+    // Statically known values of pc after executing this block, where `ip` is the address
+    // of the block. Returns `None` if pc is computed at runtime.
+    pub fn branch_targets(&self, ip: u64) -> Option<SmallVec<[u64; 2]>> {
+        let pc_item = self.items.iter().rev().find(|item| item.dest().is_branch());
+
+        match pc_item {
+            Some(item) if !matches!(item.dest(), BlockDestination::Pc) => None,
+            Some(item) => match item.root() {
+                Ir::If(_, _, Operand::Ir(if_true), Operand::Ir(if_false)) => {
+                    Some(smallvec![static_pc(if_true, ip)?, static_pc(if_false, ip)?])
+                }
+                ir => Some(smallvec![static_pc(ir, ip)?]),
+            },
+            None => Some(smallvec![ip + self.original_size as u64]),
+        }
+    }
+}
+
+impl IrBlock {
+    // Merge blocks of consecutive instructions starting at `ip` into one block.
+    //
+    // Only the last instruction may write pc. `Operand::Ip` is resolved to the address
+    // of each instruction, and pc is updated before instructions which may fault, so
+    // that it points to the faulting instruction.
+    pub fn superblock(ip: u64, blocks: Vec<IrBlock>) -> IrBlock {
+        let size = blocks.iter().map(|b| b.original_size).sum();
+        let mut result = IrBlock::new(size);
+        let mut pc_written = false;
+
+        let mut instr_ip = ip;
+        for (idx, block) in blocks.into_iter().enumerate() {
+            result.is_restore_flag |= block.is_restore_flag;
+            result.is_atomic |= block.is_atomic;
+
+            if idx != 0 && block.items.iter().any(IrBlockItem::may_fault) {
+                result.append(
+                    Ir::Value(Operand::imm(Type::U64, instr_ip)),
+                    BlockDestination::Pc,
+                );
+            }
+
+            for mut item in block.items {
+                resolve_ip(&mut item.ir_root, instr_ip);
+                if let BlockDestination::MemoryIr(ir) = &mut item.ir_dest {
+                    resolve_ip(ir, instr_ip);
+                }
+
+                pc_written |= item.ir_dest.is_branch();
+                result.items.push(item);
+            }
+
+            instr_ip += block.original_size as u64;
+        }
+
+        if !pc_written {
+            result.append(
+                Ir::Value(Operand::imm(Type::U64, instr_ip)),
+                BlockDestination::Pc,
+            );
+        }
+
+        result
+    }
+}
+
+fn resolve_ip(ir: &mut Ir, ip: u64) {
+    for op in ir.operands_mut() {
+        resolve_ip_operand(op, ip);
+    }
+}
+
+fn resolve_ip_operand(op: &mut Operand, ip: u64) {
+    match op {
+        Operand::Ip => *op = Operand::imm(Type::U64, ip),
+        Operand::Ir(ir) | Operand::VoidIr(ir) => resolve_ip(ir, ip),
+        Operand::Dbg(_, inner) => resolve_ip_operand(inner, ip),
+        _ => {}
+    }
+}
+
+fn static_pc(ir: &Ir, ip: u64) -> Option<u64> {
+    match ir {
+        Ir::Add(Type::U64, Operand::Ip, Operand::Immediate(_, imm)) => Some(ip.wrapping_add(*imm)),
+        Ir::Sub(Type::U64, Operand::Ip, Operand::Immediate(_, imm)) => Some(ip.wrapping_sub(*imm)),
+        Ir::Value(Operand::Immediate(_, imm)) => Some(*imm),
+        Ir::Value(Operand::Ip) => Some(ip),
+        _ => None,
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -102,4 +241,42 @@ impl IrBlockItem {
     pub fn dest(&self) -> &BlockDestination {
         &self.ir_dest
     }
+
+    pub fn root_mut(&mut self) -> &mut Ir {
+        &mut self.ir_root
+    }
+
+    pub fn into_parts(self) -> (Ir, BlockDestination) {
+        (self.ir_root, self.ir_dest)
+    }
+
+    // Whether executing the item may stop the block early, by a memory fault, an
+    // exception or an exit.
+    pub fn may_fault(&self) -> bool {
+        match &self.ir_dest {
+            BlockDestination::Memory(..)
+            | BlockDestination::MemoryRelI64(..)
+            | BlockDestination::MemoryRelU64(..)
+            | BlockDestination::MemoryIr(..)
+            | BlockDestination::Exception
+            | BlockDestination::ExceptionReturn
+            | BlockDestination::Exit => true,
+            _ => has_load(&self.ir_root),
+        }
+    }
+}
+
+fn has_load(ir: &Ir) -> bool {
+    match ir {
+        Ir::Load(..) => true,
+        ir => ir.operands().into_iter().any(operand_has_load),
+    }
+}
+
+fn operand_has_load(op: &Operand) -> bool {
+    match op {
+        Operand::Ir(ir) | Operand::VoidIr(ir) => has_load(ir),
+        Operand::Dbg(_, op) => operand_has_load(op),
+        _ => false,
+    }
 }
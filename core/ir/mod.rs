@@ -2,9 +2,12 @@ mod block;
 pub use block::*;
 mod operand;
 pub use operand::*;
+pub mod opt;
 mod ty;
 pub use ty::*;
 
+use smallvec::{smallvec, SmallVec};
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Ir {
     Add(Type, Operand, Operand),
@@ -82,4 +85,68 @@ impl Ir {
             Ir::CmpEq(_, _) | Ir::CmpNe(_, _) | Ir::CmpGt(_, _) | Ir::CmpLt(_, _) => Type::Bool,
         }
     }
+
+    pub fn operands(&self) -> SmallVec<[&Operand; 3]> {
+        match self {
+            Ir::Add(_, a, b)
+            | Ir::Sub(_, a, b)
+            | Ir::Mul(_, a, b)
+            | Ir::Div(_, a, b)
+            | Ir::Mod(_, a, b)
+            | Ir::Addc(_, a, b)
+            | Ir::Subc(_, a, b)
+            | Ir::And(_, a, b)
+            | Ir::Or(_, a, b)
+            | Ir::Xor(_, a, b)
+            | Ir::LShl(_, a, b)
+            | Ir::LShr(_, a, b)
+            | Ir::AShr(_, a, b)
+            | Ir::Rotr(_, a, b)
+            | Ir::CmpEq(a, b)
+            | Ir::CmpNe(a, b)
+            | Ir::CmpGt(a, b)
+            | Ir::CmpLt(a, b)
+            | Ir::Shuffle(_, a, b) => smallvec![a, b],
+            Ir::Not(_, a)
+            | Ir::Load(_, a)
+            | Ir::ZextCast(_, a)
+            | Ir::SextCast(_, a)
+            | Ir::BitCast(_, a)
+            | Ir::Value(a) => smallvec![a],
+            Ir::If(_, c, a, b) => smallvec![c, a, b],
+            Ir::Nop => smallvec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> SmallVec<[&mut Operand; 3]> {
+        match self {
+            Ir::Add(_, a, b)
+            | Ir::Sub(_, a, b)
+            | Ir::Mul(_, a, b)
+            | Ir::Div(_, a, b)
+            | Ir::Mod(_, a, b)
+            | Ir::Addc(_, a, b)
+            | Ir::Subc(_, a, b)
+            | Ir::And(_, a, b)
+            | Ir::Or(_, a, b)
+            | Ir::Xor(_, a, b)
+            | Ir::LShl(_, a, b)
+            | Ir::LShr(_, a, b)
+            | Ir::AShr(_, a, b)
+            | Ir::Rotr(_, a, b)
+            | Ir::CmpEq(a, b)
+            | Ir::CmpNe(a, b)
+            | Ir::CmpGt(a, b)
+            | Ir::CmpLt(a, b)
+            | Ir::Shuffle(_, a, b) => smallvec![a, b],
+            Ir::Not(_, a)
+            | Ir::Load(_, a)
+            | Ir::ZextCast(_, a)
+            | Ir::SextCast(_, a)
+            | Ir::BitCast(_, a)
+            | Ir::Value(a) => smallvec![a],
+            Ir::If(_, c, a, b) => smallvec![c, a, b],
+            Ir::Nop => smallvec![],
+        }
+    }
 }
@@ -3,6 +3,10 @@ use crate::register::RegId;
 
 use crate::value::Value;
 
+// Block-local temporary value, which is not part of the guest state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TempId(pub u16);
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Operand {
     Ir(Box<Ir>),
@@ -12,6 +16,7 @@ pub enum Operand {
     Fpr(Type, RegId),
     Immediate(Type, u64),
     ImmediateValue(Type, Value),
+    Temp(Type, TempId),
     Ip,
     Flag,
     Dbg(String, Box<Operand>),
@@ -27,6 +32,7 @@ impl Operand {
             Operand::Sys(t, _) => *t,
             Operand::Immediate(t, _) => *t,
             Operand::ImmediateValue(t, _) => *t,
+            Operand::Temp(t, _) => *t,
             Operand::Ip => Type::U64,
             Operand::Flag => Type::U64,
             Operand::Dbg(_, operand) => operand.get_type(),
@@ -61,6 +67,10 @@ impl Operand {
         Operand::Sys(t, reg)
     }
 
+    pub const fn temp(t: Type, id: TempId) -> Self {
+        Operand::Temp(t, id)
+    }
+
     pub fn dbg(msg: impl AsRef<str>, operand: Operand) -> Self {
         Operand::Dbg(msg.as_ref().to_string(), Box::new(operand))
     }
@@ -0,0 +1,860 @@
+use crate::ir::{BlockDestination, Ir, IrBlock, Operand, TempId, Type};
+use crate::register::RegId;
+
+// Transformation over a sequence of IR blocks, which are executed in order.
+//
+// Passes must preserve the observable behavior of the sequence when it is executed
+// as a whole. Executing only a prefix of an optimized sequence is not supported.
+pub trait Pass {
+    fn name(&self) -> &'static str;
+
+    fn run(&self, blocks: &mut [IrBlock]);
+}
+
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+}
+
+impl PassManager {
+    // Manager without any pass.
+    pub fn new() -> Self {
+        Self { passes: Vec::new() }
+    }
+
+    pub fn add<P>(&mut self, pass: P) -> &mut Self
+    where
+        P: Pass + 'static,
+    {
+        self.passes.push(Box::new(pass));
+        self
+    }
+
+    pub fn passes(&self) -> impl Iterator<Item = &str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+
+    pub fn run(&self, blocks: &mut [IrBlock]) {
+        for pass in &self.passes {
+            pass.run(blocks);
+        }
+    }
+}
+
+impl Default for PassManager {
+    // Standard pipeline.
+    fn default() -> Self {
+        let mut manager = Self::new();
+        manager
+            .add(RedundantCastElimination)
+            .add(AlgebraicSimplification)
+            .add(ConstantFolding)
+            .add(DeadFlagElimination)
+            .add(RegisterCaching);
+
+        manager
+    }
+}
+
+// Rewrite every Ir node bottom-up with `f`.
+fn rewrite_blocks(blocks: &mut [IrBlock], f: &impl Fn(&mut Ir)) {
+    for block in blocks {
+        for item in block.items_mut() {
+            rewrite(item.root_mut(), f);
+        }
+    }
+}
+
+fn rewrite(ir: &mut Ir, f: &impl Fn(&mut Ir)) {
+    for op in ir.operands_mut() {
+        rewrite_operand(op, f);
+    }
+    f(ir);
+}
+
+fn rewrite_operand(op: &mut Operand, f: &impl Fn(&mut Ir)) {
+    match op {
+        Operand::Ir(inner) => {
+            rewrite(inner, f);
+
+            // Ir(Value(x)) is just x.
+            if let Ir::Value(value) = inner.as_ref() {
+                *op = value.clone();
+            }
+        }
+        Operand::VoidIr(inner) => rewrite(inner, f),
+        Operand::Dbg(_, inner) => rewrite_operand(inner, f),
+        _ => {}
+    }
+}
+
+fn into_ir(op: Operand) -> Ir {
+    match op {
+        Operand::Ir(ir) => *ir,
+        op => Ir::Value(op),
+    }
+}
+
+fn is_int(ty: Type) -> bool {
+    ty.is_scalar() && !ty.is_float()
+}
+
+fn is_signed(ty: Type) -> bool {
+    matches!(ty, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+}
+
+fn imm(op: &Operand) -> Option<u64> {
+    match op {
+        Operand::Immediate(ty, val) if is_int(*ty) => Some(val & ty.gen_mask()),
+        _ => None,
+    }
+}
+
+// Whether evaluating `ir` has no effect other than producing a value.
+fn is_pure(ir: &Ir) -> bool {
+    match ir {
+        Ir::Load(..) | Ir::Addc(..) | Ir::Subc(..) => false,
+        ir => ir.operands().into_iter().all(is_pure_operand),
+    }
+}
+
+fn is_pure_operand(op: &Operand) -> bool {
+    match op {
+        Operand::Ir(ir) | Operand::VoidIr(ir) => is_pure(ir),
+        Operand::Dbg(..) => false,
+        _ => true,
+    }
+}
+
+// Number of direct reads of the flag register in `ir`, or `None` if flags are
+// accessed implicitly (by a carry-setting operation).
+fn flag_reads(ir: &Ir) -> Option<usize> {
+    if let Ir::Addc(..) | Ir::Subc(..) = ir {
+        return None;
+    }
+
+    ir.operands().into_iter().map(operand_flag_reads).sum()
+}
+
+fn operand_flag_reads(op: &Operand) -> Option<usize> {
+    match op {
+        Operand::Flag => Some(1),
+        Operand::Ir(ir) | Operand::VoidIr(ir) => flag_reads(ir),
+        Operand::Dbg(_, op) => operand_flag_reads(op),
+        _ => Some(0),
+    }
+}
+
+fn replace_flag(ir: &mut Ir, with: &Ir) {
+    for op in ir.operands_mut() {
+        match op {
+            Operand::Flag => *op = Operand::ir(with.clone()),
+            Operand::Ir(ir) | Operand::VoidIr(ir) => replace_flag(ir, with),
+            _ => {}
+        }
+    }
+}
+
+// Removes casts that don't change the value.
+//
+// `ZextCast(t, ZextCast(u, x))` becomes `ZextCast(t, x)` when `u` is not narrower than `x`,
+// and casts into the type of the operand are removed.
+pub struct RedundantCastElimination;
+
+impl Pass for RedundantCastElimination {
+    fn name(&self) -> &'static str {
+        "redundant-cast-elimination"
+    }
+
+    fn run(&self, blocks: &mut [IrBlock]) {
+        rewrite_blocks(blocks, &|ir| match ir {
+            Ir::ZextCast(ty, op) | Ir::BitCast(ty, op) if op.get_type() == *ty => {
+                *ir = into_ir(op.clone());
+            }
+            Ir::ZextCast(ty, Operand::Ir(inner)) => {
+                if let Ir::ZextCast(inner_ty, op) = inner.as_ref() {
+                    if is_int(op.get_type()) && inner_ty.size() >= op.get_type().size() {
+                        *ir = Ir::ZextCast(*ty, op.clone());
+                    }
+                }
+            }
+            _ => {}
+        });
+    }
+}
+
+// Simplifies operations with identity or absorbing operands, e.g. `x + 0`, `x & 0`.
+pub struct AlgebraicSimplification;
+
+impl Pass for AlgebraicSimplification {
+    fn name(&self) -> &'static str {
+        "algebraic-simplification"
+    }
+
+    fn run(&self, blocks: &mut [IrBlock]) {
+        rewrite_blocks(blocks, &|ir| {
+            if let Some(simplified) = simplify(ir) {
+                *ir = simplified;
+            }
+        });
+    }
+}
+
+fn simplify(ir: &Ir) -> Option<Ir> {
+    let ty = ir.get_type();
+    if !is_int(ty) {
+        return None;
+    }
+
+    // `op`, if it has the type of the result.
+    let keep = |op: &Operand| (op.get_type() == ty).then(|| into_ir(op.clone()));
+    // Constant `val`, if `op` can be dropped.
+    let constant = |op: &Operand, val: u64| {
+        is_pure_operand(op).then(|| Ir::Value(Operand::imm(ty, val & ty.gen_mask())))
+    };
+    let ones = ty.gen_mask();
+
+    match ir {
+        Ir::Add(_, x, y) | Ir::Or(_, x, y) | Ir::Xor(_, x, y) if imm(x) == Some(0) => keep(y),
+        Ir::Add(_, x, y)
+        | Ir::Sub(_, x, y)
+        | Ir::Or(_, x, y)
+        | Ir::Xor(_, x, y)
+        | Ir::LShl(_, x, y)
+        | Ir::LShr(_, x, y)
+        | Ir::AShr(_, x, y)
+        | Ir::Rotr(_, x, y)
+            if imm(y) == Some(0) =>
+        {
+            keep(x)
+        }
+        Ir::Mul(_, x, y) if imm(x) == Some(1) => keep(y),
+        Ir::Mul(_, x, y) if imm(y) == Some(1) => keep(x),
+        Ir::Mul(_, x, y) | Ir::And(_, x, y) if imm(x) == Some(0) => constant(y, 0),
+        Ir::Mul(_, x, y) | Ir::And(_, x, y) if imm(y) == Some(0) => constant(x, 0),
+        Ir::And(_, x, y) if imm(x) == Some(ones) => keep(y),
+        Ir::And(_, x, y) if imm(y) == Some(ones) => keep(x),
+        Ir::Or(_, x, y) if imm(x) == Some(ones) => constant(y, ones),
+        Ir::Or(_, x, y) if imm(y) == Some(ones) => constant(x, ones),
+        Ir::Sub(_, x, y) | Ir::Xor(_, x, y) if x == y => constant(x, 0),
+        Ir::Not(_, Operand::Ir(inner)) => match inner.as_ref() {
+            Ir::Not(inner_ty, x) if *inner_ty == ty => keep(x),
+            _ => None,
+        },
+        Ir::If(_, cond, x, y) if x == y && is_pure_operand(cond) => keep(x),
+        _ => None,
+    }
+}
+
+// Evaluates operations whose operands are all constants.
+pub struct ConstantFolding;
+
+impl Pass for ConstantFolding {
+    fn name(&self) -> &'static str {
+        "constant-folding"
+    }
+
+    fn run(&self, blocks: &mut [IrBlock]) {
+        rewrite_blocks(blocks, &|ir| {
+            if let Ir::If(ty, cond, if_true, if_false) = ir {
+                if let Some(cond) = imm(cond) {
+                    let taken = if cond != 0 { if_true } else { if_false };
+                    if taken.get_type() == *ty {
+                        *ir = into_ir(taken.clone());
+                    }
+                }
+                return;
+            }
+
+            if let Some(val) = evaluate(ir) {
+                let ty = ir.get_type();
+                *ir = Ir::Value(Operand::imm(ty, val & ty.gen_mask()));
+            }
+        });
+    }
+}
+
+fn evaluate(ir: &Ir) -> Option<u64> {
+    let ty = ir.get_type();
+    if !is_int(ty) {
+        return None;
+    }
+
+    let bits = ty.size() as u64 * 8;
+    let sext = |val: u64, ty: Type| {
+        let shift = 64 - ty.size() as u32 * 8;
+        (((val << shift) as i64) >> shift) as u64
+    };
+
+    Some(match ir {
+        Ir::Add(_, a, b) => imm(a)?.wrapping_add(imm(b)?),
+        Ir::Sub(_, a, b) => imm(a)?.wrapping_sub(imm(b)?),
+        Ir::Mul(_, a, b) => imm(a)?.wrapping_mul(imm(b)?),
+        // Signed division is left to codegen, whose semantics differ in corner cases.
+        Ir::Div(_, a, b) if ty.is_unsigned() && imm(b)? != 0 => imm(a)? / imm(b)?,
+        Ir::Mod(_, a, b) if ty.is_unsigned() && imm(b)? != 0 => imm(a)? % imm(b)?,
+        Ir::And(_, a, b) => imm(a)? & imm(b)?,
+        Ir::Or(_, a, b) => imm(a)? | imm(b)?,
+        Ir::Xor(_, a, b) => imm(a)? ^ imm(b)?,
+        Ir::Not(_, a) => !imm(a)?,
+        Ir::LShl(_, a, b) if imm(b)? < bits => imm(a)? << imm(b)?,
+        Ir::LShr(_, a, b) if imm(b)? < bits => imm(a)? >> imm(b)?,
+        Ir::AShr(_, a, b) if imm(b)? < bits => {
+            if is_signed(ty) {
+                ((sext(imm(a)?, ty) as i64) >> imm(b)?) as u64
+            } else {
+                imm(a)? >> imm(b)?
+            }
+        }
+        Ir::Rotr(_, a, b) => {
+            let (a, b) = (imm(a)?, imm(b)? % bits);
+            if b == 0 {
+                a
+            } else {
+                (a >> b) | (a << (bits - b))
+            }
+        }
+        Ir::ZextCast(_, a) => imm(a)?,
+        Ir::SextCast(_, a) if is_signed(a.get_type()) => sext(imm(a)?, a.get_type()),
+        Ir::SextCast(_, a) => imm(a)?,
+        Ir::BitCast(_, a) => imm(a)?,
+        Ir::CmpEq(a, b) => (imm(a)? == imm(b)?) as u64,
+        Ir::CmpNe(a, b) => (imm(a)? != imm(b)?) as u64,
+        Ir::CmpGt(a, b) => (imm(a)? > imm(b)?) as u64,
+        Ir::CmpLt(a, b) => (imm(a)? < imm(b)?) as u64,
+        _ => return None,
+    })
+}
+
+// Removes writes to the flag register which are overwritten before being observed.
+//
+// A write is observed by a read of `Operand::Flag`, a carry-setting operation, or by
+// anything that may leave the sequence early (memory accesses, pc writes). When the
+// next write reads the flags exactly once, the earlier value is forwarded into it.
+pub struct DeadFlagElimination;
+
+impl Pass for DeadFlagElimination {
+    fn name(&self) -> &'static str {
+        "dead-flag-elimination"
+    }
+
+    fn run(&self, blocks: &mut [IrBlock]) {
+        let mut dead = Vec::new();
+        // Position of the last pure flags write, and whether its value can still be
+        // recomputed, i.e. nothing it reads has been written since.
+        let mut pending: Option<(usize, usize, bool)> = None;
+
+        for b in 0..blocks.len() {
+            for i in 0..blocks[b].items().len() {
+                let item = &blocks[b].items()[i];
+
+                if !matches!(item.dest(), BlockDestination::Flags) {
+                    if !is_transparent(item.root(), item.dest()) {
+                        pending = None;
+                    } else if let Some((pb, pi, forwardable)) = &mut pending {
+                        let prev = blocks[*pb].items()[*pi].root();
+                        *forwardable &= !reads_dest(prev, item.dest());
+                    }
+                    continue;
+                }
+
+                if let Some((pb, pi, forwardable)) = pending {
+                    match flag_reads(item.root()) {
+                        Some(0) => dead.push((pb, pi)),
+                        Some(1) if forwardable && is_pure(item.root()) => {
+                            let prev = blocks[pb].items()[pi].root().clone();
+                            replace_flag(blocks[b].items_mut()[i].root_mut(), &prev);
+                            dead.push((pb, pi));
+                        }
+                        _ => {}
+                    }
+                }
+
+                let root = blocks[b].items()[i].root();
+                pending = is_pure(root).then_some((b, i, true));
+            }
+        }
+
+        for (b, block) in blocks.iter_mut().enumerate() {
+            let mut i = 0;
+            block.retain_items(|_| {
+                i += 1;
+                !dead.contains(&(b, i - 1))
+            });
+        }
+    }
+}
+
+// Whether an item neither observes the flags nor leaves the sequence early.
+fn is_transparent(root: &Ir, dest: &BlockDestination) -> bool {
+    let dest_ok = matches!(
+        dest,
+        BlockDestination::Gpr(..)
+            | BlockDestination::Fpr(..)
+            | BlockDestination::Sys(..)
+            | BlockDestination::FprSlot(..)
+            | BlockDestination::Temp(..)
+            | BlockDestination::None
+    );
+
+    dest_ok && is_pure(root) && flag_reads(root) == Some(0)
+}
+
+// Whether `ir` reads the location written by `dest`.
+fn reads_dest(ir: &Ir, dest: &BlockDestination) -> bool {
+    ir.operands()
+        .into_iter()
+        .any(|op| operand_reads_dest(op, dest))
+}
+
+fn operand_reads_dest(op: &Operand, dest: &BlockDestination) -> bool {
+    match (op, dest) {
+        (Operand::Ir(ir) | Operand::VoidIr(ir), _) => reads_dest(ir, dest),
+        (Operand::Dbg(_, op), _) => operand_reads_dest(op, dest),
+        (Operand::Gpr(_, a), BlockDestination::Gpr(_, b))
+        | (Operand::Fpr(_, a), BlockDestination::Fpr(_, b) | BlockDestination::FprSlot(_, b, _))
+        | (Operand::Sys(_, a), BlockDestination::Sys(_, b)) => a == b,
+        (Operand::Temp(_, a), BlockDestination::Temp(_, b)) => a == b,
+        _ => false,
+    }
+}
+
+// Keeps general purpose registers, which are accessed repeatedly in a block, in
+// temporaries.
+//
+// A cached register is loaded once and its dirty value is written back at the end of
+// the block. Items which may fault observe the guest state, so dirty registers are
+// written back before them. Writes to system registers or flags, and exceptions, may
+// change which register an id refers to (the selected stack pointer), so the cache is
+// dropped.
+// Only 64-bit accesses are cached, narrower ones access the register directly.
+pub struct RegisterCaching;
+
+impl Pass for RegisterCaching {
+    fn name(&self) -> &'static str {
+        "register-caching"
+    }
+
+    fn run(&self, blocks: &mut [IrBlock]) {
+        for block in blocks {
+            cache_registers(block);
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct CachedReg {
+    id: RegId,
+    temp: TempId,
+    dirty: bool,
+}
+
+fn cache_registers(block: &mut IrBlock) {
+    let cacheable = cacheable_registers(block);
+    if cacheable.is_empty() {
+        return;
+    }
+
+    let mut cache: Vec<CachedReg> = Vec::new();
+    for item in block.take_items() {
+        let may_fault = item.may_fault();
+        let (mut root, mut dest) = item.into_parts();
+
+        let mut narrow = Vec::new();
+        gpr_accesses(&root, &mut |ty, id| {
+            if ty != Type::U64 {
+                narrow.push(id);
+            }
+        });
+        let narrow_write = match dest {
+            BlockDestination::Gpr(ty, id) if ty != Type::U64 => Some(id),
+            _ => None,
+        };
+        narrow.extend(narrow_write);
+
+        let barrier = matches!(
+            dest,
+            BlockDestination::Sys(..)
+                | BlockDestination::Flags
+                | BlockDestination::Exception
+                | BlockDestination::ExceptionReturn
+        );
+        for reg in &mut cache {
+            if (may_fault || barrier || narrow.contains(&reg.id)) && reg.dirty {
+                block.append(
+                    Ir::Value(Operand::temp(Type::U64, reg.temp)),
+                    BlockDestination::Gpr(Type::U64, reg.id),
+                );
+                reg.dirty = false;
+            }
+        }
+
+        let mut reads = Vec::new();
+        gpr_accesses(&root, &mut |ty, id| {
+            if ty == Type::U64 && cacheable.contains(&id) && !reads.contains(&id) {
+                reads.push(id);
+            }
+        });
+        for id in reads {
+            if !cache.iter().any(|reg| reg.id == id) {
+                let temp = block.new_temp();
+                block.append(
+                    Ir::Value(Operand::gpr(Type::U64, id)),
+                    BlockDestination::Temp(Type::U64, temp),
+                );
+                cache.push(CachedReg {
+                    id,
+                    temp,
+                    dirty: false,
+                });
+            }
+        }
+        replace_gprs(&mut root, &cache);
+
+        if let BlockDestination::Gpr(Type::U64, id) = dest {
+            if cacheable.contains(&id) {
+                let temp = match cache.iter_mut().find(|reg| reg.id == id) {
+                    Some(reg) => {
+                        reg.dirty = true;
+                        reg.temp
+                    }
+                    None => {
+                        let temp = block.new_temp();
+                        cache.push(CachedReg {
+                            id,
+                            temp,
+                            dirty: true,
+                        });
+                        temp
+                    }
+                };
+                dest = BlockDestination::Temp(Type::U64, temp);
+            }
+        }
+
+        block.append(root, dest);
+
+        if barrier {
+            cache.clear();
+        } else if let Some(id) = narrow_write {
+            cache.retain(|reg| reg.id != id);
+        }
+    }
+
+    for reg in cache.iter().filter(|reg| reg.dirty) {
+        block.append(
+            Ir::Value(Operand::temp(Type::U64, reg.temp)),
+            BlockDestination::Gpr(Type::U64, reg.id),
+        );
+    }
+}
+
+// Registers whose 64-bit value is read or written more than once.
+fn cacheable_registers(block: &IrBlock) -> Vec<RegId> {
+    let mut reads: Vec<(RegId, usize)> = Vec::new();
+    let mut writes: Vec<(RegId, usize)> = Vec::new();
+    let count = |counts: &mut Vec<(RegId, usize)>, id| match counts.iter_mut().find(|c| c.0 == id) {
+        Some(c) => c.1 += 1,
+        None => counts.push((id, 1)),
+    };
+
+    for item in block.items() {
+        gpr_accesses(item.root(), &mut |ty, id| {
+            if ty == Type::U64 {
+                count(&mut reads, id);
+            }
+        });
+        if let BlockDestination::Gpr(Type::U64, id) = item.dest() {
+            count(&mut writes, *id);
+        }
+    }
+
+    reads
+        .iter()
+        .chain(writes.iter())
+        .filter(|(_, n)| *n > 1)
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+fn gpr_accesses(ir: &Ir, f: &mut impl FnMut(Type, RegId)) {
+    for op in ir.operands() {
+        gpr_operand_accesses(op, f);
+    }
+}
+
+fn gpr_operand_accesses(op: &Operand, f: &mut impl FnMut(Type, RegId)) {
+    match op {
+        Operand::Gpr(ty, id) => f(*ty, *id),
+        Operand::Ir(ir) | Operand::VoidIr(ir) => gpr_accesses(ir, f),
+        Operand::Dbg(_, op) => gpr_operand_accesses(op, f),
+        _ => {}
+    }
+}
+
+fn replace_gprs(ir: &mut Ir, cache: &[CachedReg]) {
+    for op in ir.operands_mut() {
+        replace_gprs_operand(op, cache);
+    }
+}
+
+fn replace_gprs_operand(op: &mut Operand, cache: &[CachedReg]) {
+    match op {
+        Operand::Gpr(Type::U64, id) => {
+            if let Some(reg) = cache.iter().find(|reg| reg.id == *id) {
+                *op = Operand::temp(Type::U64, reg.temp);
+            }
+        }
+        Operand::Ir(ir) | Operand::VoidIr(ir) => replace_gprs(ir, cache),
+        Operand::Dbg(_, op) => replace_gprs_operand(op, cache),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::codegen::flag_policy::AArch64FlagPolicy;
+    use crate::codegen::rustjit::InterpretCodegen;
+    use crate::codegen::{Codegen, Executable, ExecutionContext};
+    use crate::cpu::Architecture;
+    use crate::register::RegId;
+    use crate::softmmu::Mmu;
+    use crate::Cpu;
+
+    fn reg(cpu: &Cpu, name: &str) -> RegId {
+        cpu.reg_by_name(name).unwrap()
+    }
+
+    // Run `blocks` with the interpreter, return resulting (x0, x1, flags, pc).
+    fn interpret(blocks: &[IrBlock]) -> (u64, u64, u64, u64) {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let (x0, x1) = (reg(&cpu, "x0"), reg(&cpu, "x1"));
+
+        *cpu.gpr_mut(x0).u64_mut() = 0x1234_5678_9abc_def0;
+        *cpu.gpr_mut(x1).u64_mut() = 7;
+        cpu.set_flag(0x3c0);
+        cpu.set_pc(0x1000);
+
+        let cgen = InterpretCodegen::new(AArch64FlagPolicy);
+        let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+        for block in blocks {
+            unsafe { cgen.compile_ir_block(block).execute(&mut ctx) };
+        }
+
+        (cpu.gpr(x0).u64(), cpu.gpr(x1).u64(), cpu.flag(), cpu.pc())
+    }
+
+    fn item_count(blocks: &[IrBlock]) -> usize {
+        blocks.iter().map(|b| b.items().len()).sum()
+    }
+
+    fn check(blocks: Vec<IrBlock>) -> Vec<IrBlock> {
+        let expected = interpret(&blocks);
+
+        let mut optimized = blocks;
+        PassManager::default().run(&mut optimized);
+        assert_eq!(interpret(&optimized), expected);
+
+        optimized
+    }
+
+    #[test]
+    fn constant_folding_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let x0 = reg(&cpu, "x0");
+
+        let mut block = IrBlock::new(4);
+        block.append(
+            Ir::Add(
+                Type::U64,
+                Operand::ir(Ir::LShl(
+                    Type::U64,
+                    Operand::imm(Type::U64, 3),
+                    Operand::imm(Type::U64, 4),
+                )),
+                Operand::ir(Ir::SextCast(
+                    Type::U64,
+                    Operand::ir(Ir::BitCast(Type::I8, Operand::imm(Type::U8, 0xfe))),
+                )),
+            ),
+            BlockDestination::Gpr(Type::U64, x0),
+        );
+
+        let optimized = check(vec![block]);
+        match optimized[0].items()[0].root() {
+            Ir::Value(Operand::Immediate(Type::U64, 0x2e)) => {}
+            ir => panic!("not folded: {:?}", ir),
+        }
+    }
+
+    #[test]
+    fn algebraic_simplification_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let (x0, x1) = (reg(&cpu, "x0"), reg(&cpu, "x1"));
+        let gpr = |id| Operand::gpr(Type::U64, id);
+
+        let mut block = IrBlock::new(4);
+        block.append(
+            Ir::Or(
+                Type::U64,
+                Operand::ir(Ir::Mul(Type::U64, gpr(x1), Operand::imm(Type::U64, 1))),
+                Operand::ir(Ir::And(Type::U64, gpr(x0), Operand::imm(Type::U64, 0))),
+            ),
+            BlockDestination::Gpr(Type::U64, x0),
+        );
+        block.append(
+            Ir::Sub(Type::U64, gpr(x1), gpr(x1)),
+            BlockDestination::Gpr(Type::U64, x1),
+        );
+
+        let optimized = check(vec![block]);
+        assert!(
+            matches!(optimized[0].items()[0].root(), Ir::Value(Operand::Gpr(_, id)) if *id == x1)
+        );
+        assert!(matches!(
+            optimized[0].items()[1].root(),
+            Ir::Value(Operand::Immediate(_, 0))
+        ));
+    }
+
+    #[test]
+    fn redundant_cast_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let x0 = reg(&cpu, "x0");
+
+        let mut block = IrBlock::new(4);
+        block.append(
+            Ir::ZextCast(
+                Type::U64,
+                Operand::ir(Ir::ZextCast(Type::U32, Operand::gpr(Type::U16, x0))),
+            ),
+            BlockDestination::Gpr(Type::U64, x0),
+        );
+
+        let optimized = check(vec![block]);
+        match optimized[0].items()[0].root() {
+            Ir::ZextCast(Type::U64, Operand::Gpr(Type::U16, _)) => {}
+            ir => panic!("not removed: {:?}", ir),
+        }
+    }
+
+    #[test]
+    fn dead_flag_elimination_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let (x0, x1) = (reg(&cpu, "x0"), reg(&cpu, "x1"));
+
+        let set_bits = |bits| {
+            let mut block = IrBlock::new(4);
+            block.append(
+                Ir::Or(Type::U64, Operand::Flag, Operand::imm(Type::U64, bits)),
+                BlockDestination::Flags,
+            );
+            block
+        };
+
+        let mut mov = IrBlock::new(4);
+        mov.append(
+            Ir::Value(Operand::gpr(Type::U64, x0)),
+            BlockDestination::Gpr(Type::U64, x1),
+        );
+
+        let mut overwrite = IrBlock::new(4);
+        overwrite.append(
+            Ir::Value(Operand::imm(Type::U64, 0x10)),
+            BlockDestination::Flags,
+        );
+
+        // First two writes are merged into the second, which is overwritten by the last.
+        let blocks = vec![set_bits(1 << 63), mov.clone(), set_bits(1 << 62), overwrite];
+        let optimized = check(blocks.clone());
+        assert_eq!(item_count(&optimized), 2);
+
+        // A load may fault, so the write before it must be kept.
+        let mut load = IrBlock::new(4);
+        load.append(
+            Ir::Load(Type::U64, Operand::imm(Type::U64, 0)),
+            BlockDestination::None,
+        );
+        let mut blocks = blocks;
+        blocks.insert(1, load);
+
+        let mut optimized = blocks;
+        DeadFlagElimination.run(&mut optimized);
+        assert_eq!(item_count(&optimized), 4);
+
+        // The value of a write can't be forwarded past a write to a register it reads.
+        let mut read_x1 = IrBlock::new(4);
+        read_x1.append(
+            Ir::Or(Type::U64, Operand::Flag, Operand::gpr(Type::U64, x1)),
+            BlockDestination::Flags,
+        );
+        let optimized = check(vec![read_x1, mov, set_bits(1 << 62)]);
+        assert_eq!(item_count(&optimized), 3);
+    }
+
+    #[test]
+    fn register_caching_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let (x0, x1) = (reg(&cpu, "x0"), reg(&cpu, "x1"));
+        let gpr = |id| Operand::gpr(Type::U64, id);
+        let instr = |ir, dest| {
+            let mut block = IrBlock::new(4);
+            block.append(ir, dest);
+            block
+        };
+        let gpr_writes = |block: &IrBlock| {
+            let writes = block.items().iter().filter_map(|item| match item.dest() {
+                BlockDestination::Gpr(_, id) => Some(*id),
+                _ => None,
+            });
+            writes.collect::<Vec<_>>()
+        };
+
+        // x0 += 1; x0 += x1; x1 = x0 * 2; x0 += 1
+        let add_x0 = |op| {
+            instr(
+                Ir::Add(Type::U64, gpr(x0), op),
+                BlockDestination::Gpr(Type::U64, x0),
+            )
+        };
+        let blocks = vec![
+            add_x0(Operand::imm(Type::U64, 1)),
+            add_x0(gpr(x1)),
+            instr(
+                Ir::Mul(Type::U64, gpr(x0), Operand::imm(Type::U64, 2)),
+                BlockDestination::Gpr(Type::U64, x1),
+            ),
+            add_x0(Operand::imm(Type::U64, 1)),
+        ];
+
+        let optimized = check(vec![IrBlock::superblock(0x1000, blocks.clone())]);
+        assert_eq!(gpr_writes(&optimized[0]), vec![x1, x0]);
+
+        // Dirty registers are written back before an item which may fault.
+        let mut blocks = blocks;
+        blocks.insert(
+            2,
+            instr(
+                Ir::Load(Type::U64, gpr(x1)),
+                BlockDestination::Gpr(Type::U64, x1),
+            ),
+        );
+        let mut optimized = vec![IrBlock::superblock(0x1000, blocks)];
+        RegisterCaching.run(&mut optimized);
+
+        let items = optimized[0].items();
+        let load = items
+            .iter()
+            .position(|item| matches!(item.root(), Ir::Load(..)))
+            .unwrap();
+        assert!(matches!(items[load - 1].dest(), BlockDestination::Gpr(_, id) if *id == x0));
+        assert!(matches!(
+            (items[load - 2].root(), items[load - 2].dest()),
+            (
+                Ir::Value(Operand::Immediate(_, 0x1008)),
+                BlockDestination::Pc
+            )
+        ));
+    }
+}
@@ -3,11 +3,16 @@ pub mod codegen;
 pub mod compiler;
 pub mod cpu;
 pub mod debug;
+pub mod device;
 pub mod error;
+pub mod exception;
 pub mod image;
 pub mod ir;
+pub mod psci;
 pub mod register;
 pub mod softmmu;
+pub mod symbol;
+pub mod user;
 pub mod value;
 
 pub use cpu::Cpu;
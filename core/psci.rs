@@ -0,0 +1,155 @@
+// Power State Coordination Interface, implemented by the emulator in place of the
+// firmware. The guest calls it with `HVC` or `SMC`, passing the function in `x0` and
+// arguments in `x1` to `x3`, and the result is returned in `x0`.
+//
+// https://developer.arm.com/documentation/den0022/latest
+use crate::Cpu;
+
+// Function IDs. Functions taking addresses have both SMC32 and SMC64 conventions.
+const PSCI_VERSION: u32 = 0x8400_0000;
+const CPU_OFF: u32 = 0x8400_0002;
+const CPU_ON: u32 = 0x8400_0003;
+const AFFINITY_INFO: u32 = 0x8400_0004;
+const MIGRATE_INFO_TYPE: u32 = 0x8400_0006;
+const SYSTEM_OFF: u32 = 0x8400_0008;
+const SYSTEM_RESET: u32 = 0x8400_0009;
+const PSCI_FEATURES: u32 = 0x8400_000a;
+const SMC64: u32 = 0x4000_0000;
+
+// Return codes.
+const SUCCESS: i64 = 0;
+const NOT_SUPPORTED: i64 = -1;
+const INVALID_PARAMETERS: i64 = -2;
+const ALREADY_ON: i64 = -4;
+
+// PSCI 1.0, which introduced `PSCI_FEATURES`.
+const VERSION: i64 = 1 << 16;
+
+// `AFFINITY_INFO` states.
+const AFFINITY_ON: i64 = 0;
+
+// `MIGRATE_INFO_TYPE`: there is no trusted OS which needs migration.
+const MIGRATE_NOT_REQUIRED: i64 = 2;
+
+// Affinity fields `Aff3` and `Aff2` to `Aff0` of `MPIDR_EL1`.
+const MPIDR_AFFINITY: u64 = 0xff_00ff_ffff;
+
+// Power state of the system requested by the guest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shutdown {
+    PowerOff,
+    Reset,
+    // Exit of the process with its status, in user-mode emulation.
+    Exit(i32),
+}
+
+// Handle a call of the guest, which is stopped if the call returns a power state.
+//
+// The board has a single cpu, so turning it off powers off the system.
+pub fn call(cpu: &mut Cpu) -> Option<Shutdown> {
+    let function = gpr(cpu, 0) as u32;
+    // Arguments of SMC32 functions are in the lower 32 bits of the registers.
+    let arg = |cpu: &Cpu, n| {
+        let arg = gpr(cpu, n);
+        if function & SMC64 != 0 {
+            arg
+        } else {
+            arg as u32 as u64
+        }
+    };
+
+    let ret = match function & !SMC64 {
+        PSCI_VERSION => VERSION,
+        PSCI_FEATURES => match arg(cpu, 1) as u32 {
+            PSCI_VERSION | CPU_OFF | MIGRATE_INFO_TYPE | SYSTEM_OFF | SYSTEM_RESET
+            | PSCI_FEATURES => SUCCESS,
+            id if matches!(id & !SMC64, CPU_ON | AFFINITY_INFO) => SUCCESS,
+            _ => NOT_SUPPORTED,
+        },
+        CPU_ON if is_self(cpu, arg(cpu, 1)) => ALREADY_ON,
+        CPU_ON => INVALID_PARAMETERS,
+        AFFINITY_INFO if is_self(cpu, arg(cpu, 1)) => AFFINITY_ON,
+        AFFINITY_INFO => INVALID_PARAMETERS,
+        MIGRATE_INFO_TYPE => MIGRATE_NOT_REQUIRED,
+        CPU_OFF | SYSTEM_OFF => return Some(Shutdown::PowerOff),
+        SYSTEM_RESET => return Some(Shutdown::Reset),
+        _ => NOT_SUPPORTED,
+    };
+    set_gpr(cpu, 0, ret as u64);
+
+    None
+}
+
+// Whether `mpidr` names the cpu making the call.
+fn is_self(cpu: &Cpu, mpidr: u64) -> bool {
+    let id = cpu.reg_by_name("mpidr_el1").unwrap();
+    (cpu.sys(id).u64() ^ mpidr) & MPIDR_AFFINITY == 0
+}
+
+fn gpr(cpu: &Cpu, n: u8) -> u64 {
+    let id = cpu.reg_by_name(format!("x{}", n)).unwrap();
+    cpu.gpr(id).u64()
+}
+
+fn set_gpr(cpu: &mut Cpu, n: u8, val: u64) {
+    let id = cpu.reg_by_name(format!("x{}", n)).unwrap();
+    *cpu.gpr_mut(id).u64_mut() = val;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::Architecture;
+
+    fn call_with(cpu: &mut Cpu, args: &[u64]) -> (Option<Shutdown>, i64) {
+        for (n, &arg) in args.iter().enumerate() {
+            set_gpr(cpu, n as u8, arg);
+        }
+        let shutdown = call(cpu);
+        (shutdown, gpr(cpu, 0) as i64)
+    }
+
+    #[test]
+    fn psci_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+
+        assert_eq!(call_with(&mut cpu, &[PSCI_VERSION as u64]), (None, VERSION));
+        assert_eq!(
+            call_with(&mut cpu, &[PSCI_FEATURES as u64, (CPU_ON | SMC64) as u64]),
+            (None, SUCCESS)
+        );
+        assert_eq!(
+            call_with(&mut cpu, &[PSCI_FEATURES as u64, 0x8400_0001]), // CPU_SUSPEND
+            (None, NOT_SUPPORTED)
+        );
+
+        // The only cpu is already on, and no other cpu exists.
+        let cpu_on = (CPU_ON | SMC64) as u64;
+        assert_eq!(
+            call_with(&mut cpu, &[cpu_on, 0, 0x4000_0000]),
+            (None, ALREADY_ON)
+        );
+        assert_eq!(
+            call_with(&mut cpu, &[cpu_on, 1, 0x4000_0000]),
+            (None, INVALID_PARAMETERS)
+        );
+        assert_eq!(
+            call_with(&mut cpu, &[AFFINITY_INFO as u64, 0, 0]),
+            (None, AFFINITY_ON)
+        );
+
+        // SMC32 functions ignore the upper bits of `x0`.
+        assert_eq!(
+            call_with(&mut cpu, &[0xdead_0000_0000 | SYSTEM_OFF as u64]),
+            (
+                Some(Shutdown::PowerOff),
+                SYSTEM_OFF as i64 | 0xdead_0000_0000
+            )
+        );
+        assert_eq!(
+            call_with(&mut cpu, &[SYSTEM_RESET as u64]).0,
+            Some(Shutdown::Reset)
+        );
+        assert_eq!(call_with(&mut cpu, &[0xc200_0000]), (None, NOT_SUPPORTED));
+    }
+}
@@ -6,22 +6,62 @@ use std::cell::UnsafeCell;
 // is very dangerous.
 #[derive(Debug)]
 pub struct HostMemory {
-    memory: UnsafeCell<Box<[u8]>>,
+    memory: UnsafeCell<Backing>,
+}
+
+#[derive(Debug)]
+enum Backing {
+    Heap(Box<[u8]>),
+    // Anonymous mapping, committed by the host on first touch.
+    Anonymous(*mut u8, usize),
 }
 
 impl HostMemory {
     pub fn new(size: usize) -> Self {
-        assert!(size % 4096 == 0, "size must be a multiple of 4096");
+        assert!(size.is_multiple_of(4096), "size must be a multiple of 4096");
 
         let mut memory = Vec::with_capacity(size);
         memory.resize(size, 0);
         HostMemory {
-            memory: UnsafeCell::new(memory.into_boxed_slice()),
+            memory: UnsafeCell::new(Backing::Heap(memory.into_boxed_slice())),
+        }
+    }
+
+    // Allocate zeroed memory lazily, so that untouched parts of large regions
+    // don't consume host memory.
+    pub fn anonymous(size: usize) -> Self {
+        assert!(size.is_multiple_of(4096), "size must be a multiple of 4096");
+
+        if size == 0 {
+            return Self::new(0);
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        assert!(ptr != libc::MAP_FAILED, "failed to map host memory");
+
+        HostMemory {
+            memory: UnsafeCell::new(Backing::Anonymous(ptr as *mut u8, size)),
         }
     }
 
     pub unsafe fn slice(&self) -> &mut [u8] {
-        unsafe { &mut *self.memory.get() }
+        match unsafe { &mut *self.memory.get() } {
+            Backing::Heap(memory) => memory,
+            Backing::Anonymous(ptr, size) => unsafe { std::slice::from_raw_parts_mut(*ptr, *size) },
+        }
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        unsafe { self.slice().as_mut_ptr() }
     }
 }
 
@@ -32,7 +72,22 @@ impl Clone for HostMemory {
             memory.extend_from_slice(self.slice());
         }
 
-        let memory = UnsafeCell::new(memory.into_boxed_slice());
+        let memory = UnsafeCell::new(Backing::Heap(memory.into_boxed_slice()));
         Self { memory }
     }
 }
+
+// Guest memory is shared by views of a region, and accessed only through unsafe
+// methods which leave synchronization to the guest.
+unsafe impl Send for HostMemory {}
+unsafe impl Sync for HostMemory {}
+
+impl Drop for HostMemory {
+    fn drop(&mut self) {
+        if let Backing::Anonymous(ptr, size) = *self.memory.get_mut() {
+            unsafe {
+                libc::munmap(ptr as *mut libc::c_void, size);
+            }
+        }
+    }
+}
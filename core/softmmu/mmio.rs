@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::error::MmuError;
+
+use super::page::Page;
+
+// Device accessed through memory mapped registers.
+//
+// Accesses are naturally aligned and 1, 2, 4 or 8 bytes long, and values are in
+// little endian.
+pub trait MmioDevice: Send {
+    // Read `size` bytes at `offset` from the start of the device.
+    fn read(&mut self, offset: u64, size: usize) -> u64;
+
+    // Write the lower `size` bytes of `value` at `offset` from the start of the device.
+    fn write(&mut self, offset: u64, size: usize, value: u64);
+}
+
+struct MmioMapping {
+    end: u64,
+    device: Arc<Mutex<dyn MmioDevice>>,
+}
+
+// Devices registered at physical address ranges.
+//
+// Accesses which hit no device read as zero, and writes to them are ignored.
+#[derive(Default)]
+pub struct MmioBus {
+    devices: RwLock<BTreeMap<u64, MmioMapping>>,
+}
+
+impl MmioBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &self,
+        range: Range<u64>,
+        device: Arc<Mutex<dyn MmioDevice>>,
+    ) -> Result<(), MmuError> {
+        let mut devices = self.devices.write().unwrap();
+
+        let overlapped = devices
+            .range(..range.end)
+            .next_back()
+            .is_some_and(|(_, mapping)| mapping.end > range.start);
+        if range.is_empty() || overlapped {
+            return Err(MmuError::DeviceAlreadyMapped(range.start));
+        }
+
+        devices.insert(
+            range.start,
+            MmioMapping {
+                end: range.end,
+                device,
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn read(&self, addr: u64, size: usize) -> u64 {
+        match self.device_at(addr) {
+            Some((start, device)) => device.lock().unwrap().read(addr - start, size),
+            None => 0,
+        }
+    }
+
+    pub fn write(&self, addr: u64, size: usize, value: u64) {
+        if let Some((start, device)) = self.device_at(addr) {
+            device.lock().unwrap().write(addr - start, size, value);
+        }
+    }
+
+    fn device_at(&self, addr: u64) -> Option<(u64, Arc<Mutex<dyn MmioDevice>>)> {
+        let devices = self.devices.read().unwrap();
+        let (&start, mapping) = devices.range(..=addr).next_back()?;
+
+        (addr < mapping.end).then(|| (start, mapping.device.clone()))
+    }
+}
+
+// Page which routes accesses to devices on a bus.
+#[derive(Clone)]
+pub struct MmioPage {
+    bus: Arc<MmioBus>,
+}
+
+impl MmioPage {
+    pub fn new(bus: Arc<MmioBus>) -> Self {
+        Self { bus }
+    }
+}
+
+impl Page for MmioPage {
+    unsafe fn try_write(&self, addr: u64, buf: &[u8]) -> Result<(), MmuError> {
+        for (addr, chunk) in chunks(addr, buf.len()) {
+            let mut bytes = [0u8; 8];
+            bytes[..chunk.len()].copy_from_slice(&buf[chunk.clone()]);
+            self.bus.write(addr, chunk.len(), u64::from_le_bytes(bytes));
+        }
+
+        Ok(())
+    }
+
+    unsafe fn try_read(&self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError> {
+        for (addr, chunk) in chunks(addr, buf.len()) {
+            let value = self.bus.read(addr, chunk.len());
+            buf[chunk.clone()].copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+
+    fn is_readable(&self) -> bool {
+        true
+    }
+
+    fn is_writable(&self) -> bool {
+        true
+    }
+
+    fn is_executable(&self) -> bool {
+        false
+    }
+
+    fn is_mmio(&self) -> bool {
+        true
+    }
+
+    fn into_inner(self: Box<Self>) -> Box<dyn Page> {
+        self
+    }
+}
+
+// Split `len` bytes at `addr` into naturally aligned device accesses.
+fn chunks(addr: u64, len: usize) -> impl Iterator<Item = (u64, Range<usize>)> {
+    let mut cursor = 0;
+    std::iter::from_fn(move || {
+        if cursor == len {
+            return None;
+        }
+
+        let addr = addr + cursor as u64;
+        let size = [8, 4, 2, 1]
+            .into_iter()
+            .find(|&size| size <= len - cursor && addr.is_multiple_of(size as u64))
+            .unwrap();
+
+        let chunk = cursor..cursor + size;
+        cursor += size;
+        Some((addr, chunk))
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::softmmu::Mmu;
+
+    // Device with 8 registers, logging every access.
+    #[derive(Default)]
+    struct Registers {
+        regs: [u64; 8],
+        log: Vec<(bool, u64, usize)>,
+    }
+
+    impl MmioDevice for Registers {
+        fn read(&mut self, offset: u64, size: usize) -> u64 {
+            self.log.push((false, offset, size));
+            self.regs[offset as usize / 8] >> (offset % 8 * 8)
+        }
+
+        fn write(&mut self, offset: u64, size: usize, value: u64) {
+            self.log.push((true, offset, size));
+            let shift = offset % 8 * 8;
+            let mask = (u64::MAX >> (64 - size * 8)) << shift;
+            let reg = &mut self.regs[offset as usize / 8];
+            *reg = (*reg & !mask) | ((value << shift) & mask);
+        }
+    }
+
+    #[test]
+    fn mmio_bus_test() {
+        let bus = MmioBus::new();
+        let device = Arc::new(Mutex::new(Registers::default()));
+        bus.register(0x1000..0x1040, device.clone()).unwrap();
+
+        bus.write(0x1008, 4, 0xdeadbeef);
+        assert_eq!(bus.read(0x1008, 8), 0xdeadbeef);
+
+        // Unassigned addresses read as zero and ignore writes.
+        bus.write(0x1040, 4, 0xdeadbeef);
+        assert_eq!(bus.read(0x1040, 4), 0);
+
+        let overlapped = Arc::new(Mutex::new(Registers::default()));
+        assert!(bus.register(0x0ff0..0x1010, overlapped.clone()).is_err());
+        assert!(bus.register(0x1038..0x1048, overlapped.clone()).is_err());
+        bus.register(0x1040..0x1080, overlapped).unwrap();
+    }
+
+    #[test]
+    fn mmio_page_test() {
+        let bus = Arc::new(MmioBus::new());
+        let device = Arc::new(Mutex::new(Registers::default()));
+        bus.register(0x9000_0000..0x9000_0040, device.clone())
+            .unwrap();
+
+        let mmu = Mmu::new();
+        mmu.mmap_mmio(0x9000_0000, 0x1000, bus).unwrap();
+
+        unsafe {
+            mmu.write(0x9000_0010, &0x1122_3344u32.to_le_bytes())
+                .unwrap();
+            let mut buf = [0u8; 2];
+            mmu.read(0x9000_0012, &mut buf).unwrap();
+            assert_eq!(buf, 0x1122u16.to_le_bytes());
+
+            // Unaligned accesses are split into aligned device accesses.
+            let mut buf = [0u8; 16];
+            mmu.read(0x9000_000c, &mut buf).unwrap();
+        }
+
+        assert_eq!(
+            device.lock().unwrap().log,
+            vec![
+                (true, 0x10, 4),
+                (false, 0x12, 2),
+                (false, 0xc, 4),
+                (false, 0x10, 8),
+                (false, 0x18, 4),
+            ]
+        );
+
+        // Device pages are never accessed directly or executed.
+        assert!(!mmu.is_executable(0x9000_0000..0x9000_0004));
+        assert_eq!(mmu.host_page(0x9000_0000, false), None);
+    }
+}
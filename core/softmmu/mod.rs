@@ -1,5 +1,9 @@
 mod host_memory;
+mod mmio;
 mod page;
+mod region;
+mod tlb;
+mod translate;
 
 use crate::debug::WatchKind;
 use crate::debug::WatchPoint;
@@ -7,15 +11,21 @@ use crate::debug::WatchPoint;
 use crate::error::DebugError;
 use crate::error::MmuError;
 
+pub use mmio::{MmioBus, MmioDevice, MmioPage};
 pub use page::BasicPage;
 pub use page::Page;
 pub use page::PageWithCallback;
+pub use page::RegionPage;
+pub use region::RamRegion;
+pub use tlb::Tlb;
+pub use translate::{Access, Stage1, Translation};
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
 
-const PAGE_SIZE: usize = 0xFFF + 1;
+pub(crate) const PAGE_SIZE: usize = 0xFFF + 1;
 const PAGE_ADDRESS_MASK: usize = usize::MAX - (PAGE_SIZE - 1);
 
 #[derive(Debug, PartialEq)]
@@ -29,16 +39,27 @@ pub struct Mmu {
     inner: Arc<RwLock<MmuData>>,
     watchpoints: Arc<RwLock<Vec<WatchPoint>>>,
     events: Arc<RwLock<Vec<MmuEvent>>>,
+
+    // Pages which have translated code derived from them, and writes into those pages
+    // which are not yet reflected to the translation cache.
+    code_pages: Arc<RwLock<HashSet<u64>>>,
+    code_events: Arc<RwLock<Vec<MmuEvent>>>,
+
+    // Incremented when translations cached in TLBs become stale.
+    generation: Arc<AtomicU64>,
 }
 
 pub struct MmuData {
     mapped_pages: HashMap<u64, Box<dyn Page>>,
+    // Regions by start address, whose pages are all mapped as views into them.
+    regions: BTreeMap<u64, RamRegion>,
 }
 
 impl Mmu {
     pub fn new() -> Self {
         let inner = Arc::new(RwLock::new(MmuData {
             mapped_pages: HashMap::new(),
+            regions: BTreeMap::new(),
         }));
 
         let watchpoints = Arc::new(RwLock::new(Vec::new()));
@@ -49,12 +70,19 @@ impl Mmu {
             inner,
             watchpoints,
             events,
+            code_pages: Arc::new(RwLock::new(HashSet::new())),
+            code_events: Arc::new(RwLock::new(Vec::new())),
+            generation: Arc::new(AtomicU64::new(0)),
         }
     }
 
     pub unsafe fn write(&self, addr: u64, buf: &[u8]) -> Result<(), MmuError> {
         let inner = self.inner.read().unwrap();
-        inner.write(addr, buf)
+        inner.write(addr, buf)?;
+
+        self.check_code_write(addr..addr + buf.len() as u64);
+
+        Ok(())
     }
 
     pub unsafe fn read(&self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError> {
@@ -77,6 +105,46 @@ impl Mmu {
         inner.is_executable(range)
     }
 
+    // Whether every page of `range` is mapped to memory rather than to devices.
+    pub fn is_memory(&self, range: Range<u64>) -> bool {
+        let inner = self.inner.read().unwrap();
+        inner.is_memory(range)
+    }
+
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    // Flush translations cached in TLBs of every cpu. Translated code is kept, and the
+    // board checks it against the new translations before running it again.
+    pub fn flush_tlb(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    // Host memory of the page at `addr`, if it can be accessed directly.
+    // Writable pages are not returned for writes if they hold translated code, as
+    // writes into them must be tracked.
+    pub fn host_page(&self, addr: u64, write: bool) -> Option<*mut u8> {
+        let inner = self.inner.read().unwrap();
+        let page = inner.get_page(addr).ok()?;
+        let host = page.host_ptr()?;
+
+        let permitted = if write {
+            page.is_readable()
+                && page.is_writable()
+                && !self
+                    .code_pages
+                    .read()
+                    .unwrap()
+                    .contains(&page_initial_address(addr))
+        } else {
+            page.is_readable()
+        };
+
+        permitted.then_some(host)
+    }
+
     // Iterating MMU is unsafe
     pub unsafe fn iter(&self, start_addr: u64) -> MmuIter {
         MmuIter {
@@ -105,6 +173,7 @@ impl Mmu {
             );
 
             inner.mmap(watchpoint.addr, Box::new(page))?;
+            self.flush_tlb();
 
             watchpoints.push(watchpoint);
 
@@ -120,9 +189,8 @@ impl Mmu {
             let mut inner = self.inner.write().unwrap();
             let page = inner.munmap(watchpoint.addr)?;
 
-            let page = BasicPage::from_page(page);
-
-            inner.mmap(watchpoint.addr, Box::new(page))?;
+            inner.mmap(watchpoint.addr, page.into_inner())?;
+            self.flush_tlb();
 
             watchpoints.push(watchpoint);
 
@@ -151,6 +219,68 @@ impl Mmu {
         evs.clear();
     }
 
+    // Mark pages in `range` as the source of translated code.
+    // Only executable pages can be the source of translation.
+    pub fn mark_translated(&self, range: Range<u64>) {
+        let inner = self.inner.read().unwrap();
+        let mut code_pages = self.code_pages.write().unwrap();
+
+        let mut inserted = false;
+        for page in pages_of(range) {
+            if inner.is_executable(page..page + 1) {
+                inserted |= code_pages.insert(page);
+            }
+        }
+
+        // Writes into the pages are no longer allowed to bypass `Mmu::write`.
+        if inserted {
+            self.flush_tlb();
+        }
+    }
+
+    // Request invalidation of translations derived from `range`.
+    // Invalidation is done in page granularity.
+    pub fn invalidate_code(&self, range: Range<u64>) {
+        let mut code_pages = self.code_pages.write().unwrap();
+        let mut code_events = self.code_events.write().unwrap();
+
+        for page in pages_of(range) {
+            if code_pages.remove(&page) {
+                code_events.push(MmuEvent::Write(page..page + PAGE_SIZE as u64));
+            }
+        }
+    }
+
+    // Request invalidation of every translation.
+    pub fn invalidate_all_code(&self) {
+        let mut code_pages = self.code_pages.write().unwrap();
+        let mut code_events = self.code_events.write().unwrap();
+
+        code_pages.clear();
+        code_events.push(MmuEvent::Write(0..u64::MAX));
+    }
+
+    pub fn has_code_events(&self) -> bool {
+        !self.code_events.read().unwrap().is_empty()
+    }
+
+    // Take writes into translated pages since last call.
+    pub fn take_code_events(&self) -> Vec<MmuEvent> {
+        let mut code_events = self.code_events.write().unwrap();
+        std::mem::take(&mut *code_events)
+    }
+
+    fn check_code_write(&self, range: Range<u64>) {
+        let is_code = {
+            let code_pages = self.code_pages.read().unwrap();
+            !code_pages.is_empty() && pages_of(range.clone()).any(|p| code_pages.contains(&p))
+        };
+
+        if is_code {
+            self.invalidate_code(range);
+        }
+    }
+
     pub fn mmap<P>(&self, addr: u64, size: u64, page: Box<P>) -> Result<(), MmuError>
     where
         P: Page + Clone + 'static,
@@ -166,6 +296,53 @@ impl Mmu {
         for addr in range {
             inner.mmap(addr, page.clone())?;
         }
+        self.flush_tlb();
+
+        Ok(())
+    }
+
+    // Map `region` at page aligned `addr`.
+    pub fn mmap_region(&self, addr: u64, region: RamRegion) -> Result<(), MmuError> {
+        let mut inner = self.inner.write().unwrap();
+        let pages = inner.unmapped_pages(addr, region.size())?;
+
+        for page in pages {
+            inner.mmap(page, Box::new(region.page(page - addr)))?;
+        }
+        inner.regions.insert(addr, region);
+        self.flush_tlb();
+
+        Ok(())
+    }
+
+    // Unmap pages of `size` bytes at page aligned `addr`, skipping pages not mapped.
+    pub fn unmap(&self, addr: u64, size: u64) -> Result<(), MmuError> {
+        let range = addr..addr.checked_add(size).ok_or(MmuError::PageNotExist(addr))?;
+        if offset(addr) != 0 {
+            return Err(MmuError::UnalignedRegion(addr));
+        }
+
+        {
+            let mut inner = self.inner.write().unwrap();
+            for page in pages_of(range.clone()) {
+                let _ = inner.munmap(page);
+            }
+        }
+        self.invalidate_code(range);
+        self.flush_tlb();
+
+        Ok(())
+    }
+
+    // Map pages routing accesses to devices on `bus`, at page aligned `addr`.
+    pub fn mmap_mmio(&self, addr: u64, size: u64, bus: Arc<MmioBus>) -> Result<(), MmuError> {
+        let mut inner = self.inner.write().unwrap();
+        let pages = inner.unmapped_pages(addr, size)?;
+
+        for page in pages {
+            inner.mmap(page, Box::new(MmioPage::new(bus.clone())))?;
+        }
+        self.flush_tlb();
 
         Ok(())
     }
@@ -173,24 +350,18 @@ impl Mmu {
 
 impl MmuData {
     unsafe fn write(&self, addr: u64, buf: &[u8]) -> Result<(), MmuError> {
-        let bias = if (addr + buf.len() as u64) % PAGE_SIZE as u64 == 0 {
-            0
-        } else {
-            PAGE_SIZE as u64
-        };
-        let range = (addr..addr + buf.len() as u64 + bias)
-            .step_by(PAGE_SIZE)
-            .map(|v| {
-                if v != addr {
-                    page_initial_address(v)
-                } else {
-                    v
-                }
-            });
+        if let Some((start, region)) = self.region_of(addr, buf.len()) {
+            if region.is_writable() {
+                let host = region.host_ptr(addr - start);
+                std::ptr::copy_nonoverlapping(buf.as_ptr(), host, buf.len());
+                return Ok(());
+            }
+        }
 
         let mut cursor = 0;
 
-        for addr in range {
+        while cursor < buf.len() {
+            let addr = addr + cursor as u64;
             let page = self.get_page(addr)?;
             let write_len = usize::min(PAGE_SIZE - offset(addr), buf.len() - cursor);
 
@@ -203,24 +374,18 @@ impl MmuData {
     }
 
     unsafe fn read(&self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError> {
-        let bias = if (addr + buf.len() as u64) % PAGE_SIZE as u64 == 0 {
-            0
-        } else {
-            PAGE_SIZE as u64
-        };
-        let range = (addr..addr + buf.len() as u64 + bias)
-            .step_by(PAGE_SIZE)
-            .map(|v| {
-                if v != addr {
-                    page_initial_address(v)
-                } else {
-                    v
-                }
-            });
+        if let Some((start, region)) = self.region_of(addr, buf.len()) {
+            if region.is_readable() {
+                let host = region.host_ptr(addr - start);
+                std::ptr::copy_nonoverlapping(host, buf.as_mut_ptr(), buf.len());
+                return Ok(());
+            }
+        }
 
         let mut cursor = 0;
 
-        for addr in range {
+        while cursor < buf.len() {
+            let addr = addr + cursor as u64;
             let page = self.get_page(addr)?;
             let read_len = usize::min(PAGE_SIZE - offset(addr), buf.len() - cursor);
 
@@ -262,6 +427,17 @@ impl MmuData {
         })
     }
 
+    // Every page overlapping `range` is checked, even if it isn't aligned.
+    fn is_memory(&self, range: Range<u64>) -> bool {
+        if range.is_empty() {
+            return true;
+        }
+
+        (page_initial_address(range.start)..range.end)
+            .step_by(PAGE_SIZE)
+            .all(|addr| matches!(self.get_page(addr), Ok(page) if !page.is_mmio()))
+    }
+
     fn mmap(&mut self, addr: u64, page: Box<dyn Page>) -> Result<(), MmuError> {
         let init_addr = page_initial_address(addr);
         if self.mapped_pages.contains_key(&init_addr) {
@@ -278,9 +454,36 @@ impl MmuData {
             .remove(&page_initial_address(addr))
             .ok_or(MmuError::PageNotMapped(addr))?;
 
+        // Pages of the region are no longer uniform, so accesses go through each page.
+        if let Some((start, _)) = self.region_of(addr, 1) {
+            self.regions.remove(&start);
+        }
+
         Ok(page)
     }
 
+    // Pages of `size` bytes at `addr`, checking that none of them is mapped.
+    fn unmapped_pages(&self, addr: u64, size: u64) -> Result<impl Iterator<Item = u64>, MmuError> {
+        if offset(addr) != 0 {
+            return Err(MmuError::UnalignedRegion(addr));
+        }
+
+        let end = addr.checked_add(size).ok_or(MmuError::PageNotExist(addr))?;
+        if let Some(page) = pages_of(addr..end).find(|p| self.mapped_pages.contains_key(p)) {
+            return Err(MmuError::PageAlreadyMapped(page));
+        }
+
+        Ok(pages_of(addr..end))
+    }
+
+    // Region containing `len` bytes at `addr`.
+    fn region_of(&self, addr: u64, len: usize) -> Option<(u64, &RamRegion)> {
+        let (&start, region) = self.regions.range(..=addr).next_back()?;
+        let end = (addr - start).checked_add(len as u64)?;
+
+        (end <= region.size()).then_some((start, region))
+    }
+
     fn get_page(&self, addr: u64) -> Result<&Box<dyn Page>, MmuError> {
         self.mapped_pages
             .get(&page_initial_address(addr))
@@ -317,6 +520,17 @@ fn offset(addr: u64) -> usize {
     addr as usize & (PAGE_SIZE - 1)
 }
 
+fn pages_of(range: Range<u64>) -> impl Iterator<Item = u64> {
+    let first = page_initial_address(range.start);
+    let count = if range.is_empty() {
+        0
+    } else {
+        (page_initial_address(range.end - 1) - first) / PAGE_SIZE as u64 + 1
+    };
+
+    (0..count).map(move |i| first + i * PAGE_SIZE as u64)
+}
+
 mod test {
     use super::*;
 
@@ -393,4 +607,118 @@ mod test {
 
         assert_eq!(test_buf, result);
     }
+
+    #[test]
+    fn mmu_last_page_test() {
+        let mmu = Mmu::new();
+        mmu.mmap(
+            0x1000,
+            PAGE_SIZE as u64,
+            Box::new(BasicPage::new(true, true, true)),
+        )
+        .unwrap();
+
+        // Accesses at the end of the last mapped page don't touch the next page.
+        unsafe {
+            mmu.write(0x1ff8, &[1; 8]).unwrap();
+            let mut buf = [0; 8];
+            mmu.read(0x1ff8, &mut buf).unwrap();
+            assert_eq!(buf, [1; 8]);
+
+            assert!(mmu.write(0x1ffc, &[1; 8]).is_err());
+        }
+    }
+
+    #[test]
+    fn mmu_region_test() {
+        let mmu = Mmu::new();
+        let region = RamRegion::lazy((PAGE_SIZE * 4) as u64, true, true, true);
+        mmu.mmap_region(0x10000, region.clone()).unwrap();
+
+        // Accesses across pages of the region are done on the host memory at once.
+        let test_buf: Vec<_> = (0..(PAGE_SIZE * 2)).map(|i| i as u8).collect();
+        unsafe {
+            mmu.write(0x10800, &test_buf).unwrap();
+            let host = std::slice::from_raw_parts(region.host_ptr(0x800), test_buf.len());
+            assert_eq!(host, test_buf);
+        }
+
+        // Pages of the region are views into the same memory.
+        assert_eq!(mmu.host_page(0x11000, false), Some(region.host_ptr(0x1000)));
+
+        // A page replaced by another page is still accessed through the page.
+        let page = mmu.inner.write().unwrap().munmap(0x11000).unwrap();
+        let events = Arc::new(RwLock::new(Vec::new()));
+        let e = events.clone();
+        let page = PageWithCallback::from_page(
+            page,
+            Box::new(move |event| e.write().unwrap().push(event)),
+        );
+        mmu.inner
+            .write()
+            .unwrap()
+            .mmap(0x11000, Box::new(page))
+            .unwrap();
+
+        let mut result: Vec<_> = (0..(PAGE_SIZE * 2)).map(|_| 0u8).collect();
+        unsafe { mmu.read(0x10800, &mut result).unwrap() }
+        assert_eq!(test_buf, result);
+        assert_eq!(
+            *events.read().unwrap(),
+            vec![MmuEvent::Read(0x11000..0x12000)]
+        );
+
+        assert!(matches!(
+            mmu.mmap_region(0x13000, RamRegion::new(PAGE_SIZE as u64, true, true, true)),
+            Err(MmuError::PageAlreadyMapped(0x13000))
+        ));
+        assert!(matches!(
+            mmu.mmap_region(0x14800, RamRegion::new(PAGE_SIZE as u64, true, true, true)),
+            Err(MmuError::UnalignedRegion(0x14800))
+        ));
+
+        // Pages left after unmapping part of the region keep their memory.
+        mmu.unmap(0x12000, (PAGE_SIZE * 4) as u64).unwrap();
+        let mut byte = [0u8; 1];
+        unsafe {
+            assert!(mmu.read(0x12000, &mut byte).is_err());
+            mmu.read(0x10800, &mut byte).unwrap();
+        }
+        assert_eq!(byte, [0]);
+        mmu.mmap_region(0x12000, RamRegion::new(PAGE_SIZE as u64, true, true, true))
+            .unwrap();
+    }
+
+    #[test]
+    fn mmu_code_write_test() {
+        let mmu = Mmu::new();
+        mmu.mmap(
+            0x1000,
+            (PAGE_SIZE * 3) as u64,
+            Box::new(BasicPage::new(true, true, true)),
+        )
+        .unwrap();
+
+        // Writing into pages without translation is not reported.
+        unsafe { mmu.write(0x1000, &[0; 4]).unwrap() }
+        assert!(!mmu.has_code_events());
+
+        mmu.mark_translated(0x1ffc..0x2004);
+
+        unsafe { mmu.write(0x2010, &[0; 4]).unwrap() }
+        assert_eq!(
+            mmu.take_code_events(),
+            vec![MmuEvent::Write(0x2000..0x3000)]
+        );
+
+        // Page is no longer treated as code until it is translated again.
+        unsafe { mmu.write(0x2010, &[0; 4]).unwrap() }
+        assert!(!mmu.has_code_events());
+
+        mmu.invalidate_code(0x1000..0x1004);
+        assert_eq!(
+            mmu.take_code_events(),
+            vec![MmuEvent::Write(0x1000..0x2000)]
+        );
+    }
 }
@@ -1,5 +1,7 @@
 use crate::error::MmuError;
 
+use std::sync::Arc;
+
 use super::host_memory::HostMemory;
 use super::MmuEvent;
 use super::PAGE_SIZE;
@@ -8,7 +10,8 @@ fn offset(addr: u64) -> usize {
     addr as usize & (PAGE_SIZE - 1)
 }
 
-pub trait Page {
+// Pages are shared by cpus and devices, which access guest memory from any thread.
+pub trait Page: Send + Sync {
     unsafe fn try_write(&self, addr: u64, buf: &[u8]) -> Result<(), MmuError>;
     unsafe fn try_read(&self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError>;
 
@@ -16,7 +19,19 @@ pub trait Page {
     fn is_writable(&self) -> bool;
     fn is_executable(&self) -> bool;
 
-    unsafe fn into_inner(self: Box<Self>) -> HostMemory;
+    // Host memory of the page if it can be accessed without calling `try_read` and
+    // `try_write`.
+    fn host_ptr(&self) -> Option<*mut u8> {
+        None
+    }
+
+    // Whether accesses to the page are handled by devices.
+    fn is_mmio(&self) -> bool {
+        false
+    }
+
+    // Page without callbacks attached to it.
+    fn into_inner(self: Box<Self>) -> Box<dyn Page>;
 }
 
 #[derive(Clone)]
@@ -36,19 +51,6 @@ impl BasicPage {
             executable,
         }
     }
-
-    pub fn from_page(page: Box<dyn Page>) -> Self {
-        let readable = page.is_readable();
-        let writable = page.is_writable();
-        let executable = page.is_executable();
-        let memory = unsafe { page.into_inner() };
-        Self {
-            memory,
-            readable,
-            writable,
-            executable,
-        }
-    }
 }
 
 impl Page for BasicPage {
@@ -94,66 +96,75 @@ impl Page for BasicPage {
         self.executable
     }
 
-    unsafe fn into_inner(self: Box<Self>) -> HostMemory {
-        self.memory
+    fn host_ptr(&self) -> Option<*mut u8> {
+        Some(self.memory.as_ptr())
+    }
+
+    fn into_inner(self: Box<Self>) -> Box<dyn Page> {
+        self
     }
 }
 
-pub struct PageWithCallback {
-    memory: HostMemory,
-    callback: Box<dyn Fn(MmuEvent) -> ()>,
+// View of a page in a `RamRegion`.
+pub struct RegionPage {
+    memory: Arc<HostMemory>,
+    offset: usize,
     readable: bool,
     writable: bool,
     executable: bool,
 }
 
-impl PageWithCallback {
-    pub fn from_page(page: Box<dyn Page>, callback: Box<dyn Fn(MmuEvent) -> ()>) -> Self {
-        let readable = page.is_readable();
-        let writable = page.is_writable();
-        let executable = page.is_executable();
-        let memory = unsafe { page.into_inner() };
+impl RegionPage {
+    pub(super) fn new(
+        memory: Arc<HostMemory>,
+        offset: usize,
+        readable: bool,
+        writable: bool,
+        executable: bool,
+    ) -> Self {
+        debug_assert!(offset.is_multiple_of(PAGE_SIZE), "page must be aligned");
+
         Self {
             memory,
-            callback,
+            offset,
             readable,
             writable,
             executable,
         }
     }
+
+    fn ptr(&self) -> *mut u8 {
+        unsafe { self.memory.as_ptr().add(self.offset) }
+    }
 }
 
-impl Page for PageWithCallback {
+impl Page for RegionPage {
     unsafe fn try_write(&self, addr: u64, buf: &[u8]) -> Result<(), MmuError> {
-        if self.writable == false {
+        if !self.writable {
             return Err(MmuError::AccessViolation(addr));
         }
 
-        debug_assert!(buf.len() <= PAGE_SIZE, "buffer is too large");
-
         let start = offset(addr);
         let end = start + buf.len();
 
-        self.memory.slice()[start..end].copy_from_slice(buf);
+        debug_assert!(end <= PAGE_SIZE, "buffer is too large");
 
-        (self.callback)(MmuEvent::Write(addr..addr + buf.len() as u64));
+        std::ptr::copy_nonoverlapping(buf.as_ptr(), self.ptr().add(start), buf.len());
 
         Ok(())
     }
 
     unsafe fn try_read(&self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError> {
-        if self.readable == false {
+        if !self.readable {
             return Err(MmuError::AccessViolation(addr));
         }
 
-        debug_assert!(buf.len() <= PAGE_SIZE, "buffer is too large");
-
         let start = offset(addr);
         let end = start + buf.len();
 
-        buf.copy_from_slice(&self.memory.slice()[start..end]);
+        debug_assert!(end <= PAGE_SIZE, "buffer is too large");
 
-        (self.callback)(MmuEvent::Read(addr..addr + buf.len() as u64));
+        std::ptr::copy_nonoverlapping(self.ptr().add(start), buf.as_mut_ptr(), buf.len());
 
         Ok(())
     }
@@ -170,8 +181,71 @@ impl Page for PageWithCallback {
         self.executable
     }
 
-    unsafe fn into_inner(self: Box<Self>) -> HostMemory {
-        self.memory
+    fn host_ptr(&self) -> Option<*mut u8> {
+        Some(self.ptr())
+    }
+
+    fn into_inner(self: Box<Self>) -> Box<dyn Page> {
+        self
+    }
+}
+
+pub struct PageWithCallback {
+    page: Box<dyn Page>,
+    callback: Box<dyn Fn(MmuEvent) -> () + Send + Sync>,
+}
+
+impl PageWithCallback {
+    pub fn from_page(
+        page: Box<dyn Page>,
+        callback: Box<dyn Fn(MmuEvent) -> () + Send + Sync>,
+    ) -> Self {
+        Self {
+            page: page.into_inner(),
+            callback,
+        }
+    }
+}
+
+impl Page for PageWithCallback {
+    unsafe fn try_write(&self, addr: u64, buf: &[u8]) -> Result<(), MmuError> {
+        debug_assert!(buf.len() <= PAGE_SIZE, "buffer is too large");
+
+        self.page.try_write(addr, buf)?;
+
+        (self.callback)(MmuEvent::Write(addr..addr + buf.len() as u64));
+
+        Ok(())
+    }
+
+    unsafe fn try_read(&self, addr: u64, buf: &mut [u8]) -> Result<(), MmuError> {
+        debug_assert!(buf.len() <= PAGE_SIZE, "buffer is too large");
+
+        self.page.try_read(addr, buf)?;
+
+        (self.callback)(MmuEvent::Read(addr..addr + buf.len() as u64));
+
+        Ok(())
+    }
+
+    fn is_readable(&self) -> bool {
+        self.page.is_readable()
+    }
+
+    fn is_writable(&self) -> bool {
+        self.page.is_writable()
+    }
+
+    fn is_executable(&self) -> bool {
+        self.page.is_executable()
+    }
+
+    fn is_mmio(&self) -> bool {
+        self.page.is_mmio()
+    }
+
+    fn into_inner(self: Box<Self>) -> Box<dyn Page> {
+        self.page
     }
 }
 
@@ -232,7 +306,9 @@ mod test {
             page.try_write(0xbee, content).unwrap();
         }
 
-        let result = unsafe { &page.memory.slice()[0xbee..0xbee + content.len()] };
+        let result = unsafe {
+            std::slice::from_raw_parts(page.page.host_ptr().unwrap().add(0xbee), content.len())
+        };
 
         assert_eq!(content, result);
 
@@ -260,7 +336,8 @@ mod test {
         let content = "Hello World".as_bytes();
 
         unsafe {
-            page.memory.slice()[0xbee..0xbee + content.len()].copy_from_slice(content);
+            let host = page.page.host_ptr().unwrap().add(0xbee);
+            std::ptr::copy_nonoverlapping(content.as_ptr(), host, content.len());
         }
 
         let mut result: Vec<_> = (0..content.len()).map(|_| 0u8).collect();
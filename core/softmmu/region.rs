@@ -0,0 +1,85 @@
+use std::sync::Arc;
+
+use super::host_memory::HostMemory;
+use super::page::RegionPage;
+use super::PAGE_SIZE;
+
+// Guest RAM backed by a single host allocation.
+//
+// Pages mapped from a region are views into the allocation, so accesses which
+// cross pages of the region are done with a single copy.
+#[derive(Clone)]
+pub struct RamRegion {
+    memory: Arc<HostMemory>,
+    size: u64,
+    readable: bool,
+    writable: bool,
+    executable: bool,
+}
+
+impl RamRegion {
+    pub fn new(size: u64, readable: bool, writable: bool, executable: bool) -> Self {
+        let size = round_up(size);
+        Self {
+            memory: Arc::new(HostMemory::new(size as usize)),
+            size,
+            readable,
+            writable,
+            executable,
+        }
+    }
+
+    // Region whose memory is committed by the host on first access, for large
+    // regions which are sparsely used.
+    pub fn lazy(size: u64, readable: bool, writable: bool, executable: bool) -> Self {
+        let size = round_up(size);
+        Self {
+            memory: Arc::new(HostMemory::anonymous(size as usize)),
+            size,
+            readable,
+            writable,
+            executable,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn is_readable(&self) -> bool {
+        self.readable
+    }
+
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    // Initialize contents at `offset` before the region is mapped, regardless of its
+    // permissions.
+    pub fn fill(&self, offset: u64, buf: &[u8]) {
+        assert!(
+            offset + buf.len() as u64 <= self.size,
+            "buffer is out of the region"
+        );
+        unsafe { std::ptr::copy_nonoverlapping(buf.as_ptr(), self.host_ptr(offset), buf.len()) }
+    }
+
+    pub(super) fn host_ptr(&self, offset: u64) -> *mut u8 {
+        debug_assert!(offset <= self.size, "offset is out of the region");
+        unsafe { self.memory.as_ptr().add(offset as usize) }
+    }
+
+    pub(super) fn page(&self, offset: u64) -> RegionPage {
+        RegionPage::new(
+            self.memory.clone(),
+            offset as usize,
+            self.readable,
+            self.writable,
+            self.executable,
+        )
+    }
+}
+
+fn round_up(size: u64) -> u64 {
+    size.next_multiple_of(PAGE_SIZE as u64)
+}
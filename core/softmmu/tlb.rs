@@ -0,0 +1,125 @@
+use super::{page_initial_address, PAGE_SIZE};
+
+const TLB_SIZE: usize = 256;
+
+// Page addresses are aligned, so this never matches a page.
+const INVALID_PAGE: u64 = u64::MAX;
+
+#[derive(Clone, Copy)]
+struct TlbEntry {
+    page: u64,    // virtual address of the page
+    context: u64, // access mode of the cpu the entry is filled in
+    host: *mut u8,
+    writable: bool,
+}
+
+const INVALID_ENTRY: TlbEntry = TlbEntry {
+    page: INVALID_PAGE,
+    context: 0,
+    host: std::ptr::null_mut(),
+    writable: false,
+};
+
+// Per-cpu cache of translations from virtual pages into host memory of RAM pages.
+//
+// Every entry is readable. Entries are flushed when the generation of the `Mmu`
+// changes, see `Mmu::generation`.
+pub struct Tlb {
+    entries: Box<[TlbEntry]>,
+    generation: u64,
+}
+
+impl Tlb {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![INVALID_ENTRY; TLB_SIZE].into_boxed_slice(),
+            generation: 0,
+        }
+    }
+
+    // Host address of `len` bytes at `addr`, if they are in a cached page.
+    #[inline]
+    pub fn lookup(
+        &mut self,
+        generation: u64,
+        context: u64,
+        addr: u64,
+        len: usize,
+        write: bool,
+    ) -> Option<*mut u8> {
+        if generation != self.generation {
+            self.flush(generation);
+            return None;
+        }
+
+        let page = page_initial_address(addr);
+        let offset = (addr - page) as usize;
+        let entry = &self.entries[index(page)];
+        if entry.page != page
+            || entry.context != context
+            || (write && !entry.writable)
+            || offset + len > PAGE_SIZE
+        {
+            return None;
+        }
+
+        Some(unsafe { entry.host.add(offset) })
+    }
+
+    // Cache the translation of the page containing `addr` into `host`.
+    pub fn fill(&mut self, context: u64, addr: u64, host: *mut u8, writable: bool) {
+        let page = page_initial_address(addr);
+        self.entries[index(page)] = TlbEntry {
+            page,
+            context,
+            host,
+            writable,
+        };
+    }
+
+    pub fn flush(&mut self, generation: u64) {
+        self.entries.fill(INVALID_ENTRY);
+        self.generation = generation;
+    }
+}
+
+impl Default for Tlb {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn index(page: u64) -> usize {
+    (page as usize / PAGE_SIZE) % TLB_SIZE
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tlb_test() {
+        let mut memory = vec![0u8; PAGE_SIZE];
+        let host = memory.as_mut_ptr();
+        let mut tlb = Tlb::new();
+
+        tlb.fill(1, 0x5000, host, false);
+        assert_eq!(
+            tlb.lookup(0, 1, 0x5010, 8, false),
+            Some(host.wrapping_add(0x10))
+        );
+
+        // Different context, read-only page, and accesses crossing the page miss.
+        assert_eq!(tlb.lookup(0, 0, 0x5010, 8, false), None);
+        assert_eq!(tlb.lookup(0, 1, 0x5010, 8, true), None);
+        assert_eq!(tlb.lookup(0, 1, 0x5ffc, 8, false), None);
+
+        // Entries are flushed when the generation changes.
+        assert_eq!(tlb.lookup(1, 1, 0x5010, 8, false), None);
+        tlb.fill(1, 0x5000, host, true);
+        assert_eq!(
+            tlb.lookup(1, 1, 0x5010, 8, true),
+            Some(host.wrapping_add(0x10))
+        );
+    }
+}
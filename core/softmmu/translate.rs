@@ -0,0 +1,432 @@
+use crate::compiler::aarch64_prelude::Pstate;
+use crate::error::MmuError;
+use crate::register::RegId;
+use crate::Cpu;
+
+use super::Mmu;
+
+// Kind of memory access being translated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+// Physical address of a translated address, and the size of the page or block
+// containing it. A size of 0 means the whole address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Translation {
+    pub pa: u64,
+    pub size: u64,
+}
+
+impl Translation {
+    // Translation of an address when stage 1 translation is disabled.
+    pub fn flat(addr: u64) -> Self {
+        Self { pa: addr, size: 0 }
+    }
+
+    // Number of bytes from `pa` to the end of its page.
+    pub fn remaining(&self) -> u64 {
+        match self.size {
+            0 => u64::MAX,
+            size => size - (self.pa & (size - 1)),
+        }
+    }
+}
+
+const SCTLR_M: u64 = 1 << 0;
+const SCTLR_WXN: u64 = 1 << 19;
+
+// Output address bits of descriptors.
+const DESC_ADDRESS_MASK: u64 = 0x0000_ffff_ffff_f000;
+const TTBR_ADDRESS_MASK: u64 = 0x0000_ffff_ffff_fffe;
+
+const DESC_VALID: u64 = 1 << 0;
+const DESC_TABLE: u64 = 1 << 1; // Table at level 0 to 2, page at level 3
+const DESC_AF: u64 = 1 << 10;
+const DESC_PXN: u64 = 1 << 53;
+const DESC_UXN: u64 = 1 << 54;
+
+// Access permissions of `AP[2:1]` and `APTable`.
+const AP_EL0: u64 = 0b01; // Accessible from EL0
+const AP_RO: u64 = 0b10; // Read-only
+
+// Stage 1 translation of the EL1&0 translation regime.
+//
+// Translation at EL2 and EL3 is not implemented, so their addresses are physical.
+#[derive(Clone, Copy, Debug)]
+pub struct Stage1 {
+    sctlr: RegId,
+    tcr: RegId,
+    ttbr0: RegId,
+    ttbr1: RegId,
+}
+
+// Translation table properties of the address range selected by `TTBR0` or `TTBR1`.
+struct Regime {
+    ttbr: u64,
+    input_size: u64,
+    granule: u64, // log2 of the granule size
+    disabled: bool,
+    tbi: bool,
+}
+
+impl Stage1 {
+    // Returns `None` if `cpu` doesn't have registers controlling translation.
+    pub fn new(cpu: &Cpu) -> Option<Self> {
+        Some(Self {
+            sctlr: cpu.reg_by_name("sctlr_el1")?,
+            tcr: cpu.reg_by_name("tcr_el1")?,
+            ttbr0: cpu.reg_by_name("ttbr0_el1")?,
+            ttbr1: cpu.reg_by_name("ttbr1_el1")?,
+        })
+    }
+
+    pub fn is_enabled(&self, cpu: &Cpu) -> bool {
+        cpu.el() <= 1 && cpu.sys(self.sctlr).u64() & SCTLR_M != 0
+    }
+
+    // Walk translation tables in `mmu` to translate `va`.
+    pub fn translate(
+        &self,
+        cpu: &Cpu,
+        mmu: &Mmu,
+        va: u64,
+        access: Access,
+    ) -> Result<Translation, MmuError> {
+        let tcr = cpu.sys(self.tcr).u64();
+        let upper = va >> 55 & 1 == 1;
+        let regime = if upper {
+            Regime {
+                ttbr: cpu.sys(self.ttbr1).u64(),
+                input_size: 64 - (tcr >> 16 & 0x3f),
+                granule: match tcr >> 30 & 0b11 {
+                    0b01 => 14,
+                    0b11 => 16,
+                    _ => 12,
+                },
+                disabled: tcr >> 23 & 1 == 1,
+                tbi: tcr >> 38 & 1 == 1,
+            }
+        } else {
+            Regime {
+                ttbr: cpu.sys(self.ttbr0).u64(),
+                input_size: 64 - (tcr & 0x3f),
+                granule: match tcr >> 14 & 0b11 {
+                    0b01 => 16,
+                    0b10 => 14,
+                    _ => 12,
+                },
+                disabled: tcr >> 7 & 1 == 1,
+                tbi: tcr >> 37 & 1 == 1,
+            }
+        };
+
+        // Bits above the input address size must be the same as bit 55.
+        let top = if regime.tbi && access != Access::Execute {
+            56
+        } else {
+            64
+        };
+        let ext = (va & ones_between(regime.input_size, top)) >> regime.input_size;
+        let expected = if upper {
+            ones_between(0, top - regime.input_size)
+        } else {
+            0
+        };
+        if regime.disabled || ext != expected {
+            return Err(MmuError::TranslationFault(va, 0));
+        }
+
+        let granule = regime.granule;
+        let stride = granule - 3;
+        let levels = (regime.input_size - granule).div_ceil(stride);
+        let output_size = output_size(tcr >> 32 & 0b111);
+
+        let mut level = 4 - levels;
+        let mut table = regime.ttbr & TTBR_ADDRESS_MASK;
+        let mut ap_table = 0;
+        let mut xn_table = 0;
+
+        loop {
+            let shift = granule + stride * (3 - level);
+            let index_bits = stride.min(regime.input_size - shift);
+            let index = va >> shift & ((1 << index_bits) - 1);
+
+            let mut buf = [0u8; 8];
+            unsafe { mmu.read(table + index * 8, &mut buf)? };
+            let desc = u64::from_le_bytes(buf);
+
+            if desc & DESC_VALID == 0 {
+                return Err(MmuError::TranslationFault(va, level as u8));
+            }
+
+            let address = desc & DESC_ADDRESS_MASK & !((1 << granule) - 1);
+            if level < 3 && desc & DESC_TABLE != 0 {
+                ap_table |= desc >> 61 & 0b11;
+                xn_table |= desc >> 59 & 0b11; // UXNTable and PXNTable
+                table = address;
+                level += 1;
+                continue;
+            }
+
+            // Blocks are allowed at level 1 and 2 with 4KB granule, and level 2 otherwise.
+            let min_block_level = if granule == 12 { 1 } else { 2 };
+            if level < min_block_level || (level == 3 && desc & DESC_TABLE == 0) {
+                return Err(MmuError::TranslationFault(va, level as u8));
+            }
+
+            let size = 1u64 << shift;
+            let pa = desc & DESC_ADDRESS_MASK & !(size - 1) | va & (size - 1);
+            if pa >> output_size != 0 {
+                return Err(MmuError::AddressSizeFault(va, level as u8));
+            }
+            if desc & DESC_AF == 0 {
+                return Err(MmuError::AccessFlagFault(va, level as u8));
+            }
+
+            // `APTable[1]` disallows writes, and `APTable[0]` disallows EL0 accesses.
+            let mut ap = desc >> 6 & 0b11;
+            if ap_table & 0b10 != 0 {
+                ap |= AP_RO;
+            }
+            if ap_table & 0b01 != 0 {
+                ap &= !AP_EL0;
+            }
+            let uxn = desc & DESC_UXN != 0 || xn_table & 0b10 != 0;
+            let pxn = desc & DESC_PXN != 0 || xn_table & 0b01 != 0;
+
+            if !self.is_permitted(cpu, access, ap, uxn, pxn) {
+                return Err(MmuError::PermissionFault(va, level as u8));
+            }
+
+            return Ok(Translation { pa, size });
+        }
+    }
+
+    fn is_permitted(&self, cpu: &Cpu, access: Access, ap: u64, uxn: bool, pxn: bool) -> bool {
+        let el0 = cpu.el() == 0;
+        let user = ap & AP_EL0 != 0;
+        let writable = ap & AP_RO == 0;
+        let wxn = cpu.sys(self.sctlr).u64() & SCTLR_WXN != 0 && writable;
+
+        // EL1 can't access memory accessible from EL0 while PAN is set.
+        let pan = cpu.access_mode() & Pstate::PAN.mask() != 0;
+        let data = if el0 { user } else { !(pan && user) };
+
+        match access {
+            Access::Read => data,
+            Access::Write => data && writable,
+            Access::Execute if el0 => user && !uxn && !wxn,
+            // Memory writable from EL0 is never executable at EL1.
+            Access::Execute => !(pxn || wxn || user && writable),
+        }
+    }
+}
+
+// Mask of bits from `lo` up to `hi` (exclusive).
+fn ones_between(lo: u64, hi: u64) -> u64 {
+    let ones = |n: u64| if n >= 64 { u64::MAX } else { (1 << n) - 1 };
+    ones(hi) & !ones(lo)
+}
+
+// Physical address size of `TCR_ELx.IPS`.
+fn output_size(ips: u64) -> u64 {
+    match ips {
+        0b000 => 32,
+        0b001 => 36,
+        0b010 => 40,
+        0b011 => 42,
+        0b100 => 44,
+        0b101 => 48,
+        _ => 52,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::Architecture;
+    use crate::softmmu::BasicPage;
+
+    const TCR_4K_39BIT: u64 = 25 | 25 << 16 | 0b10 << 30 | 0b101 << 32;
+    const KERNEL_PAGE: u64 = 0b11 | DESC_AF; // EL1 RW
+    const USER_PAGE: u64 = 0b11 | DESC_AF | AP_EL0 << 6; // EL0 and EL1 RW
+
+    fn set_sys(cpu: &mut Cpu, name: &str, val: u64) {
+        let id = cpu.reg_by_name(name).unwrap();
+        *cpu.sys_mut(id).u64_mut() = val;
+    }
+
+    unsafe fn write_desc(mmu: &Mmu, addr: u64, desc: u64) {
+        mmu.write(addr, &desc.to_le_bytes()).unwrap();
+    }
+
+    // Level 1 table at 0x1000, level 2 at 0x2000 and level 3 at 0x3000.
+    unsafe fn setup() -> (Cpu, Mmu, Stage1) {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        mmu.mmap(0x1000, 0x3000, Box::new(BasicPage::new(true, true, true)))
+            .unwrap();
+
+        write_desc(&mmu, 0x1000, 0x2000 | 0b11);
+        write_desc(&mmu, 0x2000, 0x3000 | 0b11);
+        write_desc(&mmu, 0x2008, 0x4000_0000 | 0b01 | DESC_AF); // 2MB block
+        write_desc(&mmu, 0x3000 + 5 * 8, 0x8000 | KERNEL_PAGE);
+        write_desc(&mmu, 0x3000 + 6 * 8, 0x9000 | USER_PAGE | DESC_PXN);
+        write_desc(&mmu, 0x3000 + 7 * 8, 0xa000 | 0b11); // Access flag is clear
+
+        set_sys(&mut cpu, "tcr_el1", TCR_4K_39BIT);
+        set_sys(&mut cpu, "ttbr0_el1", 0x1000);
+        set_sys(&mut cpu, "sctlr_el1", SCTLR_M);
+
+        let stage1 = Stage1::new(&cpu).unwrap();
+        (cpu, mmu, stage1)
+    }
+
+    #[test]
+    fn translate_test() {
+        unsafe {
+            let (cpu, mmu, stage1) = setup();
+            assert!(stage1.is_enabled(&cpu));
+
+            let result = stage1.translate(&cpu, &mmu, 0x5123, Access::Read).unwrap();
+            assert_eq!(
+                result,
+                Translation {
+                    pa: 0x8123,
+                    size: 0x1000
+                }
+            );
+            assert_eq!(result.remaining(), 0xedd);
+
+            let result = stage1
+                .translate(&cpu, &mmu, 0x20_1234, Access::Write)
+                .unwrap();
+            assert_eq!(result.pa, 0x4000_1234);
+            assert_eq!(result.size, 0x20_0000);
+
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x8000, Access::Read),
+                Err(MmuError::TranslationFault(0x8000, 3))
+            ));
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x4000_0000, Access::Read),
+                Err(MmuError::TranslationFault(_, 1))
+            ));
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x7000, Access::Read),
+                Err(MmuError::AccessFlagFault(0x7000, 3))
+            ));
+
+            // Addresses out of the input address range fault at level 0.
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x0000_0080_0000_0000, Access::Read),
+                Err(MmuError::TranslationFault(_, 0))
+            ));
+        }
+    }
+
+    #[test]
+    fn upper_range_test() {
+        unsafe {
+            let (mut cpu, mmu, stage1) = setup();
+            set_sys(&mut cpu, "ttbr1_el1", 0x1000);
+            set_sys(&mut cpu, "tcr_el1", TCR_4K_39BIT | 1 << 38);
+
+            // Top byte is ignored for data accesses when `TBI1` is set.
+            let result = stage1.translate(&cpu, &mmu, 0x00ff_ff80_0000_5008, Access::Read);
+            assert_eq!(result.unwrap().pa, 0x8008);
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x00ff_ff80_0000_5008, Access::Execute),
+                Err(MmuError::TranslationFault(_, 0))
+            ));
+
+            // `EPD1` disables walks of the upper range.
+            set_sys(&mut cpu, "tcr_el1", TCR_4K_39BIT | 1 << 23);
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0xffff_ff80_0000_5008, Access::Read),
+                Err(MmuError::TranslationFault(_, 0))
+            ));
+        }
+    }
+
+    #[test]
+    fn permission_test() {
+        unsafe {
+            let (cpu, mmu, stage1) = setup();
+
+            // EL1 can't execute pages writable from EL0.
+            assert!(stage1
+                .translate(&cpu, &mmu, 0x5000, Access::Execute)
+                .is_ok());
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x6000, Access::Execute),
+                Err(MmuError::PermissionFault(0x6000, 3))
+            ));
+
+            cpu.add_flag(Pstate::PAN.mask());
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x6000, Access::Read),
+                Err(MmuError::PermissionFault(_, 3))
+            ));
+
+            // EL0 can only access pages accessible from EL0.
+            cpu.set_flag(0);
+            assert!(stage1.translate(&cpu, &mmu, 0x6000, Access::Write).is_ok());
+            assert!(matches!(
+                stage1.translate(&cpu, &mmu, 0x5000, Access::Read),
+                Err(MmuError::PermissionFault(_, 3))
+            ));
+        }
+    }
+
+    #[test]
+    fn context_translate_test() {
+        use crate::codegen::ExecutionContext;
+
+        unsafe {
+            let (mut cpu, mmu, _) = setup();
+            mmu.mmap(0x8000, 0x2000, Box::new(BasicPage::new(true, true, true)))
+                .unwrap();
+            mmu.write(0x8ffc, &[1, 2, 3, 4]).unwrap();
+            mmu.write(0x9000, &[5, 6, 7, 8]).unwrap();
+
+            // Accesses crossing pages are translated page by page.
+            let mut ctx = ExecutionContext::new(&mut cpu, &mmu);
+            assert_eq!(ctx.mem_read_u64(0x5ffc).unwrap(), 0x0807_0605_0403_0201);
+            ctx.mem_write_u32(0x5000, 0).unwrap();
+            assert!(matches!(
+                ctx.mem_write_u64(0x6ffc, 0),
+                Err(MmuError::AccessFlagFault(0x7000, 3))
+            ));
+        }
+    }
+
+    #[test]
+    fn granule_64k_test() {
+        unsafe {
+            let (mut cpu, mmu, stage1) = setup();
+
+            // 42-bit input address with 64KB granule uses level 2 and 3.
+            set_sys(&mut cpu, "tcr_el1", 22 | 0b01 << 14 | 0b101 << 32);
+            write_desc(&mmu, 0x1000, 0x10000 | 0b11);
+            mmu.mmap(0x10000, 0x1000, Box::new(BasicPage::new(true, true, true)))
+                .unwrap();
+            write_desc(&mmu, 0x10000 + 2 * 8, 0x5_0000 | KERNEL_PAGE);
+
+            let result = stage1
+                .translate(&cpu, &mmu, 0x2_1234, Access::Read)
+                .unwrap();
+            assert_eq!(
+                result,
+                Translation {
+                    pa: 0x5_1234,
+                    size: 0x1_0000
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use crate::error::ImageError;
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use elf::abi::{STT_FUNC, STT_NOTYPE, STT_OBJECT};
+use elf::endian::AnyEndian;
+use elf::ElfBytes;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub addr: u64,
+    pub size: u64, // Zero if unknown.
+    pub name: String,
+}
+
+// Symbols of an image sorted by address, to resolve addresses into `symbol+offset`.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    symbols: Vec<Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new(mut symbols: Vec<Symbol>) -> Self {
+        symbols.sort_by_key(|sym| sym.addr);
+        Self { symbols }
+    }
+
+    // Functions, objects and labels in `.symtab` of an ELF.
+    pub fn from_elf(image: &[u8]) -> Result<Self, ImageError> {
+        let file = ElfBytes::<AnyEndian>::minimal_parse(image)
+            .map_err(|e| ImageError::Elf(e.to_string()))?;
+        let Some((symtab, strtab)) = file
+            .symbol_table()
+            .map_err(|e| ImageError::Elf(e.to_string()))?
+        else {
+            return Ok(Self::default());
+        };
+
+        let mut symbols = Vec::new();
+        for sym in symtab.iter() {
+            let kind = sym.st_symtype();
+            if sym.is_undefined() || ![STT_FUNC, STT_OBJECT, STT_NOTYPE].contains(&kind) {
+                continue;
+            }
+
+            let name = strtab
+                .get(sym.st_name as usize)
+                .map_err(|e| ImageError::Elf(e.to_string()))?;
+            // Mapping symbols mark code and data, such as `$x` and `$d`.
+            if name.is_empty() || name.starts_with('$') {
+                continue;
+            }
+
+            symbols.push(Symbol {
+                addr: sym.st_value,
+                size: sym.st_size,
+                name: name.to_string(),
+            });
+        }
+
+        Ok(Self::new(symbols))
+    }
+
+    // Lines of `System.map` or `nm` output, as `<address> <type> <name>`. Symbols are
+    // moved by `bias`, for images relocated after they are linked.
+    pub fn from_map(map: &str, bias: u64) -> Self {
+        let symbols = map
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let addr = u64::from_str_radix(fields.next()?, 16).ok()?;
+                let _kind = fields.next()?;
+                let name = fields.next()?;
+
+                Some(Symbol {
+                    addr: addr.wrapping_add(bias),
+                    size: 0,
+                    name: name.to_string(),
+                })
+            })
+            .collect();
+
+        Self::new(symbols)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    // Closest symbol at or below `addr`, and the offset of `addr` from it. Symbols of
+    // known size only cover their size.
+    pub fn resolve(&self, addr: u64) -> Option<(&Symbol, u64)> {
+        let index = self.symbols.partition_point(|sym| sym.addr <= addr);
+        let sym = &self.symbols[index.checked_sub(1)?];
+        let offset = addr - sym.addr;
+        if sym.size != 0 && offset >= sym.size {
+            return None;
+        }
+
+        Some((sym, offset))
+    }
+
+    // Address followed by the symbol it's in, if any.
+    pub fn describe(&self, addr: u64) -> Described<'_> {
+        Described {
+            addr,
+            symbol: self.resolve(addr),
+        }
+    }
+}
+
+pub struct Described<'a> {
+    addr: u64,
+    symbol: Option<(&'a Symbol, u64)>,
+}
+
+impl Display for Described<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "0x{:x}", self.addr)?;
+        match self.symbol {
+            Some((sym, 0)) => write!(f, " <{}>", sym.name),
+            Some((sym, offset)) => write!(f, " <{}+0x{:x}>", sym.name, offset),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn symbol_test() {
+        let map = "\
+            0000000000001000 T _start\n\
+            0000000000001040 t loop\n\
+            0000000000002000 D table\n\
+            bogus line\n";
+        let table = SymbolTable::from_map(map, 0x4000_0000);
+        assert_eq!(table.symbols().len(), 3);
+
+        let (sym, offset) = table.resolve(0x4000_1044).unwrap();
+        assert_eq!((sym.name.as_str(), offset), ("loop", 4));
+        assert!(table.resolve(0x4000_0fff).is_none());
+        assert_eq!(
+            table.describe(0x4000_1000).to_string(),
+            "0x40001000 <_start>"
+        );
+        assert_eq!(
+            table.describe(0x4000_2010).to_string(),
+            "0x40002010 <table+0x10>"
+        );
+        assert_eq!(table.describe(0x10).to_string(), "0x10");
+
+        // Symbols of known size don't cover addresses past them.
+        let table = SymbolTable::new(vec![Symbol {
+            addr: 0x100,
+            size: 0x10,
+            name: "f".to_string(),
+        }]);
+        assert!(table.resolve(0x10f).is_some());
+        assert!(table.resolve(0x110).is_none());
+    }
+}
@@ -0,0 +1,13 @@
+use crate::exception::Exception;
+use crate::psci::Shutdown;
+use crate::softmmu::Mmu;
+use crate::Cpu;
+
+// Kernel emulated in place of the guest's, for user-mode emulation where the guest is a
+// single process running at EL0.
+pub trait Kernel: Send {
+    // Handle `exc` taken from EL0 at the current pc, such as `SVC` for system calls.
+    // The handler sets the pc to continue at, and the guest stops if it returns a
+    // shutdown.
+    fn handle_exception(&mut self, cpu: &mut Cpu, mmu: &Mmu, exc: Exception) -> Option<Shutdown>;
+}
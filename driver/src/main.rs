@@ -1,156 +1,568 @@
 use machineinstr::aarch64::AArch64InstrParserRule;
 use machineinstr::MachineInstrParserRule;
 
-use core::board::Board;
-use core::codegen::flag_policy::AArch64FlagPolicy;
+use core::board::{Board, UndefinedPolicy};
+use core::codegen::cranelift::CraneliftCodegen;
+use core::codegen::flag_policy::{AArch64FlagPolicy, AArch64LazyFlagPolicy, FlagPolicy};
 use core::codegen::rustjit::InterpretCodegen;
 use core::codegen::Codegen;
 use core::compiler::aarch64::AArch64Compiler;
 use core::compiler::Compiler;
 use core::debug::aarch64::AArch64;
-use core::softmmu::BasicPage;
+use core::debug::*;
+use core::device::gic::{GIC_CPU_OFFSET, GIC_SIZE};
+use core::device::pl011::stdin_receiver;
+use core::device::{Clock, CpuLines, GenericTimer, Gic, IrqLine, Pl011, Poll};
+use core::error::Error;
+use core::image::{Image, LINUX_BASE_ALIGN};
+use core::psci::Shutdown;
+use core::softmmu::MmioBus;
 use core::softmmu::Mmu;
+use core::softmmu::RamRegion;
+use core::symbol::SymbolTable;
 use core::Cpu;
-use core::debug::*;
 
-use std::convert::Infallible;
-use std::path::PathBuf;
 use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
 use gdbstub::conn::ConnectionExt;
 use gdbstub::stub::{DisconnectReason, GdbStub, GdbStubError};
+use virtio::mmio::MMIO_SIZE;
+use virtio::{Block, Console, DiskMode, Fdt, MmioTransport, VirtIo};
 type DynResult<T> = Result<T, Box<dyn std::error::Error>>;
 
+const ADDR_RAM: u64 = 0x4000_0000;
+
+// Exit status when the guest requests a reset, to tell it from a power off (0) and from
+// errors of the emulator (1).
+const EXIT_RESET: i32 = 2;
+
 struct Configuration {
+    image: String, // Path of the image, which errors of loading it name.
+    cpus: u32,
     ram_size: u64,
+    serial: Serial,
+    clock: ClockKind,
+    drives: Vec<Drive>,
+    bootargs: Option<String>,
+    initrd: Option<PathBuf>,
+    trace: bool,
+    undefined: UndefinedPolicy,
+}
+
+// Image run by the board.
+enum Kernel {
+    // Firmware or bare-metal program, which finds the device tree at the start of RAM.
+    Bare(Image),
+    // arm64 Linux kernel `Image`, booted as its boot protocol says.
+    Linux(Image),
+}
+
+// Raw disk image attached as a virtio block device.
+struct Drive {
+    path: PathBuf,
+    mode: DiskMode,
+}
+
+// Host side of the UART.
+enum Serial {
+    Stdio,
+    File(PathBuf),
+}
+
+// Source of the system counter of the generic timer.
+enum ClockKind {
+    Host,
+    Instructions,
+}
+
+enum CodegenKind {
+    Interpret,
+    Cranelift,
+}
+
+struct Options {
+    codegen: CodegenKind,
+    lazy_flags: bool,
+    serial: Serial,
+    clock: ClockKind,
+    drives: Vec<Drive>,
+    memory: u64,
+    append: Option<String>,
+    initrd: Option<PathBuf>,
+    symbols: Option<Symbols>,
+    trace: bool,
+    undefined: UndefinedPolicy,
+    filename: String,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: driver [--codegen interpret|cranelift] [--lazy-flags] [--serial stdio|<file>] [--clock host|instructions] [--drive file=<file>[,readonly=on][,snapshot=on]]... [--memory <MiB>] [--append <cmdline>] [--initrd <file>] [--symbols <file>[,bias=<addr>]] [--trace] [--undefined stop|raise] <image>"
+    );
+    std::process::exit(1)
+}
+
+fn parse_args() -> Options {
+    let mut codegen = CodegenKind::Interpret;
+    let mut lazy_flags = false;
+    let mut serial = Serial::Stdio;
+    let mut clock = ClockKind::Host;
+    let mut drives = Vec::new();
+    let mut memory = 2;
+    let mut append = None;
+    let mut initrd = None;
+    let mut symbols = None;
+    let mut trace = false;
+    let mut undefined = UndefinedPolicy::Stop;
+    let mut filename = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--codegen" => {
+                codegen = match args.next().as_deref() {
+                    Some("interpret") => CodegenKind::Interpret,
+                    Some("cranelift") => CodegenKind::Cranelift,
+                    _ => usage(),
+                }
+            }
+            "--lazy-flags" => lazy_flags = true,
+            "--serial" => {
+                serial = match args.next() {
+                    Some(arg) if arg == "stdio" => Serial::Stdio,
+                    Some(arg) => Serial::File(PathBuf::from(arg)),
+                    None => usage(),
+                }
+            }
+            "--clock" => {
+                clock = match args.next().as_deref() {
+                    Some("host") => ClockKind::Host,
+                    Some("instructions") => ClockKind::Instructions,
+                    _ => usage(),
+                }
+            }
+            "--drive" => drives.push(parse_drive(&args.next().unwrap_or_else(|| usage()))),
+            "--memory" => {
+                memory = match args.next().map(|arg| arg.parse()) {
+                    Some(Ok(memory)) if memory > 0 => memory,
+                    _ => usage(),
+                }
+            }
+            "--append" => append = Some(args.next().unwrap_or_else(|| usage())),
+            "--initrd" => initrd = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--symbols" => symbols = Some(parse_symbols(&args.next().unwrap_or_else(|| usage()))),
+            "--trace" => trace = true,
+            "--undefined" => {
+                undefined = match args.next().as_deref() {
+                    Some("stop") => UndefinedPolicy::Stop,
+                    Some("raise") => UndefinedPolicy::Raise,
+                    _ => usage(),
+                }
+            }
+            _ if filename.is_none() => filename = Some(arg),
+            _ => usage(),
+        }
+    }
+
+    Options {
+        codegen,
+        lazy_flags,
+        serial,
+        clock,
+        drives,
+        memory,
+        append,
+        initrd,
+        symbols,
+        trace,
+        undefined,
+        filename: filename.unwrap_or_else(|| usage()),
+    }
+}
+
+// Parse `file=<file>[,readonly=on][,snapshot=on]`. Writes of snapshot drives are
+// discarded when the emulator exits.
+fn parse_drive(arg: &str) -> Drive {
+    let mut path = None;
+    let (mut readonly, mut snapshot) = (false, false);
+    for option in arg.split(',') {
+        match option.split_once('=') {
+            Some(("file", file)) => path = Some(PathBuf::from(file)),
+            Some(("readonly", "on")) => readonly = true,
+            Some(("readonly", "off")) => readonly = false,
+            Some(("snapshot", "on")) => snapshot = true,
+            Some(("snapshot", "off")) => snapshot = false,
+            _ => usage(),
+        }
+    }
+
+    let mode = if snapshot {
+        DiskMode::Snapshot
+    } else if readonly {
+        DiskMode::ReadOnly
+    } else {
+        DiskMode::ReadWrite
+    };
+
+    Drive {
+        path: path.unwrap_or_else(|| usage()),
+        mode,
+    }
+}
+
+// `System.map` or `nm` output, for images without symbols.
+struct Symbols {
+    path: PathBuf,
+    bias: u64, // Added to addresses in the file.
+}
+
+// Parse `<file>[,bias=<addr>]`, where the bias is hexadecimal.
+fn parse_symbols(arg: &str) -> Symbols {
+    let mut options = arg.split(',');
+    let path = PathBuf::from(options.next().unwrap_or_else(|| usage()));
+    let mut bias = 0;
+    for option in options {
+        bias = match option.split_once('=') {
+            Some(("bias", addr)) => {
+                u64::from_str_radix(addr.trim_start_matches("0x"), 16).unwrap_or_else(|_| usage())
+            }
+            _ => usage(),
+        }
+    }
+
+    Symbols { path, bias }
 }
 
 fn main() {
-    // get file
-    let args: Vec<String> = std::env::args().collect();
-    let filename = &args[1];
+    let options = parse_args();
 
     // initialize basic components
-    let cpu = Cpu::new(core::cpu::Architecture::AArch64Bin);
+    let mut cpu = Cpu::new(core::cpu::Architecture::AArch64Bin);
     let mmu = Mmu::new();
     let comp = AArch64Compiler::new(cpu.get_register_info());
-    let cgen = InterpretCodegen::new(AArch64FlagPolicy);
     let parser_rule = AArch64InstrParserRule;
 
+    // The board runs a single cpu.
     let config = Configuration {
-        ram_size: 2 * 1024 * 1024,
+        image: options.filename.clone(),
+        cpus: 1,
+        ram_size: options.memory * 1024 * 1024,
+        serial: options.serial,
+        clock: options.clock,
+        drives: options.drives,
+        bootargs: options.append,
+        initrd: options.initrd,
+        trace: options.trace,
+        undefined: options.undefined,
+    };
+
+    let flag_policy: Box<dyn FlagPolicy> = if options.lazy_flags {
+        Box::new(AArch64LazyFlagPolicy)
+    } else {
+        Box::new(AArch64FlagPolicy)
+    };
+
+    let mut image = read_image(&options.filename).unwrap_or_else(|err| {
+        eprintln!("{}: {}", options.filename, err);
+        std::process::exit(1)
+    });
+    let (Kernel::Bare(inner) | Kernel::Linux(inner)) = &mut image;
+    if let Some(symbols) = &options.symbols {
+        let map = std::fs::read_to_string(&symbols.path).unwrap();
+        inner.set_symbols(SymbolTable::from_map(&map, symbols.bias));
+    }
+    cpu.set_symbols(Arc::new(inner.symbols().clone()));
+
+    let shutdown = match options.codegen {
+        CodegenKind::Interpret => {
+            let cgen = InterpretCodegen::new(flag_policy);
+            unsafe { init_and_run(config, cpu, mmu, comp, cgen, parser_rule, image) }
+        }
+        CodegenKind::Cranelift => {
+            let cgen = CraneliftCodegen::new(flag_policy);
+            unsafe { init_and_run(config, cpu, mmu, comp, cgen, parser_rule, image) }
+        }
     };
 
-    // initialize image into mmu.
-    let image = std::fs::read(PathBuf::from(filename)).unwrap();
-    unsafe { init_and_run(config, cpu, mmu, comp, cgen, parser_rule, image) };
+    // The board doesn't reboot, so a reset stops it as well.
+    let status = match shutdown {
+        Shutdown::PowerOff => 0,
+        Shutdown::Reset => {
+            eprintln!("Reset requested by the guest.");
+            EXIT_RESET
+        }
+        Shutdown::Exit(status) => status,
+    };
+    std::process::exit(status);
 }
 
-unsafe fn init_and_run<C, G, P>(
-    config: Configuration,
-    cpu: Cpu,
-    mmu: Mmu,
-    comp: C,
-    cgen: G,
-    mci_parser: P,
-    image: Vec<u8>,
-) -> Infallible
-where
-    C: Compiler,
-    P: MachineInstrParserRule<MachineInstr = C::Item>,
-    G: Codegen,
-{
-    // https://qemu.readthedocs.io/en/latest/system/arm/virt.html
+// ELF images are loaded as their program headers say, and others into the flash.
+fn read_image(path: &str) -> DynResult<Kernel> {
+    let image = std::fs::read(path)?;
 
+    Ok(if Image::is_linux(&image) {
+        Kernel::Linux(Image::from_linux(image, ADDR_RAM)?)
+    } else if image.starts_with(b"\x7fELF") {
+        Kernel::Bare(Image::from_elf(image)?)
+    } else {
+        Kernel::Bare(Image::from_raw(image, 0))
+    })
+}
+
+// Map memory and devices of the board, and load `image` into them.
+//
+// https://qemu.readthedocs.io/en/latest/system/arm/virt.html
+unsafe fn map_memory(
+    config: &Configuration,
+    cpu: &mut Cpu,
+    mmu: &Mmu,
+    image: Kernel,
+) -> DynResult<Peripherals> {
     let addr_flash = 0x0000_0000u64;
     let size_flash = 0x0800_0000u64;
-    mmu.mmap(
-        addr_flash,
-        size_flash,
-        Box::new(BasicPage::new(true, true, true)),
-    )
-    .unwrap(); // flash is read-only
-    mmu.write(addr_flash, &image).unwrap();
+    mmu.mmap_region(addr_flash, RamRegion::lazy(size_flash, true, true, true))
+        .unwrap(); // flash is read-only
 
     let addr_lowmem_peripherals = 0x0800_0000u64;
     let size_lowmem_peripherals = 0x3800_0000u64;
-    mmu.mmap(
+    let bus = Arc::new(MmioBus::new());
+    mmu.mmap_mmio(
         addr_lowmem_peripherals,
         size_lowmem_peripherals,
-        Box::new(BasicPage::new(true, true, true)),
+        bus.clone(),
     )
     .unwrap();
 
-    let addr_ram = 0x4000_0000u64;
+    // The device tree describes the board to the guest, and is placed at the start of
+    // the RAM.
+    let addr_ram = ADDR_RAM;
     let size_ram = config.ram_size;
-    mmu.mmap(
-        addr_ram,
-        size_ram,
-        Box::new(BasicPage::new(true, true, true)),
-    )
-    .unwrap();
-    {
-        let dtb = std::fs::read("../binaries/virt-dtb.dtb").unwrap();
-        mmu.write(addr_ram, &dtb).unwrap();
+    let mut fdt = Fdt::new(config.cpus, addr_ram..addr_ram + size_ram);
+    if let Some(bootargs) = &config.bootargs {
+        fdt.bootargs(bootargs);
+    }
+
+    let addr_gic = 0x0800_0000u64;
+    let interrupts = CpuLines::default();
+    let gic = Arc::new(Mutex::new(Gic::new(interrupts.clone())));
+    bus.register(addr_gic..addr_gic + GIC_SIZE, gic.clone())
+        .unwrap();
+    fdt.gic(addr_gic, addr_gic + GIC_CPU_OFFSET);
+
+    let addr_uart = 0x0900_0000u64;
+    let size_uart = 0x0000_1000u64;
+    let irq_uart = 32 + 1;
+    // Input from the host goes to the console of the guest, which is the UART unless the
+    // command line chooses the virtio console.
+    let virtio_console = config.bootargs.as_deref().is_some_and(|bootargs| {
+        let console = bootargs
+            .split_whitespace()
+            .rev()
+            .find_map(|arg| arg.strip_prefix("console="));
+        console.is_some_and(|console| console.starts_with("hvc"))
+    });
+    let uart_rx = (!virtio_console).then(stdin_receiver);
+    let mut uart = match &config.serial {
+        Serial::Stdio => Pl011::new(Box::new(std::io::stdout()), uart_rx),
+        Serial::File(path) => Pl011::new(Box::new(std::fs::File::create(path).unwrap()), uart_rx),
+    };
+    uart.connect_irq(IrqLine::new(gic.clone(), irq_uart));
+    let uart = Arc::new(Mutex::new(uart));
+    bus.register(addr_uart..addr_uart + size_uart, uart.clone())
+        .unwrap();
+    fdt.uart(addr_uart..addr_uart + size_uart, irq_uart);
+
+    let console = Console::new(
+        Box::new(std::io::stdout()),
+        virtio_console.then(stdin_receiver),
+    );
+    let mut virtio_devices: Vec<Box<dyn VirtIo>> = vec![Box::new(console)];
+    for drive in &config.drives {
+        let block = Block::open(&drive.path, drive.mode).unwrap();
+        virtio_devices.push(Box::new(block));
+    }
+    let mut virtio_devices = virtio_devices.into_iter();
+    let mut polled: Vec<Arc<Mutex<dyn Poll>>> = vec![uart];
+
+    let addr_virtio = 0x0a00_0000u64;
+    let irq_virtio = 32 + 16;
+    for slot in 0..32 {
+        let device = virtio_devices.next();
+        let present = device.is_some();
+        let mut transport = match device {
+            Some(device) => MmioTransport::new(device, mmu.clone()),
+            None => MmioTransport::empty(mmu.clone()),
+        };
+        transport.connect_irq(IrqLine::new(gic.clone(), irq_virtio + slot as u32));
+
+        let transport = Arc::new(Mutex::new(transport));
+        let addr = addr_virtio + slot * MMIO_SIZE;
+        bus.register(addr..addr + MMIO_SIZE, transport.clone())
+            .unwrap();
+        fdt.virtio_mmio(addr..addr + MMIO_SIZE, irq_virtio + slot as u32);
+        if present {
+            polled.push(transport);
+        }
     }
 
-    let board = Board::new(comp, cgen, mci_parser, (), mmu, cpu);
-    board.run().unwrap()
+    let irq_timer_phys = 16 + 14;
+    let irq_timer_virt = 16 + 11;
+    let clock = match config.clock {
+        ClockKind::Host => Clock::host(),
+        ClockKind::Instructions => Clock::instructions(),
+    };
+    let mut timer = GenericTimer::new(cpu, clock);
+    timer.connect_irq(
+        IrqLine::new(gic.clone(), irq_timer_phys),
+        IrqLine::new(gic, irq_timer_virt),
+    );
+    fdt.timer(irq_timer_phys, irq_timer_virt);
+
+    mmu.mmap_region(addr_ram, RamRegion::lazy(size_ram, true, true, true))
+        .unwrap();
+    match image {
+        Kernel::Bare(image) => {
+            mmu.write(addr_ram, &fdt.build()?)?;
+
+            // The image is written over the device tree, if they overlap.
+            image
+                .load(mmu)
+                .map_err(|err| format!("{}: {}", config.image, err))?;
+            cpu.set_pc(image.entrypoint());
+        }
+        Kernel::Linux(image) => boot_linux(config, cpu, mmu, &image, fdt, addr_ram + size_ram)?,
+    }
+
+    Ok(Peripherals {
+        interrupts,
+        polled,
+        timer,
+    })
 }
 
+// Load the kernel at the bottom of RAM, and the device tree and the initrd at the top of
+// it below `ram_end`, then enter the kernel with the MMU off at EL1.
+//
+// https://docs.kernel.org/arch/arm64/booting.html
+unsafe fn boot_linux(
+    config: &Configuration,
+    cpu: &mut Cpu,
+    mmu: &Mmu,
+    image: &Image,
+    mut fdt: Fdt,
+    ram_end: u64,
+) -> DynResult<()> {
+    // The device tree is at most 2 MiB, in its own 2 MiB aligned block.
+    let addr_dtb = ram_end.saturating_sub(LINUX_BASE_ALIGN) & !(LINUX_BASE_ALIGN - 1);
+    let initrd = match &config.initrd {
+        Some(path) => {
+            Some(std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?)
+        }
+        None => None,
+    };
+    let initrd_len = initrd.as_ref().map_or(0, |initrd| initrd.len() as u64);
+    let addr_initrd = addr_dtb.saturating_sub(initrd_len) & !0xfff;
+    if addr_initrd < image.end() {
+        return Err("RAM is too small for the kernel".into());
+    }
 
-unsafe fn init_and_debug<C, G, P>(
+    image
+        .load(mmu)
+        .map_err(|err| format!("{}: {}", config.image, err))?;
+    if let Some(initrd) = initrd {
+        mmu.write(addr_initrd, &initrd)?;
+        fdt.initrd(addr_initrd..addr_initrd + initrd_len);
+    }
+    mmu.write(addr_dtb, &fdt.build()?)?;
+
+    // The reset state is EL1 with the MMU off and interrupts masked. x1 to x3 are
+    // reserved, and must be zero.
+    let regs = [addr_dtb, 0, 0, 0];
+    for (i, value) in regs.into_iter().enumerate() {
+        let reg = cpu.reg_by_name(format!("x{}", i)).unwrap();
+        *cpu.gpr_mut(reg).u64_mut() = value;
+    }
+    cpu.set_pc(image.entrypoint());
+
+    Ok(())
+}
+
+// Devices connected to the cpu.
+struct Peripherals {
+    interrupts: CpuLines,
+    polled: Vec<Arc<Mutex<dyn Poll>>>,
+    timer: GenericTimer,
+}
+
+impl Peripherals {
+    fn connect<C, R, G: Codegen, A>(self, board: &mut Board<C, R, G, A>) {
+        board.set_interrupt_lines(self.interrupts);
+        for device in self.polled {
+            board.add_device(device);
+        }
+        board.set_timer(self.timer);
+    }
+}
+
+unsafe fn init_and_run<C, G, P>(
     config: Configuration,
-    cpu: Cpu,
+    mut cpu: Cpu,
     mmu: Mmu,
     comp: C,
     cgen: G,
     mci_parser: P,
-    image: Vec<u8>,
-) -> DynResult<()>
+    image: Kernel,
+) -> Shutdown
 where
     C: Compiler,
     P: MachineInstrParserRule<MachineInstr = C::Item>,
     G: Codegen,
 {
-    // https://qemu.readthedocs.io/en/latest/system/arm/virt.html
+    let peripherals = map_memory(&config, &mut cpu, &mmu, image).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
 
-    let addr_flash = 0x0000_0000u64;
-    let size_flash = 0x0800_0000u64;
-    mmu.mmap(
-        addr_flash,
-        size_flash,
-        Box::new(BasicPage::new(true, true, true)),
-    )
-    .unwrap(); // flash is read-only
-    mmu.write(addr_flash, &image).unwrap();
-
-    let addr_lowmem_peripherals = 0x0800_0000u64;
-    let size_lowmem_peripherals = 0x3800_0000u64;
-    mmu.mmap(
-        addr_lowmem_peripherals,
-        size_lowmem_peripherals,
-        Box::new(BasicPage::new(true, true, true)),
-    )
-    .unwrap();
+    let mut board = Board::new(comp, cgen, mci_parser, (), mmu, cpu);
+    peripherals.connect(&mut board);
+    board.set_trace(config.trace);
+    board.set_undefined_policy(config.undefined);
+    board.run().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        // The board has dumped the cpu already if it panicked.
+        if !matches!(err, Error::Panic(_)) {
+            if let Some(cpu) = board.cpu().get() {
+                cpu.lock().unwrap().dump();
+            }
+        }
+        std::process::exit(1)
+    })
+}
 
-    let addr_ram = 0x4000_0000u64;
-    let size_ram = config.ram_size;
-    mmu.mmap(
-        addr_ram,
-        size_ram,
-        Box::new(BasicPage::new(true, true, true)),
-    )
-    .unwrap();
-    {
-        let dtb = std::fs::read("../binaries/virt-dtb.dtb").unwrap();
-        mmu.write(addr_ram, &dtb).unwrap();
-    }
+unsafe fn init_and_debug<C, G, P>(
+    config: Configuration,
+    mut cpu: Cpu,
+    mmu: Mmu,
+    comp: C,
+    cgen: G,
+    mci_parser: P,
+    image: Kernel,
+) -> DynResult<()>
+where
+    C: Compiler,
+    P: MachineInstrParserRule<MachineInstr = C::Item>,
+    G: Codegen,
+{
+    let peripherals = map_memory(&config, &mut cpu, &mmu, image)?;
 
     let mut board = Board::new(comp, cgen, mci_parser, AArch64, mmu, cpu);
+    peripherals.connect(&mut board);
+    board.set_undefined_policy(config.undefined);
 
-    
-    let connection: Box<dyn ConnectionExt<Error = std::io::Error>> = Box::new(wait_for_tcp(9001).unwrap());
+    let connection: Box<dyn ConnectionExt<Error = std::io::Error>> =
+        Box::new(wait_for_tcp(9001).unwrap());
 
     let gdb = GdbStub::new(connection);
 
@@ -187,4 +599,4 @@ fn wait_for_tcp(port: u16) -> DynResult<TcpStream> {
     eprintln!("Debugger connected from {}", addr);
 
     Ok(stream)
-}
\ No newline at end of file
+}
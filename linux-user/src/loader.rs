@@ -0,0 +1,255 @@
+use core::image::Image;
+use core::softmmu::{Mmu, RamRegion};
+
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStrExt;
+
+use elf::abi::{PT_INTERP, PT_LOAD};
+use elf::endian::AnyEndian;
+use elf::ElfBytes;
+
+use crate::DynResult;
+
+pub const PAGE_SIZE: u64 = 0x1000;
+
+// The stack is at the top of the lower half of the address space, below the area mmap
+// allocates from.
+const STACK_TOP: u64 = 0x0000_7fff_f000_0000;
+const STACK_SIZE: u64 = 0x80_0000;
+
+// Entries of the auxiliary vector.
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_PLATFORM: u64 = 15;
+const AT_HWCAP: u64 = 16;
+const AT_CLKTCK: u64 = 17;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+const AT_EXECFN: u64 = 31;
+
+// FP and AdvSIMD, which are always present in AArch64 Linux.
+const HWCAP_FP: u64 = 1 << 0;
+const HWCAP_ASIMD: u64 = 1 << 1;
+
+// Process whose program is loaded into memory, before it starts.
+pub struct Process {
+    pub image: Image,
+    pub sp: u64,
+    pub brk: u64, // Start of the heap, after the program.
+}
+
+// Map the static executable `program` into `mmu` with a stack holding `args`, `envs`
+// and the auxiliary vector, as the kernel does on `execve`.
+pub fn load(
+    mmu: &Mmu,
+    program: Vec<u8>,
+    args: &[OsString],
+    envs: &[OsString],
+) -> DynResult<Process> {
+    let file = ElfBytes::<AnyEndian>::minimal_parse(&program)?;
+    let segments: Vec<_> = file.segments().into_iter().flatten().collect();
+    if segments.iter().any(|phdr| phdr.p_type == PT_INTERP) {
+        return Err("dynamically linked programs are not supported".into());
+    }
+
+    // Program headers are in memory if a segment loads them.
+    let phoff = file.ehdr.e_phoff;
+    let phdr = segments
+        .iter()
+        .find(|phdr| {
+            phdr.p_type == PT_LOAD
+                && (phdr.p_offset..phdr.p_offset + phdr.p_filesz).contains(&phoff)
+        })
+        .map_or(0, |phdr| phdr.p_vaddr + phoff - phdr.p_offset);
+    let (phent, phnum) = (file.ehdr.e_phentsize as u64, file.ehdr.e_phnum as u64);
+
+    let image = Image::from_elf(program)?;
+    image.map(mmu)?;
+    let brk = image.end().next_multiple_of(PAGE_SIZE);
+
+    mmu.mmap_region(
+        STACK_TOP - STACK_SIZE,
+        RamRegion::lazy(STACK_SIZE, true, true, false),
+    )?;
+    let mut stack = Stack::new(mmu, STACK_TOP);
+
+    let mut random = [0u8; 16];
+    unsafe { libc::getrandom(random.as_mut_ptr().cast(), random.len(), 0) };
+    let random = stack.push_bytes(&random)?;
+    let platform = stack.push_bytes(b"aarch64\0")?;
+    let execfn = stack.push_str(&args[0])?;
+    let argv = args
+        .iter()
+        .map(|arg| stack.push_str(arg))
+        .collect::<DynResult<Vec<_>>>()?;
+    let envp = envs
+        .iter()
+        .map(|env| stack.push_str(env))
+        .collect::<DynResult<Vec<_>>>()?;
+
+    let (uid, gid) = unsafe { (libc::getuid() as u64, libc::getgid() as u64) };
+    let auxv = [
+        (AT_PHDR, phdr),
+        (AT_PHENT, phent),
+        (AT_PHNUM, phnum),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_BASE, 0),
+        (AT_ENTRY, image.entrypoint()),
+        (AT_UID, uid),
+        (AT_EUID, uid),
+        (AT_GID, gid),
+        (AT_EGID, gid),
+        (AT_PLATFORM, platform),
+        (AT_HWCAP, HWCAP_FP | HWCAP_ASIMD),
+        (AT_CLKTCK, 100),
+        (AT_SECURE, 0),
+        (AT_RANDOM, random),
+        (AT_EXECFN, execfn),
+        (AT_NULL, 0),
+    ];
+
+    // argc, argv and envp terminated by null, and the auxiliary vector, from the
+    // 16-byte aligned stack pointer.
+    let mut words = vec![argv.len() as u64];
+    words.extend(&argv);
+    words.push(0);
+    words.extend(&envp);
+    words.push(0);
+    words.extend(auxv.iter().flat_map(|&(key, value)| [key, value]));
+
+    let sp = (stack.sp - words.len() as u64 * 8) & !0xf;
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    unsafe { mmu.write(sp, &bytes)? };
+
+    Ok(Process { image, sp, brk })
+}
+
+// Stack being filled from the top.
+struct Stack<'a> {
+    mmu: &'a Mmu,
+    sp: u64,
+}
+
+impl<'a> Stack<'a> {
+    fn new(mmu: &'a Mmu, top: u64) -> Self {
+        Self { mmu, sp: top }
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> DynResult<u64> {
+        self.sp -= bytes.len() as u64;
+        unsafe { self.mmu.write(self.sp, bytes)? };
+        Ok(self.sp)
+    }
+
+    // Push `s` terminated by null.
+    fn push_str(&mut self, s: &OsString) -> DynResult<u64> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.push(0);
+        self.push_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use elf::abi::{EM_AARCH64, PF_R, PF_X};
+
+    // Static executable whose only loaded segment includes the headers, and optionally
+    // an interpreter.
+    fn elf(interp: bool) -> Vec<u8> {
+        let phnum = if interp { 2 } else { 1 };
+        let mut elf = vec![0u8; 0x100];
+        elf[0..4].copy_from_slice(b"\x7fELF");
+        elf[4] = 2; // ELFCLASS64
+        elf[5] = 1; // ELFDATA2LSB
+        elf[6] = 1; // EV_CURRENT
+        elf[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+        elf[18..20].copy_from_slice(&EM_AARCH64.to_le_bytes());
+        elf[20..24].copy_from_slice(&1u32.to_le_bytes());
+        elf[24..32].copy_from_slice(&0x40_00f0u64.to_le_bytes()); // e_entry
+        elf[32..40].copy_from_slice(&64u64.to_le_bytes()); // e_phoff
+        elf[52..54].copy_from_slice(&64u16.to_le_bytes()); // e_ehsize
+        elf[54..56].copy_from_slice(&56u16.to_le_bytes()); // e_phentsize
+        elf[56..58].copy_from_slice(&(phnum as u16).to_le_bytes()); // e_phnum
+
+        let segments = [
+            (PT_LOAD, PF_R | PF_X, 0u64, 0x40_0000u64, 0x100u64),
+            (PT_INTERP, PF_R, 0xf0, 0x40_00f0, 0x10),
+        ];
+        for (i, (ty, flags, offset, vaddr, size)) in segments.into_iter().take(phnum).enumerate() {
+            let phdr = &mut elf[64 + i * 56..64 + (i + 1) * 56];
+            phdr[0..4].copy_from_slice(&ty.to_le_bytes());
+            phdr[4..8].copy_from_slice(&flags.to_le_bytes());
+            phdr[8..16].copy_from_slice(&offset.to_le_bytes());
+            phdr[16..24].copy_from_slice(&vaddr.to_le_bytes());
+            phdr[24..32].copy_from_slice(&vaddr.to_le_bytes());
+            phdr[32..40].copy_from_slice(&size.to_le_bytes());
+            phdr[40..48].copy_from_slice(&size.to_le_bytes());
+        }
+        elf
+    }
+
+    fn read_word(mmu: &Mmu, addr: u64) -> u64 {
+        let mut buf = [0u8; 8];
+        unsafe { mmu.read(addr, &mut buf).unwrap() };
+        u64::from_le_bytes(buf)
+    }
+
+    fn read_str(mmu: &Mmu, addr: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        loop {
+            let mut byte = [0u8];
+            unsafe { mmu.read(addr + bytes.len() as u64, &mut byte).unwrap() };
+            if byte[0] == 0 {
+                return bytes;
+            }
+            bytes.push(byte[0]);
+        }
+    }
+
+    #[test]
+    fn load_test() {
+        let mmu = Mmu::new();
+        let args = ["prog", "arg"].map(OsString::from);
+        let envs = [OsString::from("KEY=value")];
+        let process = load(&mmu, elf(false), &args, &envs).unwrap();
+        assert_eq!(process.image.entrypoint(), 0x40_00f0);
+        assert_eq!(process.brk, 0x40_1000);
+        assert_eq!(process.sp % 16, 0);
+
+        // argc, argv and envp terminated by null, and the auxiliary vector.
+        let word = |index: u64| read_word(&mmu, process.sp + index * 8);
+        assert_eq!(word(0), 2);
+        assert_eq!(read_str(&mmu, word(1)), b"prog");
+        assert_eq!(read_str(&mmu, word(2)), b"arg");
+        assert_eq!(word(3), 0);
+        assert_eq!(read_str(&mmu, word(4)), b"KEY=value");
+        assert_eq!(word(5), 0);
+
+        let auxv: Vec<(u64, u64)> = (0..)
+            .map(|i| (word(6 + i * 2), word(7 + i * 2)))
+            .take_while(|&(key, _)| key != AT_NULL)
+            .collect();
+        let aux = |key| auxv.iter().find(|entry| entry.0 == key).unwrap().1;
+        assert_eq!(aux(AT_PHDR), 0x40_0040);
+        assert_eq!(aux(AT_PHENT), 56);
+        assert_eq!(aux(AT_PHNUM), 1);
+        assert_eq!(aux(AT_PAGESZ), PAGE_SIZE);
+        assert_eq!(aux(AT_ENTRY), 0x40_00f0);
+        assert_eq!(read_str(&mmu, aux(AT_PLATFORM)), b"aarch64");
+        assert_eq!(read_str(&mmu, aux(AT_EXECFN)), b"prog");
+        assert!(aux(AT_RANDOM) > process.sp);
+
+        // Programs which need an interpreter are refused.
+        assert!(load(&Mmu::new(), elf(true), &args, &envs).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+mod loader;
+mod syscall;
+
+use machineinstr::aarch64::AArch64InstrParserRule;
+
+use core::board::{Board, UndefinedPolicy};
+use core::codegen::cranelift::CraneliftCodegen;
+use core::codegen::flag_policy::{AArch64FlagPolicy, AArch64LazyFlagPolicy, FlagPolicy};
+use core::codegen::rustjit::InterpretCodegen;
+use core::codegen::Codegen;
+use core::compiler::aarch64::AArch64Compiler;
+use core::psci::Shutdown;
+use core::softmmu::Mmu;
+use core::Cpu;
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use syscall::Linux;
+
+type DynResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+enum CodegenKind {
+    Interpret,
+    Cranelift,
+}
+
+struct Options {
+    codegen: CodegenKind,
+    lazy_flags: bool,
+    undefined: UndefinedPolicy,
+    program: PathBuf,
+    args: Vec<OsString>, // Arguments of the program, including its name.
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: linux-user [--codegen interpret|cranelift] [--lazy-flags] [--undefined stop|raise] <program> [<args>...]"
+    );
+    std::process::exit(1)
+}
+
+// Options of the emulator come before the program, and the rest are its arguments.
+fn parse_args() -> Options {
+    let mut codegen = CodegenKind::Interpret;
+    let mut lazy_flags = false;
+    let mut undefined = UndefinedPolicy::Stop;
+
+    let mut args = std::env::args_os().skip(1);
+    let program = loop {
+        let Some(arg) = args.next() else { usage() };
+        match arg.to_str() {
+            Some("--codegen") => {
+                codegen = match args.next().as_ref().and_then(|arg| arg.to_str()) {
+                    Some("interpret") => CodegenKind::Interpret,
+                    Some("cranelift") => CodegenKind::Cranelift,
+                    _ => usage(),
+                }
+            }
+            Some("--lazy-flags") => lazy_flags = true,
+            Some("--undefined") => {
+                undefined = match args.next().as_ref().and_then(|arg| arg.to_str()) {
+                    Some("stop") => UndefinedPolicy::Stop,
+                    Some("raise") => UndefinedPolicy::Raise,
+                    _ => usage(),
+                }
+            }
+            Some(opt) if opt.starts_with("--") => usage(),
+            _ => break arg,
+        }
+    };
+
+    let mut program_args = vec![program.clone()];
+    program_args.extend(args);
+
+    Options {
+        codegen,
+        lazy_flags,
+        undefined,
+        program: PathBuf::from(program),
+        args: program_args,
+    }
+}
+
+fn main() {
+    let options = parse_args();
+
+    let mut cpu = Cpu::new(core::cpu::Architecture::AArch64Bin);
+    let mmu = Mmu::new();
+    let comp = AArch64Compiler::new(cpu.get_register_info());
+
+    let program = std::fs::read(&options.program).unwrap_or_else(|err| {
+        eprintln!("{}: {}", options.program.display(), err);
+        std::process::exit(1)
+    });
+    let envs: Vec<OsString> = std::env::vars_os()
+        .map(|(key, value)| {
+            let mut env = key;
+            env.push("=");
+            env.push(value);
+            env
+        })
+        .collect();
+    let process = loader::load(&mmu, program, &options.args, &envs).unwrap_or_else(|err| {
+        eprintln!("{}: {}", options.program.display(), err);
+        std::process::exit(1)
+    });
+
+    // The process starts at EL0 with the MMU off, so addresses are not translated.
+    cpu.set_flag(0);
+    let sp = cpu.reg_by_name("sp").unwrap();
+    *cpu.gpr_mut(sp).u64_mut() = process.sp;
+    cpu.set_pc(process.image.entrypoint());
+    cpu.set_symbols(Arc::new(process.image.symbols().clone()));
+    let kernel = Linux::new(&cpu, options.program, process.brk);
+
+    let flag_policy: Box<dyn FlagPolicy> = if options.lazy_flags {
+        Box::new(AArch64LazyFlagPolicy)
+    } else {
+        Box::new(AArch64FlagPolicy)
+    };
+    let shutdown = match options.codegen {
+        CodegenKind::Interpret => {
+            let cgen = InterpretCodegen::new(flag_policy);
+            unsafe { run(cpu, mmu, comp, cgen, kernel, options.undefined) }
+        }
+        CodegenKind::Cranelift => {
+            let cgen = CraneliftCodegen::new(flag_policy);
+            unsafe { run(cpu, mmu, comp, cgen, kernel, options.undefined) }
+        }
+    };
+
+    match shutdown {
+        Shutdown::Exit(status) => std::process::exit(status),
+        _ => std::process::exit(0),
+    }
+}
+
+unsafe fn run<G: Codegen>(
+    cpu: Cpu,
+    mmu: Mmu,
+    comp: AArch64Compiler,
+    cgen: G,
+    kernel: Linux,
+    undefined: UndefinedPolicy,
+) -> Shutdown {
+    let mut board = Board::new(comp, cgen, AArch64InstrParserRule, (), mmu, cpu);
+    board.set_kernel(Box::new(kernel));
+    board.set_undefined_policy(undefined);
+    board.run().unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    })
+}
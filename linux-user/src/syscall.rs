@@ -0,0 +1,747 @@
+use core::exception::Exception;
+use core::psci::Shutdown;
+use core::register::RegId;
+use core::softmmu::{Mmu, RamRegion};
+use core::user::Kernel;
+use core::Cpu;
+
+use std::ffi::{CString, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::PathBuf;
+
+use crate::loader::PAGE_SIZE;
+
+// Numbers of system calls in AArch64 Linux.
+const SYS_GETCWD: u64 = 17;
+const SYS_IOCTL: u64 = 29;
+const SYS_FACCESSAT: u64 = 48;
+const SYS_OPENAT: u64 = 56;
+const SYS_CLOSE: u64 = 57;
+const SYS_LSEEK: u64 = 62;
+const SYS_READ: u64 = 63;
+const SYS_WRITE: u64 = 64;
+const SYS_READV: u64 = 65;
+const SYS_WRITEV: u64 = 66;
+const SYS_READLINKAT: u64 = 78;
+const SYS_NEWFSTATAT: u64 = 79;
+const SYS_FSTAT: u64 = 80;
+const SYS_EXIT: u64 = 93;
+const SYS_EXIT_GROUP: u64 = 94;
+const SYS_SET_TID_ADDRESS: u64 = 96;
+const SYS_FUTEX: u64 = 98;
+const SYS_SET_ROBUST_LIST: u64 = 99;
+const SYS_NANOSLEEP: u64 = 101;
+const SYS_CLOCK_GETTIME: u64 = 113;
+const SYS_SIGALTSTACK: u64 = 132;
+const SYS_RT_SIGACTION: u64 = 134;
+const SYS_RT_SIGPROCMASK: u64 = 135;
+const SYS_UNAME: u64 = 160;
+const SYS_GETTIMEOFDAY: u64 = 169;
+const SYS_GETPID: u64 = 172;
+const SYS_GETPPID: u64 = 173;
+const SYS_GETUID: u64 = 174;
+const SYS_GETEUID: u64 = 175;
+const SYS_GETGID: u64 = 176;
+const SYS_GETEGID: u64 = 177;
+const SYS_GETTID: u64 = 178;
+const SYS_BRK: u64 = 214;
+const SYS_MUNMAP: u64 = 215;
+const SYS_MMAP: u64 = 222;
+const SYS_MPROTECT: u64 = 226;
+const SYS_MADVISE: u64 = 233;
+const SYS_PRLIMIT64: u64 = 261;
+const SYS_GETRANDOM: u64 = 278;
+
+// Flags of `openat` which differ between AArch64 and the host.
+const O_DIRECTORY: u64 = 0o40000;
+const O_NOFOLLOW: u64 = 0o100000;
+const O_DIRECT: u64 = 0o200000;
+const O_LARGEFILE: u64 = 0o400000;
+
+const PROT_EXEC: u64 = 4;
+const MAP_FIXED: u64 = 0x10;
+const MAP_ANONYMOUS: u64 = 0x20;
+
+// `ioctl`s of terminals, whose arguments have the same layout on every architecture.
+const TCGETS: u64 = 0x5401;
+const TIOCGWINSZ: u64 = 0x5413;
+const TERMIOS_SIZE: usize = 36;
+const WINSIZE_SIZE: usize = 8;
+
+const STAT_SIZE: usize = 128;
+const SIGACTION_SIZE: usize = 32;
+const SIGSET_SIZE: usize = 8;
+const UTSNAME_FIELD_SIZE: usize = 65;
+
+// Anonymous and file mappings are allocated upwards from here.
+const MMAP_BASE: u64 = 0x0000_7f00_0000_0000;
+
+// Largest buffer of a single `read` or `write`.
+const IO_LIMIT: u64 = 0x10_0000;
+
+// Signals which kill the process on faults.
+const SIGILL: i32 = 4;
+const SIGSEGV: i32 = 11;
+
+type SyscallResult = Result<u64, i32>;
+
+// Linux kernel of a single-threaded process, whose system calls are translated into
+// calls of the host.
+//
+// File descriptors are shared with the host. Memory is always readable and writable,
+// as protection changed by `mprotect` is not emulated.
+pub struct Linux {
+    regs: Vec<RegId>, // x0 to x8
+    exe: PathBuf,
+    brk_start: u64,
+    brk: u64,
+    brk_mapped: u64, // End of pages mapped for the heap.
+    mmap_next: u64,
+}
+
+impl Linux {
+    pub fn new(cpu: &Cpu, exe: PathBuf, brk: u64) -> Self {
+        let regs = (0..9)
+            .map(|i| cpu.reg_by_name(format!("x{}", i)).unwrap())
+            .collect();
+
+        Self {
+            regs,
+            exe,
+            brk_start: brk,
+            brk,
+            brk_mapped: brk,
+            mmap_next: MMAP_BASE,
+        }
+    }
+
+    fn arg(&self, cpu: &Cpu, index: usize) -> u64 {
+        cpu.gpr(self.regs[index]).u64()
+    }
+
+    fn syscall(&mut self, cpu: &mut Cpu, mmu: &Mmu) -> Option<Shutdown> {
+        let nr = self.arg(cpu, 8);
+        let args: [u64; 6] = std::array::from_fn(|i| self.arg(cpu, i));
+        let [a0, a1, a2, a3, a4, a5] = args;
+
+        let result = unsafe {
+            match nr {
+                SYS_EXIT | SYS_EXIT_GROUP => return Some(Shutdown::Exit(a0 as i32)),
+
+                SYS_READ => read(mmu, a0, a1, a2),
+                SYS_WRITE => write(mmu, a0, a1, a2),
+                SYS_READV => readv(mmu, a0, a1, a2),
+                SYS_WRITEV => writev(mmu, a0, a1, a2),
+                SYS_OPENAT => {
+                    let path = read_cstr(mmu, a1);
+                    path.and_then(|path| {
+                        host(libc::openat(
+                            a0 as i32,
+                            path.as_ptr(),
+                            open_flags(a2),
+                            a3 as u32,
+                        ))
+                    })
+                }
+                SYS_CLOSE => host(libc::close(a0 as i32)),
+                SYS_LSEEK => host(libc::lseek(a0 as i32, a1 as i64, a2 as i32)),
+                SYS_FSTAT => fstat(mmu, a1, |stat| libc::fstat(a0 as i32, stat)),
+                SYS_NEWFSTATAT => read_cstr(mmu, a1).and_then(|path| {
+                    fstat(mmu, a2, |stat| {
+                        libc::fstatat(a0 as i32, path.as_ptr(), stat, a3 as i32)
+                    })
+                }),
+                SYS_FACCESSAT => read_cstr(mmu, a1)
+                    .and_then(|path| host(libc::faccessat(a0 as i32, path.as_ptr(), a2 as i32, 0))),
+                SYS_READLINKAT => self.readlinkat(mmu, a0, a1, a2, a3),
+                SYS_GETCWD => getcwd(mmu, a0, a1),
+                SYS_IOCTL => ioctl(mmu, a0, a1, a2),
+
+                SYS_BRK => Ok(self.brk(mmu, a0)),
+                SYS_MMAP => self.mmap(mmu, a0, a1, a2, a3, a4, a5),
+                SYS_MUNMAP => munmap(mmu, a0, a1),
+                SYS_MPROTECT | SYS_MADVISE => Ok(0),
+
+                SYS_CLOCK_GETTIME => {
+                    let mut ts = std::mem::zeroed::<libc::timespec>();
+                    host(libc::clock_gettime(a0 as i32, &mut ts))
+                        .and_then(|_| write_words(mmu, a1, &[ts.tv_sec as u64, ts.tv_nsec as u64]))
+                }
+                SYS_GETTIMEOFDAY => {
+                    let mut tv = std::mem::zeroed::<libc::timeval>();
+                    libc::gettimeofday(&mut tv, std::ptr::null_mut());
+                    match a0 {
+                        0 => Ok(0),
+                        _ => write_words(mmu, a0, &[tv.tv_sec as u64, tv.tv_usec as u64]),
+                    }
+                }
+                SYS_NANOSLEEP => read_words::<2>(mmu, a0).and_then(|[sec, nsec]| {
+                    let ts = libc::timespec {
+                        tv_sec: sec as i64,
+                        tv_nsec: nsec as i64,
+                    };
+                    host(libc::nanosleep(&ts, std::ptr::null_mut()))
+                }),
+                SYS_UNAME => uname(mmu, a0),
+                SYS_GETRANDOM => {
+                    let mut buf = vec![0u8; a1.min(IO_LIMIT) as usize];
+                    host(libc::getrandom(buf.as_mut_ptr().cast(), buf.len(), a2 as u32) as i64)
+                        .and_then(|len| write_bytes(mmu, a0, &buf[..len as usize]).map(|_| len))
+                }
+                SYS_PRLIMIT64 => prlimit(mmu, a0, a1, a2, a3),
+
+                SYS_GETPID | SYS_GETTID | SYS_SET_TID_ADDRESS => Ok(libc::getpid() as u64),
+                SYS_GETPPID => Ok(libc::getppid() as u64),
+                SYS_GETUID => Ok(libc::getuid() as u64),
+                SYS_GETEUID => Ok(libc::geteuid() as u64),
+                SYS_GETGID => Ok(libc::getgid() as u64),
+                SYS_GETEGID => Ok(libc::getegid() as u64),
+
+                // Signals are never delivered, so no handler is installed and none is
+                // blocked.
+                SYS_RT_SIGACTION => write_zeros(mmu, a2, SIGACTION_SIZE),
+                SYS_RT_SIGPROCMASK => write_zeros(mmu, a2, SIGSET_SIZE),
+                SYS_SIGALTSTACK => Ok(0),
+                // The only thread never waits.
+                SYS_SET_ROBUST_LIST | SYS_FUTEX => Ok(0),
+
+                _ => {
+                    eprintln!("Unsupported syscall: {}", nr);
+                    Err(libc::ENOSYS)
+                }
+            }
+        };
+
+        let ret = match result {
+            Ok(value) => value,
+            Err(errno) => (-errno as i64) as u64,
+        };
+        *cpu.gpr_mut(self.regs[0]).u64_mut() = ret;
+
+        None
+    }
+
+    // Move the end of the heap to `addr`, and return the end. Pages are mapped as the
+    // heap grows, and are kept when it shrinks.
+    fn brk(&mut self, mmu: &Mmu, addr: u64) -> u64 {
+        if addr < self.brk_start {
+            return self.brk;
+        }
+
+        let end = addr.next_multiple_of(PAGE_SIZE);
+        if end > self.brk_mapped {
+            let region = RamRegion::lazy(end - self.brk_mapped, true, true, false);
+            if mmu.mmap_region(self.brk_mapped, region).is_err() {
+                return self.brk;
+            }
+            self.brk_mapped = end;
+        }
+
+        self.brk = addr;
+        self.brk
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn mmap(
+        &mut self,
+        mmu: &Mmu,
+        addr: u64,
+        len: u64,
+        prot: u64,
+        flags: u64,
+        fd: u64,
+        offset: u64,
+    ) -> SyscallResult {
+        if len == 0 || !offset.is_multiple_of(PAGE_SIZE) {
+            return Err(libc::EINVAL);
+        }
+        let size = len.next_multiple_of(PAGE_SIZE);
+
+        let addr = if flags & MAP_FIXED != 0 {
+            munmap(mmu, addr, size)?;
+            addr
+        } else {
+            let addr = self.mmap_next;
+            self.mmap_next += size;
+            addr
+        };
+
+        let region = RamRegion::lazy(size, true, true, prot & PROT_EXEC != 0);
+        if flags & MAP_ANONYMOUS == 0 {
+            // The file is read in chunks, as the mapping may be much larger than it.
+            let mut data = vec![0u8; len.min(IO_LIMIT) as usize];
+            let mut filled = 0;
+            while filled < len {
+                let chunk = &mut data[..(len - filled).min(IO_LIMIT) as usize];
+                let read = host(libc::pread(
+                    fd as i32,
+                    chunk.as_mut_ptr().cast(),
+                    chunk.len(),
+                    (offset + filled) as i64,
+                ) as i64)?;
+                if read == 0 {
+                    break;
+                }
+                region.fill(filled, &chunk[..read as usize]);
+                filled += read;
+            }
+        }
+
+        mmu.mmap_region(addr, region).map_err(|_| libc::ENOMEM)?;
+        Ok(addr)
+    }
+
+    // `/proc/self/exe` is the program run, not the emulator.
+    unsafe fn readlinkat(
+        &self,
+        mmu: &Mmu,
+        dirfd: u64,
+        path: u64,
+        buf: u64,
+        size: u64,
+    ) -> SyscallResult {
+        let path = read_cstr(mmu, path)?;
+        let target = if path.as_bytes() == b"/proc/self/exe" {
+            std::fs::canonicalize(&self.exe).map_err(errno)?
+        } else {
+            let mut target = vec![0u8; libc::PATH_MAX as usize];
+            let len = host(libc::readlinkat(
+                dirfd as i32,
+                path.as_ptr(),
+                target.as_mut_ptr().cast(),
+                target.len(),
+            ) as i64)?;
+            target.truncate(len as usize);
+            PathBuf::from(OsString::from_vec(target))
+        };
+
+        let target = target.as_os_str().as_bytes();
+        let len = target.len().min(size as usize);
+        write_bytes(mmu, buf, &target[..len])?;
+        Ok(len as u64)
+    }
+}
+
+impl Kernel for Linux {
+    fn handle_exception(&mut self, cpu: &mut Cpu, mmu: &Mmu, exc: Exception) -> Option<Shutdown> {
+        let pc = cpu.pc();
+        let signal = match exc {
+            Exception::Svc(_) => {
+                cpu.set_pc(pc + 4);
+                return self.syscall(cpu, mmu);
+            }
+            Exception::InstructionAbort { addr, .. } | Exception::DataAbort { addr, .. } => {
+                eprintln!("Segmentation fault at 0x{:x}", addr);
+                SIGSEGV
+            }
+            _ => {
+                eprintln!("Illegal instruction");
+                SIGILL
+            }
+        };
+
+        // The process is killed, as signals are not delivered.
+        eprintln!("pc: {}", cpu.symbols().describe(pc));
+        Some(Shutdown::Exit(128 + signal))
+    }
+}
+
+fn errno(err: io::Error) -> i32 {
+    err.raw_os_error().unwrap_or(libc::EIO)
+}
+
+// Result of a call of the host, which sets `errno` if it fails.
+fn host<T: Into<i64>>(ret: T) -> SyscallResult {
+    let ret = ret.into();
+    if ret < 0 {
+        Err(errno(io::Error::last_os_error()))
+    } else {
+        Ok(ret as u64)
+    }
+}
+
+fn open_flags(flags: u64) -> i32 {
+    let same = flags & !(O_DIRECTORY | O_NOFOLLOW | O_DIRECT | O_LARGEFILE);
+    let mut host = same as i32;
+    for (guest, flag) in [
+        (O_DIRECTORY, libc::O_DIRECTORY),
+        (O_NOFOLLOW, libc::O_NOFOLLOW),
+        (O_DIRECT, libc::O_DIRECT),
+        (O_LARGEFILE, libc::O_LARGEFILE),
+    ] {
+        if flags & guest != 0 {
+            host |= flag;
+        }
+    }
+    host
+}
+
+unsafe fn read_bytes(mmu: &Mmu, addr: u64, len: u64) -> Result<Vec<u8>, i32> {
+    let mut buf = vec![0u8; len as usize];
+    mmu.read(addr, &mut buf).map_err(|_| libc::EFAULT)?;
+    Ok(buf)
+}
+
+unsafe fn write_bytes(mmu: &Mmu, addr: u64, buf: &[u8]) -> SyscallResult {
+    mmu.write(addr, buf).map_err(|_| libc::EFAULT)?;
+    Ok(0)
+}
+
+// Clear the old state returned at `addr`, unless it's null.
+unsafe fn write_zeros(mmu: &Mmu, addr: u64, size: usize) -> SyscallResult {
+    if addr != 0 {
+        write_bytes(mmu, addr, &vec![0u8; size])?;
+    }
+    Ok(0)
+}
+
+unsafe fn read_words<const N: usize>(mmu: &Mmu, addr: u64) -> Result<[u64; N], i32> {
+    let bytes = read_bytes(mmu, addr, N as u64 * 8)?;
+    Ok(std::array::from_fn(|i| {
+        u64::from_le_bytes(bytes[i * 8..i * 8 + 8].try_into().unwrap())
+    }))
+}
+
+unsafe fn write_words(mmu: &Mmu, addr: u64, words: &[u64]) -> SyscallResult {
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    write_bytes(mmu, addr, &bytes)
+}
+
+unsafe fn read_cstr(mmu: &Mmu, addr: u64) -> Result<CString, i32> {
+    let mut bytes = Vec::new();
+    loop {
+        let [byte] = read_bytes(mmu, addr + bytes.len() as u64, 1)?[..] else {
+            unreachable!()
+        };
+        if byte == 0 {
+            return Ok(CString::new(bytes).unwrap());
+        }
+        if bytes.len() == libc::PATH_MAX as usize {
+            return Err(libc::ENAMETOOLONG);
+        }
+        bytes.push(byte);
+    }
+}
+
+unsafe fn read(mmu: &Mmu, fd: u64, buf: u64, count: u64) -> SyscallResult {
+    let mut data = vec![0u8; count.min(IO_LIMIT) as usize];
+    let len = host(libc::read(fd as i32, data.as_mut_ptr().cast(), data.len()) as i64)?;
+    write_bytes(mmu, buf, &data[..len as usize])?;
+    Ok(len)
+}
+
+unsafe fn write(mmu: &Mmu, fd: u64, buf: u64, count: u64) -> SyscallResult {
+    let data = read_bytes(mmu, buf, count.min(IO_LIMIT))?;
+    host(libc::write(fd as i32, data.as_ptr().cast(), data.len()) as i64)
+}
+
+// Buffers of `iovec`s at `iov`, as address and length.
+unsafe fn iovecs(mmu: &Mmu, iov: u64, count: u64) -> Result<Vec<(u64, u64)>, i32> {
+    (0..count)
+        .map(|i| read_words::<2>(mmu, iov + i * 16).map(|[base, len]| (base, len)))
+        .collect()
+}
+
+unsafe fn readv(mmu: &Mmu, fd: u64, iov: u64, count: u64) -> SyscallResult {
+    let mut total = 0;
+    for (base, len) in iovecs(mmu, iov, count)? {
+        let read = read(mmu, fd, base, len)?;
+        total += read;
+        if read < len {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+// Buffers are written at once, so that they are not interleaved with other output.
+unsafe fn writev(mmu: &Mmu, fd: u64, iov: u64, count: u64) -> SyscallResult {
+    let mut data = Vec::new();
+    for (base, len) in iovecs(mmu, iov, count)? {
+        data.extend(read_bytes(mmu, base, len)?);
+    }
+    host(libc::write(fd as i32, data.as_ptr().cast(), data.len()) as i64)
+}
+
+// Call `f` to fill a `stat` of the host, and write it into `buf` as AArch64 lays it out.
+unsafe fn fstat(mmu: &Mmu, buf: u64, f: impl FnOnce(*mut libc::stat) -> i32) -> SyscallResult {
+    let mut st = std::mem::zeroed::<libc::stat>();
+    host(f(&mut st))?;
+
+    let mut bytes = [0u8; STAT_SIZE];
+    let mut put = |offset: usize, value: &[u8]| {
+        bytes[offset..offset + value.len()].copy_from_slice(value);
+    };
+    put(0, &st.st_dev.to_le_bytes());
+    put(8, &st.st_ino.to_le_bytes());
+    put(16, &st.st_mode.to_le_bytes());
+    put(20, &(st.st_nlink as u32).to_le_bytes());
+    put(24, &st.st_uid.to_le_bytes());
+    put(28, &st.st_gid.to_le_bytes());
+    put(32, &st.st_rdev.to_le_bytes());
+    put(48, &st.st_size.to_le_bytes());
+    put(56, &(st.st_blksize as i32).to_le_bytes());
+    put(64, &st.st_blocks.to_le_bytes());
+    put(72, &st.st_atime.to_le_bytes());
+    put(80, &st.st_atime_nsec.to_le_bytes());
+    put(88, &st.st_mtime.to_le_bytes());
+    put(96, &st.st_mtime_nsec.to_le_bytes());
+    put(104, &st.st_ctime.to_le_bytes());
+    put(112, &st.st_ctime_nsec.to_le_bytes());
+
+    write_bytes(mmu, buf, &bytes)
+}
+
+unsafe fn getcwd(mmu: &Mmu, buf: u64, size: u64) -> SyscallResult {
+    let cwd = std::env::current_dir().map_err(errno)?;
+    let mut cwd = cwd.as_os_str().as_bytes().to_vec();
+    cwd.push(0);
+    if cwd.len() as u64 > size {
+        return Err(libc::ERANGE);
+    }
+
+    write_bytes(mmu, buf, &cwd)?;
+    Ok(cwd.len() as u64)
+}
+
+unsafe fn ioctl(mmu: &Mmu, fd: u64, request: u64, arg: u64) -> SyscallResult {
+    let size = match request {
+        TCGETS => TERMIOS_SIZE,
+        TIOCGWINSZ => WINSIZE_SIZE,
+        _ => return Err(libc::ENOTTY),
+    };
+
+    let mut buf = vec![0u8; size];
+    host(libc::ioctl(fd as i32, request, buf.as_mut_ptr()))?;
+    write_bytes(mmu, arg, &buf)
+}
+
+// The machine is the guest's, and the rest is the host's.
+unsafe fn uname(mmu: &Mmu, buf: u64) -> SyscallResult {
+    let mut uts = std::mem::zeroed::<libc::utsname>();
+    host(libc::uname(&mut uts))?;
+
+    let mut machine = [0 as libc::c_char; UTSNAME_FIELD_SIZE];
+    for (dst, src) in machine.iter_mut().zip(b"aarch64") {
+        *dst = *src as libc::c_char;
+    }
+    uts.machine = machine;
+
+    let fields = [
+        uts.sysname,
+        uts.nodename,
+        uts.release,
+        uts.version,
+        uts.machine,
+        uts.domainname,
+    ];
+    let bytes: Vec<u8> = fields.iter().flatten().map(|&c| c as u8).collect();
+    write_bytes(mmu, buf, &bytes)
+}
+
+// Only limits of the process can be read.
+unsafe fn prlimit(mmu: &Mmu, pid: u64, resource: u64, new: u64, old: u64) -> SyscallResult {
+    if pid != 0 || new != 0 {
+        return Err(libc::EPERM);
+    }
+
+    let mut limit = std::mem::zeroed::<libc::rlimit>();
+    host(libc::prlimit(
+        0,
+        resource as _,
+        std::ptr::null(),
+        &mut limit,
+    ))?;
+    if old != 0 {
+        write_words(mmu, old, &[limit.rlim_cur, limit.rlim_max])?;
+    }
+    Ok(0)
+}
+
+unsafe fn munmap(mmu: &Mmu, addr: u64, len: u64) -> SyscallResult {
+    if !addr.is_multiple_of(PAGE_SIZE) {
+        return Err(libc::EINVAL);
+    }
+
+    mmu.unmap(addr, len.next_multiple_of(PAGE_SIZE))
+        .map_err(|_| libc::EINVAL)?;
+    Ok(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::cpu::Architecture;
+
+    use std::os::fd::AsRawFd;
+
+    const BUF: u64 = 0x1000;
+    const MAP_PRIVATE: u64 = 2;
+    const PROT_READ_WRITE: u64 = 3;
+
+    fn memory() -> Mmu {
+        let mmu = Mmu::new();
+        mmu.mmap_region(BUF, RamRegion::new(0x1000, true, true, false))
+            .unwrap();
+        mmu
+    }
+
+    fn linux(cpu: &Cpu) -> Linux {
+        Linux::new(cpu, PathBuf::from("prog"), 0x10_0000)
+    }
+
+    // Make system call `nr` with `args`, and return its result.
+    fn call(linux: &mut Linux, cpu: &mut Cpu, mmu: &Mmu, nr: u64, args: &[u64]) -> u64 {
+        for (&reg, &value) in linux.regs.iter().zip(args) {
+            *cpu.gpr_mut(reg).u64_mut() = value;
+        }
+        *cpu.gpr_mut(linux.regs[8]).u64_mut() = nr;
+        assert!(linux.syscall(cpu, mmu).is_none());
+        cpu.gpr(linux.regs[0]).u64()
+    }
+
+    #[test]
+    fn open_flags_test() {
+        assert_eq!(open_flags(0), libc::O_RDONLY);
+        let flags = (libc::O_WRONLY | libc::O_CREAT) as u64 | O_DIRECTORY | O_NOFOLLOW;
+        assert_eq!(
+            open_flags(flags),
+            libc::O_WRONLY | libc::O_CREAT | libc::O_DIRECTORY | libc::O_NOFOLLOW
+        );
+        assert_eq!(
+            open_flags(O_DIRECT | O_LARGEFILE),
+            libc::O_DIRECT | libc::O_LARGEFILE
+        );
+    }
+
+    #[test]
+    fn fstat_test() {
+        let mmu = memory();
+        let ret = unsafe {
+            fstat(&mmu, BUF, |st| {
+                (*st).st_ino = 2;
+                (*st).st_mode = 0o100644;
+                (*st).st_nlink = 1;
+                (*st).st_size = 0x1234;
+                (*st).st_blksize = 0x1000;
+                (*st).st_mtime = 5;
+                (*st).st_mtime_nsec = 6;
+                0
+            })
+        };
+        assert_eq!(ret, Ok(0));
+
+        let bytes = unsafe { read_bytes(&mmu, BUF, STAT_SIZE as u64).unwrap() };
+        assert_eq!(bytes[8..16], 2u64.to_le_bytes());
+        assert_eq!(bytes[16..20], 0o100644u32.to_le_bytes());
+        assert_eq!(bytes[20..24], 1u32.to_le_bytes());
+        assert_eq!(bytes[48..56], 0x1234u64.to_le_bytes());
+        assert_eq!(bytes[56..60], 0x1000u32.to_le_bytes());
+        assert_eq!(bytes[88..96], 5u64.to_le_bytes());
+        assert_eq!(bytes[96..104], 6u64.to_le_bytes());
+    }
+
+    #[test]
+    fn brk_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let mut linux = linux(&cpu);
+        assert_eq!(linux.brk(&mmu, 0), 0x10_0000);
+        assert!(!mmu.is_readable(0x10_0000..0x10_1000));
+
+        assert_eq!(linux.brk(&mmu, 0x10_2010), 0x10_2010);
+        assert!(mmu.is_writable(0x10_0000..0x10_3000));
+
+        // Pages are kept when the heap shrinks, and the heap grows over them again.
+        assert_eq!(linux.brk(&mmu, 0x10_0800), 0x10_0800);
+        assert!(mmu.is_writable(0x10_0000..0x10_3000));
+        assert_eq!(linux.brk(&mmu, 0x10_4000), 0x10_4000);
+        assert!(mmu.is_writable(0x10_0000..0x10_4000));
+
+        // The heap doesn't grow over other mappings.
+        mmu.mmap_region(0x10_5000, RamRegion::new(0x1000, true, true, false))
+            .unwrap();
+        assert_eq!(linux.brk(&mmu, 0x10_6000), 0x10_4000);
+    }
+
+    #[test]
+    fn mmap_test() {
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let mut linux = linux(&cpu);
+        let anon = MAP_PRIVATE | MAP_ANONYMOUS;
+        unsafe {
+            let mmap = |linux: &mut Linux, addr, len, flags, offset| {
+                linux.mmap(&mmu, addr, len, PROT_READ_WRITE, flags, u64::MAX, offset)
+            };
+            assert_eq!(mmap(&mut linux, 0, 0x1800, anon, 0), Ok(MMAP_BASE));
+            assert_eq!(mmap(&mut linux, 0, 0x1000, anon, 0), Ok(MMAP_BASE + 0x2000));
+            assert!(mmu.is_writable(MMAP_BASE..MMAP_BASE + 0x3000));
+            assert_eq!(mmap(&mut linux, 0, 0, anon, 0), Err(libc::EINVAL));
+            assert_eq!(mmap(&mut linux, 0, 0x1000, anon, 0x10), Err(libc::EINVAL));
+
+            // Fixed mappings replace pages mapped before.
+            write_bytes(&mmu, MMAP_BASE, b"old").unwrap();
+            let fixed = anon | MAP_FIXED;
+            assert_eq!(mmap(&mut linux, MMAP_BASE, 0x1000, fixed, 0), Ok(MMAP_BASE));
+            assert_eq!(read_bytes(&mmu, MMAP_BASE, 3), Ok(vec![0; 3]));
+
+            assert_eq!(munmap(&mmu, MMAP_BASE + 1, 0x1000), Err(libc::EINVAL));
+            assert_eq!(munmap(&mmu, MMAP_BASE, 0x1800), Ok(0));
+            assert!(!mmu.is_readable(MMAP_BASE..MMAP_BASE + 0x2000));
+            assert!(mmu.is_readable(MMAP_BASE + 0x2000..MMAP_BASE + 0x3000));
+        }
+    }
+
+    #[test]
+    fn mmap_file_test() {
+        let path = std::env::temp_dir().join(format!("linux-user-mmap-{}", std::process::id()));
+        let mut content = vec![0x55u8; 0x1000];
+        content.extend([0xaa; 0x10]);
+        std::fs::write(&path, &content).unwrap();
+        let file = std::fs::File::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // The mapping is larger than the rest of the file, which is followed by zeros.
+        let cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = Mmu::new();
+        let mut linux = linux(&cpu);
+        let fd = file.as_raw_fd() as u64;
+        let addr = unsafe { linux.mmap(&mmu, 0, 0x2000, 1, MAP_PRIVATE, fd, 0x1000) }.unwrap();
+        let bytes = unsafe { read_bytes(&mmu, addr, 0x20).unwrap() };
+        assert_eq!(bytes[..0x10], [0xaa; 0x10]);
+        assert_eq!(bytes[0x10..], [0; 0x10]);
+    }
+
+    #[test]
+    fn signal_test() {
+        let mut cpu = Cpu::new(Architecture::AArch64Bin);
+        let mmu = memory();
+        let mut linux = linux(&cpu);
+        unsafe { write_bytes(&mmu, BUF, &[0xff; 0x40]).unwrap() };
+
+        // Old actions and masks are empty, as signals are never delivered.
+        let args = [libc::SIGINT as u64, 0, BUF, SIGSET_SIZE as u64];
+        assert_eq!(call(&mut linux, &mut cpu, &mmu, SYS_RT_SIGACTION, &args), 0);
+        let bytes = unsafe { read_bytes(&mmu, BUF, 0x40).unwrap() };
+        assert_eq!(bytes[..SIGACTION_SIZE], [0; SIGACTION_SIZE]);
+        assert_eq!(bytes[SIGACTION_SIZE..], [0xff; 0x40 - SIGACTION_SIZE]);
+
+        let args = [libc::SIG_BLOCK as u64, 0, BUF + 0x20, SIGSET_SIZE as u64];
+        assert_eq!(
+            call(&mut linux, &mut cpu, &mmu, SYS_RT_SIGPROCMASK, &args),
+            0
+        );
+        let bytes = unsafe { read_bytes(&mmu, BUF + 0x20, 0x20).unwrap() };
+        assert_eq!(bytes[..SIGSET_SIZE], [0; SIGSET_SIZE]);
+        assert_eq!(bytes[SIGSET_SIZE..], [0xff; 0x20 - SIGSET_SIZE]);
+
+        // Null pointers are left alone.
+        let args = [libc::SIG_BLOCK as u64, 0, 0, SIGSET_SIZE as u64];
+        assert_eq!(
+            call(&mut linux, &mut cpu, &mmu, SYS_RT_SIGPROCMASK, &args),
+            0
+        );
+    }
+}
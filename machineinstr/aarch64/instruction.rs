@@ -804,6 +804,10 @@ pub enum AArch64Instr {
     FminvEncoding(QSizeRnRd),
 
     Udf(Imm16),
+    // Encoding which isn't allocated to any instruction.
+    Unallocated,
+    // Encoding which the parser doesn't decode yet.
+    Undecoded,
 
     Casb(RsRnRt),
     Caslb(RsRnRt),
@@ -19,11 +19,11 @@ impl MachineInstrParserRule for AArch64InstrParserRule {
         I: Iterator<Item = u8>,
     {
         // Todo features : FEAT_PAuth, FEAT_LSE
-        parse_aarch64_instr(buf).map(|v| NativeInstr { op: v, size: 4 })
+        parse_aarch64_instr(buf).map(|(op, raw)| NativeInstr { op, size: 4, raw })
     }
 }
 
-fn parse_aarch64_instr<I>(reader: &mut ByteReader<I>) -> Option<AArch64Instr>
+fn parse_aarch64_instr<I>(reader: &mut ByteReader<I>) -> Option<(AArch64Instr, u32)>
 where
     I: Iterator<Item = u8>,
 {
@@ -39,14 +39,22 @@ where
 
             match (op0, op1) {
                 (0b00, 0b000000000) => AArch64Instr::Udf(imm16),
-                _ => todo!("Unknown reserved instruction {:032b}", raw_instr),
+                _ => AArch64Instr::Unallocated,
             }
         })
+        // SME encodings
         .bind("1_xx_0000_xxxxxxxxxxxxxxxxxxxxxxxxx", |_raw_instr: u32| {
-            todo!("SME encodings")
+            AArch64Instr::Undecoded
         })
+        .bind("x_xx_0001_xxxxxxxxxxxxxxxxxxxxxxxxx", |_raw_instr: u32| {
+            AArch64Instr::Unallocated
+        })
+        // SVE encodings
         .bind("x_xx_0010_xxxxxxxxxxxxxxxxxxxxxxxxx", |_raw_instr: u32| {
-            todo!("SVE encodings")
+            AArch64Instr::Undecoded
+        })
+        .bind("x_xx_0011_xxxxxxxxxxxxxxxxxxxxxxxxx", |_raw_instr: u32| {
+            AArch64Instr::Unallocated
         })
         .bind("x_xx_100x_xxxxxxxxxxxxxxxxxxxxxxxxx", parse_aarch64_d_p_i)
         .bind(
@@ -66,15 +74,10 @@ where
         m
     });
 
-    if let Some(raw_instr) = reader.read32() {
-        if let Some(instr) = MATCHER.handle(raw_instr) {
-            return Some(instr);
-        } else {
-            todo!("Unknown instruction {:032b}", raw_instr);
-        }
-    }
+    let raw_instr = reader.read32()?;
+    let instr = MATCHER.handle(raw_instr).unwrap_or(AArch64Instr::Undecoded);
 
-    None
+    Some((instr, raw_instr))
 }
 
 // parse DPI(Data Processing Immediate) instructions in AArch64
@@ -104,7 +107,7 @@ fn parse_aarch64_d_p_i(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -164,7 +167,7 @@ fn parse_aarch64_d_p_r(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -176,56 +179,64 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0100", "0x", "x101", "00xxxxx10"
             ),
-            |_raw_instr: u32| todo!("Cryptographic AES"),
+            // Cryptographic AES
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0101", "0x", "x0xx", "xxx0xxx00"
             ),
-            |_raw_instr: u32| todo!("Cryptographic three-register SHA"),
+            // Cryptographic three-register SHA
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0101", "0x", "x101", "00xxxxx10"
             ),
-            |_raw_instr: u32| todo!("Cryptographic two-register SHA"),
+            // Cryptographic two-register SHA
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "00", "00xx", "xxx0xxxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar copy"),
+            // Advanced SIMD scalar copy
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "0x", "10xx", "xxx00xxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar three same FP16"),
+            // Advanced SIMD scalar three same FP16
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "0x", "1111", "00xxxxx10"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar two-register miscellaneous FP16"),
+            // Advanced SIMD scalar two-register miscellaneous FP16
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "0x", "x0xx", "xxx1xxxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar three same extra"),
+            // Advanced SIMD scalar three same extra
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "0x", "x100", "00xxxxx10"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar two-register miscellaneous"),
+            // Advanced SIMD scalar two-register miscellaneous
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -239,21 +250,24 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "0x", "x1xx", "xxxxxxx00"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar three different"),
+            // Advanced SIMD scalar three different
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "0x", "x1xx", "xxxxxxxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar three same"),
+            // Advanced SIMD scalar three same
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "01x1", "10", "xxxx", "xxxxxxxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD scalar shifted by immediate"),
+            // Advanced SIMD scalar shifted by immediate
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -267,7 +281,8 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0x00", "0x", "x0xx", "xxx0xxx00"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD table lookup"),
+            // Advanced SIMD table lookup
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -295,21 +310,24 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0xx0", "0x", "10xx", "xxx00xxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD three same (FP16)"),
+            // Advanced SIMD three same (FP16)
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0xx0", "0x", "1111", "00xxxxx10"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD two-register miscellaneous (FP16)"),
+            // Advanced SIMD two-register miscellaneous (FP16)
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0xx0", "0x", "x0xx", "xxx1xxxx1"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD three-register extension"),
+            // Advanced SIMD three-register extension
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -330,7 +348,8 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "0xx0", "0x", "x1xx", "xxxxxxx00"
             ),
-            |_raw_instr: u32| todo!("Advanced SIMD three different"),
+            // Advanced SIMD three different
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -364,35 +383,40 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "1100", "00", "10xx", "xxx10xxxx"
             ),
-            |_raw_instr: u32| todo!("Cryptographic three-register, imm2"),
+            // Cryptographic three-register, imm2
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "1100", "00", "11xx", "xxx1x00xx"
             ),
-            |_raw_instr: u32| todo!("Cryptographic three-reigster SHA 512"),
+            // Cryptographic three-reigster SHA 512
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "1100", "00", "xxxx", "xxx0xxxxx"
             ),
-            |_raw_instr: u32| todo!("Cryptographic four-register"),
+            // Cryptographic four-register
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "1100", "01", "00xx", "xxxxxxxxx"
             ),
-            |_raw_instr: u32| todo!("XAR"),
+            // XAR
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "1100", "01", "1000", "0001000xx"
             ),
-            |_raw_instr: u32| todo!("Cryptographic two-register SHA 512"),
+            // Cryptographic two-register SHA 512
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -434,7 +458,8 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
                 "{}_xxx_{}_{}_{}_xxxxxxxxxx",
                 "x0x1", "0x", "x1xx", "xxxxxxx01"
             ),
-            |_raw_instr: u32| todo!("Floating-point conditional compare"),
+            // Floating-point conditional compare
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             &format!(
@@ -464,7 +489,7 @@ fn parse_aarch64_dp_sfp_adv_simd(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -490,9 +515,8 @@ fn parse_aarch64_load_and_stores(raw_instr: u32) -> AArch64Instr {
         )
         .bind(
             "0x00_1_1_0_11_x_xxxxxx_xxxx_xx_xxxxxxxxxx",
-            |_raw_instr: u32| {
-                todo!("Advanced SIMD Load/Store single structure(post-indexed)");
-            },
+            // Advanced SIMD Load/Store single structure(post-indexed)
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             "1101_1_0_0_1x_x_1xxxxx_xxxx_xx_xxxxxxxxxx",
@@ -524,9 +548,8 @@ fn parse_aarch64_load_and_stores(raw_instr: u32) -> AArch64Instr {
         )
         .bind(
             "xx01_1_x_0_1x_x_0xxxxx_xxxx_01_xxxxxxxxxx",
-            |_raw_instr: u32| {
-                todo!("Memory Copy and Memory Set");
-            },
+            // Memory Copy and Memory Set
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             "xx10_1_x_0_00_x_xxxxxx_xxxx_xx_xxxxxxxxxx",
@@ -570,9 +593,8 @@ fn parse_aarch64_load_and_stores(raw_instr: u32) -> AArch64Instr {
         )
         .bind(
             "xx11_1_x_0_0x_x_1xxxxx_xxxx_x1_xxxxxxxxxx",
-            |_raw_instr: u32| {
-                todo!("Load/Store register (pac)"); // Need to do FEAT_PAuth feature instructions
-            },
+            // Load/Store register (pac)
+            |_raw_instr: u32| AArch64Instr::Undecoded,
         )
         .bind(
             "xx11_1_x_0_1x_x_xxxxxx_xxxx_xx_xxxxxxxxxx",
@@ -585,7 +607,7 @@ fn parse_aarch64_load_and_stores(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -635,7 +657,7 @@ fn parse_aarch64_branches_exception_gen_and_sys_instr(raw_instr: u32) -> AArch64
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -644,7 +666,7 @@ fn parse_add_sub_shifted_reg(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xxx_01011_xx_0_xxxxxxxxxxxxxxxxxxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              shift: Extract<BitRange<22, 24>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
@@ -669,7 +691,7 @@ fn parse_add_sub_shifted_reg(raw_instr: u32) -> AArch64Instr {
                     (0b110, _, _) => AArch64Instr::SubShiftedReg64(data),
                     (0b111, _, _) => AArch64Instr::SubsShiftedReg64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -680,7 +702,7 @@ fn parse_add_sub_shifted_reg(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -689,7 +711,7 @@ fn parse_add_sub_immediate(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_100010_x_xxxxxxxxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              sh: Extract<BitRange<22, 23>, u8>,
              imm12: Extract<BitRange<10, 22>, u16>,
@@ -711,7 +733,7 @@ fn parse_add_sub_immediate(raw_instr: u32) -> AArch64Instr {
                     0b101 => AArch64Instr::AddsImm64(data),
                     0b110 => AArch64Instr::SubImm64(data),
                     0b111 => AArch64Instr::SubsImm64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -722,7 +744,7 @@ fn parse_add_sub_immediate(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -731,7 +753,7 @@ fn parse_fp_data_processing_3src(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11111_xx_x_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              m: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -761,7 +783,7 @@ fn parse_fp_data_processing_3src(raw_instr: u32) -> AArch64Instr {
                     (0b0, 0b0, 0b11, 0b0, 0b1) => AArch64Instr::FmSubHalfPrecision(data),
                     (0b0, 0b0, 0b11, 0b1, 0b0) => AArch64Instr::FnmAddHalfPrecision(data),
                     (0b0, 0b0, 0b11, 0b1, 0b1) => AArch64Instr::FnmSubHalfPrecision(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -772,7 +794,7 @@ fn parse_fp_data_processing_3src(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -781,7 +803,7 @@ fn parse_load_store_reg_unsigned_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_01_xx_xxxxxxxxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              idxt: Extract<BitRange<24, 26>, u8>,
@@ -823,7 +845,7 @@ fn parse_load_store_reg_unsigned_imm(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b0, 0b10) => AArch64Instr::PrfmImm(data),
                     (0b11, 0b1, 0b00) => AArch64Instr::StrImmSimdFP64(data),
                     (0b11, 0b1, 0b01) => AArch64Instr::LdrImmSimdFP64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -834,7 +856,7 @@ fn parse_load_store_reg_unsigned_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -843,7 +865,7 @@ fn parse_move_wide_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_100101_xx_xxxxxxxxxxxxxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_opc: Extract<BitRange<29, 32>, u8>,
              hw: Extract<BitRange<21, 23>, u8>,
              imm16: Extract<BitRange<5, 21>, u16>,
@@ -861,7 +883,7 @@ fn parse_move_wide_imm(raw_instr: u32) -> AArch64Instr {
                     (0b100, _) => AArch64Instr::MovnVar64(data),
                     (0b110, _) => AArch64Instr::MovzVar64(data),
                     (0b111, _) => AArch64Instr::MovkVar64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -872,7 +894,7 @@ fn parse_move_wide_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -903,47 +925,35 @@ fn parse_uncond_branch_reg(raw_instr: u32) -> AArch64Instr {
 
                 match (opc.value, op2.value, op3.value, rn, rm) {
                     (0b0000, 0b11111, 0b000000, _, 0b00000) => AArch64Instr::Br(data),
-                    (0b0000, 0b11111, 0b000010, _, 0b11111) => {
-                        todo!("BRAA, BRAAZ, BRAB, BRABZ. Key A, zero modifier")
-                    }
-                    (0b0000, 0b11111, 0b000011, _, 0b11111) => {
-                        todo!("BRAA, BRAAZ, BRAB, BRABZ. Key B, zero modifier")
-                    }
+                    // BRAA, BRAAZ, BRAB, BRABZ. Key A, zero modifier
+                    (0b0000, 0b11111, 0b000010, _, 0b11111) => AArch64Instr::Undecoded,
+                    // BRAA, BRAAZ, BRAB, BRABZ. Key B, zero modifier
+                    (0b0000, 0b11111, 0b000011, _, 0b11111) => AArch64Instr::Undecoded,
                     (0b0001, 0b11111, 0b000000, _, 0b00000) => AArch64Instr::Blr(data),
-                    (0b0001, 0b11111, 0b000010, _, 0b11111) => {
-                        todo!("BLRAA, BLRAAZ, BLRAB, BLRABZ. Key A, zero modifier")
-                    }
-                    (0b0001, 0b11111, 0b000011, _, 0b11111) => {
-                        todo!("BLRAA, BLRAAZ, BLRAB, BLRABZ. Key B, zero modifier")
-                    }
+                    // BLRAA, BLRAAZ, BLRAB, BLRABZ. Key A, zero modifier
+                    (0b0001, 0b11111, 0b000010, _, 0b11111) => AArch64Instr::Undecoded,
+                    // BLRAA, BLRAAZ, BLRAB, BLRABZ. Key B, zero modifier
+                    (0b0001, 0b11111, 0b000011, _, 0b11111) => AArch64Instr::Undecoded,
                     (0b0010, 0b11111, 0b000000, _, 0b00000) => AArch64Instr::Ret(data),
-                    (0b0010, 0b11111, 0b000010, 0b11111, 0b11111) => {
-                        todo!("RETAA, RETAB - RETAA variant")
-                    }
-                    (0b0010, 0b11111, 0b000011, 0b11111, 0b11111) => {
-                        todo!("RETAA, RETAB - RETAB variant")
-                    }
+                    // RETAA, RETAB - RETAA variant
+                    (0b0010, 0b11111, 0b000010, 0b11111, 0b11111) => AArch64Instr::Undecoded,
+                    // RETAA, RETAB - RETAB variant
+                    (0b0010, 0b11111, 0b000011, 0b11111, 0b11111) => AArch64Instr::Undecoded,
                     (0b0100, 0b11111, 0b000000, 0b11111, 0b00000) => AArch64Instr::ERet(data),
-                    (0b0100, 0b11111, 0b000010, 0b11111, 0b11111) => {
-                        todo!("ERETAA, ERETAB - ERETAA variant")
-                    }
-                    (0b0100, 0b11111, 0b000011, 0b11111, 0b11111) => {
-                        todo!("ERETAA, ERETAB - ERETAB variant")
-                    }
+                    // ERETAA, ERETAB - ERETAA variant
+                    (0b0100, 0b11111, 0b000010, 0b11111, 0b11111) => AArch64Instr::Undecoded,
+                    // ERETAA, ERETAB - ERETAB variant
+                    (0b0100, 0b11111, 0b000011, 0b11111, 0b11111) => AArch64Instr::Undecoded,
                     (0b0101, 0b11111, 0b000000, 0b11111, 0b00000) => AArch64Instr::Drps(data),
-                    (0b1000, 0b11111, 0b000010, _, _) => {
-                        todo!("BRAA, BRAAZ, BRAB, BRABZ - Key A, register modifier")
-                    }
-                    (0b1000, 0b11111, 0b000011, _, _) => {
-                        todo!("BRAA, BRAAZ, BRAB, BRABZ - Key B, register modifier")
-                    }
-                    (0b1001, 0b11111, 0b000010, _, _) => {
-                        todo!("BLRAA, BLRAAZ, BLRAB, BLRABZ - Key A, register modifier")
-                    }
-                    (0b1001, 0b11111, 0b000011, _, _) => {
-                        todo!("BLRAA, BLRAAZ, BLRAB, BLRABZ - Key B, register modifier")
-                    }
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    // BRAA, BRAAZ, BRAB, BRABZ - Key A, register modifier
+                    (0b1000, 0b11111, 0b000010, _, _) => AArch64Instr::Undecoded,
+                    // BRAA, BRAAZ, BRAB, BRABZ - Key B, register modifier
+                    (0b1000, 0b11111, 0b000011, _, _) => AArch64Instr::Undecoded,
+                    // BLRAA, BLRAAZ, BLRAB, BLRABZ - Key A, register modifier
+                    (0b1001, 0b11111, 0b000010, _, _) => AArch64Instr::Undecoded,
+                    // BLRAA, BLRAAZ, BLRAB, BLRABZ - Key B, register modifier
+                    (0b1001, 0b11111, 0b000011, _, _) => AArch64Instr::Undecoded,
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -954,7 +964,7 @@ fn parse_uncond_branch_reg(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -963,7 +973,7 @@ fn parse_uncond_branch_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_00101_xxxxxxxxxxxxxxxxxxxxxxxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              op: Extract<BitRange<31, 32>, u8>,
              imm26: Extract<BitRange<0, 26>, u32>| {
                 let data = Imm26 { imm26: imm26.value };
@@ -971,7 +981,7 @@ fn parse_uncond_branch_imm(raw_instr: u32) -> AArch64Instr {
                 match op.value {
                     0b0 => AArch64Instr::BImm(data),
                     0b1 => AArch64Instr::BlImm(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -982,7 +992,7 @@ fn parse_uncond_branch_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -991,7 +1001,7 @@ fn parse_cond_branch_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0101010_x_xxxxxxxxxxxxxxxxxxx_x_xxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              o1: Extract<BitRange<24, 25>, u8>,
              imm19: Extract<BitRange<5, 24>, u32>,
              o0: Extract<BitRange<4, 5>, u8>,
@@ -1004,7 +1014,7 @@ fn parse_cond_branch_imm(raw_instr: u32) -> AArch64Instr {
                 match (o1.value, o0.value) {
                     (0b0, 0b0) => AArch64Instr::BCond(data),
                     (0b0, 0b1) => AArch64Instr::BcCond(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1015,7 +1025,7 @@ fn parse_cond_branch_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1024,7 +1034,7 @@ fn parse_cond_sel(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_11010100_xxxxx_xxxx_xx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
              cond: Extract<BitRange<12, 16>, u8>,
@@ -1047,7 +1057,7 @@ fn parse_cond_sel(raw_instr: u32) -> AArch64Instr {
                     (0b100, 0b01) => AArch64Instr::Csinc64(data),
                     (0b110, 0b00) => AArch64Instr::Csinv64(data),
                     (0b110, 0b01) => AArch64Instr::Csneg64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1058,7 +1068,7 @@ fn parse_cond_sel(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1067,7 +1077,7 @@ fn parse_test_and_branch_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_011011_x_xxxxx_xxxxxxxxxxxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              b5: Extract<BitRange<31, 32>, u8>,
              op: Extract<BitRange<24, 25>, u8>,
              b40: Extract<BitRange<19, 24>, u8>,
@@ -1083,7 +1093,7 @@ fn parse_test_and_branch_imm(raw_instr: u32) -> AArch64Instr {
                 match op.value {
                     0b0 => AArch64Instr::Tbz(data),
                     0b1 => AArch64Instr::Tbnz(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1094,7 +1104,7 @@ fn parse_test_and_branch_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1103,7 +1113,7 @@ fn parse_logical_shifted_register(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_01010_xx_x_xxxxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              opc: Extract<BitRange<29, 31>, u8>,
              shift: Extract<BitRange<22, 24>, u8>,
@@ -1121,7 +1131,7 @@ fn parse_logical_shifted_register(raw_instr: u32) -> AArch64Instr {
                 };
 
                 match (sf.value, opc.value, n.value) {
-                    (0b0, _, _) if imm6.value & 0b100000 == 0b100000 => unreachable!(),
+                    (0b0, _, _) if imm6.value & 0b100000 == 0b100000 => AArch64Instr::Unallocated,
                     (0b0, 0b00, 0b0) => AArch64Instr::AndShiftedReg32(data),
                     (0b0, 0b00, 0b1) => AArch64Instr::BicShiftedReg32(data),
                     (0b0, 0b01, 0b0) => AArch64Instr::OrrShiftedReg32(data),
@@ -1138,7 +1148,7 @@ fn parse_logical_shifted_register(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b10, 0b1) => AArch64Instr::EonShiftedReg64(data),
                     (0b1, 0b11, 0b0) => AArch64Instr::AndsShiftedReg64(data),
                     (0b1, 0b11, 0b1) => AArch64Instr::BicsShiftedReg64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1149,7 +1159,7 @@ fn parse_logical_shifted_register(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1158,7 +1168,7 @@ fn parse_hints(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "11010101000000110010_xxxx_xxx_11111",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              crm: Extract<BitRange<8, 12>, u8>,
              op2: Extract<BitRange<5, 8>, u8>| {
                 match (crm.value, op2.value) {
@@ -1183,7 +1193,7 @@ fn parse_hints(raw_instr: u32) -> AArch64Instr {
                     (0b0011, 0b101) => AArch64Instr::AutiaspVar,
                     (0b0011, 0b110) => AArch64Instr::AutibzVar,
                     (0b0011, 0b111) => AArch64Instr::AutibspVar,
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1194,7 +1204,7 @@ fn parse_hints(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1203,7 +1213,7 @@ fn parse_pc_rel_addressing(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_10000_xxxxxxxxxxxxxxxxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              op: Extract<BitRange<31, 32>, u8>,
              immlo: Extract<BitRange<29, 31>, u8>,
              immhi: Extract<BitRange<5, 24>, u32>,
@@ -1217,7 +1227,7 @@ fn parse_pc_rel_addressing(raw_instr: u32) -> AArch64Instr {
                 match op.value {
                     0b0 => AArch64Instr::Adr(data),
                     0b1 => AArch64Instr::Adrp(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1228,7 +1238,7 @@ fn parse_pc_rel_addressing(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1237,7 +1247,7 @@ fn parse_exception_gen(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "11010100_xxx_xxxxxxxxxxxxxxxx_xxx_xx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<21, 24>, u8>,
              imm16: Extract<BitRange<5, 21>, u16>,
              op2: Extract<BitRange<2, 5>, u8>,
@@ -1259,7 +1269,7 @@ fn parse_exception_gen(raw_instr: u32) -> AArch64Instr {
                     (0b101, 0b000, 0b01) => AArch64Instr::DcpS1(data),
                     (0b101, 0b000, 0b10) => AArch64Instr::DcpS2(data),
                     (0b101, 0b000, 0b11) => AArch64Instr::DcpS3(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1270,7 +1280,7 @@ fn parse_exception_gen(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1279,7 +1289,7 @@ fn parse_load_store_reg_reg_offset(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_00_xx_1_xxxxx_xxx_x_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              opc: Extract<BitRange<22, 24>, u8>,
@@ -1328,7 +1338,7 @@ fn parse_load_store_reg_reg_offset(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b0, 0b00, _) => AArch64Instr::StrReg64(data),
                     (0b11, 0b0, 0b01, _) => AArch64Instr::LdrReg64(data),
                     (0b11, 0b0, 0b10, _) => AArch64Instr::PrfmReg(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1339,7 +1349,7 @@ fn parse_load_store_reg_reg_offset(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1348,7 +1358,7 @@ fn parse_add_sub_ext_reg(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_01011_xx_1_xxxxx_xxx_xxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              opt: Extract<BitRange<22, 24>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
@@ -1373,7 +1383,7 @@ fn parse_add_sub_ext_reg(raw_instr: u32) -> AArch64Instr {
                     (0b101, 0b00) => AArch64Instr::AddsExtReg64(data),
                     (0b110, 0b00) => AArch64Instr::SubExtReg64(data),
                     (0b111, 0b00) => AArch64Instr::SubsExtReg64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1384,7 +1394,7 @@ fn parse_add_sub_ext_reg(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1393,7 +1403,7 @@ fn parse_bitfield(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_100110_x_xxxxxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              opc: Extract<BitRange<29, 31>, u8>,
              n: Extract<BitRange<22, 23>, u8>,
@@ -1417,7 +1427,7 @@ fn parse_bitfield(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b01, 0b1) => AArch64Instr::Bfm64(data),
                     (0b1, 0b10, 0b1) => AArch64Instr::Ubfm64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1428,7 +1438,7 @@ fn parse_bitfield(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1437,7 +1447,7 @@ fn parse_logical_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_100100_x_xxxxxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              opc: Extract<BitRange<29, 31>, u8>,
              n: Extract<BitRange<22, 23>, u8>,
@@ -1463,7 +1473,7 @@ fn parse_logical_imm(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b10, _) => AArch64Instr::EorImm64(data),
                     (0b1, 0b11, _) => AArch64Instr::AndsImm64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1474,7 +1484,7 @@ fn parse_logical_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1483,7 +1493,7 @@ fn parse_load_store_reg_pair_offset(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_101_x_010_x_xxxxxxx_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
@@ -1513,7 +1523,7 @@ fn parse_load_store_reg_pair_offset(raw_instr: u32) -> AArch64Instr {
                     (0b10, 0b0, 0b1) => AArch64Instr::LdpVar64(data),
                     (0b10, 0b1, 0b0) => AArch64Instr::StpSimdFpVar128(data),
                     (0b10, 0b1, 0b1) => AArch64Instr::LdpSimdFpVar128(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1524,7 +1534,7 @@ fn parse_load_store_reg_pair_offset(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1533,7 +1543,7 @@ fn parse_add_sub_imm_with_tags(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_100011_x_xxxxxx_xx_xxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              o2: Extract<BitRange<22, 23>, u8>,
              uimm6: Extract<BitRange<16, 22>, u8>,
@@ -1553,7 +1563,7 @@ fn parse_add_sub_imm_with_tags(raw_instr: u32) -> AArch64Instr {
                 match (sf_op_s.value, o2.value) {
                     (0b100, 0b0) => AArch64Instr::Addg(data),
                     (0b110, 0b0) => AArch64Instr::Subg(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1564,7 +1574,7 @@ fn parse_add_sub_imm_with_tags(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1573,7 +1583,7 @@ fn parse_extract(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_100111_x_x_xxxxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op21: Extract<BitRange<29, 32>, u8>,
              n: Extract<BitRange<22, 23>, u8>,
              o0: Extract<BitRange<21, 22>, u8>,
@@ -1593,7 +1603,7 @@ fn parse_extract(raw_instr: u32) -> AArch64Instr {
                         AArch64Instr::Extr32(data)
                     }
                     (0b100, 1, 0, _) => AArch64Instr::Extr64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1604,7 +1614,7 @@ fn parse_extract(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1613,7 +1623,7 @@ fn parse_data_proc_1src(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_1_x_11010110_xxxxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              opcode2: Extract<BitRange<16, 21>, u8>,
@@ -1637,7 +1647,7 @@ fn parse_data_proc_1src(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b0, 0b00000, 0b000011) => AArch64Instr::RevVar64(data),
                     (0b1, 0b0, 0b00000, 0b000100) => AArch64Instr::ClzVar64(data),
                     (0b1, 0b0, 0b00000, 0b000101) => AArch64Instr::ClsVar64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1648,7 +1658,7 @@ fn parse_data_proc_1src(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1657,7 +1667,7 @@ fn parse_cmp_and_branch_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_011010_x_xxxxxxxxxxxxxxxxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              op: Extract<BitRange<24, 25>, u8>,
              imm19: Extract<BitRange<5, 24>, u32>,
@@ -1672,7 +1682,7 @@ fn parse_cmp_and_branch_imm(raw_instr: u32) -> AArch64Instr {
                     (0b0, 0b1) => AArch64Instr::Cbnz32(data),
                     (0b1, 0b0) => AArch64Instr::Cbz64(data),
                     (0b1, 0b1) => AArch64Instr::Cbnz64(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1683,7 +1693,7 @@ fn parse_cmp_and_branch_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1692,7 +1702,7 @@ fn parse_data_proccessing_3src(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_xx_11011_xxx_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              op54: Extract<BitRange<29, 31>, u8>,
              op31: Extract<BitRange<21, 24>, u8>,
@@ -1719,7 +1729,7 @@ fn parse_data_proccessing_3src(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b00, 0b101, 0b0) => AArch64Instr::Umaddl(data),
                     (0b1, 0b00, 0b101, 0b1) => AArch64Instr::Umsubl(data),
                     (0b1, 0b00, 0b110, 0b0) => AArch64Instr::Umulh(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1730,7 +1740,7 @@ fn parse_data_proccessing_3src(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1739,7 +1749,7 @@ fn parse_load_store_reg_unscaled_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_00_xx_0_xxxxxxxxx_00_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              opc: Extract<BitRange<22, 24>, u8>,
@@ -1778,7 +1788,7 @@ fn parse_load_store_reg_unscaled_imm(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b1, 0b00) => AArch64Instr::SturSimdFP64(data),
                     (0b11, 0b1, 0b01) => AArch64Instr::LdurSimdFP64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1789,7 +1799,7 @@ fn parse_load_store_reg_unscaled_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1798,7 +1808,7 @@ fn parse_sys_reg_mov(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "1101010100_x_1_x_xxx_xxxx_xxxx_xxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              l: Extract<BitRange<21, 22>, u8>,
              o0: Extract<BitRange<19, 20>, u8>,
              op1: Extract<BitRange<16, 19>, u8>,
@@ -1818,7 +1828,7 @@ fn parse_sys_reg_mov(raw_instr: u32) -> AArch64Instr {
                 match l.value {
                     0 => AArch64Instr::MsrReg(data),
                     1 => AArch64Instr::Mrs(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1829,7 +1839,7 @@ fn parse_sys_reg_mov(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1838,7 +1848,7 @@ fn parse_load_store_reg_pair_pre_indexed(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_101_x_011_x_xxxxxxx_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
@@ -1868,7 +1878,7 @@ fn parse_load_store_reg_pair_pre_indexed(raw_instr: u32) -> AArch64Instr {
                     (0b10, 0b0, 0b1) => AArch64Instr::LdpVar64(data),
                     (0b10, 0b1, 0b0) => AArch64Instr::StpSimdFpVar128(data),
                     (0b10, 0b1, 0b1) => AArch64Instr::LdpSimdFpVar128(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1879,7 +1889,7 @@ fn parse_load_store_reg_pair_pre_indexed(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1888,7 +1898,7 @@ fn parse_load_store_reg_pair_post_indexed(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_101_x_001_x_xxxxxxx_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
@@ -1918,7 +1928,7 @@ fn parse_load_store_reg_pair_post_indexed(raw_instr: u32) -> AArch64Instr {
                     (0b10, 0b0, 0b1) => AArch64Instr::LdpVar64(data),
                     (0b10, 0b1, 0b0) => AArch64Instr::StpSimdFpVar128(data),
                     (0b10, 0b1, 0b1) => AArch64Instr::LdpSimdFpVar128(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1929,7 +1939,7 @@ fn parse_load_store_reg_pair_post_indexed(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1938,7 +1948,7 @@ fn parse_data_proc_2src(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11010110_xxxxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
@@ -1966,7 +1976,7 @@ fn parse_data_proc_2src(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b0, 0b001011) => AArch64Instr::RorvVar64(data),
 
                     (0b1, 0b0, 0b001100) => AArch64Instr::Pacga(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -1977,7 +1987,7 @@ fn parse_data_proc_2src(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -1986,7 +1996,7 @@ fn parse_load_store_reg_imm_pre_indexed(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_00_xx_0_xxxxxxxxx_11_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              idxt: Extract<BitRange<24, 26>, u8>, // Indexing type
@@ -2029,7 +2039,7 @@ fn parse_load_store_reg_imm_pre_indexed(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b1, 0b00) => AArch64Instr::StrImmSimdFP64(data),
                     (0b11, 0b1, 0b01) => AArch64Instr::LdrImmSimdFP64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2040,7 +2050,7 @@ fn parse_load_store_reg_imm_pre_indexed(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2049,7 +2059,7 @@ fn parse_load_store_reg_imm_post_indexed(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_00_xx_0_xxxxxxxxx_01_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              idxt: Extract<BitRange<24, 26>, u8>,
@@ -2092,7 +2102,7 @@ fn parse_load_store_reg_imm_post_indexed(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b1, 0b00) => AArch64Instr::StrImmSimdFP64(data),
                     (0b11, 0b1, 0b01) => AArch64Instr::LdrImmSimdFP64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2103,7 +2113,7 @@ fn parse_load_store_reg_imm_post_indexed(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2112,7 +2122,7 @@ fn parse_barriers(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "11010101000000110011_xxxx_xxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              crm: Extract<BitRange<8, 12>, u8>,
              op2: Extract<BitRange<5, 8>, u8>,
              rt: Extract<BitRange<0, 5>, u8>| {
@@ -2124,7 +2134,7 @@ fn parse_barriers(raw_instr: u32) -> AArch64Instr {
                     (_, 0b101, 0b11111) => AArch64Instr::Dmb(data),
                     (_, 0b110, 0b11111) => AArch64Instr::Isb(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2135,7 +2145,7 @@ fn parse_barriers(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2144,7 +2154,7 @@ fn parse_advanced_simd_copy(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_01110000_xxxxx_0_xxxx_1_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              op: Extract<BitRange<29, 30>, u8>,
              imm5: Extract<BitRange<16, 21>, u8>,
@@ -2168,7 +2178,7 @@ fn parse_advanced_simd_copy(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b0, _, 0b0011) => AArch64Instr::InsGeneral(data),
                     (0b1, 0b1, _, _) => AArch64Instr::InsElement(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2179,7 +2189,7 @@ fn parse_advanced_simd_copy(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2188,7 +2198,7 @@ fn parse_cond_cmp_reg(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_11010010_xxxxx_xxxx_0_x_xxxxx_x_xxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
              cond: Extract<BitRange<12, 16>, u8>,
@@ -2209,7 +2219,7 @@ fn parse_cond_cmp_reg(raw_instr: u32) -> AArch64Instr {
                     (0b101, 0b0, 0b0) => AArch64Instr::CcmnRegVar64(data),
                     (0b111, 0b0, 0b0) => AArch64Instr::CcmpRegVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2220,7 +2230,7 @@ fn parse_cond_cmp_reg(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2229,7 +2239,7 @@ fn parse_adv_simd_ld_st_multi_structures(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_0011000_x_000000_xxxx_xx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              opcode: Extract<BitRange<12, 16>, u8>,
@@ -2260,7 +2270,7 @@ fn parse_adv_simd_ld_st_multi_structures(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b1000) => AArch64Instr::Ld2MulStructures(data),
                     (0b1, 0b1010) => AArch64Instr::Ld1MulStructures2RegsVar(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2271,7 +2281,7 @@ fn parse_adv_simd_ld_st_multi_structures(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2280,7 +2290,7 @@ fn parse_advanced_simd_extract(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_101110_xx_0_xxxxx_0_xxxx_0_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              op2: Extract<BitRange<22, 24>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
@@ -2297,7 +2307,7 @@ fn parse_advanced_simd_extract(raw_instr: u32) -> AArch64Instr {
 
                 match op2.value {
                     0b00 => AArch64Instr::Ext(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2308,7 +2318,7 @@ fn parse_advanced_simd_extract(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2317,7 +2327,7 @@ fn parse_adv_simd_ld_st_multi_structures_post_indexed(raw_instr: u32) -> AArch64
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_0011001_x_0_xxxxx_xxxx_xx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
@@ -2393,7 +2403,7 @@ fn parse_adv_simd_ld_st_multi_structures_post_indexed(raw_instr: u32) -> AArch64
                     (0b1, 0b11111, 0b0111) => AArch64Instr::Ld1MulStructures1RegImmOffsetVar(data),
                     (0b1, 0b11111, 0b1000) => AArch64Instr::Ld2MulStructuresImmOffsetVar(data),
                     (0b1, 0b11111, 0b1010) => AArch64Instr::Ld1MulStructures2RegImmOffsetVar(data),
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2404,7 +2414,7 @@ fn parse_adv_simd_ld_st_multi_structures_post_indexed(raw_instr: u32) -> AArch64
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2413,7 +2423,7 @@ fn parse_conv_between_float_and_int(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_1_xx_xxx_000000_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -2597,7 +2607,7 @@ fn parse_conv_between_float_and_int(raw_instr: u32) -> AArch64Instr {
                         AArch64Instr::FmovGeneral64toTopHalfOf128(data)
                     }
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2608,7 +2618,7 @@ fn parse_conv_between_float_and_int(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2617,7 +2627,7 @@ fn parse_adv_simd_modified_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_01111_00000_x_x_x_xxxx_x_1_x_x_x_x_x_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              op: Extract<BitRange<29, 30>, u8>,
              a: Extract<BitRange<18, 19>, u8>,
@@ -2671,7 +2681,7 @@ fn parse_adv_simd_modified_imm(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b1, 1, 1, 1, 0, 0b0) => AArch64Instr::MoviVectorVar64(data),
                     (0b1, 0b1, 1, 1, 1, 1, 0b0) => AArch64Instr::FmovVecImmDoublePrecisionVar(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2682,7 +2692,7 @@ fn parse_adv_simd_modified_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2691,7 +2701,7 @@ fn parse_cond_cmp_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_11010010_xxxxx_xxxx_1_x_xxxxx_x_xxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              imm5: Extract<BitRange<16, 21>, u8>,
              cond: Extract<BitRange<12, 16>, u8>,
@@ -2712,7 +2722,7 @@ fn parse_cond_cmp_imm(raw_instr: u32) -> AArch64Instr {
                     (0b101, 0b0, 0b0) => AArch64Instr::CcmnImmVar64(data),
                     (0b111, 0b0, 0b0) => AArch64Instr::CcmpImmVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2723,7 +2733,7 @@ fn parse_cond_cmp_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2732,7 +2742,7 @@ fn parse_load_store_exclusive_register(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_0010000_x_0_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              rs: Extract<BitRange<16, 21>, u8>,
@@ -2766,7 +2776,7 @@ fn parse_load_store_exclusive_register(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b0, 0b1) => AArch64Instr::StlxrVar64(data),
                     (0b11, 0b1, 0b1) => AArch64Instr::LdaxrVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2777,7 +2787,7 @@ fn parse_load_store_exclusive_register(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2786,7 +2796,7 @@ fn parse_load_store_ordered(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_0010001_x_0_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              rs: Extract<BitRange<16, 21>, u8>,
@@ -2811,7 +2821,7 @@ fn parse_load_store_ordered(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b0, 0b1) => AArch64Instr::StlrVar64(data),
                     (0b11, 0b1, 0b1) => AArch64Instr::LdarVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2822,7 +2832,7 @@ fn parse_load_store_ordered(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2831,7 +2841,7 @@ fn parse_advanced_simd_three_same(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_01110_xx_1_xxxxx_xxxxx_1_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              u: Extract<BitRange<29, 30>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
@@ -2936,7 +2946,7 @@ fn parse_advanced_simd_three_same(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b10, 0b00011) => AArch64Instr::Bit(data),
                     (0b1, 0b11, 0b00011) => AArch64Instr::Bif(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -2947,7 +2957,7 @@ fn parse_advanced_simd_three_same(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -2956,7 +2966,7 @@ fn parse_adv_simd_shift_by_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_011110_xxxx_xxx_xxxxx_1_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              u: Extract<BitRange<29, 30>, u8>,
              immb: Extract<BitRange<16, 19>, u8>,
@@ -3004,7 +3014,7 @@ fn parse_adv_simd_shift_by_imm(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b11100) => AArch64Instr::UcvtfVecFixedPt(data),
                     (0b1, 0b11111) => AArch64Instr::FcvtzuVecFixedPt(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3015,7 +3025,7 @@ fn parse_adv_simd_shift_by_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3024,7 +3034,7 @@ fn parse_float_data_proc_1src(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_1_xxxxxx_10000_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              m: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -3101,7 +3111,7 @@ fn parse_float_data_proc_1src(raw_instr: u32) -> AArch64Instr {
                         AArch64Instr::FrintiScalarDoublePrecisionVar(data)
                     }
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3112,7 +3122,7 @@ fn parse_float_data_proc_1src(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3121,7 +3131,7 @@ fn parse_adv_simd_scalar_pairwise(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "01_x_11110_xx_11000_xxxxx_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              u: Extract<BitRange<29, 30>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
              opcode: Extract<BitRange<12, 17>, u8>,
@@ -3141,7 +3151,7 @@ fn parse_adv_simd_scalar_pairwise(raw_instr: u32) -> AArch64Instr {
                     (0b0, 0b10 | 0b11, 0b01100) => AArch64Instr::FminnmpScalarEncoding(data),
                     (0b0, 0b10 | 0b11, 11) => AArch64Instr::FminpScalarEncoding(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3152,7 +3162,7 @@ fn parse_adv_simd_scalar_pairwise(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3161,7 +3171,7 @@ fn parse_adv_simd_ld_st_single_structure(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_0011010_x_x_00000_xxx_x_xx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              r: Extract<BitRange<21, 22>, u8>,
@@ -3244,7 +3254,7 @@ fn parse_adv_simd_ld_st_single_structure(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b1, 0b110, 0b0, _) => AArch64Instr::Ld2r(data),
                     (0b1, 0b1, 0b111, 0b0, _) => AArch64Instr::Ld2r(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3255,7 +3265,7 @@ fn parse_adv_simd_ld_st_single_structure(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3264,7 +3274,7 @@ fn parse_adv_simd_2reg_miscellaneous(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_01110_xx_10000_xxxxx_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              u: Extract<BitRange<29, 30>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
@@ -3348,7 +3358,7 @@ fn parse_adv_simd_2reg_miscellaneous(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b10 | 0b11, 0b11101) => AArch64Instr::Frsqrte(data),
                     (0b1, 0b10 | 0b11, 0b11111) => AArch64Instr::FsqrtVec(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3359,7 +3369,7 @@ fn parse_adv_simd_2reg_miscellaneous(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3368,7 +3378,7 @@ fn parse_adv_simd_across_lanes(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_01110_xx_11000_xxxxx_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              u: Extract<BitRange<29, 30>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
@@ -3398,7 +3408,7 @@ fn parse_adv_simd_across_lanes(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b10 | 0b11, 0b01100) => AArch64Instr::FminnmvEncoding(data),
                     (0b1, 0b10 | 0b11, 0b01111) => AArch64Instr::FminvEncoding(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3409,7 +3419,7 @@ fn parse_adv_simd_across_lanes(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3418,7 +3428,7 @@ fn parse_compare_and_swap(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_0010001_x_1_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              rs: Extract<BitRange<16, 21>, u8>,
@@ -3453,7 +3463,7 @@ fn parse_compare_and_swap(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b1, 0b0, 0b11111) => AArch64Instr::CasaVar64(data),
                     (0b11, 0b1, 0b1, 0b11111) => AArch64Instr::CasalVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3464,7 +3474,7 @@ fn parse_compare_and_swap(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3473,7 +3483,7 @@ fn parse_atomic_memory_operations(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_00_x_x_1_xxxxx_x_xxx_00_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              a: Extract<BitRange<23, 24>, u8>,
@@ -3663,7 +3673,7 @@ fn parse_atomic_memory_operations(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b0, 0b1, 0b1, _, 0b0, 0b111) => AArch64Instr::LduminalVar64(data),
                     (0b11, 0b0, 0b1, 0b1, _, 0b1, 0b000) => AArch64Instr::SwpalVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3674,7 +3684,7 @@ fn parse_atomic_memory_operations(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3683,7 +3693,7 @@ fn parse_add_sub_with_carry(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_11010000_xxxxx_000000_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
              rn: Extract<BitRange<5, 10>, u8>,
@@ -3705,7 +3715,7 @@ fn parse_add_sub_with_carry(raw_instr: u32) -> AArch64Instr {
                     0b110 => AArch64Instr::SbcVar64(data),
                     0b111 => AArch64Instr::SbcsVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3716,7 +3726,7 @@ fn parse_add_sub_with_carry(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3725,7 +3735,7 @@ fn parse_floating_point_compare(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_1_xxxxx_xx_1000_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              m: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -3749,7 +3759,7 @@ fn parse_floating_point_compare(raw_instr: u32) -> AArch64Instr {
                     | (0b0, 0b0, 0b01, 0b00, 0b10000 | 0b11000)
                     | (0b0, 0b0, 0b11, 0b01, 0b10000 | 0b11000) => AArch64Instr::Fcmp(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3760,7 +3770,7 @@ fn parse_floating_point_compare(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3769,7 +3779,7 @@ fn parse_advanced_simd_permute(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_001110_xx_0_xxxxx_0_xxx_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<30, 31>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
              rm: Extract<BitRange<16, 21>, u8>,
@@ -3793,7 +3803,7 @@ fn parse_advanced_simd_permute(raw_instr: u32) -> AArch64Instr {
                     0b110 => AArch64Instr::Trn2(data),
                     0b111 => AArch64Instr::Zip2(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3804,7 +3814,7 @@ fn parse_advanced_simd_permute(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3813,7 +3823,7 @@ fn parse_float_data_proc_2src(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_1_xxxxx_xxxx_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              m: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -3848,7 +3858,7 @@ fn parse_float_data_proc_2src(raw_instr: u32) -> AArch64Instr {
                     (0b0, 0b0, 0b01, 0b0111) => AArch64Instr::FminnmScalarDoublePrecisionVar(data),
                     (0b0, 0b0, 0b01, 0b1000) => AArch64Instr::FnmulScalarDoublePrecisionVar(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3859,7 +3869,7 @@ fn parse_float_data_proc_2src(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3868,7 +3878,7 @@ fn parse_floating_point_immediate(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_1_xxxxxxxx_100_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              m: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -3888,7 +3898,7 @@ fn parse_floating_point_immediate(raw_instr: u32) -> AArch64Instr {
                         AArch64Instr::FmovScalarImmDoublePrecisionVar(data)
                     }
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3899,7 +3909,7 @@ fn parse_floating_point_immediate(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -3908,7 +3918,7 @@ fn parse_conv_between_float_and_fixed_point(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_0_xx_xxx_xxxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -3983,7 +3993,7 @@ fn parse_conv_between_float_and_fixed_point(raw_instr: u32) -> AArch64Instr {
                         AArch64Instr::FcvtzuScalarFixedPtDoublePrecisionTo64(data)
                     }
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -3994,7 +4004,7 @@ fn parse_conv_between_float_and_fixed_point(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4003,7 +4013,7 @@ fn parse_floating_point_conditional_select(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_0_x_11110_xx_1_xxxxx_xxxx_11_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              m: Extract<BitRange<31, 32>, u8>,
              s: Extract<BitRange<29, 30>, u8>,
              ptype: Extract<BitRange<22, 24>, u8>,
@@ -4022,7 +4032,7 @@ fn parse_floating_point_conditional_select(raw_instr: u32) -> AArch64Instr {
                     (0b0, 0b0, 0b00) => AArch64Instr::FcselSinglePrecisionVar(data),
                     (0b0, 0b0, 0b01) => AArch64Instr::FcselDoublePrecisionVar(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4033,7 +4043,7 @@ fn parse_floating_point_conditional_select(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4042,7 +4052,7 @@ fn parse_adv_simd_vec_x_indexed_elem(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_x_01111_xx_x_x_xxxx_xxxx_x_0_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              q: Extract<BitRange<31, 32>, u8>,
              u: Extract<BitRange<29, 30>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
@@ -4087,7 +4097,7 @@ fn parse_adv_simd_vec_x_indexed_elem(raw_instr: u32) -> AArch64Instr {
 
                     (0b1, 0b10 | 0b11, 0b1001) => AArch64Instr::FmulxByElemEncoding(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4098,7 +4108,7 @@ fn parse_adv_simd_vec_x_indexed_elem(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4107,7 +4117,7 @@ fn parse_adv_simd_scalar_x_indexed_elem(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "01_x_11111_xx_x_x_xxxx_xxxx_x_0_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              u: Extract<BitRange<29, 30>, u8>,
              size: Extract<BitRange<22, 24>, u8>,
              l: Extract<BitRange<21, 22>, u8>,
@@ -4141,7 +4151,7 @@ fn parse_adv_simd_scalar_x_indexed_elem(raw_instr: u32) -> AArch64Instr {
 
                     (0b1, 0b10 | 0b11, 0b1001) => AArch64Instr::FmulxByElemEncoding(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4152,7 +4162,7 @@ fn parse_adv_simd_scalar_x_indexed_elem(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4161,7 +4171,7 @@ fn parse_sys_instr_with_reg_arg(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "11010101000000110001_xxxx_xxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              crm: Extract<BitRange<8, 12>, u8>,
              op2: Extract<BitRange<5, 8>, u8>,
              rt: Extract<BitRange<0, 5>, u8>| {
@@ -4171,7 +4181,7 @@ fn parse_sys_instr_with_reg_arg(raw_instr: u32) -> AArch64Instr {
                     (0b0000, 0b000) => AArch64Instr::Wfet(data),
                     (0b0000, 0b001) => AArch64Instr::Wfit(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4182,7 +4192,7 @@ fn parse_sys_instr_with_reg_arg(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4191,7 +4201,7 @@ fn parse_pstate(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "1101010100000_xxx_0100_xxxx_xxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              op1: Extract<BitRange<16, 19>, u8>,
              crm: Extract<BitRange<8, 12>, u8>,
              op2: Extract<BitRange<5, 8>, u8>,
@@ -4208,7 +4218,7 @@ fn parse_pstate(raw_instr: u32) -> AArch64Instr {
                     (0b000, 0b010, 0b11111) => AArch64Instr::Axflag(data),
                     (_, _, 0b11111) => AArch64Instr::MsrImm(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4219,7 +4229,7 @@ fn parse_pstate(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4228,7 +4238,7 @@ fn parse_sys_with_result(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "1101010100000_xxx_0100_xxxx_xxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              op1: Extract<BitRange<16, 19>, u8>,
              crn: Extract<BitRange<12, 16>, u8>,
              crm: Extract<BitRange<8, 12>, u8>,
@@ -4240,7 +4250,7 @@ fn parse_sys_with_result(raw_instr: u32) -> AArch64Instr {
                     (0b011, 0b0011, 0b0000, 0b011) => AArch64Instr::Tstart(data),
                     (0b011, 0b0011, 0b0001, 0b011) => AArch64Instr::Ttest(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4251,7 +4261,7 @@ fn parse_sys_with_result(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4259,8 +4269,8 @@ fn parse_sys_instr(raw_instr: u32) -> AArch64Instr {
     pub static MATCHER: Lazy<BitPatternMatcher<AArch64Instr>> = Lazy::new(|| {
         let mut m = BitPatternMatcher::new();
         m.bind(
-            "1101010100000_xxx_0100_xxxx_xxx_xxxxx",
-            |raw_instr: u32,
+            "1101010100_x_01_xxx_xxxx_xxxx_xxx_xxxxx",
+            |_raw_instr: u32,
              l: Extract<BitRange<21, 22>, u8>,
              op1: Extract<BitRange<16, 19>, u8>,
              crn: Extract<BitRange<12, 16>, u8>,
@@ -4279,7 +4289,7 @@ fn parse_sys_instr(raw_instr: u32) -> AArch64Instr {
                     0b0 => AArch64Instr::Sys(data),
                     0b1 => AArch64Instr::Sysl(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4290,7 +4300,7 @@ fn parse_sys_instr(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4299,7 +4309,7 @@ fn parse_rot_right_into_flags(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_11010000_xxxxxx_00001_xxxxx_x_xxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              imm6: Extract<BitRange<15, 21>, u8>,
              rn: Extract<BitRange<5, 10>, u8>,
@@ -4314,7 +4324,7 @@ fn parse_rot_right_into_flags(raw_instr: u32) -> AArch64Instr {
                 match (sf_op_s.value, o2.value) {
                     (0b101, 0b0) => AArch64Instr::Rmif(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4325,7 +4335,7 @@ fn parse_rot_right_into_flags(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4334,7 +4344,7 @@ fn parse_eval_into_flags(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "x_x_x_11010000_xxxxxx_x_0010_xxxxx_x_xxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sf_op_s: Extract<BitRange<29, 32>, u8>,
              opcode2: Extract<BitRange<15, 21>, u8>,
              sz: Extract<BitRange<14, 15>, u8>,
@@ -4347,7 +4357,7 @@ fn parse_eval_into_flags(raw_instr: u32) -> AArch64Instr {
                     (0b001, 0b000000, 0b0, 0b0, 0b1101) => AArch64Instr::SetfVar8(data),
                     (0b001, 0b000000, 0b1, 0b0, 0b1101) => AArch64Instr::SetfVar16(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4358,7 +4368,7 @@ fn parse_eval_into_flags(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4367,7 +4377,7 @@ fn parse_load_register_literal(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_011_x_00_xxxxxxxxxxxxxxxxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              imm19: Extract<BitRange<5, 24>, u32>,
@@ -4386,7 +4396,7 @@ fn parse_load_register_literal(raw_instr: u32) -> AArch64Instr {
                     (0b10, 0b1) => AArch64Instr::LdrLitSimdFPVar128(data),
                     (0b11, 0b0) => AArch64Instr::PrfmLit(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4397,7 +4407,7 @@ fn parse_load_register_literal(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4406,7 +4416,7 @@ fn parse_compare_and_swap_pair(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "0_x_0010000_x_1_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sz: Extract<BitRange<30, 31>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              rs: Extract<BitRange<16, 21>, u8>,
@@ -4431,7 +4441,7 @@ fn parse_compare_and_swap_pair(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b1, 0b0, 0b11111) => AArch64Instr::CaspaVar64(data),
                     (0b1, 0b1, 0b1, 0b11111) => AArch64Instr::CaspalVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4442,7 +4452,7 @@ fn parse_compare_and_swap_pair(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4451,7 +4461,7 @@ fn parse_load_store_memory_tags(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "11011001_xx_1_xxxxxxxxx_xx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<22, 24>, u8>,
              imm9: Extract<BitRange<12, 221>, u16>,
              op2: Extract<BitRange<10, 12>, u8>,
@@ -4474,7 +4484,7 @@ fn parse_load_store_memory_tags(raw_instr: u32) -> AArch64Instr {
                     (0b11, _, 0b01 | 0b10 | 0b11) => AArch64Instr::Stz2gEncoding(data),
                     (0b11, 0b000000000, 0b00) => AArch64Instr::Ldgm(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4485,7 +4495,7 @@ fn parse_load_store_memory_tags(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4494,7 +4504,7 @@ fn parse_load_store_exclusive_pair(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "1_x_0010000_x_1_xxxxx_x_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              sz: Extract<BitRange<30, 31>, u8>,
              l: Extract<BitRange<22, 23>, u8>,
              rs: Extract<BitRange<16, 21>, u8>,
@@ -4520,7 +4530,7 @@ fn parse_load_store_exclusive_pair(raw_instr: u32) -> AArch64Instr {
                     (0b1, 0b1, 0b0) => AArch64Instr::LdxpVar64(data),
                     (0b1, 0b1, 0b1) => AArch64Instr::LdaxpVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4531,7 +4541,7 @@ fn parse_load_store_exclusive_pair(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4540,7 +4550,7 @@ fn parse_ldapr_stlr_unscaled_imm(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_011001_xx_0_xxxxxxxxx_00_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              opc: Extract<BitRange<22, 24>, u8>,
              imm9: Extract<BitRange<12, 21>, u16>,
@@ -4570,7 +4580,7 @@ fn parse_ldapr_stlr_unscaled_imm(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b00) => AArch64Instr::StlurVar64(data),
                     (0b11, 0b01) => AArch64Instr::LdapurVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4581,7 +4591,7 @@ fn parse_ldapr_stlr_unscaled_imm(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4590,7 +4600,7 @@ fn parse_ld_st_no_alloc_pair_offset(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_101_x_000_x_xxxxxxx_xxxxx_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              opc: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              l: Extract<BitRange<22, 23>, u16>,
@@ -4618,7 +4628,7 @@ fn parse_ld_st_no_alloc_pair_offset(raw_instr: u32) -> AArch64Instr {
                     (0b10, 0b1, 0b0) => AArch64Instr::StnpSimdFPVar128(data),
                     (0b10, 0b1, 0b1) => AArch64Instr::LdnpSimdFPVar128(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4629,7 +4639,7 @@ fn parse_ld_st_no_alloc_pair_offset(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
 
@@ -4638,7 +4648,7 @@ fn parse_load_store_reg_unprivileged(raw_instr: u32) -> AArch64Instr {
         let mut m = BitPatternMatcher::new();
         m.bind(
             "xx_111_x_00_xx_0_xxxxxxxxx_10_xxxxx_xxxxx",
-            |raw_instr: u32,
+            |_raw_instr: u32,
              size: Extract<BitRange<30, 32>, u8>,
              v: Extract<BitRange<26, 27>, u8>,
              opc: Extract<BitRange<22, 24>, u8>,
@@ -4669,7 +4679,7 @@ fn parse_load_store_reg_unprivileged(raw_instr: u32) -> AArch64Instr {
                     (0b11, 0b0, 0b00) => AArch64Instr::SttrVar64(data),
                     (0b11, 0b0, 0b01) => AArch64Instr::LdtrVar64(data),
 
-                    _ => todo!("Unknown instruction {:032b}", raw_instr),
+                    _ => AArch64Instr::Unallocated,
                 }
             },
         );
@@ -4680,6 +4690,6 @@ fn parse_load_store_reg_unprivileged(raw_instr: u32) -> AArch64Instr {
     if let Some(instr) = MATCHER.handle(raw_instr) {
         instr
     } else {
-        todo!("Unknown instruction {:032b}", raw_instr);
+        AArch64Instr::Undecoded
     }
 }
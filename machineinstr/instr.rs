@@ -2,4 +2,5 @@
 pub struct NativeInstr<I> {
     pub op: I,
     pub size: u8,
+    pub raw: u32, // Encoding of the instruction.
 }
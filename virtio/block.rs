@@ -0,0 +1,314 @@
+use core::softmmu::Mmu;
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::{DescriptorChain, Queue, VirtIo, VirtIoError, DEVICE_BLOCK};
+
+// Features
+const F_SEG_MAX: u64 = 1 << 2;
+const F_RO: u64 = 1 << 5;
+const F_FLUSH: u64 = 1 << 9;
+
+// Request types
+const T_IN: u32 = 0;
+const T_OUT: u32 = 1;
+const T_FLUSH: u32 = 4;
+const T_GET_ID: u32 = 8;
+
+// Request status
+const S_OK: u8 = 0;
+const S_IOERR: u8 = 1;
+const S_UNSUPP: u8 = 2;
+
+const HEADER_SIZE: usize = 16;
+const ID_SIZE: usize = 20;
+
+pub const SECTOR_SIZE: u64 = 512;
+
+const QUEUE_SIZE: u16 = 128;
+
+// How writes of the guest reach the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiskMode {
+    ReadOnly,
+    ReadWrite,
+    // Writes are kept in memory, and discarded when the emulator exits.
+    Snapshot,
+}
+
+// Virtio block device backed by a raw image file, with a single request queue.
+//
+// The capacity is the size of the image rounded down to sectors.
+pub struct Block {
+    file: File,
+    mode: DiskMode,
+    capacity: u64,
+    overlay: HashMap<u64, Box<[u8]>>, // Sectors written in snapshot mode.
+}
+
+impl Block {
+    pub fn open(path: impl AsRef<Path>, mode: DiskMode) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(mode == DiskMode::ReadWrite)
+            .open(path)?;
+        let capacity = file.metadata()?.len() / SECTOR_SIZE;
+
+        Ok(Self {
+            file,
+            mode,
+            capacity,
+            overlay: HashMap::new(),
+        })
+    }
+
+    // Capacity in sectors.
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    fn config(&self) -> [u8; 16] {
+        let mut config = [0u8; 16];
+        config[0..8].copy_from_slice(&self.capacity.to_le_bytes());
+        // seg_max, leaving room for the header and the status.
+        config[12..16].copy_from_slice(&(QUEUE_SIZE as u32 - 2).to_le_bytes());
+        config
+    }
+
+    // Check that `len` bytes at `sector` are whole sectors in the disk.
+    fn check_range(&self, sector: u64, len: usize) -> io::Result<()> {
+        let sectors = (len as u64).div_ceil(SECTOR_SIZE);
+        match sector.checked_add(sectors) {
+            Some(end) if end <= self.capacity && (len as u64).is_multiple_of(SECTOR_SIZE) => Ok(()),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> io::Result<()> {
+        self.check_range(sector, buf.len())?;
+        self.file.read_exact_at(buf, sector * SECTOR_SIZE)?;
+
+        for (i, chunk) in buf.chunks_mut(SECTOR_SIZE as usize).enumerate() {
+            if let Some(data) = self.overlay.get(&(sector + i as u64)) {
+                chunk.copy_from_slice(data);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, sector: u64, buf: &[u8]) -> io::Result<()> {
+        self.check_range(sector, buf.len())?;
+
+        match self.mode {
+            DiskMode::ReadOnly => Err(io::ErrorKind::PermissionDenied.into()),
+            DiskMode::ReadWrite => self.file.write_all_at(buf, sector * SECTOR_SIZE),
+            DiskMode::Snapshot => {
+                for (i, chunk) in buf.chunks(SECTOR_SIZE as usize).enumerate() {
+                    self.overlay.insert(sector + i as u64, chunk.into());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Execute a request, and return data for its writable buffers and the status.
+    fn execute(&mut self, chain: &DescriptorChain, mem: &Mmu) -> Result<Vec<u8>, VirtIoError> {
+        let readable = chain.read_all(mem)?;
+        if readable.len() < HEADER_SIZE || chain.writable_len() == 0 {
+            return Err(VirtIoError::InvalidChain(chain.head()));
+        }
+
+        let request = u32::from_le_bytes(readable[0..4].try_into().unwrap());
+        let sector = u64::from_le_bytes(readable[8..16].try_into().unwrap());
+        let data_len = chain.writable_len() - 1;
+
+        let (mut data, status) = match request {
+            T_IN => {
+                let mut data = vec![0u8; data_len];
+                let status = io_status(self.read_sectors(sector, &mut data));
+                (data, status)
+            }
+            T_OUT => {
+                let status = io_status(self.write_sectors(sector, &readable[HEADER_SIZE..]));
+                (Vec::new(), status)
+            }
+            T_FLUSH => (Vec::new(), io_status(self.file.sync_data())),
+            T_GET_ID => {
+                let mut id = b"crate-virtio-blk".to_vec();
+                id.resize(ID_SIZE.min(data_len), 0);
+                (id, S_OK)
+            }
+            _ => (Vec::new(), S_UNSUPP),
+        };
+
+        // The status is the last byte of the writable buffers.
+        data.resize(data_len, 0);
+        data.push(status);
+        Ok(data)
+    }
+}
+
+fn io_status(result: io::Result<()>) -> u8 {
+    match result {
+        Ok(()) => S_OK,
+        Err(_) => S_IOERR,
+    }
+}
+
+impl VirtIo for Block {
+    fn device_id(&self) -> u32 {
+        DEVICE_BLOCK
+    }
+
+    fn features(&self) -> u64 {
+        let ro = if self.mode == DiskMode::ReadOnly {
+            F_RO
+        } else {
+            0
+        };
+        F_SEG_MAX | F_FLUSH | ro
+    }
+
+    fn queue_sizes(&self) -> &[u16] {
+        &[QUEUE_SIZE]
+    }
+
+    fn read_config(&self, offset: u64, size: usize) -> u64 {
+        let config = self.config();
+        let mut bytes = [0u8; 8];
+        for (i, byte) in bytes.iter_mut().take(size).enumerate() {
+            *byte = config.get(offset as usize + i).copied().unwrap_or(0);
+        }
+
+        u64::from_le_bytes(bytes)
+    }
+
+    fn init(&mut self, _features: u64) {}
+
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Queue],
+        mem: &Mmu,
+    ) -> Result<bool, VirtIoError> {
+        let queue = &mut queues[queue];
+
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem)? {
+            let data = self.execute(&chain, mem)?;
+            let written = chain.write_all(mem, &data)?;
+
+            queue.add_used(mem, chain.head(), written as u32)?;
+            used = true;
+        }
+
+        Ok(used)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queue::test::*;
+    use crate::Descriptor;
+
+    use std::path::PathBuf;
+
+    // Image file of 4 sectors, where each sector is filled with its number.
+    fn image(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("virtio-{}-{}.img", name, std::process::id()));
+        let data: Vec<u8> = (0..4).flat_map(|i| [i; SECTOR_SIZE as usize]).collect();
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    // Issue a request whose data buffer has `len` bytes, and return its status.
+    fn request(block: &mut Block, mem: &Mmu, kind: u32, sector: u64, len: u32) -> u8 {
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(&kind.to_le_bytes());
+        header[8..16].copy_from_slice(&sector.to_le_bytes());
+        write(mem, 0x4000_8000, &header);
+
+        let header = Descriptor {
+            addr: 0x4000_8000,
+            len: HEADER_SIZE as u32,
+            writable: false,
+        };
+        let data = Descriptor {
+            addr: 0x4001_0000,
+            len,
+            writable: kind != T_OUT,
+        };
+        let status = Descriptor {
+            addr: 0x4000_9000,
+            len: 1,
+            writable: true,
+        };
+        set_desc(mem, 0, header, Some(1));
+        set_desc(mem, 1, data, Some(2));
+        set_desc(mem, 2, status, None);
+
+        let mut queues = [ready_queue(8)];
+        write(mem, AVAIL_RING + 2, &[0, 0]);
+        make_available(mem, 0, 0);
+        assert!(block.notify(0, &mut queues, mem).unwrap());
+
+        read(mem, status.addr, 1)[0]
+    }
+
+    #[test]
+    fn block_test() {
+        let path = image("block");
+        let mem = guest_memory();
+        let mut block = Block::open(&path, DiskMode::ReadWrite).unwrap();
+        assert_eq!(block.capacity(), 4);
+        assert_eq!(block.read_config(0, 8), 4);
+        assert_eq!(block.features() & F_RO, 0);
+
+        assert_eq!(request(&mut block, &mem, T_IN, 1, 1024), S_OK);
+        assert_eq!(
+            read(&mem, 0x4001_0000, 1024),
+            [[1u8; 512], [2u8; 512]].concat()
+        );
+
+        write(&mem, 0x4001_0000, &[0xaa; 512]);
+        assert_eq!(request(&mut block, &mem, T_OUT, 3, 512), S_OK);
+        assert_eq!(request(&mut block, &mem, T_FLUSH, 0, 0), S_OK);
+        assert_eq!(std::fs::read(&path).unwrap()[3 * 512..], [0xaa; 512]);
+
+        // Requests beyond the capacity fail.
+        assert_eq!(request(&mut block, &mem, T_IN, 3, 1024), S_IOERR);
+        assert_eq!(request(&mut block, &mem, 0xff, 0, 0), S_UNSUPP);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn block_snapshot_test() {
+        let path = image("snapshot");
+        let mem = guest_memory();
+
+        let mut block = Block::open(&path, DiskMode::ReadOnly).unwrap();
+        assert_eq!(block.features() & F_RO, F_RO);
+        assert_eq!(request(&mut block, &mem, T_OUT, 0, 512), S_IOERR);
+
+        // Writes are visible to the guest, but not written into the image.
+        let mut block = Block::open(&path, DiskMode::Snapshot).unwrap();
+        write(&mem, 0x4001_0000, &[0xbb; 512]);
+        assert_eq!(request(&mut block, &mem, T_OUT, 2, 512), S_OK);
+        write(&mem, 0x4001_0000, &[0; 1024]);
+        assert_eq!(request(&mut block, &mem, T_IN, 1, 1024), S_OK);
+        assert_eq!(
+            read(&mem, 0x4001_0000, 1024),
+            [[1u8; 512], [0xbb; 512]].concat()
+        );
+        assert_eq!(std::fs::read(&path).unwrap()[2 * 512..3 * 512], [2; 512]);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1 +1,181 @@
+use core::device::pl011::stdin_receiver;
+use core::softmmu::Mmu;
 
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::mpsc::Receiver;
+
+use crate::{Queue, VirtIo, VirtIoError, DEVICE_CONSOLE};
+
+const RECEIVEQ: usize = 0;
+const TRANSMITQ: usize = 1;
+
+const QUEUE_SIZE: u16 = 64;
+
+// Virtio console with a single port, and no device-specific features.
+//
+// Transmitted bytes are written into the host immediately. Received bytes are taken
+// from `rx` when the device is notified or polled, and are kept until the driver
+// makes receive buffers available.
+pub struct Console {
+    tx: Box<dyn Write + Send>,
+    rx: Option<Receiver<u8>>,
+    input: VecDeque<u8>,
+}
+
+impl Console {
+    pub fn new(tx: Box<dyn Write + Send>, rx: Option<Receiver<u8>>) -> Self {
+        Self {
+            tx,
+            rx,
+            input: VecDeque::new(),
+        }
+    }
+
+    // Console connected to stdout and stdin of the host.
+    pub fn stdio() -> Self {
+        Self::new(Box::new(std::io::stdout()), Some(stdin_receiver()))
+    }
+
+    fn transmit(&mut self, queue: &mut Queue, mem: &Mmu) -> Result<bool, VirtIoError> {
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem)? {
+            let bytes = chain.read_all(mem)?;
+            // Output is best effort, as a real console drops bytes nobody listens to.
+            let _ = self.tx.write_all(&bytes).and_then(|_| self.tx.flush());
+
+            queue.add_used(mem, chain.head(), 0)?;
+            used = true;
+        }
+
+        Ok(used)
+    }
+
+    fn receive(&mut self, queue: &mut Queue, mem: &Mmu) -> Result<bool, VirtIoError> {
+        if let Some(rx) = &self.rx {
+            self.input.extend(rx.try_iter());
+        }
+
+        let mut used = false;
+        while !self.input.is_empty() {
+            let Some(chain) = queue.pop(mem)? else {
+                break;
+            };
+
+            let len = chain.writable_len().min(self.input.len());
+            let bytes: Vec<u8> = self.input.drain(..len).collect();
+            let written = chain.write_all(mem, &bytes)?;
+
+            queue.add_used(mem, chain.head(), written as u32)?;
+            used = true;
+        }
+
+        Ok(used)
+    }
+}
+
+impl VirtIo for Console {
+    fn device_id(&self) -> u32 {
+        DEVICE_CONSOLE
+    }
+
+    fn features(&self) -> u64 {
+        0
+    }
+
+    fn queue_sizes(&self) -> &[u16] {
+        &[QUEUE_SIZE, QUEUE_SIZE]
+    }
+
+    // `cols`, `rows`, `max_nr_ports` and `emerg_wr` are not used without features.
+    fn read_config(&self, _offset: u64, _size: usize) -> u64 {
+        0
+    }
+
+    fn init(&mut self, _features: u64) {}
+
+    fn reset(&mut self) {
+        self.input.clear();
+    }
+
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Queue],
+        mem: &Mmu,
+    ) -> Result<bool, VirtIoError> {
+        match queue {
+            RECEIVEQ => self.receive(&mut queues[RECEIVEQ], mem),
+            TRANSMITQ => self.transmit(&mut queues[TRANSMITQ], mem),
+            _ => Ok(false),
+        }
+    }
+
+    fn poll(&mut self, queues: &mut [Queue], mem: &Mmu) -> Result<bool, VirtIoError> {
+        self.receive(&mut queues[RECEIVEQ], mem)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queue::test::*;
+    use crate::Descriptor;
+
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn console_test() {
+        let mem = guest_memory();
+        let output = Output::default();
+        let (sender, receiver) = channel();
+        let mut console = Console::new(Box::new(output.clone()), Some(receiver));
+        let mut queues = [ready_queue(8), ready_queue(8)];
+
+        let buffer = Descriptor {
+            addr: 0x4000_8000,
+            len: 5,
+            writable: false,
+        };
+        write(&mem, buffer.addr, b"hello");
+        set_desc(&mem, 0, buffer, None);
+        make_available(&mem, 0, 0);
+        assert!(console.notify(TRANSMITQ, &mut queues, &mem).unwrap());
+        assert_eq!(*output.0.lock().unwrap(), b"hello");
+
+        // Input is kept until a receive buffer is available. Queues of the test share
+        // their rings, so start over with empty ones.
+        for byte in *b"abc" {
+            sender.send(byte).unwrap();
+        }
+        let mut queues = [ready_queue(8), ready_queue(8)];
+        write(&mem, AVAIL_RING + 2, &[0, 0]);
+        assert!(!console.poll(&mut queues, &mem).unwrap());
+
+        let buffer = Descriptor {
+            addr: 0x4000_9000,
+            len: 2,
+            writable: true,
+        };
+        set_desc(&mem, 1, buffer, None);
+        make_available(&mem, 0, 1);
+        assert!(console.poll(&mut queues, &mem).unwrap());
+        assert_eq!(read(&mem, buffer.addr, 2), b"ab");
+        assert_eq!(console.input, [b'c']);
+    }
+}
@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+use core::error::MmuError;
+
+#[derive(Debug, Error, Clone)]
+pub enum VirtIoError {
+    #[error("Guest memory error: {0}")]
+    Memory(#[from] MmuError),
+
+    #[error("Invalid descriptor chain: {0}")]
+    InvalidChain(u16),
+
+    #[error("Buffer is not in guest memory: {0:#x}")]
+    NotMemory(u64),
+}
@@ -1,2 +1,248 @@
-// flatten device tree
-pub struct Fdt {}
+// Flattened device tree describing the board to the guest, in the layout of the
+// QEMU `virt` machine.
+//
+// Interrupts are given as GIC interrupt IDs, where PPIs are 16 to 31 and SPIs start
+// at 32.
+use std::ops::Range;
+
+use vm_fdt::{Error, FdtWriter};
+
+const PHANDLE_GIC: u32 = 1;
+const PHANDLE_CLOCK: u32 = 2;
+
+// Cells of `interrupts`.
+const GIC_SPI: u32 = 0;
+const GIC_PPI: u32 = 1;
+const IRQ_TYPE_LEVEL_HIGH: u32 = 4;
+
+// Secure and hypervisor timers are not emulated, but the binding requires their PPIs.
+const IRQ_TIMER_SECURE: u32 = 16 + 13;
+const IRQ_TIMER_HYP: u32 = 16 + 10;
+
+// Clock of the UART.
+const APB_CLOCK_FREQUENCY: u32 = 24_000_000;
+
+#[derive(Default)]
+pub struct Fdt {
+    cpus: u32,
+    memory: Range<u64>,
+    gic: Option<(u64, u64)>,
+    timer: Option<(u32, u32)>,
+    uart: Option<(Range<u64>, u32)>,
+    virtio: Vec<(Range<u64>, u32)>,
+    bootargs: Option<String>,
+    initrd: Option<Range<u64>>,
+}
+
+impl Fdt {
+    pub fn new(cpus: u32, memory: Range<u64>) -> Self {
+        Self {
+            cpus,
+            memory,
+            ..Default::default()
+        }
+    }
+
+    // GICv2 with the distributor and the cpu interface at `dist` and `cpu`.
+    pub fn gic(&mut self, dist: u64, cpu: u64) {
+        self.gic = Some((dist, cpu));
+    }
+
+    // Generic timer with interrupts of the physical and virtual timers.
+    pub fn timer(&mut self, phys: u32, virt: u32) {
+        self.timer = Some((phys, virt));
+    }
+
+    // PL011, which is the console of the guest.
+    pub fn uart(&mut self, range: Range<u64>, irq: u32) {
+        self.uart = Some((range, irq));
+    }
+
+    pub fn virtio_mmio(&mut self, range: Range<u64>, irq: u32) {
+        self.virtio.push((range, irq));
+    }
+
+    pub fn bootargs(&mut self, bootargs: &str) {
+        self.bootargs = Some(bootargs.to_string());
+    }
+
+    pub fn initrd(&mut self, range: Range<u64>) {
+        self.initrd = Some(range);
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        let mut fdt = FdtWriter::new()?;
+
+        let root = fdt.begin_node("")?;
+        fdt.property_string("compatible", "linux,dummy-virt")?;
+        fdt.property_u32("#address-cells", 2)?;
+        fdt.property_u32("#size-cells", 2)?;
+        if self.gic.is_some() {
+            fdt.property_u32("interrupt-parent", PHANDLE_GIC)?;
+        }
+
+        let chosen = fdt.begin_node("chosen")?;
+        if let Some((range, _)) = &self.uart {
+            fdt.property_string("stdout-path", &format!("/pl011@{:x}", range.start))?;
+        }
+        if let Some(bootargs) = &self.bootargs {
+            fdt.property_string("bootargs", bootargs)?;
+        }
+        if let Some(initrd) = &self.initrd {
+            fdt.property_u64("linux,initrd-start", initrd.start)?;
+            fdt.property_u64("linux,initrd-end", initrd.end)?;
+        }
+        fdt.end_node(chosen)?;
+
+        let memory = fdt.begin_node(&format!("memory@{:x}", self.memory.start))?;
+        fdt.property_string("device_type", "memory")?;
+        fdt.property_array_u64("reg", &[self.memory.start, size(&self.memory)])?;
+        fdt.end_node(memory)?;
+
+        let cpus = fdt.begin_node("cpus")?;
+        fdt.property_u32("#address-cells", 1)?;
+        fdt.property_u32("#size-cells", 0)?;
+        for cpu in 0..self.cpus {
+            let node = fdt.begin_node(&format!("cpu@{}", cpu))?;
+            fdt.property_string("device_type", "cpu")?;
+            fdt.property_string("compatible", "arm,cortex-a57")?;
+            fdt.property_u32("reg", cpu)?;
+            fdt.property_string("enable-method", "psci")?;
+            fdt.end_node(node)?;
+        }
+        fdt.end_node(cpus)?;
+
+        // PSCI calls are handled by the emulator, through either conduit.
+        let psci = fdt.begin_node("psci")?;
+        fdt.property_string_list(
+            "compatible",
+            vec![
+                "arm,psci-1.0".into(),
+                "arm,psci-0.2".into(),
+                "arm,psci".into(),
+            ],
+        )?;
+        fdt.property_string("method", "hvc")?;
+        fdt.end_node(psci)?;
+
+        if let Some((dist, cpu)) = self.gic {
+            let gic = fdt.begin_node(&format!("intc@{:x}", dist))?;
+            fdt.property_string("compatible", "arm,cortex-a15-gic")?;
+            fdt.property_u32("#interrupt-cells", 3)?;
+            fdt.property_null("interrupt-controller")?;
+            fdt.property_array_u64("reg", &[dist, 0x10000, cpu, 0x10000])?;
+            fdt.property_phandle(PHANDLE_GIC)?;
+            fdt.end_node(gic)?;
+        }
+
+        if let Some((phys, virt)) = self.timer {
+            // PPIs are routed to every cpu, of which the GIC serves at most 8.
+            let flags = ((1 << self.cpus.min(8)) - 1) << 8 | IRQ_TYPE_LEVEL_HIGH;
+            let irqs = [IRQ_TIMER_SECURE, phys, virt, IRQ_TIMER_HYP];
+
+            let timer = fdt.begin_node("timer")?;
+            fdt.property_string("compatible", "arm,armv8-timer")?;
+            let cells: Vec<u32> = irqs
+                .iter()
+                .flat_map(|&irq| [GIC_PPI, irq - 16, flags])
+                .collect();
+            fdt.property_array_u32("interrupts", &cells)?;
+            fdt.property_null("always-on")?;
+            fdt.end_node(timer)?;
+        }
+
+        if let Some((range, irq)) = &self.uart {
+            let clock = fdt.begin_node("apb-pclk")?;
+            fdt.property_string("compatible", "fixed-clock")?;
+            fdt.property_u32("#clock-cells", 0)?;
+            fdt.property_u32("clock-frequency", APB_CLOCK_FREQUENCY)?;
+            fdt.property_string("clock-output-names", "clk24mhz")?;
+            fdt.property_phandle(PHANDLE_CLOCK)?;
+            fdt.end_node(clock)?;
+
+            let uart = fdt.begin_node(&format!("pl011@{:x}", range.start))?;
+            fdt.property_string_list(
+                "compatible",
+                vec!["arm,pl011".into(), "arm,primecell".into()],
+            )?;
+            fdt.property_array_u64("reg", &[range.start, size(range)])?;
+            fdt.property_array_u32("interrupts", &spi(*irq))?;
+            fdt.property_array_u32("clocks", &[PHANDLE_CLOCK, PHANDLE_CLOCK])?;
+            fdt.property_string_list("clock-names", vec!["uartclk".into(), "apb_pclk".into()])?;
+            fdt.end_node(uart)?;
+        }
+
+        for (range, irq) in &self.virtio {
+            let virtio = fdt.begin_node(&format!("virtio_mmio@{:x}", range.start))?;
+            fdt.property_string("compatible", "virtio,mmio")?;
+            fdt.property_array_u64("reg", &[range.start, size(range)])?;
+            fdt.property_array_u32("interrupts", &spi(*irq))?;
+            fdt.property_null("dma-coherent")?;
+            fdt.end_node(virtio)?;
+        }
+
+        fdt.end_node(root)?;
+        fdt.finish()
+    }
+}
+
+fn size(range: &Range<u64>) -> u64 {
+    range.end - range.start
+}
+
+// Cells of a level-sensitive SPI.
+fn spi(irq: u32) -> [u32; 3] {
+    [GIC_SPI, irq - 32, IRQ_TYPE_LEVEL_HIGH]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Find `needle` in the blob, where strings of the tree are stored as is.
+    fn contains(blob: &[u8], needle: &[u8]) -> bool {
+        blob.windows(needle.len()).any(|window| window == needle)
+    }
+
+    #[test]
+    fn fdt_test() {
+        let mut fdt = Fdt::new(1, 0x4000_0000..0x4800_0000);
+        fdt.gic(0x0800_0000, 0x0801_0000);
+        fdt.timer(30, 27);
+        fdt.uart(0x0900_0000..0x0900_1000, 33);
+        fdt.virtio_mmio(0x0a00_0000..0x0a00_0200, 48);
+        fdt.bootargs("console=ttyAMA0");
+        let blob = fdt.build().unwrap();
+
+        // Magic of the header.
+        assert_eq!(blob[0..4], [0xd0, 0x0d, 0xfe, 0xed]);
+        for node in [
+            &b"memory@40000000\0"[..],
+            b"cpu@0\0",
+            b"intc@8000000\0",
+            b"pl011@9000000\0",
+            b"virtio_mmio@a000000\0",
+            b"console=ttyAMA0\0",
+            b"/pl011@9000000\0",
+        ] {
+            assert!(contains(&blob, node), "{}", String::from_utf8_lossy(node));
+        }
+
+        // `reg` of the memory, and `interrupts` of the UART.
+        let memory = [0, 0, 0, 0, 0x40, 0, 0, 0, 0, 0, 0, 0, 0x08, 0, 0, 0];
+        assert!(contains(&blob, &memory));
+        assert!(contains(&blob, &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 4]));
+    }
+
+    #[test]
+    fn fdt_cpus_test() {
+        let mut fdt = Fdt::new(32, 0x4000_0000..0x4800_0000);
+        fdt.gic(0x0800_0000, 0x0801_0000);
+        fdt.timer(30, 27);
+        let blob = fdt.build().unwrap();
+
+        assert!(contains(&blob, b"cpu@31\0"));
+        // Flags of the timer interrupts target the first 8 cpus.
+        assert!(contains(&blob, &[0, 0, 0, 1, 0, 0, 0, 14, 0, 0, 0xff, 4]));
+    }
+}
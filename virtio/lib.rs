@@ -1,7 +1,61 @@
-mod console;
-mod fdt;
+// Virtio devices and the virtio-mmio transport.
+//
+// https://docs.oasis-open.org/virtio/virtio/v1.2/virtio-v1.2.html
+pub mod block;
+pub mod console;
+pub mod error;
+pub mod fdt;
+pub mod mmio;
+pub mod queue;
 
-pub trait VirtIo {
-    fn init(&self);
-    fn irq(&self, irq: u32);
+pub use block::{Block, DiskMode};
+pub use console::Console;
+pub use error::VirtIoError;
+pub use fdt::Fdt;
+pub use mmio::MmioTransport;
+pub use queue::{Descriptor, DescriptorChain, Queue};
+
+use core::softmmu::Mmu;
+
+// Device IDs.
+pub const DEVICE_BLOCK: u32 = 2;
+pub const DEVICE_CONSOLE: u32 = 3;
+
+// Feature bits reserved for the transport.
+pub const F_VERSION_1: u64 = 1 << 32;
+
+// Device behind a virtio transport.
+pub trait VirtIo: Send {
+    fn device_id(&self) -> u32;
+
+    // Device-specific features offered to the driver.
+    fn features(&self) -> u64;
+
+    // Maximum size of each queue of the device.
+    fn queue_sizes(&self) -> &[u16];
+
+    // Access the device-specific configuration space.
+    fn read_config(&self, offset: u64, size: usize) -> u64;
+    fn write_config(&mut self, _offset: u64, _size: usize, _value: u64) {}
+
+    // Start the device with `features` accepted by the driver.
+    fn init(&mut self, features: u64);
+
+    // Stop the device, after the driver resets it.
+    fn reset(&mut self) {}
+
+    // Process requests the driver made available in `queues[queue]`, and return
+    // whether any of them is used.
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Queue],
+        mem: &Mmu,
+    ) -> Result<bool, VirtIoError>;
+
+    // Do work not requested by the driver, such as taking input from the host, and
+    // return whether any request is used.
+    fn poll(&mut self, _queues: &mut [Queue], _mem: &Mmu) -> Result<bool, VirtIoError> {
+        Ok(false)
+    }
 }
@@ -0,0 +1,457 @@
+use core::device::{IrqLine, Poll};
+use core::softmmu::{MmioDevice, Mmu};
+
+use crate::{Queue, VirtIo, VirtIoError, F_VERSION_1};
+
+// Registers
+const MAGIC_VALUE: u64 = 0x000;
+const VERSION: u64 = 0x004;
+const DEVICE_ID: u64 = 0x008;
+const VENDOR_ID: u64 = 0x00c;
+const DEVICE_FEATURES: u64 = 0x010;
+const DEVICE_FEATURES_SEL: u64 = 0x014;
+const DRIVER_FEATURES: u64 = 0x020;
+const DRIVER_FEATURES_SEL: u64 = 0x024;
+const QUEUE_SEL: u64 = 0x030;
+const QUEUE_NUM_MAX: u64 = 0x034;
+const QUEUE_NUM: u64 = 0x038;
+const QUEUE_READY: u64 = 0x044;
+const QUEUE_NOTIFY: u64 = 0x050;
+const INTERRUPT_STATUS: u64 = 0x060;
+const INTERRUPT_ACK: u64 = 0x064;
+const STATUS: u64 = 0x070;
+const QUEUE_DESC_LOW: u64 = 0x080;
+const QUEUE_DESC_HIGH: u64 = 0x084;
+const QUEUE_DRIVER_LOW: u64 = 0x090;
+const QUEUE_DRIVER_HIGH: u64 = 0x094;
+const QUEUE_DEVICE_LOW: u64 = 0x0a0;
+const QUEUE_DEVICE_HIGH: u64 = 0x0a4;
+const CONFIG_GENERATION: u64 = 0x0fc;
+const CONFIG: u64 = 0x100;
+
+const MAGIC: u64 = 0x7472_6976; // "virt"
+const VENDOR: u64 = 0x554d_4551; // "QEMU"
+
+// Device status
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_NEEDS_RESET: u32 = 0x40;
+
+// Interrupt status
+const INT_USED_BUFFER: u32 = 1 << 0;
+const INT_CONFIG_CHANGE: u32 = 1 << 1;
+
+// Size of the register window of a transport.
+pub const MMIO_SIZE: u64 = 0x200;
+
+// Virtio-mmio transport (version 2) of a device.
+pub struct MmioTransport {
+    device: Box<dyn VirtIo>,
+    mem: Mmu,
+    queues: Vec<Queue>,
+
+    device_features_sel: u32,
+    driver_features_sel: u32,
+    driver_features: u64,
+    queue_sel: u32,
+    status: u32,
+    interrupt_status: u32,
+    irq: Option<IrqLine>,
+}
+
+impl MmioTransport {
+    // Transport of `device`, which accesses queues in `mem`.
+    pub fn new(device: Box<dyn VirtIo>, mem: Mmu) -> Self {
+        let queues = device.queue_sizes().iter().map(|&size| Queue::new(size));
+        Self {
+            queues: queues.collect(),
+            device,
+            mem,
+            device_features_sel: 0,
+            driver_features_sel: 0,
+            driver_features: 0,
+            queue_sel: 0,
+            status: 0,
+            interrupt_status: 0,
+            irq: None,
+        }
+    }
+
+    // Transport of an empty slot, which has device ID 0.
+    pub fn empty(mem: Mmu) -> Self {
+        Self::new(Box::new(NoDevice), mem)
+    }
+
+    pub fn connect_irq(&mut self, irq: IrqLine) {
+        self.irq = Some(irq);
+        self.update_irq();
+    }
+
+    fn update_irq(&self) {
+        if let Some(irq) = &self.irq {
+            irq.set_level(self.interrupt_status != 0);
+        }
+    }
+
+    fn device_features(&self) -> u64 {
+        self.device.features() | F_VERSION_1
+    }
+
+    fn queue(&mut self) -> Option<&mut Queue> {
+        self.queues.get_mut(self.queue_sel as usize)
+    }
+
+    fn is_running(&self) -> bool {
+        self.status & STATUS_DRIVER_OK != 0 && self.status & STATUS_NEEDS_RESET == 0
+    }
+
+    fn reset(&mut self) {
+        self.device.reset();
+        for queue in &mut self.queues {
+            queue.reset();
+        }
+
+        self.device_features_sel = 0;
+        self.driver_features_sel = 0;
+        self.driver_features = 0;
+        self.queue_sel = 0;
+        self.status = 0;
+        self.interrupt_status = 0;
+        self.update_irq();
+    }
+
+    fn set_status(&mut self, status: u32) {
+        if status == 0 {
+            self.reset();
+            return;
+        }
+
+        let mut status = status;
+        // Features are accepted only if the device offers them, in the modern interface.
+        let accepted = self.driver_features & !self.device_features() == 0
+            && self.driver_features & F_VERSION_1 != 0;
+        if status & STATUS_FEATURES_OK != 0 && !accepted {
+            status &= !STATUS_FEATURES_OK;
+        }
+
+        let started = status & !self.status & STATUS_DRIVER_OK != 0;
+        self.status = status;
+        if started {
+            self.device.init(self.driver_features);
+        }
+    }
+
+    // Run the device on its queues, and interrupt the driver for used requests.
+    fn run_device(
+        &mut self,
+        f: impl FnOnce(&mut dyn VirtIo, &mut [Queue], &Mmu) -> Result<bool, VirtIoError>,
+    ) {
+        if !self.is_running() {
+            return;
+        }
+        match f(self.device.as_mut(), &mut self.queues, &self.mem) {
+            Ok(true) => self.interrupt_status |= INT_USED_BUFFER,
+            Ok(false) => return,
+            // The driver has to reset the device, which can't use its queues anymore.
+            Err(_) => {
+                self.status |= STATUS_NEEDS_RESET;
+                self.interrupt_status |= INT_CONFIG_CHANGE;
+            }
+        }
+        self.update_irq();
+    }
+}
+
+impl MmioDevice for MmioTransport {
+    fn read(&mut self, offset: u64, size: usize) -> u64 {
+        if offset >= CONFIG {
+            return self.device.read_config(offset - CONFIG, size);
+        }
+
+        match offset {
+            MAGIC_VALUE => MAGIC,
+            VERSION => 2,
+            DEVICE_ID => self.device.device_id() as u64,
+            VENDOR_ID => VENDOR,
+            DEVICE_FEATURES => match self.device_features_sel {
+                0 => self.device_features() & 0xffff_ffff,
+                1 => self.device_features() >> 32,
+                _ => 0,
+            },
+            QUEUE_NUM_MAX => self.queue().map_or(0, |queue| queue.max_size() as u64),
+            QUEUE_READY => self.queue().map_or(0, |queue| queue.ready as u64),
+            INTERRUPT_STATUS => self.interrupt_status as u64,
+            STATUS => self.status as u64,
+            CONFIG_GENERATION => 0,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, offset: u64, size: usize, value: u64) {
+        if offset >= CONFIG {
+            self.device.write_config(offset - CONFIG, size, value);
+            return;
+        }
+
+        let value = value as u32;
+        match offset {
+            DEVICE_FEATURES_SEL => self.device_features_sel = value,
+            DRIVER_FEATURES => {
+                let shift = match self.driver_features_sel {
+                    0 => 0,
+                    1 => 32,
+                    _ => return,
+                };
+                self.driver_features &= !(0xffff_ffff << shift);
+                self.driver_features |= (value as u64) << shift;
+            }
+            DRIVER_FEATURES_SEL => self.driver_features_sel = value,
+            QUEUE_SEL => self.queue_sel = value,
+            QUEUE_NUM => {
+                // Queues of invalid sizes are never ready.
+                if let Some(queue) = self.queue() {
+                    queue.size = if value.is_power_of_two() && value <= queue.max_size() as u32 {
+                        value as u16
+                    } else {
+                        0
+                    };
+                }
+            }
+            QUEUE_READY => {
+                if let Some(queue) = self.queue() {
+                    queue.ready = value & 1 != 0;
+                }
+            }
+            QUEUE_NOTIFY if (value as usize) < self.queues.len() => {
+                let queue = value as usize;
+                self.run_device(|device, queues, mem| device.notify(queue, queues, mem));
+            }
+            INTERRUPT_ACK => {
+                self.interrupt_status &= !value;
+                self.update_irq();
+            }
+            STATUS => self.set_status(value),
+            QUEUE_DESC_LOW | QUEUE_DESC_HIGH | QUEUE_DRIVER_LOW | QUEUE_DRIVER_HIGH
+            | QUEUE_DEVICE_LOW | QUEUE_DEVICE_HIGH => {
+                let Some(queue) = self.queue() else {
+                    return;
+                };
+                let addr = match offset {
+                    QUEUE_DESC_LOW | QUEUE_DESC_HIGH => &mut queue.desc_table,
+                    QUEUE_DRIVER_LOW | QUEUE_DRIVER_HIGH => &mut queue.avail_ring,
+                    _ => &mut queue.used_ring,
+                };
+                let shift = if offset & 4 == 0 { 0 } else { 32 };
+                *addr &= !(0xffff_ffff << shift);
+                *addr |= (value as u64) << shift;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Poll for MmioTransport {
+    fn poll(&mut self) {
+        self.run_device(|device, queues, mem| device.poll(queues, mem));
+    }
+}
+
+// Placeholder of the device of an empty slot, which has no queues.
+struct NoDevice;
+
+impl VirtIo for NoDevice {
+    fn device_id(&self) -> u32 {
+        0
+    }
+
+    fn features(&self) -> u64 {
+        0
+    }
+
+    fn queue_sizes(&self) -> &[u16] {
+        &[]
+    }
+
+    fn read_config(&self, _offset: u64, _size: usize) -> u64 {
+        0
+    }
+
+    fn init(&mut self, _features: u64) {}
+
+    fn notify(&mut self, _: usize, _: &mut [Queue], _: &Mmu) -> Result<bool, VirtIoError> {
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::queue::test::*;
+    use crate::Descriptor;
+
+    // Device with one queue, which echoes requests back into their buffers.
+    struct Echo;
+
+    impl VirtIo for Echo {
+        fn device_id(&self) -> u32 {
+            0x42
+        }
+
+        fn features(&self) -> u64 {
+            1 << 5
+        }
+
+        fn queue_sizes(&self) -> &[u16] {
+            &[16]
+        }
+
+        fn read_config(&self, offset: u64, _size: usize) -> u64 {
+            offset + 1
+        }
+
+        fn init(&mut self, _features: u64) {}
+
+        fn notify(
+            &mut self,
+            queue: usize,
+            queues: &mut [Queue],
+            mem: &Mmu,
+        ) -> Result<bool, VirtIoError> {
+            let queue = &mut queues[queue];
+            let mut used = false;
+            while let Some(chain) = queue.pop(mem)? {
+                let len = chain.write_all(mem, &chain.read_all(mem)?)?;
+                queue.add_used(mem, chain.head(), len as u32)?;
+                used = true;
+            }
+            Ok(used)
+        }
+    }
+
+    fn start(transport: &mut MmioTransport, features: u64) {
+        transport.write(STATUS, 4, 1 | 2); // ACKNOWLEDGE | DRIVER
+        transport.write(DRIVER_FEATURES_SEL, 4, 0);
+        transport.write(DRIVER_FEATURES, 4, features & 0xffff_ffff);
+        transport.write(DRIVER_FEATURES_SEL, 4, 1);
+        transport.write(DRIVER_FEATURES, 4, features >> 32);
+        transport.write(STATUS, 4, 1 | 2 | STATUS_FEATURES_OK as u64);
+    }
+
+    #[test]
+    fn mmio_transport_test() {
+        let mem = guest_memory();
+        let mut transport = MmioTransport::new(Box::new(Echo), mem.clone());
+
+        assert_eq!(transport.read(MAGIC_VALUE, 4), MAGIC);
+        assert_eq!(transport.read(VERSION, 4), 2);
+        assert_eq!(transport.read(DEVICE_ID, 4), 0x42);
+        assert_eq!(transport.read(DEVICE_FEATURES, 4), 1 << 5);
+        transport.write(DEVICE_FEATURES_SEL, 4, 1);
+        assert_eq!(transport.read(DEVICE_FEATURES, 4), 1);
+        assert_eq!(transport.read(CONFIG + 4, 4), 5);
+
+        // Features not offered by the device, or without `VERSION_1`, are refused.
+        start(&mut transport, 1 << 5);
+        assert_eq!(transport.read(STATUS, 4) as u32 & STATUS_FEATURES_OK, 0);
+        start(&mut transport, F_VERSION_1 | 1 << 6);
+        assert_eq!(transport.read(STATUS, 4) as u32 & STATUS_FEATURES_OK, 0);
+        start(&mut transport, F_VERSION_1 | 1 << 5);
+        assert_ne!(transport.read(STATUS, 4) as u32 & STATUS_FEATURES_OK, 0);
+
+        transport.write(QUEUE_SEL, 4, 0);
+        assert_eq!(transport.read(QUEUE_NUM_MAX, 4), 16);
+        transport.write(QUEUE_NUM, 4, 6);
+        transport.write(QUEUE_READY, 4, 1);
+        assert!(!transport.queues[0].is_ready());
+        transport.write(QUEUE_NUM, 4, 8);
+        transport.write(QUEUE_DESC_LOW, 4, DESC_TABLE);
+        transport.write(QUEUE_DESC_HIGH, 4, 0);
+        transport.write(QUEUE_DRIVER_LOW, 4, AVAIL_RING);
+        transport.write(QUEUE_DEVICE_LOW, 4, USED_RING);
+        transport.write(QUEUE_READY, 4, 1);
+        transport.write(STATUS, 4, 0xf);
+
+        let request = Descriptor {
+            addr: 0x4000_8000,
+            len: 2,
+            writable: false,
+        };
+        let response = Descriptor {
+            addr: 0x4000_9000,
+            len: 8,
+            writable: true,
+        };
+        write(&mem, request.addr, b"hi");
+        set_desc(&mem, 0, request, Some(1));
+        set_desc(&mem, 1, response, None);
+        make_available(&mem, 0, 0);
+
+        transport.write(QUEUE_NOTIFY, 4, 0);
+        assert_eq!(read(&mem, response.addr, 2), b"hi");
+        assert_eq!(read(&mem, USED_RING + 2, 2), 1u16.to_le_bytes());
+        assert_eq!(transport.read(INTERRUPT_STATUS, 4), INT_USED_BUFFER as u64);
+
+        transport.write(INTERRUPT_ACK, 4, INT_USED_BUFFER as u64);
+        assert_eq!(transport.read(INTERRUPT_STATUS, 4), 0);
+
+        // Reset stops the queues.
+        transport.write(STATUS, 4, 0);
+        assert_eq!(transport.read(QUEUE_READY, 4), 0);
+        assert_eq!(transport.read(STATUS, 4), 0);
+    }
+
+    #[test]
+    fn mmio_window_test() {
+        use core::softmmu::MmioBus;
+        use std::sync::{Arc, Mutex};
+
+        const WINDOW: u64 = 0x0a00_0000;
+
+        let mem = guest_memory();
+        let bus = Arc::new(MmioBus::new());
+        mem.mmap_mmio(WINDOW, 0x1000, bus.clone()).unwrap();
+        let transport = Arc::new(Mutex::new(MmioTransport::new(Box::new(Echo), mem.clone())));
+        bus.register(WINDOW..WINDOW + MMIO_SIZE, transport.clone())
+            .unwrap();
+
+        {
+            let mut transport = transport.lock().unwrap();
+            start(&mut transport, F_VERSION_1);
+            transport.write(QUEUE_NUM, 4, 8);
+            transport.write(QUEUE_DESC_LOW, 4, DESC_TABLE);
+            transport.write(QUEUE_DRIVER_LOW, 4, AVAIL_RING);
+            transport.write(QUEUE_DEVICE_LOW, 4, USED_RING);
+            transport.write(QUEUE_READY, 4, 1);
+            transport.write(STATUS, 4, 0xf);
+        }
+
+        // A buffer in the registers of the device itself would access it again while
+        // it's running, so the device needs a reset instead.
+        let request = Descriptor {
+            addr: WINDOW + QUEUE_NOTIFY,
+            len: 4,
+            writable: false,
+        };
+        set_desc(&mem, 0, request, None);
+        make_available(&mem, 0, 0);
+        unsafe {
+            mem.write(WINDOW + QUEUE_NOTIFY, &0u32.to_le_bytes())
+                .unwrap()
+        };
+
+        let mut transport = transport.lock().unwrap();
+        assert_ne!(transport.read(STATUS, 4) as u32 & STATUS_NEEDS_RESET, 0);
+        assert_eq!(
+            transport.read(INTERRUPT_STATUS, 4),
+            INT_CONFIG_CHANGE as u64
+        );
+    }
+
+    #[test]
+    fn mmio_empty_slot_test() {
+        let mut transport = MmioTransport::empty(Mmu::new());
+        assert_eq!(transport.read(MAGIC_VALUE, 4), MAGIC);
+        assert_eq!(transport.read(DEVICE_ID, 4), 0);
+        assert_eq!(transport.read(QUEUE_NUM_MAX, 4), 0);
+    }
+}
@@ -0,0 +1,339 @@
+use core::softmmu::Mmu;
+
+use crate::error::VirtIoError;
+
+// Flags of descriptors.
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+const DESC_SIZE: u64 = 16;
+const AVAIL_ELEM_SIZE: u64 = 2;
+const USED_ELEM_SIZE: u64 = 8;
+
+// Limit of the total size of buffers in a chain, which devices copy into host memory.
+const MAX_CHAIN_LEN: u64 = 16 << 20;
+
+// Buffer in guest memory, which is either read or written by the device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Descriptor {
+    pub addr: u64,
+    pub len: u32,
+    pub writable: bool,
+}
+
+// Descriptors of a request made available by the driver, starting at `head`.
+// Device-readable descriptors come before device-writable ones.
+#[derive(Debug)]
+pub struct DescriptorChain {
+    head: u16,
+    descriptors: Vec<Descriptor>,
+}
+
+impl DescriptorChain {
+    pub fn head(&self) -> u16 {
+        self.head
+    }
+
+    pub fn descriptors(&self) -> &[Descriptor] {
+        &self.descriptors
+    }
+
+    pub fn readable(&self) -> impl Iterator<Item = &Descriptor> {
+        self.descriptors.iter().filter(|desc| !desc.writable)
+    }
+
+    pub fn writable(&self) -> impl Iterator<Item = &Descriptor> {
+        self.descriptors.iter().filter(|desc| desc.writable)
+    }
+
+    // Total size of device-writable buffers.
+    pub fn writable_len(&self) -> usize {
+        self.writable().map(|desc| desc.len as usize).sum()
+    }
+
+    // Concatenated content of device-readable buffers.
+    pub fn read_all(&self, mem: &Mmu) -> Result<Vec<u8>, VirtIoError> {
+        let mut buf = Vec::new();
+        for desc in self.readable() {
+            let start = buf.len();
+            buf.resize(start + desc.len as usize, 0);
+            unsafe { mem.read(desc.addr, &mut buf[start..])? };
+        }
+
+        Ok(buf)
+    }
+
+    // Write `buf` into device-writable buffers, and return the number of bytes written.
+    pub fn write_all(&self, mem: &Mmu, buf: &[u8]) -> Result<usize, VirtIoError> {
+        let mut written = 0;
+        for desc in self.writable() {
+            if written == buf.len() {
+                break;
+            }
+
+            let len = (desc.len as usize).min(buf.len() - written);
+            unsafe { mem.write(desc.addr, &buf[written..written + len])? };
+            written += len;
+        }
+
+        Ok(written)
+    }
+}
+
+// Split virtqueue, whose rings are in guest memory.
+//
+// Rings are located by the driver through the transport before the queue is ready.
+#[derive(Debug)]
+pub struct Queue {
+    max_size: u16,
+    pub(crate) size: u16,
+    pub(crate) ready: bool,
+    pub(crate) desc_table: u64,
+    pub(crate) avail_ring: u64,
+    pub(crate) used_ring: u64,
+
+    next_avail: u16,
+    next_used: u16,
+}
+
+impl Queue {
+    pub fn new(max_size: u16) -> Self {
+        Self {
+            max_size,
+            size: max_size,
+            ready: false,
+            desc_table: 0,
+            avail_ring: 0,
+            used_ring: 0,
+            next_avail: 0,
+            next_used: 0,
+        }
+    }
+
+    pub fn max_size(&self) -> u16 {
+        self.max_size
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready && self.size != 0
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new(self.max_size);
+    }
+
+    // Take the next request made available by the driver.
+    pub fn pop(&mut self, mem: &Mmu) -> Result<Option<DescriptorChain>, VirtIoError> {
+        if !self.is_ready() {
+            return Ok(None);
+        }
+        self.check_rings(mem)?;
+
+        let avail_idx = read_u16(mem, self.avail_ring + 2)?;
+        if avail_idx == self.next_avail {
+            return Ok(None);
+        }
+
+        let slot = (self.next_avail % self.size) as u64;
+        let head = read_u16(mem, self.avail_ring + 4 + slot * AVAIL_ELEM_SIZE)?;
+        self.next_avail = self.next_avail.wrapping_add(1);
+
+        let mut descriptors = Vec::new();
+        let mut total_len = 0;
+        let mut index = head;
+        loop {
+            // A chain never has more descriptors than the queue, unless it's a loop.
+            if index >= self.size || descriptors.len() == self.size as usize {
+                return Err(VirtIoError::InvalidChain(head));
+            }
+
+            let mut raw = [0u8; DESC_SIZE as usize];
+            unsafe { mem.read(self.desc_table + index as u64 * DESC_SIZE, &mut raw)? };
+            let flags = u16::from_le_bytes([raw[12], raw[13]]);
+            let desc = Descriptor {
+                addr: u64::from_le_bytes(raw[0..8].try_into().unwrap()),
+                len: u32::from_le_bytes(raw[8..12].try_into().unwrap()),
+                writable: flags & DESC_F_WRITE != 0,
+            };
+            total_len += desc.len as u64;
+            if total_len > MAX_CHAIN_LEN {
+                return Err(VirtIoError::InvalidChain(head));
+            }
+            check_memory(mem, desc.addr, desc.len as u64)?;
+            descriptors.push(desc);
+
+            if flags & DESC_F_NEXT == 0 {
+                break;
+            }
+            index = u16::from_le_bytes([raw[14], raw[15]]);
+        }
+
+        Ok(Some(DescriptorChain { head, descriptors }))
+    }
+
+    // Return the request at `head` to the driver, with `len` bytes written into it.
+    pub fn add_used(&mut self, mem: &Mmu, head: u16, len: u32) -> Result<(), VirtIoError> {
+        self.check_rings(mem)?;
+
+        let slot = (self.next_used % self.size) as u64;
+        let mut elem = [0u8; USED_ELEM_SIZE as usize];
+        elem[0..4].copy_from_slice(&(head as u32).to_le_bytes());
+        elem[4..8].copy_from_slice(&len.to_le_bytes());
+
+        self.next_used = self.next_used.wrapping_add(1);
+        unsafe {
+            mem.write(self.used_ring + 4 + slot * USED_ELEM_SIZE, &elem)?;
+            mem.write(self.used_ring + 2, &self.next_used.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    // Rings have to be in RAM. Devices access them while the bus holds their lock, so
+    // a ring in the MMIO window would access the device again and deadlock.
+    fn check_rings(&self, mem: &Mmu) -> Result<(), VirtIoError> {
+        let size = self.size as u64;
+        check_memory(mem, self.desc_table, size * DESC_SIZE)?;
+        check_memory(mem, self.avail_ring, 4 + size * AVAIL_ELEM_SIZE)?;
+        check_memory(mem, self.used_ring, 4 + size * USED_ELEM_SIZE)
+    }
+}
+
+// Buffers of descriptors have to be in RAM for the same reason as rings.
+fn check_memory(mem: &Mmu, addr: u64, len: u64) -> Result<(), VirtIoError> {
+    match addr.checked_add(len) {
+        Some(end) if mem.is_memory(addr..end) => Ok(()),
+        _ => Err(VirtIoError::NotMemory(addr)),
+    }
+}
+
+fn read_u16(mem: &Mmu, addr: u64) -> Result<u16, VirtIoError> {
+    let mut buf = [0u8; 2];
+    unsafe { mem.read(addr, &mut buf)? };
+    Ok(u16::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use core::softmmu::RamRegion;
+
+    pub const DESC_TABLE: u64 = 0x4000_0000;
+    pub const AVAIL_RING: u64 = 0x4000_1000;
+    pub const USED_RING: u64 = 0x4000_2000;
+
+    pub fn guest_memory() -> Mmu {
+        let mem = Mmu::new();
+        mem.mmap_region(0x4000_0000, RamRegion::new(0x10_0000, true, true, false))
+            .unwrap();
+        mem
+    }
+
+    pub fn write(mem: &Mmu, addr: u64, buf: &[u8]) {
+        unsafe { mem.write(addr, buf).unwrap() }
+    }
+
+    pub fn read(mem: &Mmu, addr: u64, len: usize) -> Vec<u8> {
+        let mut buf = vec![0u8; len];
+        unsafe { mem.read(addr, &mut buf).unwrap() };
+        buf
+    }
+
+    // Write descriptor `index` of the table, chained to `next`.
+    pub fn set_desc(mem: &Mmu, index: u16, desc: Descriptor, next: Option<u16>) {
+        let mut raw = [0u8; 16];
+        raw[0..8].copy_from_slice(&desc.addr.to_le_bytes());
+        raw[8..12].copy_from_slice(&desc.len.to_le_bytes());
+        let flags = if desc.writable { DESC_F_WRITE } else { 0 }
+            | if next.is_some() { DESC_F_NEXT } else { 0 };
+        raw[12..14].copy_from_slice(&flags.to_le_bytes());
+        raw[14..16].copy_from_slice(&next.unwrap_or(0).to_le_bytes());
+        write(mem, DESC_TABLE + index as u64 * 16, &raw);
+    }
+
+    // Make the chain at `head` available at `slot` of the ring.
+    pub fn make_available(mem: &Mmu, slot: u16, head: u16) {
+        write(mem, AVAIL_RING + 4 + slot as u64 * 2, &head.to_le_bytes());
+        write(mem, AVAIL_RING + 2, &(slot + 1).to_le_bytes());
+    }
+
+    pub fn ready_queue(size: u16) -> Queue {
+        let mut queue = Queue::new(size);
+        queue.desc_table = DESC_TABLE;
+        queue.avail_ring = AVAIL_RING;
+        queue.used_ring = USED_RING;
+        queue.ready = true;
+        queue
+    }
+
+    #[test]
+    fn queue_test() {
+        let mem = guest_memory();
+        let mut queue = ready_queue(8);
+        assert!(queue.pop(&mem).unwrap().is_none());
+
+        let header = Descriptor {
+            addr: 0x4000_8000,
+            len: 4,
+            writable: false,
+        };
+        let data = Descriptor {
+            addr: 0x4000_9000,
+            len: 16,
+            writable: true,
+        };
+        set_desc(&mem, 3, header, Some(5));
+        set_desc(&mem, 5, data, None);
+        write(&mem, header.addr, b"ping");
+        make_available(&mem, 0, 3);
+
+        let chain = queue.pop(&mem).unwrap().unwrap();
+        assert_eq!(chain.head(), 3);
+        assert_eq!(chain.descriptors(), &[header, data]);
+        assert_eq!(chain.read_all(&mem).unwrap(), b"ping");
+        assert_eq!(chain.writable_len(), 16);
+        assert_eq!(chain.write_all(&mem, b"pong").unwrap(), 4);
+        assert_eq!(read(&mem, data.addr, 4), b"pong");
+        assert!(queue.pop(&mem).unwrap().is_none());
+
+        queue.add_used(&mem, chain.head(), 4).unwrap();
+        assert_eq!(read(&mem, USED_RING + 2, 2), 1u16.to_le_bytes());
+        assert_eq!(read(&mem, USED_RING + 4, 8), [3, 0, 0, 0, 4, 0, 0, 0]);
+
+        // Chains which loop are rejected.
+        set_desc(&mem, 0, header, Some(0));
+        make_available(&mem, 1, 0);
+        assert!(queue.pop(&mem).is_err());
+
+        // So are chains larger than the limit.
+        let huge = Descriptor {
+            addr: 0x4000_8000,
+            len: u32::MAX,
+            writable: false,
+        };
+        set_desc(&mem, 1, huge, None);
+        make_available(&mem, 2, 1);
+        assert!(queue.pop(&mem).is_err());
+
+        // And buffers outside of the RAM.
+        let unmapped = Descriptor {
+            addr: 0x1000,
+            len: 4,
+            writable: true,
+        };
+        set_desc(&mem, 2, unmapped, None);
+        make_available(&mem, 3, 2);
+        assert!(matches!(
+            queue.pop(&mem),
+            Err(VirtIoError::NotMemory(0x1000))
+        ));
+
+        // Rings have to be in the RAM as well.
+        queue.used_ring = 0x3fff_fffc;
+        make_available(&mem, 4, 5);
+        assert!(matches!(
+            queue.pop(&mem),
+            Err(VirtIoError::NotMemory(0x3fff_fffc))
+        ));
+    }
+}